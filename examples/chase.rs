@@ -0,0 +1,150 @@
+//! A minimal game: move the green player with the arrow keys while the yellow
+//! enemy chases it, replanning its path with A* every step. The cells the enemy
+//! can strike within its next few steps are shaded red.
+
+use bevy::prelude::*;
+
+use a_star::prelude::*;
+
+const GRID_WIDTH: u32 = 40;
+const GRID_HEIGHT: u32 = 30;
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(AStarPlugin::default().with_races(false))
+        .add_startup_system(setup)
+        .add_system(move_player)
+        .add_system(chase_player)
+        .add_system(sync_transforms.after(move_player).after(chase_player))
+        .run();
+}
+
+#[derive(Component)]
+struct Player {
+    grid: Entity,
+    cell_pos: CellPos,
+}
+
+#[derive(Component)]
+struct Enemy {
+    grid: Entity,
+    cell_pos: CellPos,
+    step_timer: Timer,
+}
+
+fn setup(mut commands: Commands, mut grids: ResMut<Assets<Grid>>) {
+    commands.spawn(Camera2dBundle {
+        projection: OrthographicProjection {
+            scale: 1.0 / 20.0,
+            ..default()
+        },
+        ..default()
+    });
+
+    // A few walls with gaps, so the enemy has to route around them.
+    let grid = GridBuilder::new(GRID_WIDTH, GRID_HEIGHT)
+        .wall_rect(CellPos(12, 0), CellPos(12, GRID_HEIGHT as i32 - 6))
+        .wall_rect(CellPos(26, 5), CellPos(26, GRID_HEIGHT as i32 - 1))
+        .build();
+
+    let grid = commands
+        .spawn(SpatialBundle::default())
+        .insert(Name::new("Grid editor"))
+        .insert(GridEditor::new(grids.add(grid)))
+        .insert(ThreatOverlay::new(Color::RED))
+        .id();
+
+    let agent_sprite = |color| Sprite {
+        color,
+        custom_size: Some(Vec2::new(0.8, 0.8)),
+        ..default()
+    };
+
+    commands.spawn((
+        Player { grid, cell_pos: CellPos(2, 2) },
+        SpriteBundle { sprite: agent_sprite(Color::GREEN), ..default() },
+    ));
+
+    commands.spawn((
+        Enemy {
+            grid,
+            cell_pos: CellPos(GRID_WIDTH as i32 - 3, GRID_HEIGHT as i32 - 3),
+            step_timer: Timer::from_seconds(0.2, TimerMode::Repeating),
+        },
+        ThreatSource {
+            grid,
+            cell_pos: CellPos(GRID_WIDTH as i32 - 3, GRID_HEIGHT as i32 - 3),
+            movement: 4.0,
+            attack_radius: 1,
+        },
+        SpriteBundle { sprite: agent_sprite(Color::YELLOW), ..default() },
+    ));
+}
+
+fn move_player(
+    keys: Res<Input<KeyCode>>,
+    grids: Grids,
+    mut player_query: Query<&mut Player>,
+) {
+    let mut player = player_query.single_mut();
+    let grid = grids.get(player.grid).unwrap();
+
+    let CellPos(x, y) = player.cell_pos;
+    let target = if keys.just_pressed(KeyCode::Left) {
+        CellPos(x - 1, y)
+    } else if keys.just_pressed(KeyCode::Right) {
+        CellPos(x + 1, y)
+    } else if keys.just_pressed(KeyCode::Up) {
+        CellPos(x, y + 1)
+    } else if keys.just_pressed(KeyCode::Down) {
+        CellPos(x, y - 1)
+    } else {
+        return;
+    };
+
+    if grid.cell(target).is_ok_and(|cell| !cell.is_wall) {
+        player.cell_pos = target;
+    }
+}
+
+fn chase_player(
+    time: Res<Time>,
+    grids: Grids,
+    player_query: Query<&Player>,
+    mut enemy_query: Query<(&mut Enemy, &mut ThreatSource)>,
+) {
+    let player = player_query.single();
+
+    for (mut enemy, mut threat) in &mut enemy_query {
+        if !enemy.step_timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+
+        let path = grids.find_path(enemy.grid, enemy.cell_pos, player.cell_pos).unwrap();
+
+        if let Some(&next) = path.as_ref().and_then(|path| path.cells.get(1)) {
+            enemy.cell_pos = next;
+            threat.cell_pos = next;
+        }
+    }
+}
+
+fn sync_transforms(
+    grids: Grids,
+    mut players: Query<(&Player, &mut Transform), Without<Enemy>>,
+    mut enemies: Query<(&Enemy, &mut Transform), Without<Player>>,
+) {
+    // Drawn one layer above the cell sprites.
+    let agent_transform = |grid: Entity, cell_pos| {
+        let transform = cell_transform(grids.get(grid).unwrap(), cell_pos);
+        transform.with_translation(transform.translation + Vec3::Z)
+    };
+
+    for (player, mut transform) in &mut players {
+        *transform = agent_transform(player.grid, player.cell_pos);
+    }
+    for (enemy, mut transform) in &mut enemies {
+        *transform = agent_transform(enemy.grid, enemy.cell_pos);
+    }
+}
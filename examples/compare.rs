@@ -0,0 +1,70 @@
+//! A* and Dijkstra exploring the same random grid side by side, stepping in
+//! sync. Space pauses, period steps both by one cell, minus and equals change
+//! the speed, and enter starts over with new endpoints.
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use a_star::prelude::*;
+
+const GRID_WIDTH: u32 = 60;
+const GRID_HEIGHT: u32 = 45;
+const WALL_DENSITY: f64 = 0.25;
+const PLANNERS: [Planner; 2] = [Planner::AStar, Planner::Dijkstra];
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(AStarPlugin::default())
+        .add_plugin(PlaybackControlsPlugin::default())
+        .add_plugin(SplitScreenPlugin)
+        .add_startup_system(setup)
+        .add_system(restart)
+        .run();
+}
+
+fn setup(mut commands: Commands, mut grids: ResMut<Assets<Grid>>) {
+    let mut rng = rand::thread_rng();
+    let mut grid = Grid::new(GRID_WIDTH, GRID_HEIGHT);
+
+    for y in 0..GRID_HEIGHT as i32 {
+        for x in 0..GRID_WIDTH as i32 {
+            let is_wall = rng.gen_bool(WALL_DENSITY);
+            grid.set_cell(CellPos(x, y), Cell { is_wall }).unwrap();
+        }
+    }
+
+    let (start, goal) = (random_floor(&grid), random_floor(&grid));
+    commands.spawn_comparison(grids.add(grid), PLANNERS, start, goal);
+}
+
+fn random_floor(grid: &Grid) -> CellPos {
+    let mut rng = rand::thread_rng();
+    loop {
+        let cell_pos = CellPos(
+            rng.gen_range(0..grid.width()) as i32,
+            rng.gen_range(0..grid.height()) as i32,
+        );
+        if !grid.cell(cell_pos).unwrap().is_wall {
+            return cell_pos;
+        }
+    }
+}
+
+/// Restarts every search between the same new endpoints.
+fn restart(keys: Res<Input<KeyCode>>, grids: Grids, mut visualizers: Query<&mut SearchVisualizer>) {
+    if !keys.just_pressed(KeyCode::Return) {
+        return;
+    }
+
+    let mut endpoints = None;
+    for mut visualizer in &mut visualizers {
+        let Ok(grid) = grids.get(visualizer.grid) else {
+            continue;
+        };
+        let (start, goal) = *endpoints.get_or_insert_with(|| (random_floor(grid), random_floor(grid)));
+
+        let search = Search::new(grid, visualizer.search.planner(), start, goal);
+        *visualizer = SearchVisualizer::new(visualizer.grid, search);
+    }
+}
@@ -0,0 +1,69 @@
+//! Plugging a custom cost function into A*: cells inside a swamp cost five times
+//! as much to enter, so the path prefers to walk around it when it's cheaper.
+
+use a_star::prelude::*;
+
+const WIDTH: u32 = 30;
+const HEIGHT: u32 = 12;
+
+/// Terrain stored next to the grid by the game, not by the grid itself.
+struct Terrain {
+    swamp_min: CellPos,
+    swamp_max: CellPos,
+}
+
+impl Terrain {
+    fn is_swamp(&self, CellPos(x, y): CellPos) -> bool {
+        let (CellPos(x0, y0), CellPos(x1, y1)) = (self.swamp_min, self.swamp_max);
+        (x0..=x1).contains(&x) && (y0..=y1).contains(&y)
+    }
+}
+
+impl StepCost for Terrain {
+    fn step_cost(&self, _grid: &Grid, _from: CellPos, to: CellPos) -> Option<f32> {
+        Some(if self.is_swamp(to) { 5.0 } else { 1.0 })
+    }
+}
+
+fn print_path(grid: &Grid, terrain: &Terrain, path: &Path) {
+    for y in (0..grid.height() as i32).rev() {
+        let row: String = (0..grid.width() as i32)
+            .map(|x| {
+                let cell_pos = CellPos(x, y);
+                if path.cells.contains(&cell_pos) {
+                    '*'
+                } else if grid.cell(cell_pos).unwrap().is_wall {
+                    '#'
+                } else if terrain.is_swamp(cell_pos) {
+                    '~'
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        println!("{row}");
+    }
+    println!("cost: {}, cells: {}, expanded: {}\n", path.cost, path.cells.len(), path.nodes_expanded);
+}
+
+fn main() {
+    let grid = GridBuilder::new(WIDTH, HEIGHT)
+        .wall_rect(CellPos(20, 3), CellPos(20, HEIGHT as i32 - 1))
+        .build();
+
+    let terrain = Terrain {
+        swamp_min: CellPos(8, 2),
+        swamp_max: CellPos(14, 9),
+    };
+
+    let (start, goal) = (CellPos(1, 6), CellPos(28, 6));
+
+    println!("Uniform cost:");
+    let path = AStar::new(&grid).find_path(start, goal).expect("goal is reachable");
+    print_path(&grid, &terrain, &path);
+
+    println!("Swamp-aware cost:");
+    let swamp_cost = |grid: &Grid, from, to| terrain.step_cost(grid, from, to);
+    let path = AStar::with_cost(&grid, swamp_cost).find_path(start, goal).expect("goal is reachable");
+    print_path(&grid, &terrain, &path);
+}
@@ -0,0 +1,127 @@
+//! A* exploring a random grid a few cells per tick. Press tab to cycle between
+//! the open/closed coloring and the g-score and f-score heatmaps, T to show the
+//! search tree, F to leave a fading trail behind the frontier, and enter to
+//! start over with new endpoints. Space pauses, period steps one cell at a
+//! time, and minus and equals change the speed, as do the buttons in the
+//! corner. Hover a cell to see its scores, and press R to start or stop
+//! recording the search as PNG frames in `explore_frames`. The numbers of the
+//! search are shown in the top right corner.
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use a_star::prelude::*;
+
+const GRID_WIDTH: u32 = 80;
+const GRID_HEIGHT: u32 = 60;
+const WALL_DENSITY: f64 = 0.25;
+const EXPANSIONS_PER_TICK: usize = 4;
+const TRAIL_SECONDS: f32 = 1.5;
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(AStarPlugin::default())
+        .add_plugin(PlaybackControlsPlugin::default())
+        .add_plugin(CellTooltipPlugin::default())
+        .add_plugin(SearchStatsPlugin::default())
+        .add_startup_system(setup)
+        .add_system(control_search)
+        .add_system(toggle_recording)
+        .run();
+}
+
+fn setup(mut commands: Commands, mut grids: ResMut<Assets<Grid>>) {
+    commands.spawn(Camera2dBundle {
+        projection: OrthographicProjection {
+            scale: 1.0 / 10.0,
+            ..default()
+        },
+        ..default()
+    });
+
+    let mut rng = rand::thread_rng();
+    let mut grid = Grid::new(GRID_WIDTH, GRID_HEIGHT);
+
+    for y in 0..GRID_HEIGHT as i32 {
+        for x in 0..GRID_WIDTH as i32 {
+            let is_wall = rng.gen_bool(WALL_DENSITY);
+            grid.set_cell(CellPos(x, y), Cell { is_wall }).unwrap();
+        }
+    }
+
+    let grid_entity = commands
+        .spawn(SpatialBundle::default())
+        .insert(Name::new("Grid editor"))
+        .id();
+
+    commands.spawn((Name::new("Search"), new_visualizer(grid_entity, &grid, SearchColoring::Sets)));
+    commands.entity(grid_entity).insert(GridEditor::new(grids.add(grid)));
+}
+
+fn new_visualizer(grid_entity: Entity, grid: &Grid, coloring: SearchColoring) -> SearchVisualizer {
+    let search = Search::new(grid, Planner::AStar, random_floor(grid), random_floor(grid));
+    SearchVisualizer::new(grid_entity, search)
+        .with_coloring(coloring)
+        .with_expansions_per_tick(EXPANSIONS_PER_TICK)
+}
+
+fn random_floor(grid: &Grid) -> CellPos {
+    let mut rng = rand::thread_rng();
+    loop {
+        let cell_pos = CellPos(
+            rng.gen_range(0..grid.width()) as i32,
+            rng.gen_range(0..grid.height()) as i32,
+        );
+        if !grid.cell(cell_pos).unwrap().is_wall {
+            return cell_pos;
+        }
+    }
+}
+
+fn control_search(keys: Res<Input<KeyCode>>, grids: Grids, mut visualizers: Query<&mut SearchVisualizer>) {
+    for mut visualizer in &mut visualizers {
+        let Ok(grid) = grids.get(visualizer.grid) else {
+            continue;
+        };
+
+        if keys.just_pressed(KeyCode::Tab) {
+            visualizer.coloring = visualizer.coloring.next();
+        }
+        if keys.just_pressed(KeyCode::T) {
+            visualizer.show_tree = !visualizer.show_tree;
+        }
+        if keys.just_pressed(KeyCode::F) {
+            visualizer.trail = match visualizer.trail > 0.0 {
+                true => 0.0,
+                false => TRAIL_SECONDS,
+            };
+        }
+        if keys.just_pressed(KeyCode::Return) {
+            let (show_tree, trail) = (visualizer.show_tree, visualizer.trail);
+            *visualizer = new_visualizer(visualizer.grid, grid, visualizer.coloring).with_tree(show_tree).with_trail(trail);
+        }
+    }
+}
+
+fn toggle_recording(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    grids: Query<(Option<&SearchRecorder>, Entity), With<GridEditor>>,
+) {
+    if !keys.just_pressed(KeyCode::R) {
+        return;
+    }
+
+    for (recorder, grid_entity) in &grids {
+        match recorder {
+            Some(recorder) => {
+                info!("recorded {} frames", recorder.frames());
+                commands.entity(grid_entity).remove::<SearchRecorder>();
+            }
+            None => {
+                commands.entity(grid_entity).insert(SearchRecorder::new("explore_frames"));
+            }
+        }
+    }
+}
@@ -0,0 +1,37 @@
+//! Answers a path request in an app with no window and no sprites, the way a
+//! server or an integration test would use the plugin.
+//!
+//! `cargo run --example headless --no-default-features`
+
+use bevy::{app::AppExit, prelude::*};
+
+use a_star::prelude::*;
+
+fn main() {
+    App::new()
+        .add_plugins(MinimalPlugins)
+        .add_plugin(AStarPlugin::headless())
+        .add_startup_system(request_path)
+        .add_system(print_answers)
+        .run();
+}
+
+fn request_path(mut commands: Commands) {
+    let grid = commands.spawn_grid(GridBuilder::new(16, 16).wall_rect(CellPos(8, 0), CellPos(8, 11))).id();
+    commands.spawn_empty().request_path(grid, CellPos(0, 0), CellPos(15, 0));
+}
+
+fn print_answers(
+    mut found: EventReader<PathFoundEvent>,
+    mut failed: EventReader<PathFailedEvent>,
+    mut exit: EventWriter<AppExit>,
+) {
+    for event in found.iter() {
+        println!("found a path of {} cells costing {}", event.path.len(), event.cost);
+        exit.send(AppExit);
+    }
+    for event in failed.iter() {
+        println!("no path: {:?}", event.reason);
+        exit.send(AppExit);
+    }
+}
@@ -0,0 +1,80 @@
+//! Runs batches of A* queries on randomly walled grids without opening a window,
+//! and reports timings per grid size and cell storage.
+//!
+//! `cargo run --release --example headless_benchmark`
+
+use std::time::Instant;
+
+use itertools::Itertools;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use a_star::prelude::*;
+
+const QUERIES_PER_GRID: usize = 200;
+const WALL_DENSITY: f64 = 0.25;
+
+fn random_grid(rng: &mut StdRng, width: u32, height: u32, storage: StorageKind) -> Grid {
+    let mut grid = Grid::with_storage(width, height, storage);
+
+    for y in 0..height as i32 {
+        for x in 0..width as i32 {
+            let is_wall = rng.gen_bool(WALL_DENSITY);
+            grid.set_cell(CellPos(x, y), Cell { is_wall }).unwrap();
+        }
+    }
+
+    grid
+}
+
+fn random_floor(rng: &mut StdRng, grid: &Grid) -> CellPos {
+    loop {
+        let cell_pos = CellPos(
+            rng.gen_range(0..grid.width()) as i32,
+            rng.gen_range(0..grid.height()) as i32,
+        );
+        if !grid.cell(cell_pos).unwrap().is_wall {
+            return cell_pos;
+        }
+    }
+}
+
+fn main() {
+    println!(
+        "{:>9} {:>8} {:>8} {:>8} {:>12} {:>12}",
+        "size", "storage", "queries", "found", "avg nodes", "avg time",
+    );
+
+    let storages = [StorageKind::Cells, StorageKind::Bitset];
+
+    for (size, storage) in [50, 100, 200, 300].into_iter().cartesian_product(storages) {
+        // Same seed for both storages, so they answer the same queries.
+        let mut rng = StdRng::seed_from_u64(size as u64);
+        let grid = random_grid(&mut rng, size, size, storage);
+        let mut a_star = AStar::new(&grid);
+
+        let mut found = 0;
+        let mut nodes_expanded = 0;
+        let started = Instant::now();
+
+        for _ in 0..QUERIES_PER_GRID {
+            let start = random_floor(&mut rng, &grid);
+            let goal = random_floor(&mut rng, &grid);
+
+            if let Some(path) = a_star.find_path(start, goal) {
+                found += 1;
+                nodes_expanded += path.nodes_expanded;
+            }
+        }
+
+        let elapsed = started.elapsed();
+        println!(
+            "{:>9} {:>8} {:>8} {:>8} {:>12} {:>12?}",
+            format!("{size}x{size}"),
+            format!("{storage:?}"),
+            QUERIES_PER_GRID,
+            found,
+            nodes_expanded / found.max(1),
+            elapsed / QUERIES_PER_GRID as u32,
+        );
+    }
+}
@@ -0,0 +1,69 @@
+//! Agents marching along their computed paths, each heading for a new random
+//! cell as soon as it arrives. Paint walls in their way with the left mouse
+//! button and erase them with the right.
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use a_star::prelude::*;
+
+const GRID_WIDTH: u32 = 40;
+const GRID_HEIGHT: u32 = 30;
+const WALKER_COLORS: [Color; 3] = [Color::GREEN, Color::YELLOW, Color::CYAN];
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(AStarPlugin::default())
+        .add_plugin(WallPaintingPlugin)
+        .add_startup_system(setup)
+        .add_system(pick_new_goals)
+        .run();
+}
+
+fn setup(mut commands: Commands) {
+    commands.spawn(Camera2dBundle {
+        projection: OrthographicProjection {
+            scale: 1.0 / 20.0,
+            ..default()
+        },
+        ..default()
+    });
+
+    let grid = GridBuilder::new(GRID_WIDTH, GRID_HEIGHT)
+        .wall_rect(CellPos(12, 0), CellPos(12, GRID_HEIGHT as i32 - 6))
+        .wall_rect(CellPos(26, 5), CellPos(26, GRID_HEIGHT as i32 - 1));
+    let grid = commands.spawn_grid(grid).insert((SpatialBundle::default(), Name::new("Grid editor"))).id();
+
+    for (index, color) in WALKER_COLORS.into_iter().enumerate() {
+        let start = CellPos(2, 2 + 4 * index as i32);
+        let speed = 4.0 + 2.0 * index as f32;
+        commands.spawn((WalkerBundle::new(grid, speed, color), Name::new("Walker"))).request_path(
+            grid,
+            start,
+            CellPos(GRID_WIDTH as i32 - 3, GRID_HEIGHT as i32 - 3),
+        );
+    }
+}
+
+fn pick_new_goals(
+    mut commands: Commands,
+    grids: Grids,
+    walkers: Query<(&PathWalker, Entity), Without<PathRequest>>,
+) {
+    let mut rng = rand::thread_rng();
+
+    for (walker, entity) in &walkers {
+        let (Some(cell_pos), true) = (walker.cell_pos(), walker.is_finished()) else {
+            continue;
+        };
+        let Ok(grid) = grids.get(walker.grid) else {
+            continue;
+        };
+
+        let goal = CellPos(rng.gen_range(0..grid.width()) as i32, rng.gen_range(0..grid.height()) as i32);
+        if grid.cell(goal).is_ok_and(|cell| !cell.is_wall) {
+            commands.entity(entity).request_path(walker.grid, cell_pos, goal);
+        }
+    }
+}
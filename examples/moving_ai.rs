@@ -0,0 +1,58 @@
+//! Runs the queries of a MovingAI scenario file on its map with each planner,
+//! or only the one named, and reports per bucket how many paths were optimal
+//! and how long the searches took.
+//!
+//! `cargo run --release --example moving_ai -- arena.map arena.map.scen [planner]`
+
+use std::{fs::File, io::BufReader, process::ExitCode};
+
+use a_star::{moving_ai, prelude::*};
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [map_path, scen_path, rest @ ..] = &args[..] else {
+        eprintln!("usage: moving_ai <map file> <scenario file> [planner]");
+        return ExitCode::FAILURE;
+    };
+    let planners: Vec<Planner> = match rest.first() {
+        Some(name) => Planner::ALL.into_iter().filter(|planner| planner.name().eq_ignore_ascii_case(name)).collect(),
+        None => Planner::ALL.to_vec(),
+    };
+    if planners.is_empty() {
+        let names: Vec<&str> = Planner::ALL.iter().map(|planner| planner.name()).collect();
+        eprintln!("unknown planner, expected one of: {}", names.join(", "));
+        return ExitCode::FAILURE;
+    }
+
+    let grid = match File::open(map_path)
+        .map_err(MapError::from)
+        .and_then(|file| moving_ai::load_map(BufReader::new(file)))
+    {
+        Ok(grid) => grid,
+        Err(err) => {
+            eprintln!("{map_path}: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let scenarios = match File::open(scen_path)
+        .map_err(MapError::from)
+        .and_then(|file| moving_ai::load_scenarios(BufReader::new(file)))
+    {
+        Ok(scenarios) => scenarios,
+        Err(err) => {
+            eprintln!("{scen_path}: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    for mut planner in planners {
+        match moving_ai::run_scenarios(&grid, &scenarios, &mut planner) {
+            Ok(report) => println!("{} ({} queries)\n{report}", planner.name(), report.queries()),
+            Err(err) => {
+                eprintln!("{err}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,79 @@
+//! An empty grid driven from the control panel: pick a planner, heuristic and
+//! connectivity, randomize walls, generate mazes, caves, drunkard's walks,
+//! scattered or BSP dungeons, maps by wave function collapse or terrain from
+//! noise and start searches from the window. Paint walls with the left mouse
+//! button and erase them with the right; the bracket keys resize the brush,
+//! backslash switches its shape, and keys 1 to 8 pick the brush, line,
+//! rectangle, filled rectangle, fill, select, magic wand and stamp tools.
+//! Ctrl+C copies the selection and Ctrl+V stamps it, turned with R and mirrored
+//! with M. Ctrl+Backspace, Ctrl+F and Ctrl+I clear, fill and invert the grid,
+//! Ctrl+T paints it with the brush's terrain, Ctrl+R randomizes its walls and
+//! Ctrl+M turns it into a maze; with a selection, only the selected cells,
+//! until Escape drops it. Y mirrors what is painted left to right, top to
+//! bottom, both ways or not at all. Ctrl+Z undoes a stroke, fill or randomized
+//! grid, and Ctrl+Y redoes it. Shift+S and Shift+G move the start and goal of
+//! the searches under the cursor, and dragging them redraws the path between
+//! them as they go. The swatches in the bottom right corner, or T, switch the
+//! brush between walls and road, grass, mud and water, which the path avoids
+//! the dearer they are. P picks the stamp with the next obstacle of the pattern
+//! library, which has the shapes in `patterns.txt` added to the built-in ones
+//! when there is such a file.
+
+use std::path::Path;
+
+use bevy::prelude::*;
+
+use a_star::prelude::*;
+
+const GRID_WIDTH: u32 = 80;
+const GRID_HEIGHT: u32 = 60;
+const PATTERNS_PATH: &str = "patterns.txt";
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(AStarPlugin::default())
+        .add_plugin(ControlPanelPlugin)
+        .add_plugin(WallPaintingPlugin)
+        .add_plugin(UndoPlugin)
+        .add_plugin(MarkerPlacementPlugin)
+        .add_plugin(TerrainPalettePlugin::default())
+        .add_plugin(CellTooltipPlugin::default())
+        .add_startup_system(setup)
+        .add_startup_system(load_patterns)
+        .run();
+}
+
+fn load_patterns(mut library: ResMut<PatternLibrary>, mut console: ResMut<ErrorConsole>) {
+    if !Path::new(PATTERNS_PATH).exists() {
+        return;
+    }
+    if let Err(error) = library.load(PATTERNS_PATH) {
+        console.report("loading patterns", &error);
+    }
+}
+
+fn setup(mut commands: Commands, mut grids: ResMut<Assets<Grid>>) {
+    commands.spawn(Camera2dBundle {
+        projection: OrthographicProjection {
+            scale: 1.0 / 10.0,
+            ..default()
+        },
+        ..default()
+    });
+
+    let grid = commands
+        .spawn((
+            SpatialBundle::default(),
+            Name::new("Grid editor"),
+            GridEditor::new(grids.add(Grid::new(GRID_WIDTH, GRID_HEIGHT))),
+            GridJournal::new(),
+            UndoStack::new(),
+        ))
+        .id();
+
+    let goal = CellPos(GRID_WIDTH as i32 - 1, GRID_HEIGHT as i32 - 1);
+    commands.spawn((Name::new("Start marker"), EndpointMarker::start(grid, CellPos(0, 0))));
+    commands.spawn((Name::new("Goal marker"), EndpointMarker::goal(grid, goal)));
+    commands.spawn((Name::new("Marked path"), MarkedPath::new(grid)));
+}
@@ -0,0 +1,109 @@
+//! Two planners racing on the same query: A* in orange and Dijkstra in cyan.
+//! Press space to restart with new endpoints, and tab to swap in greedy
+//! best-first as the second racer.
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use a_star::prelude::*;
+
+const GRID_WIDTH: u32 = 80;
+const GRID_HEIGHT: u32 = 60;
+const WALL_DENSITY: f64 = 0.25;
+
+const FIRST_TINT: Color = Color::ORANGE;
+const SECOND_TINT: Color = Color::CYAN;
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(AStarPlugin::default())
+        .add_startup_system(setup)
+        .add_system(restart_race)
+        .run();
+}
+
+#[derive(Resource)]
+struct Contenders([Planner; 2]);
+
+fn setup(mut commands: Commands, mut grids: ResMut<Assets<Grid>>) {
+    commands.spawn(Camera2dBundle {
+        projection: OrthographicProjection {
+            scale: 1.0 / 10.0,
+            ..default()
+        },
+        ..default()
+    });
+
+    let mut rng = rand::thread_rng();
+    let mut grid = Grid::new(GRID_WIDTH, GRID_HEIGHT);
+
+    for y in 0..GRID_HEIGHT as i32 {
+        for x in 0..GRID_WIDTH as i32 {
+            let is_wall = rng.gen_bool(WALL_DENSITY);
+            grid.set_cell(CellPos(x, y), Cell { is_wall }).unwrap();
+        }
+    }
+
+    let contenders = [Planner::AStar, Planner::Dijkstra];
+    let (start, goal) = (random_floor(&grid), random_floor(&grid));
+
+    let grid_entity = commands
+        .spawn(SpatialBundle::default())
+        .insert(Name::new("Grid editor"))
+        .id();
+
+    commands.spawn((Name::new("Race"), new_race(grid_entity, &grid, contenders, start, goal)));
+    commands.entity(grid_entity).insert(GridEditor::new(grids.add(grid)));
+    commands.insert_resource(Contenders(contenders));
+}
+
+fn new_race(grid_entity: Entity, grid: &Grid, contenders: [Planner; 2], start: CellPos, goal: CellPos) -> Race {
+    let [first, second] = contenders;
+    Race::new(grid_entity, grid, [(first, FIRST_TINT), (second, SECOND_TINT)], start, goal)
+}
+
+fn random_floor(grid: &Grid) -> CellPos {
+    let mut rng = rand::thread_rng();
+    loop {
+        let cell_pos = CellPos(
+            rng.gen_range(0..grid.width()) as i32,
+            rng.gen_range(0..grid.height()) as i32,
+        );
+        if !grid.cell(cell_pos).unwrap().is_wall {
+            return cell_pos;
+        }
+    }
+}
+
+fn restart_race(
+    keys: Res<Input<KeyCode>>,
+    grids: Grids,
+    mut contenders: ResMut<Contenders>,
+    mut races: Query<&mut Race>,
+) {
+    let swap = keys.just_pressed(KeyCode::Tab);
+    if !swap && !keys.just_pressed(KeyCode::Space) {
+        return;
+    }
+
+    if swap {
+        contenders.0[1] = match contenders.0[1] {
+            Planner::Dijkstra => Planner::GreedyBestFirst,
+            _ => Planner::Dijkstra,
+        };
+    }
+
+    for mut race in &mut races {
+        let grid = grids.get(race.grid).unwrap();
+
+        // Keep the endpoints when only the contenders change.
+        let search = &race.racers()[0].search;
+        let (start, goal) = match swap {
+            true => (search.start(), search.goal()),
+            false => (random_floor(grid), random_floor(grid)),
+        };
+
+        *race = new_race(race.grid, grid, contenders.0, start, goal);
+    }
+}
@@ -0,0 +1,96 @@
+//! A large grid editor whose walls are laid out at random, with the world
+//! inspector and frame time diagnostics, to watch how the views keep up with a
+//! whole grid changing at once. R randomizes the walls again, and minus and
+//! equals lower and raise the share of walls by 5%. WASD, middle mouse drag and
+//! the scroll wheel move the camera, and the minimap in the corner shows where
+//! it is. G toggles the lines between cells, which show once zoomed in far
+//! enough. Given a seed, the walls are laid out the same on every run.
+//!
+//! `cargo run --release --example random_walls [seed]`
+
+use bevy::{prelude::*, diagnostic::{LogDiagnosticsPlugin, FrameTimeDiagnosticsPlugin}};
+use bevy_inspector_egui::quick::WorldInspectorPlugin;
+
+use a_star::prelude::*;
+
+fn main() {
+    let map_rng = match std::env::args().nth(1) {
+        Some(seed) => MapRng::new(seed.parse().expect("seed must be a number")),
+        None => MapRng::default(),
+    };
+
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .insert_resource(map_rng)
+        .add_plugin(WorldInspectorPlugin)
+        .add_plugin(AStarPlugin::default())
+        .add_plugin(ErrorConsolePlugin::default())
+        .add_plugin(GridCameraPlugin)
+        .add_plugin(MinimapPlugin)
+        .add_startup_system(spawn_grid)
+        .insert_resource(WallDensity(0.3))
+        .add_system(randomize_walls.before(GridEditSet))
+        .add_system(toggle_grid_lines)
+        .add_plugin(LogDiagnosticsPlugin::default())
+        .add_plugin(FrameTimeDiagnosticsPlugin)
+        .run();
+}
+
+// #[derive(Component)]
+// struct AStartArc(Arc<AStar>);
+
+fn spawn_grid(mut commands: Commands, mut grids: ResMut<Assets<Grid>>) {
+    let grid = Grid::new(300, 300);
+
+    let grid_editor = GridEditor::new(grids.add(grid));
+
+    
+
+    let grid_entity = commands
+        .spawn(SpatialBundle::default())
+        .insert(Name::new("Grid editor"))
+        .insert(grid_editor)
+        .id();
+
+    let camera = commands.spawn((Camera2dBundle::default(), GridCamera::new(grid_entity))).id();
+    commands.spawn((MinimapBundle::new(grid_entity, camera), Name::new("Minimap")));
+}
+
+/// Share of the cells `randomize_walls` makes walls.
+#[derive(Resource)]
+struct WallDensity(f64);
+
+fn randomize_walls(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    mut density: ResMut<WallDensity>,
+    grid_editors: Query<Entity, With<GridEditor>>,
+    added: Query<Entity, Added<GridEditor>>,
+) {
+    let mut changed = false;
+    if keys.just_pressed(KeyCode::Minus) {
+        density.0 = (density.0 - 0.05).max(0.0);
+        changed = true;
+    }
+    if keys.just_pressed(KeyCode::Equals) {
+        density.0 = (density.0 + 0.05).min(1.0);
+        changed = true;
+    }
+    if changed {
+        info!("wall density {:.0}%", density.0 * 100.0);
+    }
+
+    let randomized: Vec<Entity> = match changed || keys.just_pressed(KeyCode::R) {
+        true => grid_editors.iter().collect(),
+        false => added.iter().collect(),
+    };
+    for entity in randomized {
+        commands.entity(entity).insert(RandomizeWalls::new(density.0));
+    }
+}
+
+fn toggle_grid_lines(keys: Res<Input<KeyCode>>, mut style: ResMut<GridLineStyle>) {
+    if keys.just_pressed(KeyCode::G) {
+        style.visible = !style.visible;
+    }
+}
@@ -0,0 +1,169 @@
+//! Runs a headless app for a long time, continuously editing grids, searching
+//! them, and spawning and despawning grids and agents, while `SoakMonitorPlugin`
+//! watches for counters that keep growing. Prints the counters at the end and
+//! exits with an error if any of them leaked.
+//!
+//! `cargo run --release --example soak [minutes]`
+
+use std::time::Duration;
+
+use bevy::{app::AppExit, prelude::*};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use a_star::prelude::*;
+
+const MAX_GRIDS: usize = 4;
+const MAX_AGENTS: usize = 32;
+const SEARCHES_PER_FRAME: usize = 8;
+const MAX_JOURNAL_ENTRIES: usize = 1_000;
+
+#[derive(Resource)]
+struct Soak {
+    rng: StdRng,
+    duration: Duration,
+    grids: Vec<Entity>,
+}
+
+#[derive(Component)]
+struct Agent {
+    grid: Entity,
+    expires: f64,
+}
+
+fn main() {
+    let minutes: u64 = std::env::args()
+        .nth(1)
+        .map(|arg| arg.parse().expect("minutes must be a number"))
+        .unwrap_or(10);
+    let duration = Duration::from_secs(minutes * 60);
+
+    let mut app = App::new();
+    app
+        .add_plugins(MinimalPlugins)
+        .add_plugin(AStarPlugin::default())
+        .add_plugin(SoakMonitorPlugin { interval: Some((duration / 200).max(Duration::from_millis(100))) })
+        .insert_resource(Soak { rng: StdRng::seed_from_u64(0), duration, grids: Vec::new() })
+        .add_system(churn_grids)
+        .add_system(edit_grids)
+        .add_system(churn_agents)
+        .add_system(search_grids)
+        .add_system(stop_after_duration);
+    app.run();
+
+    let monitor = app.world.resource::<SoakMonitor>();
+    println!("{}", monitor.summary());
+
+    let growing = monitor.growing();
+    for growth in &growing {
+        println!("leak: {growth}");
+    }
+    if !growing.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+fn random_cell(rng: &mut StdRng, grid: &Grid) -> CellPos {
+    CellPos(rng.gen_range(0..grid.width()) as i32, rng.gen_range(0..grid.height()) as i32)
+}
+
+/// Replaces the oldest grid with a new one of random size every so often.
+fn churn_grids(mut commands: Commands, mut soak: ResMut<Soak>, mut grids: ResMut<Assets<Grid>>) {
+    if soak.grids.len() == MAX_GRIDS && !soak.rng.gen_bool(0.01) {
+        return;
+    }
+    if soak.grids.len() == MAX_GRIDS {
+        let oldest = soak.grids.remove(0);
+        commands.entity(oldest).despawn_recursive();
+    }
+
+    let (width, height) = (soak.rng.gen_range(10..60), soak.rng.gen_range(10..60));
+    let mut builder = GridBuilder::new(width, height);
+    for _ in 0..width * height / 4 {
+        let cell_pos = CellPos(soak.rng.gen_range(0..width) as i32, soak.rng.gen_range(0..height) as i32);
+        builder = builder.wall(cell_pos);
+    }
+
+    let grid = commands
+        .spawn(SpatialBundle::default())
+        .insert(GridEditor::new(grids.add(builder.build())))
+        .insert(GridJournal::new())
+        .insert(ThreatOverlay::new(Color::ORANGE))
+        .id();
+    soak.grids.push(grid);
+}
+
+/// Toggles a random cell of every grid and trims the journals.
+fn edit_grids(
+    mut commands: Commands,
+    mut soak: ResMut<Soak>,
+    grids: Grids,
+    mut journals: Query<(&mut GridJournal, Entity)>,
+) {
+    for (mut journal, entity) in &mut journals {
+        let Ok(grid) = grids.get(entity) else {
+            continue;
+        };
+        let cell_pos = random_cell(&mut soak.rng, grid);
+        commands.entity(entity).insert(UseTool::new(cell_pos));
+
+        let next_seq = journal.next_seq();
+        journal.truncate_before(next_seq.saturating_sub(MAX_JOURNAL_ENTRIES as u64));
+    }
+}
+
+/// Spawns agents with overlays on random grids and despawns them when they
+/// expire or their grid is gone.
+fn churn_agents(
+    mut commands: Commands,
+    mut soak: ResMut<Soak>,
+    time: Res<Time>,
+    grids: Grids,
+    agents: Query<(&Agent, Entity)>,
+) {
+    let now = time.elapsed_seconds_f64();
+    for (agent, entity) in &agents {
+        if agent.expires < now || grids.get(agent.grid).is_err() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    if agents.iter().len() >= MAX_AGENTS || soak.grids.is_empty() {
+        return;
+    }
+
+    let soak = &mut *soak;
+    let grid_entity = soak.grids[soak.rng.gen_range(0..soak.grids.len())];
+    let Ok(grid) = grids.get(grid_entity) else {
+        // Spawned this frame; its commands haven't been applied yet.
+        return;
+    };
+    let start = random_cell(&mut soak.rng, grid);
+    let goal = random_cell(&mut soak.rng, grid);
+
+    commands.spawn((
+        Agent { grid: grid_entity, expires: now + soak.rng.gen_range(0.5..5.0) },
+        RangeHighlight::new(grid_entity, AStar::new(grid).reachable_within(start, 6.0), Color::GREEN),
+        ThreatSource { grid: grid_entity, cell_pos: start, movement: 4.0, attack_radius: 1 },
+        PathStream::new(grid_entity, start, goal),
+    ));
+}
+
+fn search_grids(mut soak: ResMut<Soak>, grids: Grids) {
+    let soak = &mut *soak;
+    for &grid_entity in &soak.grids {
+        let Ok(grid) = grids.get(grid_entity) else {
+            continue;
+        };
+
+        for _ in 0..SEARCHES_PER_FRAME {
+            let (start, goal) = (random_cell(&mut soak.rng, grid), random_cell(&mut soak.rng, grid));
+            grids.find_path(grid_entity, start, goal).expect("looked up above");
+        }
+    }
+}
+
+fn stop_after_duration(soak: Res<Soak>, time: Res<Time>, mut exit: EventWriter<AppExit>) {
+    if time.elapsed() >= soak.duration {
+        exit.send(AppExit);
+    }
+}
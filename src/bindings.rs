@@ -0,0 +1,231 @@
+//! The keys and mouse buttons the built-in plugins respond to.
+//!
+//! Every plugin that takes input reads it through the [`InputBindings`]
+//! resource, so an app embedding the editor can remap the controls by
+//! inserting its own before adding the plugins, or by changing it at runtime.
+//! The bindings are reflected, so they can be saved and loaded with Bevy's
+//! reflection serializers like the rest of the registered types.
+
+use std::fmt::Display;
+
+use bevy::prelude::*;
+
+/// A key pressed with exactly the modifiers given held, so Ctrl+Z doesn't also
+/// fire Ctrl+Shift+Z, nor Z alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Reflect, FromReflect)]
+pub struct KeyChord {
+    pub key: KeyCode,
+    pub ctrl: bool,
+    pub shift: bool,
+}
+
+impl KeyChord {
+    pub const fn key(key: KeyCode) -> Self {
+        KeyChord { key, ctrl: false, shift: false }
+    }
+
+    pub const fn ctrl(key: KeyCode) -> Self {
+        KeyChord { key, ctrl: true, shift: false }
+    }
+
+    pub const fn shift(key: KeyCode) -> Self {
+        KeyChord { key, ctrl: false, shift: true }
+    }
+
+    pub const fn ctrl_shift(key: KeyCode) -> Self {
+        KeyChord { key, ctrl: true, shift: true }
+    }
+
+    fn modifiers_held(&self, keys: &Input<KeyCode>) -> bool {
+        let ctrl = keys.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+        let shift = keys.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+        ctrl == self.ctrl && shift == self.shift
+    }
+
+    pub fn just_pressed(&self, keys: &Input<KeyCode>) -> bool {
+        keys.just_pressed(self.key) && self.modifiers_held(keys)
+    }
+
+    pub fn pressed(&self, keys: &Input<KeyCode>) -> bool {
+        keys.pressed(self.key) && self.modifiers_held(keys)
+    }
+}
+
+/// As shown in hints, e.g. `Ctrl+Shift+Z`.
+impl Display for KeyChord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl+")?;
+        }
+        if self.shift {
+            write!(f, "Shift+")?;
+        }
+        write!(f, "{:?}", self.key)
+    }
+}
+
+/// The chords that trigger one action; any of them does, and none disables it.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Reflect, FromReflect)]
+pub struct KeyBinding(pub Vec<KeyChord>);
+
+impl KeyBinding {
+    pub fn new(chords: impl IntoIterator<Item = KeyChord>) -> Self {
+        KeyBinding(chords.into_iter().collect())
+    }
+
+    /// A binding to `key` alone, without modifiers.
+    pub fn key(key: KeyCode) -> Self {
+        KeyBinding(vec![KeyChord::key(key)])
+    }
+
+    pub fn just_pressed(&self, keys: &Input<KeyCode>) -> bool {
+        self.0.iter().any(|chord| chord.just_pressed(keys))
+    }
+
+    pub fn pressed(&self, keys: &Input<KeyCode>) -> bool {
+        self.0.iter().any(|chord| chord.pressed(keys))
+    }
+}
+
+/// Its chords separated by slashes, e.g. `Ctrl+Y/Ctrl+Shift+Z`, or `unbound`.
+impl Display for KeyBinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "unbound");
+        }
+        for (index, chord) in self.0.iter().enumerate() {
+            if index > 0 {
+                write!(f, "/")?;
+            }
+            write!(f, "{chord}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The controls of every built-in plugin. The defaults are the controls the
+/// plugins document.
+#[derive(Resource, Debug, Clone, PartialEq, Reflect)]
+#[reflect(Resource)]
+pub struct InputBindings {
+    // `GridCameraPlugin`
+    pub pan_left: KeyBinding,
+    pub pan_right: KeyBinding,
+    pub pan_up: KeyBinding,
+    pub pan_down: KeyBinding,
+    pub drag_camera: MouseButton,
+
+    // `WallPaintingPlugin`
+    pub paint: MouseButton,
+    pub erase: MouseButton,
+    /// Pick the tools of `PaintTool::ALL`, in order.
+    pub paint_tools: Vec<KeyBinding>,
+    /// Pick the tools of `EditorTools`, in order, to paint with.
+    pub editor_tools: Vec<KeyBinding>,
+    pub shrink_brush: KeyBinding,
+    pub grow_brush: KeyBinding,
+    pub switch_brush_shape: KeyBinding,
+    pub cycle_symmetry: KeyBinding,
+    pub copy_selection: KeyBinding,
+    pub pick_stamp: KeyBinding,
+    pub next_pattern: KeyBinding,
+    pub rotate_stamp: KeyBinding,
+    pub mirror_stamp: KeyBinding,
+    pub clear_walls: KeyBinding,
+    pub fill_walls: KeyBinding,
+    pub invert_walls: KeyBinding,
+    pub set_terrain: KeyBinding,
+    pub randomize_walls: KeyBinding,
+    pub generate_maze: KeyBinding,
+    pub clear_selection: KeyBinding,
+
+    // `TerrainPalettePlugin`
+    pub cycle_terrain: KeyBinding,
+
+    // `MarkerPlacementPlugin`
+    pub place_start: KeyBinding,
+    pub place_goal: KeyBinding,
+    pub drag_marker: MouseButton,
+
+    // `UndoPlugin`
+    pub undo: KeyBinding,
+    pub redo: KeyBinding,
+
+    // `MapFilePlugin`
+    pub save_map: KeyBinding,
+    pub open_map: KeyBinding,
+
+    // `PathExportPlugin`
+    pub export_path: KeyBinding,
+
+    // `PlaybackControlsPlugin`
+    pub toggle_playback: KeyBinding,
+    pub step_playback: KeyBinding,
+    pub slower_playback: KeyBinding,
+    pub faster_playback: KeyBinding,
+
+    // `ErrorConsolePlugin`
+    pub toggle_console: KeyBinding,
+    /// Only while the console is shown.
+    pub save_console: KeyBinding,
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        use KeyCode::*;
+
+        InputBindings {
+            pan_left: KeyBinding::new([KeyChord::key(A), KeyChord::key(Left)]),
+            pan_right: KeyBinding::new([KeyChord::key(D), KeyChord::key(Right)]),
+            pan_up: KeyBinding::new([KeyChord::key(W), KeyChord::key(Up)]),
+            pan_down: KeyBinding::new([KeyChord::key(S), KeyChord::key(Down)]),
+            drag_camera: MouseButton::Middle,
+
+            paint: MouseButton::Left,
+            erase: MouseButton::Right,
+            paint_tools: [Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8].into_iter().map(KeyBinding::key).collect(),
+            editor_tools: [Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8]
+                .into_iter()
+                .map(|key| KeyBinding::new([KeyChord::shift(key)]))
+                .collect(),
+            shrink_brush: KeyBinding::key(LBracket),
+            grow_brush: KeyBinding::key(RBracket),
+            switch_brush_shape: KeyBinding::key(Backslash),
+            cycle_symmetry: KeyBinding::key(Y),
+            copy_selection: KeyBinding::new([KeyChord::ctrl(C)]),
+            pick_stamp: KeyBinding::new([KeyChord::ctrl(V)]),
+            next_pattern: KeyBinding::key(P),
+            rotate_stamp: KeyBinding::key(R),
+            mirror_stamp: KeyBinding::key(M),
+            clear_walls: KeyBinding::new([KeyChord::ctrl(Back)]),
+            fill_walls: KeyBinding::new([KeyChord::ctrl(F)]),
+            invert_walls: KeyBinding::new([KeyChord::ctrl(I)]),
+            set_terrain: KeyBinding::new([KeyChord::ctrl(T)]),
+            randomize_walls: KeyBinding::new([KeyChord::ctrl(R)]),
+            generate_maze: KeyBinding::new([KeyChord::ctrl(M)]),
+            clear_selection: KeyBinding::key(Escape),
+
+            cycle_terrain: KeyBinding::key(T),
+
+            place_start: KeyBinding::new([KeyChord::shift(S)]),
+            place_goal: KeyBinding::new([KeyChord::shift(G)]),
+            drag_marker: MouseButton::Left,
+
+            undo: KeyBinding::new([KeyChord::ctrl(Z)]),
+            redo: KeyBinding::new([KeyChord::ctrl(Y), KeyChord::ctrl_shift(Z)]),
+
+            save_map: KeyBinding::new([KeyChord::ctrl(S)]),
+            open_map: KeyBinding::new([KeyChord::ctrl(O)]),
+
+            export_path: KeyBinding::new([KeyChord::ctrl(E)]),
+
+            toggle_playback: KeyBinding::key(Space),
+            step_playback: KeyBinding::key(Period),
+            slower_playback: KeyBinding::new([KeyChord::key(Minus), KeyChord::key(NumpadSubtract)]),
+            faster_playback: KeyBinding::new([KeyChord::key(Equals), KeyChord::key(NumpadAdd)]),
+
+            toggle_console: KeyBinding::key(F12),
+            save_console: KeyBinding::key(S),
+        }
+    }
+}
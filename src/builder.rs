@@ -0,0 +1,160 @@
+//! Fluent construction of grids.
+//!
+//! ```ignore
+//! let grid = GridBuilder::new(300, 300)
+//!     .fill(Cell::floor())
+//!     .wall_rect(CellPos(10, 10), CellPos(20, 12))
+//!     .border_walls()
+//!     .build();
+//! ```
+//!
+//! Shapes are painted in the order they were added, on top of the fill. Parts of
+//! a shape that fall outside the grid are ignored.
+
+use crate::{
+    grid::{Boundary, Cell, CellPos, Connectivity, Exits, Grid},
+    storage::StorageKind,
+};
+
+#[derive(Debug, Clone)]
+enum Paint {
+    Rect { a: CellPos, b: CellPos, cell: Cell },
+    Border(Cell),
+    Cell(CellPos, Cell),
+    Portal { a: CellPos, b: CellPos, cost: f32 },
+    Exits(CellPos, Exits),
+}
+
+#[derive(Debug, Clone)]
+pub struct GridBuilder {
+    width: u32,
+    height: u32,
+    storage: StorageKind,
+    boundary: Boundary,
+    connectivity: Connectivity,
+    fill: Cell,
+    paint: Vec<Paint>,
+}
+
+impl GridBuilder {
+    pub fn new(width: u32, height: u32) -> Self {
+        GridBuilder {
+            width,
+            height,
+            storage: StorageKind::default(),
+            boundary: Boundary::default(),
+            connectivity: Connectivity::default(),
+            fill: Cell::floor(),
+            paint: Vec::new(),
+        }
+    }
+
+    pub fn storage(mut self, storage: StorageKind) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    pub fn boundary(mut self, boundary: Boundary) -> Self {
+        self.boundary = boundary;
+        self
+    }
+
+    pub fn connectivity(mut self, connectivity: Connectivity) -> Self {
+        self.connectivity = connectivity;
+        self
+    }
+
+    /// The cell every position starts as, before any shape is painted.
+    pub fn fill(mut self, cell: Cell) -> Self {
+        self.fill = cell;
+        self
+    }
+
+    /// Paints every cell between the corners `a` and `b`, both included.
+    pub fn rect(mut self, a: CellPos, b: CellPos, cell: Cell) -> Self {
+        self.paint.push(Paint::Rect { a, b, cell });
+        self
+    }
+
+    pub fn wall_rect(self, a: CellPos, b: CellPos) -> Self {
+        self.rect(a, b, Cell::wall())
+    }
+
+    pub fn floor_rect(self, a: CellPos, b: CellPos) -> Self {
+        self.rect(a, b, Cell::floor())
+    }
+
+    /// Walls along the outermost ring of cells.
+    pub fn border_walls(mut self) -> Self {
+        self.paint.push(Paint::Border(Cell::wall()));
+        self
+    }
+
+    pub fn cell(mut self, cell_pos: CellPos, cell: Cell) -> Self {
+        self.paint.push(Paint::Cell(cell_pos, cell));
+        self
+    }
+
+    pub fn wall(self, cell_pos: CellPos) -> Self {
+        self.cell(cell_pos, Cell::wall())
+    }
+
+    /// Links `a` and `b` in both directions, like `Grid::link_portals`.
+    pub fn portal(mut self, a: CellPos, b: CellPos, cost: f32) -> Self {
+        self.paint.push(Paint::Portal { a, b, cost });
+        self
+    }
+
+    pub fn exits(mut self, cell_pos: CellPos, exits: Exits) -> Self {
+        self.paint.push(Paint::Exits(cell_pos, exits));
+        self
+    }
+
+    pub fn build(self) -> Grid {
+        // Growing an empty grid is the one way to start from a fill other than floor.
+        let mut grid = Grid::with_storage(0, 0, self.storage);
+        grid.resize(self.width, self.height, self.fill);
+        grid.set_boundary(self.boundary).set_connectivity(self.connectivity);
+
+        for paint in self.paint {
+            match paint {
+                Paint::Rect { a, b, cell } => {
+                    let (CellPos(x0, y0), CellPos(x1, y1)) = (a, b);
+                    for y in y0.min(y1)..=y0.max(y1) {
+                        for x in x0.min(x1)..=x0.max(x1) {
+                            let _ = grid.set_cell(CellPos(x, y), cell);
+                        }
+                    }
+                }
+                Paint::Border(cell) => {
+                    let (right, top) = (grid.width() as i32 - 1, grid.height() as i32 - 1);
+                    for x in 0..=right {
+                        let _ = grid.set_cell(CellPos(x, 0), cell);
+                        let _ = grid.set_cell(CellPos(x, top), cell);
+                    }
+                    for y in 0..=top {
+                        let _ = grid.set_cell(CellPos(0, y), cell);
+                        let _ = grid.set_cell(CellPos(right, y), cell);
+                    }
+                }
+                Paint::Cell(cell_pos, cell) => {
+                    let _ = grid.set_cell(cell_pos, cell);
+                }
+                Paint::Portal { a, b, cost } => {
+                    let _ = grid.link_portals(a, b, cost);
+                }
+                Paint::Exits(cell_pos, exits) => {
+                    let _ = grid.set_exits(cell_pos, exits);
+                }
+            }
+        }
+
+        grid
+    }
+}
+
+impl From<GridBuilder> for Grid {
+    fn from(builder: GridBuilder) -> Self {
+        builder.build()
+    }
+}
@@ -0,0 +1,162 @@
+//! Panning and zooming a 2D camera over a grid.
+//!
+//! Add [`GridCameraPlugin`] and put a [`GridCamera`] next to a `Camera2dBundle`.
+//! WASD or the arrow keys pan, as does dragging with the middle mouse button,
+//! and the scroll wheel zooms towards the cursor. The center of the view never
+//! leaves the grid. The keys and button can be changed in the `InputBindings`.
+
+use bevy::{
+    ecs::system::SystemParam,
+    input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel},
+    prelude::*,
+};
+
+use crate::{
+    bindings::{InputBindings, KeyBinding},
+    grid::Grids,
+    view::grid_bounds,
+};
+
+/// Scroll distance, in pixels, that counts as one line of the wheel.
+const PIXELS_PER_LINE: f32 = 16.0;
+
+/// Moves the 2D camera it is on over the cells of `grid`.
+#[derive(Component, Debug, Clone)]
+pub struct GridCamera {
+    pub grid: Entity,
+    /// Screen pixels per second the keys pan by, whatever the zoom.
+    pub pan_speed: f32,
+    /// Fraction the projection scale shrinks by per line scrolled up.
+    pub zoom_step: f32,
+    /// Smallest projection scale, the closest zoom.
+    pub min_scale: f32,
+    /// Largest projection scale, the farthest zoom.
+    pub max_scale: f32,
+}
+
+impl GridCamera {
+    pub fn new(grid: Entity) -> Self {
+        GridCamera { grid, pan_speed: 600.0, zoom_step: 0.1, min_scale: 1.0 / 100.0, max_scale: 1.0 }
+    }
+
+    pub fn with_pan_speed(mut self, pan_speed: f32) -> Self {
+        self.pan_speed = pan_speed;
+        self
+    }
+
+    pub fn with_zoom_step(mut self, zoom_step: f32) -> Self {
+        self.zoom_step = zoom_step;
+        self
+    }
+
+    pub fn with_scale_range(mut self, min_scale: f32, max_scale: f32) -> Self {
+        self.min_scale = min_scale;
+        self.max_scale = max_scale;
+        self
+    }
+}
+
+/// Moves every [`GridCamera`] with the keyboard and mouse.
+pub struct GridCameraPlugin;
+
+impl Plugin for GridCameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InputBindings>().add_system(control_grid_cameras);
+    }
+}
+
+/// The input a [`GridCamera`] follows.
+#[derive(SystemParam)]
+struct CameraInput<'w, 's> {
+    time: Res<'w, Time>,
+    keys: Res<'w, Input<KeyCode>>,
+    buttons: Res<'w, Input<MouseButton>>,
+    bindings: Res<'w, InputBindings>,
+    motion: EventReader<'w, 's, MouseMotion>,
+    wheel: EventReader<'w, 's, MouseWheel>,
+    windows: Res<'w, Windows>,
+}
+
+impl CameraInput<'_, '_> {
+    /// Direction the keys pan towards, not normalized.
+    fn key_direction(&self) -> Vec2 {
+        let pressed = |binding: &KeyBinding| binding.pressed(&self.keys) as i32 as f32;
+        let bindings = &self.bindings;
+
+        Vec2::new(
+            pressed(&bindings.pan_right) - pressed(&bindings.pan_left),
+            pressed(&bindings.pan_up) - pressed(&bindings.pan_down),
+        )
+    }
+
+    /// Screen pixels dragged with the middle button since last frame, y up.
+    fn drag(&mut self) -> Vec2 {
+        let dragging = self.buttons.pressed(self.bindings.drag_camera);
+        let delta: Vec2 = self.motion.iter().map(|motion| motion.delta).sum();
+
+        match dragging {
+            true => Vec2::new(delta.x, -delta.y),
+            false => Vec2::ZERO,
+        }
+    }
+
+    /// Lines scrolled since last frame, positive up.
+    fn scroll(&mut self) -> f32 {
+        self.wheel
+            .iter()
+            .map(|wheel| match wheel.unit {
+                MouseScrollUnit::Line => wheel.y,
+                MouseScrollUnit::Pixel => wheel.y / PIXELS_PER_LINE,
+            })
+            .sum()
+    }
+
+    /// The cursor's offset from the center of the primary window, in screen pixels, y up.
+    fn cursor_offset(&self) -> Option<Vec2> {
+        let window = self.windows.get_primary()?;
+        let cursor = window.cursor_position()?;
+        Some(cursor - Vec2::new(window.width(), window.height()) / 2.0)
+    }
+}
+
+fn control_grid_cameras(
+    mut input: CameraInput,
+    grids: Grids,
+    grid_transforms: Query<&GlobalTransform>,
+    mut cameras: Query<(&GridCamera, &mut Transform, &mut OrthographicProjection)>,
+) {
+    let key_direction = input.key_direction();
+    let drag = input.drag();
+    let scroll = input.scroll();
+    let cursor_offset = input.cursor_offset();
+
+    for (camera, mut transform, mut projection) in &mut cameras {
+        let Ok(grid) = grids.get(camera.grid) else {
+            continue;
+        };
+
+        let mut center = transform.translation.truncate();
+        let pan = key_direction * camera.pan_speed * input.time.delta_seconds() - drag;
+        center += pan * projection.scale;
+
+        if scroll != 0.0 {
+            let scale = (projection.scale * (1.0 - camera.zoom_step).powf(scroll)).clamp(camera.min_scale, camera.max_scale);
+
+            // Keep the point under the cursor where it is.
+            if let Some(offset) = cursor_offset {
+                center += offset * (projection.scale - scale);
+            }
+            if scale != projection.scale {
+                projection.scale = scale;
+            }
+        }
+
+        let origin = grid_transforms.get(camera.grid).map_or(Vec3::ZERO, GlobalTransform::translation).truncate();
+        let (min, max) = grid_bounds(grid);
+        let center = center.clamp(origin + min, origin + max);
+
+        if center != transform.translation.truncate() {
+            transform.translation = center.extend(transform.translation.z);
+        }
+    }
+}
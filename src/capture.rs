@@ -0,0 +1,159 @@
+//! Recording searches as image sequences.
+//!
+//! A [`SearchRecorder`] on a grid entity writes the grid's texture to a
+//! numbered PNG file every frame a `SearchVisualizer` on that grid steps, so a
+//! search unfolding can be attached to a bug report or turned into an
+//! animation with any tool that reads image sequences. Each texture pixel is
+//! written as a block of `scale` pixels per cell side, so frames stay crisp.
+
+use std::{
+    fs,
+    io,
+    path::PathBuf,
+};
+
+use bevy::prelude::*;
+
+use crate::{console::ErrorConsole, search_view::SearchVisualizer, view::GridTexture};
+
+/// Writes a frame of the texture of the grid entity it is on whenever a search
+/// visualizer on the grid steps. Recording stops, and the recorder is removed,
+/// if a frame can't be written.
+#[derive(Component, Debug, Clone)]
+pub struct SearchRecorder {
+    /// Where the frames are written, created if missing.
+    pub directory: PathBuf,
+    /// Pixels per cell side in the written frames.
+    pub scale: u32,
+    frames: u32,
+}
+
+impl SearchRecorder {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        SearchRecorder { directory: directory.into(), scale: 8, frames: 0 }
+    }
+
+    pub fn with_scale(mut self, scale: u32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Frames written so far.
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    /// Writes the next frame, `width` by `height` RGBA texture pixels scaled up
+    /// `(x, y)` times.
+    fn write_frame(&mut self, width: u32, height: u32, pixels: &[u8], (x_scale, y_scale): (u32, u32)) -> io::Result<()> {
+        let (scaled_width, scaled_height) = (width * x_scale, height * y_scale);
+        let mut scaled = Vec::with_capacity((scaled_width * scaled_height * 4) as usize);
+        for row in pixels.chunks_exact(width as usize * 4) {
+            let scaled_row: Vec<u8> = row.chunks_exact(4).flat_map(|pixel| pixel.repeat(x_scale as usize)).collect();
+            for _ in 0..y_scale {
+                scaled.extend_from_slice(&scaled_row);
+            }
+        }
+
+        fs::create_dir_all(&self.directory)?;
+        let path = self.directory.join(format!("frame_{:05}.png", self.frames));
+        fs::write(path, encode_png(scaled_width, scaled_height, &scaled))?;
+        self.frames += 1;
+        Ok(())
+    }
+}
+
+/// Writes a frame for every recorded grid whose search stepped this frame, and
+/// the first frame of new recorders. Runs after the textures are updated.
+pub(crate) fn record_searches(
+    mut commands: Commands,
+    images: Res<Assets<Image>>,
+    mut console: ResMut<ErrorConsole>,
+    stepped: Query<&SearchVisualizer, Changed<SearchVisualizer>>,
+    mut recorders: Query<(&mut SearchRecorder, &GridTexture, Entity)>,
+) {
+    for (mut recorder, texture, grid_entity) in &mut recorders {
+        if recorder.frames > 0 && !stepped.iter().any(|visualizer| visualizer.grid == grid_entity) {
+            continue;
+        }
+        let Some(image) = images.get(texture.image()) else {
+            continue;
+        };
+
+        let size = image.texture_descriptor.size;
+        // Hex cells are more than one texture pixel wide, but as tall as square ones.
+        let scale = recorder.scale.max(1);
+        let scales = (scale, scale * texture.cell_pixels());
+
+        if let Err(error) = recorder.write_frame(size.width, size.height, &image.data, scales) {
+            console.report("record_searches", &error);
+            commands.entity(grid_entity).remove::<SearchRecorder>();
+        }
+    }
+}
+
+/// RGBA `pixels`, top row first, as a PNG file. The image data is stored
+/// without compression, which keeps the encoder small.
+fn encode_png(width: u32, height: u32, pixels: &[u8]) -> Vec<u8> {
+    // Every row starts with its filter type, none.
+    let mut raw = Vec::with_capacity(pixels.len() + height as usize);
+    for row in pixels.chunks_exact(width as usize * 4) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    // A zlib stream of stored deflate blocks, which hold at most 65535 bytes each.
+    let mut zlib = vec![0x78, 0x01];
+    let blocks = raw.chunks(u16::MAX as usize).count().max(1);
+    for (index, block) in raw.chunks(u16::MAX as usize).enumerate() {
+        let length = block.len() as u16;
+        zlib.push((index + 1 == blocks) as u8);
+        zlib.extend_from_slice(&length.to_le_bytes());
+        zlib.extend_from_slice(&(!length).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    if raw.is_empty() {
+        zlib.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // 8 bits per channel, RGBA, then the default compression, filtering and no interlacing.
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    for (kind, data) in [(b"IHDR", &header[..]), (b"IDAT", &zlib[..]), (b"IEND", &[][..])] {
+        png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        png.extend_from_slice(kind);
+        png.extend_from_slice(data);
+        png.extend_from_slice(&crc32(kind.iter().chain(data)).to_be_bytes());
+    }
+    png
+}
+
+fn crc32<'a>(bytes: impl Iterator<Item = &'a u8>) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = match crc & 1 {
+                1 => 0xedb8_8320 ^ (crc >> 1),
+                _ => crc >> 1,
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    const MODULUS: u32 = 65521;
+
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in bytes {
+        a = (a + byte as u32) % MODULUS;
+        b = (b + a) % MODULUS;
+    }
+    (b << 16) | a
+}
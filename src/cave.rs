@@ -0,0 +1,257 @@
+//! Caves grown by a cellular automaton, or dug by drunkards.
+//!
+//! [`cave`] walls off a random share of the cells, then smooths them a few
+//! times over: a cell with more than four walls among its eight neighbors
+//! becomes a wall, one with fewer becomes floor, and one with four stays as it
+//! is, the edges of the grid counting as walls. Noise clumps into rounded blobs
+//! of rock and open caverns. Last, the caverns and pillars too small to matter
+//! are filled in and cleared away, so searches aren't sent after pockets of
+//! floor a few cells wide.
+//!
+//! [`drunkard_walk`] starts from solid rock instead, and sends walkers
+//! stumbling about from the middle of the grid, digging out every cell they
+//! step on. Their tunnels wander, loop back and widen into chambers where they
+//! linger, all joined through the middle: one sprawling cave with no dead ends
+//! to speak of, unlike a maze.
+//!
+//! Inserting [`GenerateCave`] or [`DrunkardWalk`] on a grid editor entity lays
+//! a cave over its grid, like a `BulkEdit`, within its `CellSelection` if it
+//! has one.
+
+use bevy::prelude::*;
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{
+    console::ErrorConsole,
+    editor::{selection_of, set_all_cells, CellSelection},
+    grid::{Cell, CellPos, Grid, GridEditor, GridNotFound},
+    journal::GridJournal,
+    pattern::CellPattern,
+    seed::MapRng,
+};
+
+/// A `width` by `height` cave, `fill_ratio` of its cells walled off before
+/// `iterations` rounds of smoothing. Regions of floor or wall of fewer than
+/// `min_region` cells are then turned into the other.
+pub fn cave(
+    width: u32,
+    height: u32,
+    fill_ratio: f64,
+    iterations: u32,
+    min_region: usize,
+    rng: &mut impl Rng,
+) -> CellPattern {
+    let mut pattern = CellPattern::new(width, height, Cell::floor());
+    let mut cells: Vec<CellPos> = cells(&pattern).collect();
+    let wall_count = (fill_ratio.clamp(0.0, 1.0) * cells.len() as f64).round() as usize;
+    cells.shuffle(rng);
+    for &cell_pos in cells.iter().take(wall_count) {
+        pattern.set_cell(cell_pos, Cell::wall()).expect("iterated from the pattern");
+    }
+
+    for _ in 0..iterations {
+        pattern = smoothed(&pattern);
+    }
+    cull_regions(&mut pattern, Cell::floor(), Cell::wall(), min_region);
+    cull_regions(&mut pattern, Cell::wall(), Cell::floor(), min_region);
+    pattern
+}
+
+fn cells(pattern: &CellPattern) -> impl Iterator<Item = CellPos> {
+    let (width, height) = (pattern.width() as i32, pattern.height() as i32);
+    (0..height).flat_map(move |y| (0..width).map(move |x| CellPos(x, y)))
+}
+
+fn is_wall(pattern: &CellPattern, cell_pos: CellPos) -> bool {
+    pattern.cell(cell_pos).is_none_or(|cell| cell.is_wall)
+}
+
+/// One round of the automaton.
+fn smoothed(pattern: &CellPattern) -> CellPattern {
+    let mut next = pattern.clone();
+    for cell_pos in cells(pattern) {
+        let CellPos(x, y) = cell_pos;
+        let walls = (-1..=1)
+            .flat_map(|dy| (-1..=1).map(move |dx| CellPos(x + dx, y + dy)))
+            .filter(|&neighbor| neighbor != cell_pos && is_wall(pattern, neighbor))
+            .count();
+        let cell = match walls {
+            0..=3 => Cell::floor(),
+            4 => continue,
+            _ => Cell::wall(),
+        };
+        next.set_cell(cell_pos, cell).expect("iterated from the pattern");
+    }
+    next
+}
+
+/// Turns the regions of `from` cells smaller than `min_region`, connected by
+/// their sides, into `to`.
+fn cull_regions(pattern: &mut CellPattern, from: Cell, to: Cell, min_region: usize) {
+    let width = pattern.width() as i32;
+    let index = |CellPos(x, y): CellPos| (y * width + x) as usize;
+    let mut seen = vec![false; (pattern.width() * pattern.height()) as usize];
+
+    for start in cells(pattern).collect::<Vec<_>>() {
+        if seen[index(start)] || pattern.cell(start) != Some(from) {
+            continue;
+        }
+
+        seen[index(start)] = true;
+        let mut region = vec![start];
+        let mut next = 0;
+        while let Some(&CellPos(x, y)) = region.get(next) {
+            next += 1;
+            for neighbor in [CellPos(x - 1, y), CellPos(x + 1, y), CellPos(x, y - 1), CellPos(x, y + 1)] {
+                if pattern.cell(neighbor) == Some(from) && !seen[index(neighbor)] {
+                    seen[index(neighbor)] = true;
+                    region.push(neighbor);
+                }
+            }
+        }
+
+        if region.len() < min_region {
+            for cell_pos in region {
+                pattern.set_cell(cell_pos, to).expect("iterated from the pattern");
+            }
+        }
+    }
+}
+
+/// A `width` by `height` cave dug by `walkers` walkers, each taking `steps`
+/// random steps from the middle. Walls are left wherever none of them went.
+pub fn drunkard_walk(width: u32, height: u32, walkers: u32, steps: u32, rng: &mut impl Rng) -> CellPattern {
+    let mut pattern = CellPattern::new(width, height, Cell::wall());
+    let middle = CellPos(width as i32 / 2, height as i32 / 2);
+    if pattern.set_cell(middle, Cell::floor()).is_err() {
+        return pattern;
+    }
+
+    for _ in 0..walkers {
+        let mut cell_pos = middle;
+        for _ in 0..steps {
+            let CellPos(x, y) = cell_pos;
+            let neighbors = [CellPos(x - 1, y), CellPos(x + 1, y), CellPos(x, y - 1), CellPos(x, y + 1)];
+            let next = neighbors[rng.gen_range(0..neighbors.len())];
+            // Stumbling into the edge of the grid wastes the step.
+            if pattern.set_cell(next, Cell::floor()).is_ok() {
+                cell_pos = next;
+            }
+        }
+    }
+    pattern
+}
+
+/// Inserted on a grid editor entity to replace its walls with a [`cave`], all
+/// at once like a `BulkEdit`, within its `CellSelection` if it has one.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct GenerateCave {
+    /// Share of the cells walled off before smoothing. Around 0.45 leaves wide
+    /// caverns; much more closes them into pockets, much less opens them up.
+    pub fill_ratio: f64,
+    /// Rounds of smoothing; the first few matter most.
+    pub iterations: u32,
+    /// Fewest cells a cavern or pillar keeps.
+    pub min_region: usize,
+}
+
+impl GenerateCave {
+    pub fn new(fill_ratio: f64, iterations: u32) -> Self {
+        GenerateCave { fill_ratio, iterations, min_region: 16 }
+    }
+
+    pub fn with_min_region(mut self, min_region: usize) -> Self {
+        self.min_region = min_region;
+        self
+    }
+}
+
+impl Default for GenerateCave {
+    fn default() -> Self {
+        GenerateCave::new(0.45, 4)
+    }
+}
+
+/// Inserted on a grid editor entity to replace its walls with a cave dug by a
+/// [`drunkard_walk`], all at once like a `BulkEdit`, within its `CellSelection`
+/// if it has one.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DrunkardWalk {
+    /// More walkers dig out more around the middle.
+    pub walkers: u32,
+    /// Steps each walker takes. Longer walks reach farther from the middle.
+    pub steps: u32,
+}
+
+impl DrunkardWalk {
+    pub fn new(walkers: u32, steps: u32) -> Self {
+        DrunkardWalk { walkers, steps }
+    }
+}
+
+impl Default for DrunkardWalk {
+    fn default() -> Self {
+        DrunkardWalk::new(8, 400)
+    }
+}
+
+pub(crate) fn generate_caves(
+    mut commands: Commands,
+    mut grid_query: Query<(&GridEditor, &GenerateCave, Option<&mut GridJournal>, Entity)>,
+    selections: Query<&CellSelection>,
+    mut assets: ResMut<Assets<Grid>>,
+    mut console: ResMut<ErrorConsole>,
+    time: Res<Time>,
+    mut map_rng: ResMut<MapRng>,
+) {
+    for (grid_editor, generate, journal, entity) in &mut grid_query {
+        commands.entity(entity).remove::<GenerateCave>();
+
+        let Some(grid) = assets.get(&grid_editor.grid) else {
+            console.report("generate_caves", &GridNotFound { entity });
+            continue;
+        };
+
+        let (fill_ratio, iterations, min_region) = (generate.fill_ratio, generate.iterations, generate.min_region);
+        let (seed, mut rng) = map_rng.next_rng();
+        let cave = cave(grid.width(), grid.height(), fill_ratio, iterations, min_region, &mut rng);
+        let selection = selection_of(&selections, entity);
+        let changes: Vec<(CellPos, Cell, Cell)> = grid
+            .iter_cell_pos()
+            .filter(|(cell_pos, _)| selection.is_none_or(|selection| selection.cells.contains(cell_pos)))
+            .filter_map(|(cell_pos, old)| Some((cell_pos, old, cave.cell(cell_pos)?)))
+            .filter(|(_, old, new)| old != new)
+            .collect();
+        set_all_cells(&mut assets, &grid_editor.grid, journal, time.elapsed_seconds_f64(), changes, Some(seed));
+    }
+}
+
+pub(crate) fn generate_drunkard_walks(
+    mut commands: Commands,
+    mut grid_query: Query<(&GridEditor, &DrunkardWalk, Option<&mut GridJournal>, Entity)>,
+    selections: Query<&CellSelection>,
+    mut assets: ResMut<Assets<Grid>>,
+    mut console: ResMut<ErrorConsole>,
+    time: Res<Time>,
+    mut map_rng: ResMut<MapRng>,
+) {
+    for (grid_editor, walk, journal, entity) in &mut grid_query {
+        commands.entity(entity).remove::<DrunkardWalk>();
+
+        let Some(grid) = assets.get(&grid_editor.grid) else {
+            console.report("generate_drunkard_walks", &GridNotFound { entity });
+            continue;
+        };
+
+        let (seed, mut rng) = map_rng.next_rng();
+        let cave = drunkard_walk(grid.width(), grid.height(), walk.walkers, walk.steps, &mut rng);
+        let selection = selection_of(&selections, entity);
+        let changes: Vec<(CellPos, Cell, Cell)> = grid
+            .iter_cell_pos()
+            .filter(|(cell_pos, _)| selection.is_none_or(|selection| selection.cells.contains(cell_pos)))
+            .filter_map(|(cell_pos, old)| Some((cell_pos, old, cave.cell(cell_pos)?)))
+            .filter(|(_, old, new)| old != new)
+            .collect();
+        set_all_cells(&mut assets, &grid_editor.grid, journal, time.elapsed_seconds_f64(), changes, Some(seed));
+    }
+}
@@ -0,0 +1,345 @@
+//! Distance from every cell to its nearest wall, for agents bigger than a cell.
+//!
+//! Clearance is measured in cells along both axes at once (Chebyshev distance):
+//! a wall has clearance `0`, and a floor cell with clearance `n` is the center of
+//! an open square reaching `n - 1` cells out on every side. An agent of radius
+//! `r` occupies the square reaching `r` cells out from its cell, so it fits
+//! where the clearance is above `r`.
+//!
+//! The field is filled brushfire style, spreading outwards from every wall.
+//! Adding a wall lowers the clearance around it; removing one raises the cells
+//! it was closest to and refills them from the walls around. With a solid
+//! boundary the grid border counts as a wall; otherwise only stored walls do.
+//! Cells outside the stored grid aren't tracked and always fit.
+
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+};
+
+use bevy::prelude::*;
+
+use crate::{
+    grid::{Boundary, CellChangeEvent, CellPos, Grid, GridEditor, UnannouncedChanges},
+    pathfinding::{StepCost, UniformCost},
+};
+
+#[derive(Component, Debug, Clone)]
+pub struct Clearance {
+    width: u32,
+    height: u32,
+    solid_boundary: bool,
+    distances: Vec<u32>,
+}
+
+impl Clearance {
+    pub fn new(grid: &Grid) -> Self {
+        let mut clearance = Clearance {
+            width: 0,
+            height: 0,
+            solid_boundary: true,
+            distances: Vec::new(),
+        };
+        clearance.rebuild(grid);
+        clearance
+    }
+
+    /// Measures every cell from scratch.
+    pub fn rebuild(&mut self, grid: &Grid) {
+        self.width = grid.width();
+        self.height = grid.height();
+        self.solid_boundary = matches!(grid.boundary(), Boundary::Solid);
+        self.distances = vec![u32::MAX; (self.width * self.height) as usize];
+
+        let mut queue = BinaryHeap::new();
+        for (cell_pos, cell) in grid.iter_cell_pos() {
+            let distance = match cell.is_wall {
+                true => 0,
+                false => self.border_distance(cell_pos),
+            };
+            self.lower(&mut queue, cell_pos, distance);
+        }
+        self.spread(&mut queue);
+    }
+
+    /// Clearance of `cell_pos`, or `None` outside the stored grid.
+    pub fn get(&self, cell_pos: CellPos) -> Option<u32> {
+        self.index(cell_pos).map(|index| self.distances[index])
+    }
+
+    /// True if an agent of `radius` can stand on `cell_pos`.
+    pub fn fits(&self, cell_pos: CellPos, radius: u32) -> bool {
+        self.get(cell_pos).is_none_or(|distance| distance > radius)
+    }
+
+    /// A step cost that keeps agents of `radius` out of cells too narrow for them.
+    pub fn for_agent(&self, radius: u32) -> AgentClearance<'_> {
+        AgentClearance { clearance: self, radius, cost: UniformCost }
+    }
+
+    /// Updates the field after `cell_pos` changed between wall and floor. If the
+    /// grid was resized or its boundary changed since, measures it from scratch.
+    pub fn cell_changed(&mut self, grid: &Grid, cell_pos: CellPos) {
+        let solid_boundary = matches!(grid.boundary(), Boundary::Solid);
+        if grid.width() != self.width || grid.height() != self.height || solid_boundary != self.solid_boundary {
+            self.rebuild(grid);
+            return;
+        }
+
+        let Some(index) = self.index(cell_pos) else {
+            return;
+        };
+        let cell = grid.cell(cell_pos).expect("checked to be in bounds");
+
+        let mut queue = BinaryHeap::new();
+
+        match (cell.is_wall, self.distances[index] == 0) {
+            (true, false) => self.lower(&mut queue, cell_pos, 0),
+            (false, true) => {
+                // The cells this wall was closest to are exactly those whose clearance
+                // equals their distance to it, and they surround it without gaps.
+                let mut raised = vec![cell_pos];
+                let mut stack = vec![cell_pos];
+                self.distances[index] = u32::MAX;
+
+                while let Some(current) = stack.pop() {
+                    for neighbor in Self::around(current) {
+                        let Some(neighbor_index) = self.index(neighbor) else {
+                            continue;
+                        };
+                        let distance = self.distances[neighbor_index];
+                        if distance != u32::MAX && distance != 0 && distance == Self::chebyshev(neighbor, cell_pos) {
+                            self.distances[neighbor_index] = u32::MAX;
+                            raised.push(neighbor);
+                            stack.push(neighbor);
+                        }
+                    }
+                }
+
+                // Refill the raised cells from the border and from the cells around them.
+                for &raised_pos in &raised {
+                    self.lower(&mut queue, raised_pos, self.border_distance(raised_pos));
+
+                    for neighbor in Self::around(raised_pos) {
+                        if let Some(distance) = self.get(neighbor).filter(|&distance| distance != u32::MAX) {
+                            queue.push(Reverse((distance, neighbor.0, neighbor.1)));
+                        }
+                    }
+                }
+            }
+            _ => return,
+        }
+
+        self.spread(&mut queue);
+    }
+
+    fn index(&self, cell_pos: CellPos) -> Option<usize> {
+        let CellPos(x, y) = cell_pos;
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return None;
+        }
+        Some((self.width * y as u32 + x as u32) as usize)
+    }
+
+    fn chebyshev(a: CellPos, b: CellPos) -> u32 {
+        let (CellPos(x0, y0), CellPos(x1, y1)) = (a, b);
+        (x1 - x0).unsigned_abs().max((y1 - y0).unsigned_abs())
+    }
+
+    /// Distance to the first cell past the border, if the border counts as a wall.
+    fn border_distance(&self, cell_pos: CellPos) -> u32 {
+        if !self.solid_boundary {
+            return u32::MAX;
+        }
+
+        let CellPos(x, y) = cell_pos;
+        let (x, y) = (x as u32, y as u32);
+        (x + 1).min(y + 1).min(self.width - x).min(self.height - y)
+    }
+
+    /// The eight cells touching `cell_pos`.
+    fn around(cell_pos: CellPos) -> impl Iterator<Item = CellPos> {
+        let CellPos(x, y) = cell_pos;
+        (-1..=1)
+            .flat_map(move |dy| (-1..=1).map(move |dx| (dx, dy)))
+            .filter(|&offset| offset != (0, 0))
+            .map(move |(dx, dy)| CellPos(x + dx, y + dy))
+    }
+
+    fn lower(&mut self, queue: &mut BinaryHeap<Reverse<(u32, i32, i32)>>, cell_pos: CellPos, distance: u32) {
+        let Some(index) = self.index(cell_pos) else {
+            return;
+        };
+        if distance < self.distances[index] {
+            self.distances[index] = distance;
+            queue.push(Reverse((distance, cell_pos.0, cell_pos.1)));
+        }
+    }
+
+    /// Relaxes clearances outwards from the queued cells, closest to a wall first.
+    fn spread(&mut self, queue: &mut BinaryHeap<Reverse<(u32, i32, i32)>>) {
+        while let Some(Reverse((distance, x, y))) = queue.pop() {
+            let cell_pos = CellPos(x, y);
+            if self.get(cell_pos) != Some(distance) {
+                continue;
+            }
+
+            for neighbor in Self::around(cell_pos) {
+                self.lower(queue, neighbor, distance + 1);
+            }
+        }
+    }
+}
+
+/// Step cost for agents with a radius, from [`Clearance::for_agent`]. Steps onto
+/// cells the agent doesn't fit on are refused; the rest are priced by `cost`.
+/// Portal jumps aren't checked.
+pub struct AgentClearance<'a, C: StepCost = UniformCost> {
+    clearance: &'a Clearance,
+    radius: u32,
+    cost: C,
+}
+
+impl<'a, C: StepCost> AgentClearance<'a, C> {
+    pub fn with_cost<D: StepCost>(self, cost: D) -> AgentClearance<'a, D> {
+        AgentClearance { clearance: self.clearance, radius: self.radius, cost }
+    }
+}
+
+impl<C: StepCost> StepCost for AgentClearance<'_, C> {
+    fn step_cost(&self, grid: &Grid, from: CellPos, to: CellPos) -> Option<f32> {
+        if !self.clearance.fits(to, self.radius) {
+            return None;
+        }
+        self.cost.step_cost(grid, from, to)
+    }
+}
+
+/// Measures grid editors once their grid asset is available.
+pub(crate) fn measure_new_grids(
+    mut commands: Commands,
+    assets: Res<Assets<Grid>>,
+    new_grids: Query<(&GridEditor, Entity), Without<Clearance>>,
+) {
+    for (grid_editor, entity) in &new_grids {
+        if let Some(grid) = assets.get(&grid_editor.grid) {
+            commands.entity(entity).insert(Clearance::new(grid));
+        }
+    }
+}
+
+/// Updates the clearance of edited grids: incrementally for each `CellChangeEvent`,
+/// from scratch when the grid asset was modified without one. Every editor of
+/// the changed grid asset is updated, not only the one the event names.
+pub(crate) fn update_clearance(
+    assets: Res<Assets<Grid>>,
+    mut asset_events: EventReader<AssetEvent<Grid>>,
+    mut cell_events: EventReader<CellChangeEvent>,
+    mut unannounced: Local<UnannouncedChanges>,
+    editors: Query<&GridEditor>,
+    mut grids: Query<(&GridEditor, &mut Clearance, Entity)>,
+) {
+    let mut editing: HashMap<Handle<Grid>, Vec<Entity>> = HashMap::new();
+    for (grid_editor, _, entity) in &grids {
+        editing.entry(grid_editor.grid.clone()).or_default().push(entity);
+    }
+
+    for event in cell_events.iter() {
+        let Ok(grid_editor) = editors.get(event.grid) else {
+            continue;
+        };
+        unannounced.announce(&grid_editor.grid);
+        let Some(grid) = assets.get(&grid_editor.grid) else {
+            continue;
+        };
+        for &entity in editing.get(&grid_editor.grid).into_iter().flatten() {
+            if let Ok((_, mut clearance, _)) = grids.get_mut(entity) {
+                clearance.cell_changed(grid, event.cell_pos);
+            }
+        }
+    }
+
+    let unannounced = unannounced.unannounced(&mut asset_events);
+    for (grid_editor, mut clearance, _) in &mut grids {
+        if let (true, Some(grid)) = (unannounced.contains(&grid_editor.grid), assets.get(&grid_editor.grid)) {
+            clearance.rebuild(grid);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::grid::Cell;
+
+    fn assert_exact(clearance: &Clearance, grid: &Grid, context: &str) {
+        let measured = Clearance::new(grid);
+        for y in -1..=grid.height() as i32 {
+            for x in -1..=grid.width() as i32 {
+                let cell_pos = CellPos(x, y);
+                assert_eq!(clearance.get(cell_pos), measured.get(cell_pos), "{context}: {cell_pos:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn wall_edits_keep_the_field_exact() {
+        for (seed, boundary) in [Boundary::Solid, Boundary::Open { margin: 0 }].into_iter().enumerate() {
+            let mut rng = StdRng::seed_from_u64(seed as u64);
+            let mut grid = Grid::new(20, 14);
+            grid.set_boundary(boundary);
+            let all: Vec<_> = grid.iter_cell_pos().map(|(cell_pos, _)| cell_pos).collect();
+            for &cell_pos in &all {
+                grid.set_cell(cell_pos, Cell { is_wall: rng.gen_bool(0.1) }).unwrap();
+            }
+
+            let mut clearance = Clearance::new(&grid);
+            for edit in 0..300 {
+                let cell_pos = all[rng.gen_range(0..all.len())];
+                let is_wall = grid.cell(cell_pos).unwrap().is_wall;
+                grid.set_cell(cell_pos, Cell { is_wall: !is_wall }).unwrap();
+                clearance.cell_changed(&grid, cell_pos);
+                assert_exact(&clearance, &grid, &format!("seed {seed} edit {edit} at {cell_pos:?}"));
+            }
+        }
+    }
+
+    #[test]
+    fn resizes_and_boundary_changes_are_measured_again() {
+        let mut grid = Grid::new(6, 5);
+        grid.set_cell(CellPos(2, 2), Cell::wall()).unwrap();
+        let mut clearance = Clearance::new(&grid);
+
+        // Grown, with the change reported in the new part the old field doesn't cover.
+        grid.resize(12, 9, Cell::floor());
+        grid.set_cell(CellPos(10, 7), Cell::wall()).unwrap();
+        clearance.cell_changed(&grid, CellPos(10, 7));
+        assert_exact(&clearance, &grid, "grown");
+
+        // Shrunk, with the change reported where the grid no longer reaches.
+        grid.resize(4, 4, Cell::floor());
+        clearance.cell_changed(&grid, CellPos(10, 7));
+        assert_exact(&clearance, &grid, "shrunk");
+
+        // Same size, with the border no longer counting as a wall.
+        grid.set_boundary(Boundary::Open { margin: 0 });
+        grid.set_cell(CellPos(0, 0), Cell::wall()).unwrap();
+        clearance.cell_changed(&grid, CellPos(0, 0));
+        assert_exact(&clearance, &grid, "opened");
+    }
+
+    #[test]
+    fn agents_fit_where_clearance_is_above_their_radius() {
+        let mut grid = Grid::new(7, 7);
+        grid.set_cell(CellPos(1, 3), Cell::wall()).unwrap();
+        let clearance = Clearance::new(&grid);
+
+        assert_eq!(clearance.get(CellPos(1, 3)), Some(0));
+        assert_eq!(clearance.get(CellPos(3, 3)), Some(2));
+        assert!(clearance.fits(CellPos(3, 3), 1));
+        assert!(!clearance.fits(CellPos(3, 3), 2));
+        assert!(clearance.fits(CellPos(-1, 3), 5));
+    }
+}
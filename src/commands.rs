@@ -0,0 +1,109 @@
+//! Shorthands on `Commands` for spawning grids and asking for paths, so apps
+//! don't have to assemble the assets and components themselves.
+//!
+//! ```ignore
+//! let grid = commands.spawn_grid(GridBuilder::new(50, 50).border_walls()).id();
+//! commands.spawn(Unit).request_path(grid, CellPos(1, 1), CellPos(48, 48));
+//! ```
+
+use bevy::{
+    ecs::system::{Command, EntityCommands},
+    prelude::*,
+};
+
+use crate::{
+    console::ErrorConsole,
+    grid::{CellPos, Grid, GridEditor},
+    markers::{marked_cell, Endpoint, EndpointMarker, MarkerNotFound},
+    request::{PathPending, PathRequest, ScheduledSearch},
+};
+
+/// Adds `grid` to the grid assets and puts a `GridEditor` for it on `entity`.
+struct InsertGrid {
+    entity: Entity,
+    grid: Grid,
+}
+
+impl Command for InsertGrid {
+    fn write(self, world: &mut World) {
+        let handle = world.resource_mut::<Assets<Grid>>().add(self.grid);
+        // The entity may have been despawned by an earlier command.
+        if let Some(mut entity) = world.get_entity_mut(self.entity) {
+            entity.insert(GridEditor::new(handle));
+        }
+    }
+}
+
+pub trait GridCommandsExt<'w, 's> {
+    /// Spawns a grid editor for `grid`, such as a finished `GridBuilder`. The
+    /// grid is added to `Assets<Grid>` when the commands are applied.
+    fn spawn_grid<'a>(&'a mut self, grid: impl Into<Grid>) -> EntityCommands<'w, 's, 'a>;
+}
+
+impl<'w, 's> GridCommandsExt<'w, 's> for Commands<'w, 's> {
+    fn spawn_grid<'a>(&'a mut self, grid: impl Into<Grid>) -> EntityCommands<'w, 's, 'a> {
+        let entity = self.spawn_empty().id();
+        self.add(InsertGrid { entity, grid: grid.into() });
+        self.entity(entity)
+    }
+}
+
+/// Asks for a path between the markers of `grid` on `entity`.
+struct RequestMarkedPath {
+    entity: Entity,
+    grid: Entity,
+}
+
+impl Command for RequestMarkedPath {
+    fn write(self, world: &mut World) {
+        let markers: Vec<EndpointMarker> = world.query::<&EndpointMarker>().iter(world).copied().collect();
+        let [start, goal] = [Endpoint::Start, Endpoint::Goal].map(|endpoint| {
+            marked_cell(&markers, self.grid, endpoint).ok_or(MarkerNotFound { grid: self.grid, endpoint })
+        });
+
+        match (start, goal) {
+            (Ok(start), Ok(goal)) => {
+                if let Some(mut entity) = world.get_entity_mut(self.entity) {
+                    entity.insert(PathRequest::new(self.grid, start, goal));
+                }
+            }
+            (Err(error), _) | (_, Err(error)) => {
+                world.resource_mut::<ErrorConsole>().report("request_marked_path", &error);
+            }
+        }
+    }
+}
+
+pub trait PathCommandsExt {
+    /// Asks for the cheapest path from `start` to `goal` on `grid`, answered
+    /// like an inserted `PathRequest`. Insert the request itself for other
+    /// algorithms or solve modes.
+    fn request_path(&mut self, grid: Entity, start: CellPos, goal: CellPos) -> &mut Self;
+
+    /// Asks for the cheapest path between the start and goal `EndpointMarker`s
+    /// of `grid`, as they are when the commands are applied. Without both
+    /// markers nothing is requested and the `ErrorConsole` says which is missing.
+    fn request_marked_path(&mut self, grid: Entity) -> &mut Self;
+
+    /// Cancels the entity's path request, whether it is still waiting to be
+    /// picked up or already being searched, e.g. when the unit dies or changes its
+    /// mind. The partial search is dropped and no answer is sent. An answer found
+    /// earlier in the same update has already been sent.
+    fn cancel_path(&mut self) -> &mut Self;
+}
+
+impl PathCommandsExt for EntityCommands<'_, '_, '_> {
+    fn request_path(&mut self, grid: Entity, start: CellPos, goal: CellPos) -> &mut Self {
+        self.insert(PathRequest::new(grid, start, goal))
+    }
+
+    fn request_marked_path(&mut self, grid: Entity) -> &mut Self {
+        let entity = self.id();
+        self.commands().add(RequestMarkedPath { entity, grid });
+        self
+    }
+
+    fn cancel_path(&mut self) -> &mut Self {
+        self.remove::<PathRequest>().remove::<PathPending>().remove::<ScheduledSearch>()
+    }
+}
@@ -0,0 +1,192 @@
+//! Comparing planners side by side, each in its own part of the window.
+//!
+//! A [`SplitScreen`] camera draws one pane of the window, an equal share of its
+//! width, zoomed to show all of its grid; [`SplitScreenPlugin`] keeps the panes
+//! fitted as the window changes size. [`CompareCommandsExt::spawn_comparison`]
+//! sets up a whole comparison: a view of the same grid asset per planner, laid
+//! out side by side, each explored by a `SearchVisualizer` between the same
+//! endpoints and shown in its own pane. The visualizers step on the same
+//! `VisualizationClock` ticks, so after any frame they have done the same work.
+
+use bevy::{
+    core_pipeline::clear_color::ClearColorConfig,
+    ecs::system::Command,
+    prelude::*,
+    render::camera::Viewport,
+};
+
+use crate::{
+    grid::{CellPos, Grid, GridView, Grids},
+    pathfinding::{Planner, Search},
+    search_view::SearchVisualizer,
+    view::grid_bounds,
+};
+
+/// Cells left between the views of a comparison.
+const VIEW_GAP: f32 = 4.0;
+
+/// Share of a pane left around its grid.
+const PANE_PADDING: f32 = 0.05;
+
+/// Fits the 2D camera it is on to pane `pane` of `panes`, side by side across
+/// the window, showing all of `grid`.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SplitScreen {
+    pub grid: Entity,
+    pub pane: u32,
+    pub panes: u32,
+}
+
+/// A camera drawing one pane of a split screen.
+#[derive(Bundle)]
+pub struct SplitScreenBundle {
+    pub split_screen: SplitScreen,
+    #[bundle]
+    pub camera: Camera2dBundle,
+}
+
+impl SplitScreenBundle {
+    pub fn new(grid: Entity, pane: u32, panes: u32) -> Self {
+        // Clearing clears the whole window, so only the first pane does.
+        let clear_color = match pane {
+            0 => ClearColorConfig::Default,
+            _ => ClearColorConfig::None,
+        };
+
+        SplitScreenBundle {
+            split_screen: SplitScreen { grid, pane, panes },
+            camera: Camera2dBundle {
+                camera: Camera { priority: pane as isize, ..default() },
+                camera_2d: Camera2d { clear_color },
+                ..default()
+            },
+        }
+    }
+}
+
+/// Fits every [`SplitScreen`] camera to its pane.
+pub struct SplitScreenPlugin;
+
+impl Plugin for SplitScreenPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(fit_split_screens);
+    }
+}
+
+fn fit_split_screens(
+    windows: Res<Windows>,
+    grids: Grids,
+    grid_transforms: Query<&GlobalTransform>,
+    mut cameras: Query<(&SplitScreen, &mut Camera, &mut Transform, &mut OrthographicProjection)>,
+) {
+    let Some(window) = windows.get_primary() else {
+        return;
+    };
+
+    for (split_screen, mut camera, mut transform, mut projection) in &mut cameras {
+        let Ok(grid) = grids.get(split_screen.grid) else {
+            continue;
+        };
+        let panes = split_screen.panes.max(1);
+        let pane_width = window.physical_width() / panes;
+
+        let physical_position = UVec2::new(pane_width * split_screen.pane.min(panes - 1), 0);
+        let physical_size = UVec2::new(pane_width.max(1), window.physical_height().max(1));
+        let fitted = camera.viewport.as_ref().is_some_and(|viewport| {
+            viewport.physical_position == physical_position && viewport.physical_size == physical_size
+        });
+        if !fitted {
+            camera.viewport = Some(Viewport { physical_position, physical_size, ..default() });
+        }
+
+        // In logical pixels, like the projection scale.
+        let (min, max) = grid_bounds(grid);
+        let pane_size = Vec2::new(window.width() / panes as f32, window.height()) * (1.0 - 2.0 * PANE_PADDING);
+        let scale = ((max - min) / pane_size).max_element();
+        if projection.scale != scale {
+            projection.scale = scale;
+        }
+
+        let origin = grid_transforms.get(split_screen.grid).map_or(Vec3::ZERO, GlobalTransform::translation).truncate();
+        let center = origin + (min + max) / 2.0;
+        if center != transform.translation.truncate() {
+            transform.translation = center.extend(transform.translation.z);
+        }
+    }
+}
+
+/// Lays out the views of a comparison and gives each its visualizer, once the
+/// grid asset can be read.
+struct InsertComparison {
+    grid: Handle<Grid>,
+    /// The view and visualizer entity of each planner.
+    panes: Vec<(Entity, Entity, Planner)>,
+    start: CellPos,
+    goal: CellPos,
+}
+
+impl Command for InsertComparison {
+    fn write(self, world: &mut World) {
+        let assets = world.resource::<Assets<Grid>>();
+        let Some(grid) = assets.get(&self.grid) else {
+            return;
+        };
+        let spacing = grid.width() as f32 + VIEW_GAP;
+        let searches: Vec<Search> =
+            self.panes.iter().map(|&(_, _, planner)| Search::new(grid, planner, self.start, self.goal)).collect();
+
+        for ((pane, (view, visualizer, _)), search) in self.panes.into_iter().enumerate().zip(searches) {
+            // The entities may have been despawned by an earlier command.
+            if let Some(mut view) = world.get_entity_mut(view) {
+                view.insert(Transform::from_xyz(pane as f32 * spacing, 0.0, 0.0));
+            }
+            if let Some(mut visualizer) = world.get_entity_mut(visualizer) {
+                visualizer.insert(SearchVisualizer::new(view, search));
+            }
+        }
+    }
+}
+
+pub trait CompareCommandsExt {
+    /// Spawns a view of `grid` per planner, side by side, with a visualizer
+    /// searching it from `start` to `goal` and a [`SplitScreen`] camera showing
+    /// it. Returns the view entities, in the order of the planners.
+    fn spawn_comparison(
+        &mut self,
+        grid: Handle<Grid>,
+        planners: impl IntoIterator<Item = Planner>,
+        start: CellPos,
+        goal: CellPos,
+    ) -> Vec<Entity>;
+}
+
+impl CompareCommandsExt for Commands<'_, '_> {
+    fn spawn_comparison(
+        &mut self,
+        grid: Handle<Grid>,
+        planners: impl IntoIterator<Item = Planner>,
+        start: CellPos,
+        goal: CellPos,
+    ) -> Vec<Entity> {
+        let planners: Vec<Planner> = planners.into_iter().collect();
+        let panes = planners.len() as u32;
+
+        let panes: Vec<(Entity, Entity, Planner)> = planners
+            .into_iter()
+            .enumerate()
+            .map(|(pane, planner)| {
+                let name = planner.name();
+                let view = self
+                    .spawn((SpatialBundle::default(), GridView::new(grid.clone()), Name::new(format!("{name} view"))))
+                    .id();
+                let visualizer = self.spawn(Name::new(format!("{name} search"))).id();
+                self.spawn((SplitScreenBundle::new(view, pane as u32, panes), Name::new(format!("{name} pane"))));
+                (view, visualizer, planner)
+            })
+            .collect();
+
+        let views = panes.iter().map(|&(view, _, _)| view).collect();
+        self.add(InsertComparison { grid, panes, start, goal });
+        views
+    }
+}
@@ -0,0 +1,375 @@
+//! In-app error console.
+//!
+//! Errors that would otherwise only reach a terminal are collected as
+//! [`ErrorReport`]s in the [`ErrorConsole`] resource and listed in an overlay,
+//! so people running the app without a terminal can still see what went wrong
+//! and send the details along. Fallible systems can pipe their `Result` into
+//! [`report_errors`]. Grids that fail to load are reported too, as are the fonts
+//! of texts once the overlay is shown.
+//!
+//! A panicking system still takes the app down, but the panic hook installed by
+//! `ErrorConsolePlugin` writes a crash report first, and the console shows it
+//! on the next start.
+
+use std::{
+    any::type_name,
+    backtrace::Backtrace,
+    collections::{HashSet, VecDeque},
+    error::Error,
+    fmt::Display,
+    fs,
+    io,
+    panic::PanicHookInfo,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use bevy::{
+    asset::{HandleId, LoadState},
+    ecs::system::In,
+    prelude::*,
+};
+
+use crate::grid::{GridEditor, GridHandle, GridView};
+
+/// Written by the panic hook and picked up on the next start.
+const CRASH_REPORT_PATH: &str = "a_star_crash_report.txt";
+
+#[derive(Debug, Clone)]
+pub struct ErrorReport {
+    /// What reported the error, such as the system or error type.
+    pub source: String,
+    pub message: String,
+    /// Everything worth attaching to a bug report: the chain of causes, or the
+    /// location and backtrace of a panic.
+    pub details: String,
+}
+
+impl ErrorReport {
+    pub fn from_error(source: impl Into<String>, error: &dyn Error) -> Self {
+        let mut details = String::new();
+        let mut cause = error.source();
+        while let Some(error) = cause {
+            details.push_str(&format!("caused by: {error}\n"));
+            cause = error.source();
+        }
+
+        ErrorReport {
+            source: source.into(),
+            message: error.to_string(),
+            details,
+        }
+    }
+
+    fn from_panic(info: &PanicHookInfo) -> Self {
+        let payload = info.payload();
+        let message = match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()) {
+            (Some(message), _) => message.to_string(),
+            (_, Some(message)) => message.clone(),
+            _ => "panic with a non-string payload".to_string(),
+        };
+
+        let thread = std::thread::current();
+        let location = info.location().map(ToString::to_string).unwrap_or_default();
+
+        ErrorReport {
+            source: format!("panic in thread {}", thread.name().unwrap_or("<unnamed>")),
+            message,
+            details: format!("at {location}\n{}", Backtrace::force_capture()),
+        }
+    }
+}
+
+/// The full report, formatted to be pasted into a bug report.
+impl Display for ErrorReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "[{}] {}", self.source, self.message)?;
+        write!(f, "{}", self.details)
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct ErrorConsole {
+    reports: VecDeque<ErrorReport>,
+    /// Panics caught by the hook, which can't reach the resource directly.
+    panics: Arc<Mutex<Vec<ErrorReport>>>,
+    pub visible: bool,
+}
+
+impl ErrorConsole {
+    /// Oldest reports are dropped beyond this many.
+    pub const MAX_REPORTS: usize = 100;
+
+    /// Logs `error` and adds it to the console, opening the overlay.
+    pub fn report(&mut self, source: impl Into<String>, error: &dyn Error) {
+        self.push(ErrorReport::from_error(source, error));
+    }
+
+    pub fn push(&mut self, report: ErrorReport) {
+        error!("{report}");
+
+        if self.reports.len() == Self::MAX_REPORTS {
+            self.reports.pop_front();
+        }
+        self.reports.push_back(report);
+        self.visible = true;
+    }
+
+    /// Reports from oldest to newest.
+    pub fn reports(&self) -> impl Iterator<Item = &ErrorReport> {
+        self.reports.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.reports.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.reports.clear();
+    }
+
+    /// Writes every report to `path`, ready to be attached to a bug report.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let text: String = self.reports.iter().map(|report| format!("{report}\n")).collect();
+        fs::write(path, text)
+    }
+
+    /// Records panics in this console and in a crash report on disk, then hands
+    /// them on to the previously installed hook.
+    pub fn install_panic_hook(&self) {
+        let panics = self.panics.clone();
+        let previous_hook = std::panic::take_hook();
+
+        std::panic::set_hook(Box::new(move |info| {
+            let report = ErrorReport::from_panic(info);
+            // Nothing more can be done if this fails while panicking.
+            let _ = fs::write(CRASH_REPORT_PATH, report.to_string());
+            if let Ok(mut panics) = panics.lock() {
+                panics.push(report);
+            }
+
+            previous_hook(info);
+        }));
+    }
+
+    /// Moves the panics the hook caught since the last call into the reports.
+    /// `ErrorConsolePlugin` does this every frame.
+    pub fn collect_panics(&mut self) {
+        let panics = match self.panics.lock() {
+            Ok(mut panics) => std::mem::take(&mut *panics),
+            Err(_) => return,
+        };
+
+        for report in panics {
+            self.push(report);
+        }
+    }
+}
+
+/// Pipe target for fallible systems: `app.add_system(my_system.pipe(report_errors))`.
+/// Errors are reported under the name of their type, without its path or
+/// generic arguments, e.g. `Box` for a `Box<dyn Error>`.
+pub fn report_errors<E: Error>(In(result): In<Result<(), E>>, mut console: ResMut<ErrorConsole>) {
+    if let Err(error) = result {
+        let name = type_name::<E>();
+        let path = name.split('<').next().unwrap_or(name);
+        console.report(path.rsplit("::").next().unwrap_or(path), &error);
+    }
+}
+
+/// An asset the `AssetServer` couldn't load, e.g. a missing or malformed file.
+#[derive(Debug)]
+pub struct AssetLoadFailed {
+    /// The file it was loaded from, if the server knows it.
+    pub path: Option<PathBuf>,
+}
+
+impl Display for AssetLoadFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "failed to load asset {}", path.display()),
+            None => write!(f, "failed to load an asset"),
+        }
+    }
+}
+
+impl Error for AssetLoadFailed {}
+
+/// Reports each of `handles` whose asset failed to load, once.
+fn report_failed_loads(
+    source: &str,
+    handles: impl IntoIterator<Item = HandleId>,
+    asset_server: &AssetServer,
+    reported: &mut HashSet<HandleId>,
+    console: &mut ErrorConsole,
+) {
+    for handle in handles {
+        if asset_server.get_load_state(handle) == LoadState::Failed && reported.insert(handle) {
+            let path = asset_server.get_handle_path(handle).map(|path| path.path().to_path_buf());
+            console.report(source, &AssetLoadFailed { path });
+        }
+    }
+}
+
+/// Reports the grids of grid editors and views that failed to load.
+pub(crate) fn report_failed_grid_loads(
+    asset_server: Res<AssetServer>,
+    editors: Query<&GridEditor>,
+    views: Query<&GridView>,
+    mut reported: Local<HashSet<HandleId>>,
+    mut console: ResMut<ErrorConsole>,
+) {
+    let handles = editors.iter().map(|editor| editor.handle().id()).chain(views.iter().map(|view| view.handle().id()));
+    report_failed_loads("grid", handles, &asset_server, &mut reported, &mut console);
+}
+
+#[cfg(feature = "visualizer")]
+pub use overlay::ErrorConsolePlugin;
+
+/// The on-screen part of the console, which needs Bevy's UI.
+#[cfg(feature = "visualizer")]
+mod overlay {
+    use std::{collections::HashSet, fs};
+
+    use bevy::{asset::HandleId, prelude::*};
+
+    use crate::bindings::InputBindings;
+
+    use super::{report_failed_loads, ErrorConsole, ErrorReport, CRASH_REPORT_PATH};
+
+    /// Where the console saves its reports when asked to.
+    const SAVED_REPORTS_PATH: &str = "a_star_error_report.txt";
+
+    /// Shows the [`ErrorConsole`] in an overlay. F12 toggles it, and `S` saves the
+    /// reports to a file while it is open, unless the `InputBindings` say otherwise.
+    /// Reports the fonts of texts, its own included, that fail to load.
+    pub struct ErrorConsolePlugin {
+        /// Font used by the overlay, relative to the assets folder.
+        pub font: &'static str,
+    }
+
+    impl Default for ErrorConsolePlugin {
+        fn default() -> Self {
+            ErrorConsolePlugin { font: "fonts/FiraMono-Medium.ttf" }
+        }
+    }
+
+    #[derive(Resource)]
+    struct ConsoleFont(&'static str);
+
+    #[derive(Component)]
+    struct ConsoleText;
+
+    impl Plugin for ErrorConsolePlugin {
+        fn build(&self, app: &mut App) {
+            app.init_resource::<ErrorConsole>();
+            app.world.resource::<ErrorConsole>().install_panic_hook();
+
+            app
+                .init_resource::<InputBindings>()
+                .insert_resource(ConsoleFont(self.font))
+                .add_startup_system(spawn_console)
+                .add_startup_system(load_crash_report)
+                .add_system(collect_panics)
+                .add_system(report_failed_font_loads)
+                .add_system(console_keys)
+                .add_system(update_console.after(collect_panics).after(console_keys));
+        }
+    }
+
+    fn spawn_console(mut commands: Commands, asset_server: Res<AssetServer>, font: Res<ConsoleFont>) {
+        let style = TextStyle {
+            font: asset_server.load(font.0),
+            font_size: 16.0,
+            color: Color::WHITE,
+        };
+
+        commands.spawn((
+            TextBundle::from_section("", style)
+                .with_style(Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        left: Val::Px(10.0),
+                        top: Val::Px(10.0),
+                        ..default()
+                    },
+                    ..default()
+                }),
+            ConsoleText,
+            Name::new("Error console"),
+        ));
+    }
+
+    /// Shows the crash report left behind by the last run, if it crashed.
+    fn load_crash_report(mut console: ResMut<ErrorConsole>) {
+        let Ok(text) = fs::read_to_string(CRASH_REPORT_PATH) else {
+            return;
+        };
+
+        let (message, details) = text.split_once('\n').unwrap_or((&text, ""));
+        console.push(ErrorReport {
+            source: "previous run".to_string(),
+            message: message.to_string(),
+            details: details.to_string(),
+        });
+
+        if let Err(error) = fs::remove_file(CRASH_REPORT_PATH) {
+            console.report("crash report", &error);
+        }
+    }
+
+    fn collect_panics(mut console: ResMut<ErrorConsole>) {
+        console.collect_panics();
+    }
+
+    fn report_failed_font_loads(
+        asset_server: Res<AssetServer>,
+        texts: Query<&Text>,
+        mut reported: Local<HashSet<HandleId>>,
+        mut console: ResMut<ErrorConsole>,
+    ) {
+        let fonts = texts.iter().flat_map(|text| text.sections.iter().map(|section| section.style.font.id()));
+        report_failed_loads("font", fonts, &asset_server, &mut reported, &mut console);
+    }
+
+    fn console_keys(keys: Res<Input<KeyCode>>, bindings: Res<InputBindings>, mut console: ResMut<ErrorConsole>) {
+        if bindings.toggle_console.just_pressed(&keys) {
+            console.visible = !console.visible;
+        }
+
+        if console.visible && bindings.save_console.just_pressed(&keys) {
+            match console.save(SAVED_REPORTS_PATH) {
+                Ok(()) => info!("saved error reports to {SAVED_REPORTS_PATH}"),
+                Err(error) => console.report("saving error reports", &error),
+            }
+        }
+    }
+
+    fn update_console(
+        console: Res<ErrorConsole>,
+        bindings: Res<InputBindings>,
+        mut text: Query<(&mut Text, &mut Visibility), With<ConsoleText>>,
+    ) {
+        if !console.is_changed() && !bindings.is_changed() {
+            return;
+        }
+
+        for (mut text, mut visibility) in &mut text {
+            visibility.is_visible = console.visible && !console.is_empty();
+
+            let mut lines: Vec<String> = console
+                .reports()
+                .map(|report| format!("[{}] {}", report.source, report.message))
+                .collect();
+
+            if let Some(latest) = console.reports().last() {
+                lines.push(String::new());
+                lines.push(latest.details.clone());
+            }
+            let (toggle, save) = (&bindings.toggle_console, &bindings.save_console);
+            lines.push(format!("{toggle}: hide, {save}: save to {SAVED_REPORTS_PATH}"));
+
+            text.sections[0].value = lines.join("\n");
+        }
+    }
+}
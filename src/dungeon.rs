@@ -0,0 +1,360 @@
+//! Roguelike dungeons of rooms joined by corridors.
+//!
+//! [`dungeon`] scatters rectangular rooms over solid rock, skipping any that
+//! would touch a room already placed, and digs a corridor from each room to
+//! the one placed before it, bending once on the way. Every room is reached,
+//! and the corridors cutting through other rooms add the odd loop.
+//!
+//! [`bsp_dungeon`] partitions the grid instead: it splits it in two, splits
+//! the halves again and so on, until every leaf is small enough, digs a room
+//! into each leaf and joins the two halves of every split with a corridor
+//! between their nearest rooms. Rooms spread evenly over the grid, never
+//! overlap, and the corridors follow the tree of splits, short ones between
+//! neighbors and a few long ones across the map, with no loops.
+//!
+//! Inserting [`GenerateDungeon`] or [`GenerateBspDungeon`] on a grid editor
+//! entity lays a dungeon over its grid, like a `BulkEdit`, within its
+//! `CellSelection` if it has one, and tags the floor of each room with a
+//! [`DungeonRoom`] in the grid's `GridLayer<DungeonRoom>`, so the game can pick
+//! cells of a room to spawn in with [`room_cells`].
+
+use std::ops::{Range, RangeInclusive};
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{
+    console::ErrorConsole,
+    editor::{selection_of, set_all_cells, CellSelection},
+    grid::{Cell, CellPos, Grid, GridEditor, GridNotFound},
+    journal::GridJournal,
+    pattern::CellPattern,
+    seed::MapRng,
+};
+
+/// Which room of a dungeon a cell is the floor of, numbered from 1 in the
+/// order the rooms were placed. Corridors and rock are [`DungeonRoom::NONE`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Reflect, FromReflect)]
+pub struct DungeonRoom(pub u16);
+
+impl DungeonRoom {
+    pub const NONE: DungeonRoom = DungeonRoom(0);
+
+    /// The room `cell_pos` is in on `grid`, if any.
+    pub fn at(grid: &Grid, cell_pos: CellPos) -> Option<DungeonRoom> {
+        let room = grid.layer::<DungeonRoom>()?.get(cell_pos).ok().copied()?;
+        (room != DungeonRoom::NONE).then_some(room)
+    }
+}
+
+/// The cells of `grid` tagged as the floor of `room`.
+pub fn room_cells(grid: &Grid, room: DungeonRoom) -> Vec<CellPos> {
+    let Some(layer) = grid.layer::<DungeonRoom>() else {
+        return Vec::new();
+    };
+    layer.iter().filter(|&(_, &tag)| tag == room).map(|(cell_pos, _)| cell_pos).collect()
+}
+
+/// The cells of a dungeon, and its rooms.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dungeon {
+    pub cells: CellPattern,
+    /// Bottom left and top right corners of each room, both included, in the
+    /// order they were placed; room `n` of the list is `DungeonRoom(n + 1)`.
+    pub rooms: Vec<(CellPos, CellPos)>,
+}
+
+impl Dungeon {
+    /// The room `cell_pos` is in, if any.
+    pub fn room_at(&self, cell_pos: CellPos) -> Option<DungeonRoom> {
+        let CellPos(x, y) = cell_pos;
+        self.rooms
+            .iter()
+            .position(|&(min, max)| (min.0..=max.0).contains(&x) && (min.1..=max.1).contains(&y))
+            .map(|index| DungeonRoom(index as u16 + 1))
+    }
+}
+
+/// A `width` by `height` dungeon of up to `rooms` rooms, each side of each
+/// room `room_size` cells long. Fewer rooms are placed when they don't fit.
+pub fn dungeon(width: u32, height: u32, rooms: u32, room_size: RangeInclusive<u32>, rng: &mut impl Rng) -> Dungeon {
+    let mut cells = CellPattern::new(width, height, Cell::wall());
+    let mut placed: Vec<(CellPos, CellPos)> = Vec::new();
+    let (min_size, max_size) = ((*room_size.start()).max(1), (*room_size.end()).max(1));
+    let (min_size, max_size) = (min_size.min(max_size), max_size.max(min_size));
+
+    let mut open = |cell_pos: CellPos| {
+        cells.set_cell(cell_pos, Cell::floor()).expect("rooms and corridors are kept in the pattern");
+    };
+
+    // Rooms that don't fit are skipped, a few tries per room asked for.
+    for _ in 0..rooms.saturating_mul(8) {
+        if placed.len() >= rooms as usize || placed.len() >= u16::MAX as usize {
+            break;
+        }
+        let (room_width, room_height) = (rng.gen_range(min_size..=max_size), rng.gen_range(min_size..=max_size));
+        // Rock is left around the edges of the grid.
+        if room_width + 2 > width || room_height + 2 > height {
+            continue;
+        }
+        let x = rng.gen_range(1..=width - room_width - 1) as i32;
+        let y = rng.gen_range(1..=height - room_height - 1) as i32;
+        let (low, high) = (CellPos(x, y), CellPos(x + room_width as i32 - 1, y + room_height as i32 - 1));
+        // Rooms are kept a wall apart, so they don't merge into one.
+        let touches = |&(min, max): &(CellPos, CellPos)| {
+            low.0 <= max.0 + 1 && min.0 <= high.0 + 1 && low.1 <= max.1 + 1 && min.1 <= high.1 + 1
+        };
+        if placed.iter().any(touches) {
+            continue;
+        }
+
+        let room = (low, high);
+        for cell_pos in (low.1..=high.1).flat_map(|y| (low.0..=high.0).map(move |x| CellPos(x, y))) {
+            open(cell_pos);
+        }
+        if let Some(&previous) = placed.last() {
+            for cell_pos in corridor(center(previous), center(room), rng) {
+                open(cell_pos);
+            }
+        }
+        placed.push(room);
+    }
+
+    Dungeon { cells, rooms: placed }
+}
+
+/// A `width` by `height` dungeon partitioned into leaves between `min_leaf`
+/// and `max_leaf` cells across, where the grid allows, with a room in each.
+/// Leaves are at least 3 cells across, to leave room for a room and its walls.
+pub fn bsp_dungeon(width: u32, height: u32, min_leaf: u32, max_leaf: u32, rng: &mut impl Rng) -> Dungeon {
+    let min_leaf = min_leaf.max(3) as i32;
+    let max_leaf = (max_leaf as i32).max(min_leaf);
+    let mut dungeon = Dungeon { cells: CellPattern::new(width, height, Cell::wall()), rooms: Vec::new() };
+    if width > 0 && height > 0 {
+        let grid = (CellPos(0, 0), CellPos(width as i32 - 1, height as i32 - 1));
+        split_leaf(&mut dungeon, grid, (min_leaf, max_leaf), rng);
+    }
+    dungeon
+}
+
+/// Splits `leaf`, corners both included, until it is at most `max_leaf`
+/// across, digs a room into each leaf and joins the halves of each split.
+/// Returns the indices of the rooms dug in `leaf`.
+fn split_leaf(
+    dungeon: &mut Dungeon,
+    leaf: (CellPos, CellPos),
+    (min_leaf, max_leaf): (i32, i32),
+    rng: &mut impl Rng,
+) -> Range<usize> {
+    let (low, high) = leaf;
+    let (width, height) = (high.0 - low.0 + 1, high.1 - low.1 + 1);
+    let first = dungeon.rooms.len();
+    let splits = |size: i32| size > max_leaf && size >= 2 * min_leaf;
+
+    // Across the longer side, so leaves stay roughly square.
+    let across_x = match (splits(width), splits(height)) {
+        (true, true) => width > height || (width == height && rng.gen_bool(0.5)),
+        (true, false) => true,
+        (false, true) => false,
+        (false, false) => {
+            dig_room(dungeon, leaf, rng);
+            return first..dungeon.rooms.len();
+        }
+    };
+    let size = if across_x { width } else { height };
+    let at = rng.gen_range(min_leaf..=size - min_leaf);
+    let (first_half, second_half) = match across_x {
+        true => ((low, CellPos(low.0 + at - 1, high.1)), (CellPos(low.0 + at, low.1), high)),
+        false => ((low, CellPos(high.0, low.1 + at - 1)), (CellPos(low.0, low.1 + at), high)),
+    };
+    let first_rooms = split_leaf(dungeon, first_half, (min_leaf, max_leaf), rng);
+    let second_rooms = split_leaf(dungeon, second_half, (min_leaf, max_leaf), rng);
+
+    // The nearest two rooms on either side of the split.
+    let centers = |(a, b): (usize, usize)| (center(dungeon.rooms[a]), center(dungeon.rooms[b]));
+    let nearest = first_rooms
+        .flat_map(|a| second_rooms.clone().map(move |b| (a, b)))
+        .map(centers)
+        .min_by_key(|&(from, to)| (from.0 - to.0).abs() + (from.1 - to.1).abs());
+    if let Some((from, to)) = nearest {
+        for cell_pos in corridor(from, to, rng) {
+            dungeon.cells.set_cell(cell_pos, Cell::floor()).expect("corridors run between rooms");
+        }
+    }
+    first..dungeon.rooms.len()
+}
+
+/// Digs a room into `leaf`, at least half as wide and high as fits inside the
+/// walls around it.
+fn dig_room(dungeon: &mut Dungeon, (low, high): (CellPos, CellPos), rng: &mut impl Rng) {
+    let (inner_width, inner_height) = (high.0 - low.0 - 1, high.1 - low.1 - 1);
+    if inner_width < 1 || inner_height < 1 || dungeon.rooms.len() >= u16::MAX as usize {
+        return;
+    }
+
+    let room_width = rng.gen_range((inner_width + 1) / 2..=inner_width);
+    let room_height = rng.gen_range((inner_height + 1) / 2..=inner_height);
+    let x = low.0 + 1 + rng.gen_range(0..=inner_width - room_width);
+    let y = low.1 + 1 + rng.gen_range(0..=inner_height - room_height);
+    let room = (CellPos(x, y), CellPos(x + room_width - 1, y + room_height - 1));
+    for cell_pos in (room.0 .1..=room.1 .1).flat_map(|y| (room.0 .0..=room.1 .0).map(move |x| CellPos(x, y))) {
+        dungeon.cells.set_cell(cell_pos, Cell::floor()).expect("rooms are dug inside their leaf");
+    }
+    dungeon.rooms.push(room);
+}
+
+fn center((min, max): (CellPos, CellPos)) -> CellPos {
+    CellPos((min.0 + max.0) / 2, (min.1 + max.1) / 2)
+}
+
+/// The cells of a corridor from `from` to `to`, both included, bending once
+/// one way or the other.
+fn corridor(from: CellPos, to: CellPos, rng: &mut impl Rng) -> impl Iterator<Item = CellPos> {
+    let corner = match rng.gen_bool(0.5) {
+        true => CellPos(to.0, from.1),
+        false => CellPos(from.0, to.1),
+    };
+    straight_line(from, corner).chain(straight_line(corner, to))
+}
+
+/// The cells from `from` to `to`, both included, which share a row or column.
+fn straight_line(from: CellPos, to: CellPos) -> impl Iterator<Item = CellPos> {
+    let (xs, ys) = (from.0.min(to.0)..=from.0.max(to.0), from.1.min(to.1)..=from.1.max(to.1));
+    ys.flat_map(move |y| xs.clone().map(move |x| CellPos(x, y)))
+}
+
+/// Inserted on a grid editor entity to replace its walls with a [`dungeon`],
+/// all at once like a `BulkEdit`, within its `CellSelection` if it has one.
+/// The rooms are tagged in the grid's `GridLayer<DungeonRoom>`, which is
+/// added if the grid has none; like terrain, the tags aren't journaled.
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
+pub struct GenerateDungeon {
+    /// Most rooms placed.
+    pub rooms: u32,
+    /// Lengths the sides of rooms are picked from.
+    pub room_size: RangeInclusive<u32>,
+}
+
+impl GenerateDungeon {
+    pub fn new(rooms: u32) -> Self {
+        GenerateDungeon { rooms, room_size: 4..=10 }
+    }
+
+    pub fn with_room_size(mut self, room_size: RangeInclusive<u32>) -> Self {
+        self.room_size = room_size;
+        self
+    }
+}
+
+impl Default for GenerateDungeon {
+    fn default() -> Self {
+        GenerateDungeon::new(12)
+    }
+}
+
+/// Inserted on a grid editor entity to replace its walls with a
+/// [`bsp_dungeon`], tagging its rooms like [`GenerateDungeon`].
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GenerateBspDungeon {
+    /// Fewest cells across a leaf is split into.
+    pub min_leaf: u32,
+    /// Leaves more cells across than this are split again.
+    pub max_leaf: u32,
+}
+
+impl GenerateBspDungeon {
+    pub fn new(min_leaf: u32, max_leaf: u32) -> Self {
+        GenerateBspDungeon { min_leaf, max_leaf }
+    }
+}
+
+impl Default for GenerateBspDungeon {
+    fn default() -> Self {
+        GenerateBspDungeon::new(6, 16)
+    }
+}
+
+pub(crate) fn generate_dungeons(
+    mut commands: Commands,
+    mut grid_query: Query<(&GridEditor, &GenerateDungeon, Option<&mut GridJournal>, Entity)>,
+    selections: Query<&CellSelection>,
+    mut assets: ResMut<Assets<Grid>>,
+    mut console: ResMut<ErrorConsole>,
+    time: Res<Time>,
+    mut map_rng: ResMut<MapRng>,
+) {
+    for (grid_editor, generate, journal, entity) in &mut grid_query {
+        commands.entity(entity).remove::<GenerateDungeon>();
+
+        let Some(grid) = assets.get(&grid_editor.grid) else {
+            console.report("generate_dungeons", &GridNotFound { entity });
+            continue;
+        };
+
+        let (seed, mut rng) = map_rng.next_rng();
+        let dungeon = dungeon(grid.width(), grid.height(), generate.rooms, generate.room_size.clone(), &mut rng);
+        let selection = selection_of(&selections, entity);
+        lay_out(&mut assets, &grid_editor.grid, selection, journal, time.elapsed_seconds_f64(), &dungeon, seed);
+    }
+}
+
+pub(crate) fn generate_bsp_dungeons(
+    mut commands: Commands,
+    mut grid_query: Query<(&GridEditor, &GenerateBspDungeon, Option<&mut GridJournal>, Entity)>,
+    selections: Query<&CellSelection>,
+    mut assets: ResMut<Assets<Grid>>,
+    mut console: ResMut<ErrorConsole>,
+    time: Res<Time>,
+    mut map_rng: ResMut<MapRng>,
+) {
+    for (grid_editor, generate, journal, entity) in &mut grid_query {
+        commands.entity(entity).remove::<GenerateBspDungeon>();
+
+        let Some(grid) = assets.get(&grid_editor.grid) else {
+            console.report("generate_bsp_dungeons", &GridNotFound { entity });
+            continue;
+        };
+
+        let (seed, mut rng) = map_rng.next_rng();
+        let dungeon = bsp_dungeon(grid.width(), grid.height(), generate.min_leaf, generate.max_leaf, &mut rng);
+        let selection = selection_of(&selections, entity);
+        lay_out(&mut assets, &grid_editor.grid, selection, journal, time.elapsed_seconds_f64(), &dungeon, seed);
+    }
+}
+
+/// Sets the cells of the grid behind `handle` to those of `dungeon` within
+/// `selection`, journaling them, and tags its rooms.
+fn lay_out(
+    assets: &mut Assets<Grid>,
+    handle: &Handle<Grid>,
+    selection: Option<&CellSelection>,
+    journal: Option<Mut<GridJournal>>,
+    now: f64,
+    dungeon: &Dungeon,
+    seed: u64,
+) {
+    let Some(grid) = assets.get(handle) else {
+        return;
+    };
+    let selected: Vec<(CellPos, Cell)> = grid
+        .iter_cell_pos()
+        .filter(|(cell_pos, _)| selection.is_none_or(|selection| selection.cells.contains(cell_pos)))
+        .collect();
+    let changes: Vec<(CellPos, Cell, Cell)> = selected
+        .iter()
+        .filter_map(|&(cell_pos, old)| Some((cell_pos, old, dungeon.cells.cell(cell_pos)?)))
+        .filter(|(_, old, new)| old != new)
+        .collect();
+    set_all_cells(assets, handle, journal, now, changes, Some(seed));
+
+    let grid = assets.get_mut(handle).expect("read above");
+    if grid.layer::<DungeonRoom>().is_none() {
+        grid.add_layer(DungeonRoom::NONE);
+    }
+    if let Some(layer) = grid.layer_mut::<DungeonRoom>() {
+        for (cell_pos, _) in selected {
+            let room = dungeon.room_at(cell_pos).unwrap_or(DungeonRoom::NONE);
+            layer.set(cell_pos, room).expect("iterated from the grid");
+        }
+    }
+}
@@ -0,0 +1,823 @@
+//! Extension points for adding tools and overlays to grid editors from other crates.
+//!
+//! An [`EditorTool`] changes the cell it is used on. Tools are registered in the
+//! [`EditorTools`] resource, which lists them in order and tracks the selected
+//! one; inserting [`UseTool`] on a grid editor entity applies the selected tool
+//! there, the same way [`ResizeGrid`] requests a resize, and a [`UseToolEvent`]
+//! does too, as many times a frame as it is sent. The `ControlPanel` and the
+//! keys of the `WallPaintingPlugin` pick from the same list.
+//!
+//! Systems that set cells themselves can send [`SetCellEvent`]s instead of
+//! editing the grid asset, which leaves the journaling and the
+//! `CellChangeEvent`s to the editor.
+//!
+//! A [`CellOverlay`] is a component that paints over the cell colors of a grid,
+//! like `RangeHighlight` does. [`EditorAppExt::add_cell_overlay`] schedules its
+//! painting after the views reset the colors. Overlays need the `visualizer`
+//! feature.
+//!
+//! With the `egui` feature, [`EditorAppExt::add_panel_section`] adds a
+//! `PanelSection` of controls to the bottom of the `ControlPanel` window.
+
+use std::{
+    collections::{HashSet, VecDeque},
+    error::Error,
+    fmt::Display,
+};
+
+use bevy::prelude::*;
+use rand::seq::SliceRandom;
+
+use crate::{
+    console::ErrorConsole,
+    grid::{Cell, CellChangeEvent, CellPos, Grid, GridEditor, GridNotFound},
+    journal::GridJournal,
+    regions::Regions,
+    seed::MapRng,
+    terrain::Terrain,
+};
+#[cfg(feature = "egui")]
+use crate::panel::{PanelSection, PanelSections};
+#[cfg(feature = "visualizer")]
+use crate::{
+    grid::GridView,
+    theme::GridTheme,
+    view::{self, CellColors},
+    GridEditSet, PathComputeSet, ViewSyncSet,
+};
+
+/// Something the user can do to a cell of a grid editor.
+pub trait EditorTool: Send + Sync + 'static {
+    /// Shown in tool lists; also used to select the tool.
+    fn name(&self) -> &str;
+
+    /// Whether applying the tool to `cell_pos` could change the grid. Asked
+    /// before the grid asset is borrowed to be changed, which marks it modified
+    /// and rebuilds whatever was derived from it, so tools that can tell when
+    /// they would do nothing should say so.
+    fn changes(&self, _grid: &Grid, _cell_pos: CellPos) -> bool {
+        true
+    }
+
+    /// Applies the tool to `cell_pos`, returning true if that cell changed.
+    /// Changes to other cells aren't announced to the grid's other systems.
+    fn apply(&mut self, grid: &mut Grid, cell_pos: CellPos) -> bool;
+}
+
+/// Turns floor into wall and wall into floor.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ToggleWall;
+
+impl EditorTool for ToggleWall {
+    fn name(&self) -> &str {
+        "Toggle wall"
+    }
+
+    fn changes(&self, grid: &Grid, cell_pos: CellPos) -> bool {
+        grid.cell(cell_pos).is_ok()
+    }
+
+    fn apply(&mut self, grid: &mut Grid, cell_pos: CellPos) -> bool {
+        let Ok(mut cell) = grid.cell_mut(cell_pos) else {
+            return false;
+        };
+        cell.is_wall = !cell.is_wall;
+        true
+    }
+}
+
+/// Sets cells to a fixed value, e.g. `Cell::wall()` for a wall brush.
+#[derive(Debug, Clone)]
+pub struct PaintCell {
+    pub name: String,
+    pub cell: Cell,
+}
+
+impl PaintCell {
+    pub fn new(name: impl Into<String>, cell: Cell) -> Self {
+        PaintCell { name: name.into(), cell }
+    }
+}
+
+impl EditorTool for PaintCell {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn changes(&self, grid: &Grid, cell_pos: CellPos) -> bool {
+        grid.cell(cell_pos).is_ok_and(|cell| cell != self.cell)
+    }
+
+    fn apply(&mut self, grid: &mut Grid, cell_pos: CellPos) -> bool {
+        match grid.cell(cell_pos) {
+            Ok(cell) if cell != self.cell => grid.set_cell(cell_pos, self.cell).is_ok(),
+            _ => false,
+        }
+    }
+}
+
+/// The tools offered by grid editors, in the order they are listed.
+#[derive(Resource)]
+pub struct EditorTools {
+    tools: Vec<Box<dyn EditorTool>>,
+    selected: Option<usize>,
+}
+
+impl Default for EditorTools {
+    fn default() -> Self {
+        let mut tools = EditorTools { tools: Vec::new(), selected: None };
+        tools
+            .add(ToggleWall)
+            .add(PaintCell::new("Wall", Cell::wall()))
+            .add(PaintCell::new("Floor", Cell::floor()));
+        tools
+    }
+}
+
+impl EditorTools {
+    /// An empty tool list, for editors that offer only their own tools.
+    pub fn empty() -> Self {
+        EditorTools { tools: Vec::new(), selected: None }
+    }
+
+    /// Adds `tool` to the end of the list. The first tool added gets selected.
+    pub fn add(&mut self, tool: impl EditorTool) -> &mut Self {
+        self.tools.push(Box::new(tool));
+        self.selected.get_or_insert(0);
+        self
+    }
+
+    /// Selects the tool called `name`, returning false if there is none.
+    pub fn select(&mut self, name: &str) -> bool {
+        match self.tools.iter().position(|tool| tool.name() == name) {
+            Some(index) => {
+                self.selected = Some(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn selected(&self) -> Option<&dyn EditorTool> {
+        self.selected.map(|index| self.tools[index].as_ref())
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> + '_ {
+        self.tools.iter().map(|tool| tool.name())
+    }
+
+    pub fn len(&self) -> usize {
+        self.tools.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+
+    fn selected_mut(&mut self) -> Option<&mut dyn EditorTool> {
+        let index = self.selected?;
+        Some(self.tools[index].as_mut())
+    }
+}
+
+/// Inserted on a grid editor entity to apply the selected tool to a cell.
+/// `by` is journaled as the author of the change when the grid has a `GridJournal`.
+#[derive(Component)]
+pub struct UseTool {
+    pub cell_pos: CellPos,
+    pub by: Option<Entity>,
+}
+
+impl UseTool {
+    pub fn new(cell_pos: CellPos) -> Self {
+        UseTool { cell_pos, by: None }
+    }
+
+    pub fn by(mut self, author: Entity) -> Self {
+        self.by = Some(author);
+        self
+    }
+}
+
+/// Applies the selected tool to a cell of the grid held by the grid editor
+/// `grid`, like [`UseTool`] but any number of times a frame, e.g. along a stroke.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UseToolEvent {
+    pub grid: Entity,
+    pub cell_pos: CellPos,
+    /// Journaled as the author of the change.
+    pub by: Option<Entity>,
+}
+
+impl UseToolEvent {
+    pub fn new(grid: Entity, cell_pos: CellPos) -> Self {
+        UseToolEvent { grid, cell_pos, by: None }
+    }
+
+    pub fn by(mut self, author: Entity) -> Self {
+        self.by = Some(author);
+        self
+    }
+}
+
+/// Applies the [`UseTool`] requests, then the [`UseToolEvent`]s in the order
+/// they were sent.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn use_tools(
+    mut commands: Commands,
+    requests: Query<(&UseTool, Entity)>,
+    mut tool_events: EventReader<UseToolEvent>,
+    mut editors: Query<(&GridEditor, Option<&mut GridJournal>)>,
+    mut assets: ResMut<Assets<Grid>>,
+    mut tools: ResMut<EditorTools>,
+    mut console: ResMut<ErrorConsole>,
+    mut cell_events: EventWriter<CellChangeEvent>,
+    time: Res<Time>,
+) {
+    let mut uses = Vec::new();
+    for (&UseTool { cell_pos, by }, entity) in &requests {
+        commands.entity(entity).remove::<UseTool>();
+        uses.push(UseToolEvent { grid: entity, cell_pos, by });
+    }
+    uses.extend(tool_events.iter().copied());
+
+    for UseToolEvent { grid: entity, cell_pos, by } in uses {
+        let Some(tool) = tools.selected_mut() else {
+            continue;
+        };
+        let Ok((grid_editor, journal)) = editors.get_mut(entity) else {
+            console.report("use_tools", &GridNotFound { entity });
+            continue;
+        };
+        let Some(grid) = assets.get(&grid_editor.grid) else {
+            console.report("use_tools", &GridNotFound { entity });
+            continue;
+        };
+
+        // Reading first keeps uses that change nothing from modifying the asset.
+        if !tool.changes(grid, cell_pos) {
+            continue;
+        }
+        let grid = assets.get_mut(&grid_editor.grid).expect("read above");
+        let old = grid.cell(cell_pos).ok();
+        if !tool.apply(grid, cell_pos) {
+            continue;
+        }
+
+        let (Some(old), Ok(new)) = (old, grid.cell(cell_pos)) else {
+            continue;
+        };
+        if let Some(mut journal) = journal {
+            journal.log(by, time.elapsed_seconds_f64(), cell_pos, old, new);
+        }
+        cell_events.send(CellChangeEvent { grid: entity, cell_pos, old, new });
+    }
+}
+
+/// Sets a cell of the grid held by the grid editor `grid`. Queued edits are
+/// applied in the order they were sent, journaled when the grid has a
+/// `GridJournal`, and announced with a `CellChangeEvent` if they change the cell.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SetCellEvent {
+    pub grid: Entity,
+    pub cell_pos: CellPos,
+    pub cell: Cell,
+    /// Journaled as the author of the change.
+    pub by: Option<Entity>,
+}
+
+impl SetCellEvent {
+    pub fn new(grid: Entity, cell_pos: CellPos, cell: Cell) -> Self {
+        SetCellEvent { grid, cell_pos, cell, by: None }
+    }
+
+    pub fn by(mut self, author: Entity) -> Self {
+        self.by = Some(author);
+        self
+    }
+}
+
+pub(crate) fn apply_cell_edits(
+    mut edits: EventReader<SetCellEvent>,
+    mut editors: Query<(&GridEditor, Option<&mut GridJournal>)>,
+    mut assets: ResMut<Assets<Grid>>,
+    mut console: ResMut<ErrorConsole>,
+    mut cell_events: EventWriter<CellChangeEvent>,
+    time: Res<Time>,
+) {
+    for &SetCellEvent { grid: entity, cell_pos, cell, by } in edits.iter() {
+        let Ok((grid_editor, journal)) = editors.get_mut(entity) else {
+            console.report("apply_cell_edits", &GridNotFound { entity });
+            continue;
+        };
+
+        // Reading first keeps edits that change nothing from modifying the asset.
+        let old = match assets.get(&grid_editor.grid).map(|grid| grid.cell(cell_pos)) {
+            Some(Ok(old)) if old == cell => continue,
+            Some(Ok(old)) => old,
+            Some(Err(error)) => {
+                console.report("apply_cell_edits", &error);
+                continue;
+            }
+            None => {
+                console.report("apply_cell_edits", &GridNotFound { entity });
+                continue;
+            }
+        };
+
+        let grid = assets.get_mut(&grid_editor.grid).expect("read above");
+        grid.set_cell(cell_pos, cell).expect("read above");
+
+        if let Some(mut journal) = journal {
+            journal.log(by, time.elapsed_seconds_f64(), cell_pos, old, cell);
+        }
+        cell_events.send(CellChangeEvent { grid: entity, cell_pos, old, new: cell });
+    }
+}
+
+/// Inserted on a grid editor entity to request a new size for its grid.
+#[derive(Component)]
+pub struct ResizeGrid {
+    pub width: u32,
+    pub height: u32,
+    pub fill: Cell,
+}
+
+pub(crate) fn resize_grid(
+    mut commands: Commands,
+    grid_query: Query<(&GridEditor, &ResizeGrid, Entity)>,
+    mut assets: ResMut<Assets<Grid>>,
+    mut console: ResMut<ErrorConsole>,
+) {
+    for (grid_editor, resize, entity) in &grid_query {
+        commands.entity(entity).remove::<ResizeGrid>();
+
+        let Some(grid) = assets.get_mut(&grid_editor.grid) else {
+            console.report("resize_grid", &GridNotFound { entity });
+            continue;
+        };
+        grid.resize(resize.width, resize.height, resize.fill);
+    }
+}
+
+/// Inserted on a grid editor entity to change every cell of its grid at once,
+/// like [`ResizeGrid`], or only the cells of its [`CellSelection`] when it has
+/// one. The asset is modified once instead of announcing each cell with a
+/// `CellChangeEvent`, so what is derived from the grid is rebuilt in one go
+/// rather than cell by cell. The changes are still journaled.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BulkEdit {
+    /// Turns every wall into floor.
+    ClearWalls,
+    /// Turns every cell into a wall.
+    FillWalls,
+    /// Swaps walls and floor.
+    InvertWalls,
+}
+
+impl BulkEdit {
+    pub const ALL: [BulkEdit; 3] = [BulkEdit::ClearWalls, BulkEdit::FillWalls, BulkEdit::InvertWalls];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BulkEdit::ClearWalls => "Clear",
+            BulkEdit::FillWalls => "Fill",
+            BulkEdit::InvertWalls => "Invert",
+        }
+    }
+
+    /// What `cell` becomes.
+    pub fn apply(self, cell: Cell) -> Cell {
+        match self {
+            BulkEdit::ClearWalls => Cell::floor(),
+            BulkEdit::FillWalls => Cell::wall(),
+            BulkEdit::InvertWalls => Cell { is_wall: !cell.is_wall },
+        }
+    }
+}
+
+pub(crate) fn apply_bulk_edits(
+    mut commands: Commands,
+    mut grid_query: Query<(&GridEditor, &BulkEdit, Option<&mut GridJournal>, Entity)>,
+    selections: Query<&CellSelection>,
+    mut assets: ResMut<Assets<Grid>>,
+    mut console: ResMut<ErrorConsole>,
+    time: Res<Time>,
+) {
+    for (grid_editor, &edit, journal, entity) in &mut grid_query {
+        commands.entity(entity).remove::<BulkEdit>();
+
+        let Some(grid) = assets.get(&grid_editor.grid) else {
+            console.report("apply_bulk_edits", &GridNotFound { entity });
+            continue;
+        };
+        let selection = selection_of(&selections, entity);
+        let changes: Vec<(CellPos, Cell, Cell)> = grid
+            .iter_cell_pos()
+            .filter(|&(cell_pos, _)| selection.is_none_or(|selection| selection.cells.contains(&cell_pos)))
+            .map(|(cell_pos, old)| (cell_pos, old, edit.apply(old)))
+            .filter(|(_, old, new)| old != new)
+            .collect();
+        set_all_cells(&mut assets, &grid_editor.grid, journal, time.elapsed_seconds_f64(), changes, None);
+    }
+}
+
+/// Sets the cells `changes` go from and to with a single modification of the
+/// asset, journaling them, and records the `seed` they were generated from if
+/// given. Does nothing when there are no changes, so an edit that changes
+/// nothing doesn't rebuild everything.
+pub(crate) fn set_all_cells(
+    assets: &mut Assets<Grid>,
+    handle: &Handle<Grid>,
+    journal: Option<Mut<GridJournal>>,
+    now: f64,
+    changes: Vec<(CellPos, Cell, Cell)>,
+    seed: Option<u64>,
+) {
+    if changes.is_empty() {
+        return;
+    }
+
+    let Some(grid) = assets.get_mut(handle) else {
+        return;
+    };
+    for &(cell_pos, _, new) in &changes {
+        grid.set_cell(cell_pos, new).expect("iterated from the grid");
+    }
+    if seed.is_some() {
+        grid.set_seed(seed);
+    }
+
+    if let Some(mut journal) = journal {
+        for (cell_pos, old, new) in changes {
+            journal.log(None, now, cell_pos, old, new);
+        }
+    }
+}
+
+/// Inserted on a grid editor entity to lay out its walls at random, like a
+/// [`BulkEdit`]: `density` of the cells become walls and the rest floor, all at
+/// once, within the [`CellSelection`] if the grid has one. The cells of
+/// `keep_clear`, and those within `clear_radius` steps of them in any
+/// direction, are left as floor and not counted.
+///
+/// With `keep_connected` set, the layout is checked against the grid's
+/// [`Regions`] to still leave a path between the two cells, and laid out again
+/// a few times if it doesn't. Should every try wall them apart, the fewest
+/// walls cutting them off are opened again, so benchmarks run on random maps
+/// never ask for a path that can't exist, at the cost of a density a little
+/// under the one asked for.
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct RandomizeWalls {
+    /// Share of the cells that become walls, from 0 to 1.
+    pub density: f64,
+    pub keep_clear: Vec<CellPos>,
+    pub clear_radius: u32,
+    /// Start and goal left connected, and as floor.
+    pub keep_connected: Option<(CellPos, CellPos)>,
+}
+
+impl RandomizeWalls {
+    /// Layouts tried before walls are opened to connect `keep_connected`.
+    pub const ATTEMPTS: usize = 8;
+
+    pub fn new(density: f64) -> Self {
+        RandomizeWalls { density, keep_clear: Vec::new(), clear_radius: 0, keep_connected: None }
+    }
+
+    /// Keeps a path between `start` and `goal`.
+    pub fn with_connected(mut self, start: CellPos, goal: CellPos) -> Self {
+        self.keep_connected = Some((start, goal));
+        self
+    }
+
+    /// Leaves `cells` and those within `radius` steps of them as floor.
+    pub fn with_clear_cells(mut self, cells: impl IntoIterator<Item = CellPos>, radius: u32) -> Self {
+        self.keep_clear = cells.into_iter().collect();
+        self.clear_radius = radius;
+        self
+    }
+
+    fn kept_clear(&self, cell_pos: CellPos) -> bool {
+        let radius = self.clear_radius as i32;
+        let near = self
+            .keep_clear
+            .iter()
+            .any(|clear| (clear.0 - cell_pos.0).abs() <= radius && (clear.1 - cell_pos.1).abs() <= radius);
+        near || self.keep_connected.is_some_and(|(start, goal)| cell_pos == start || cell_pos == goal)
+    }
+}
+
+/// Why a randomized grid was left without a path between the cells it was
+/// asked to keep connected: walls it can't change, beyond its selection, cut
+/// them off.
+#[derive(Debug)]
+pub struct Unsolvable {
+    pub start: CellPos,
+    pub goal: CellPos,
+}
+
+impl Display for Unsolvable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (start, goal) = (self.start, self.goal);
+        write!(f, "walls outside the selection cut {start:?} off from {goal:?}")
+    }
+}
+impl Error for Unsolvable {}
+
+pub(crate) fn randomize_walls(
+    mut commands: Commands,
+    mut grid_query: Query<(&GridEditor, &RandomizeWalls, Option<&mut GridJournal>, Entity)>,
+    selections: Query<&CellSelection>,
+    mut assets: ResMut<Assets<Grid>>,
+    mut console: ResMut<ErrorConsole>,
+    time: Res<Time>,
+    mut map_rng: ResMut<MapRng>,
+) {
+    for (grid_editor, randomize, journal, entity) in &mut grid_query {
+        commands.entity(entity).remove::<RandomizeWalls>();
+
+        let Some(grid) = assets.get(&grid_editor.grid) else {
+            console.report("randomize_walls", &GridNotFound { entity });
+            continue;
+        };
+
+        let (seed, mut rng) = map_rng.next_rng();
+        let selection = selection_of(&selections, entity);
+        let selected = |cell_pos: &CellPos| selection.is_none_or(|selection| selection.cells.contains(cell_pos));
+
+        // Exactly the share asked for, rather than each cell rolling for it.
+        let mut candidates: Vec<CellPos> = grid
+            .iter_cell_pos()
+            .map(|(cell_pos, _)| cell_pos)
+            .filter(|cell_pos| selected(cell_pos) && !randomize.kept_clear(*cell_pos))
+            .collect();
+        let wall_count = (randomize.density.clamp(0.0, 1.0) * candidates.len() as f64).round() as usize;
+        let mut lay_out = || {
+            candidates.shuffle(&mut rng);
+            candidates.iter().copied().take(wall_count).collect::<HashSet<CellPos>>()
+        };
+        let laid_out = |walls: &HashSet<CellPos>| {
+            let mut laid_out = grid.clone();
+            for (cell_pos, _) in grid.iter_cell_pos().filter(|(cell_pos, _)| selected(cell_pos)) {
+                let cell = Cell { is_wall: walls.contains(&cell_pos) };
+                laid_out.set_cell(cell_pos, cell).expect("iterated from the grid");
+            }
+            laid_out
+        };
+
+        let mut walls = lay_out();
+        if let Some((start, goal)) = randomize.keep_connected {
+            let connected = |walls: &HashSet<CellPos>| Regions::new(&laid_out(walls)).is_reachable(start, goal);
+            for _ in 1..RandomizeWalls::ATTEMPTS {
+                if connected(&walls) {
+                    break;
+                }
+                walls = lay_out();
+            }
+            if !connected(&walls) && !open_path(&laid_out(&walls), &mut walls, start, goal) {
+                console.report("randomize_walls", &Unsolvable { start, goal });
+            }
+        }
+
+        let changes: Vec<(CellPos, Cell, Cell)> = grid
+            .iter_cell_pos()
+            .filter(|(cell_pos, _)| selected(cell_pos))
+            .map(|(cell_pos, old)| (cell_pos, old, Cell { is_wall: walls.contains(&cell_pos) }))
+            .filter(|(_, old, new)| old != new)
+            .collect();
+        set_all_cells(&mut assets, &grid_editor.grid, journal, time.elapsed_seconds_f64(), changes, Some(seed));
+    }
+}
+
+/// Takes out of `walls` the fewest of them that open a path from `start` to
+/// `goal` on `grid`, which has them laid out, stepping along rows and columns
+/// so the path holds whatever the connectivity. Walls not in `walls` stay.
+/// Returns false if those cut the two off anyway.
+fn open_path(grid: &Grid, walls: &mut HashSet<CellPos>, start: CellPos, goal: CellPos) -> bool {
+    let index = |CellPos(x, y): CellPos| (y as u32 * grid.width() + x as u32) as usize;
+    if !grid.in_bounds(start) || !grid.in_bounds(goal) {
+        return false;
+    }
+
+    // Breadth-first over walls opened, floor first: a deque with floor steps
+    // pushed to the front and wall steps to the back.
+    let mut opened = vec![usize::MAX; (grid.width() * grid.height()) as usize];
+    let mut came_from = vec![None; opened.len()];
+    let mut queue = VecDeque::from([(start, 0)]);
+    opened[index(start)] = 0;
+    while let Some((cell_pos, count)) = queue.pop_front() {
+        if cell_pos == goal {
+            break;
+        }
+        if count > opened[index(cell_pos)] {
+            continue;
+        }
+
+        let CellPos(x, y) = cell_pos;
+        for neighbor in [CellPos(x - 1, y), CellPos(x + 1, y), CellPos(x, y - 1), CellPos(x, y + 1)] {
+            let Some(cell) = grid.in_bounds(neighbor).then(|| grid.cell(neighbor).ok()).flatten() else {
+                continue;
+            };
+            if cell.is_wall && !walls.contains(&neighbor) {
+                continue;
+            }
+            let cost = count + usize::from(cell.is_wall);
+            if cost < opened[index(neighbor)] {
+                opened[index(neighbor)] = cost;
+                came_from[index(neighbor)] = Some(cell_pos);
+                match cell.is_wall {
+                    true => queue.push_back((neighbor, cost)),
+                    false => queue.push_front((neighbor, cost)),
+                }
+            }
+        }
+    }
+
+    if opened[index(goal)] == usize::MAX {
+        return false;
+    }
+    let mut cell_pos = goal;
+    while let Some(previous) = came_from[index(cell_pos)] {
+        walls.remove(&cell_pos);
+        cell_pos = previous;
+    }
+    true
+}
+
+/// Inserted on a grid editor entity to set the terrain of every cell at once,
+/// or of its [`CellSelection`], like a [`BulkEdit`]. Terrain isn't journaled.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SetTerrain(pub Terrain);
+
+pub(crate) fn set_terrain(
+    mut commands: Commands,
+    grid_query: Query<(&GridEditor, &SetTerrain, Entity)>,
+    selections: Query<&CellSelection>,
+    mut assets: ResMut<Assets<Grid>>,
+    mut console: ResMut<ErrorConsole>,
+) {
+    for (grid_editor, &SetTerrain(terrain), entity) in &grid_query {
+        commands.entity(entity).remove::<SetTerrain>();
+
+        let Some(grid) = assets.get(&grid_editor.grid) else {
+            console.report("set_terrain", &GridNotFound { entity });
+            continue;
+        };
+        let selection = selection_of(&selections, entity);
+        let changed: Vec<CellPos> = grid
+            .iter_cell_pos()
+            .map(|(cell_pos, _)| cell_pos)
+            .filter(|cell_pos| selection.is_none_or(|selection| selection.cells.contains(cell_pos)))
+            .filter(|&cell_pos| Terrain::at(grid, cell_pos) != terrain)
+            .collect();
+        if changed.is_empty() {
+            continue;
+        }
+
+        let grid = assets.get_mut(&grid_editor.grid).expect("read above");
+        if grid.layer::<Terrain>().is_none() {
+            grid.add_layer(Terrain::PLAIN);
+        }
+        if let Some(layer) = grid.layer_mut::<Terrain>() {
+            for cell_pos in changed {
+                layer.set(cell_pos, terrain).expect("iterated from the grid");
+            }
+        }
+    }
+}
+
+/// Cells of `grid` picked out for editing. While a grid editor has one,
+/// [`BulkEdit`], [`RandomizeWalls`], [`SetTerrain`] and the generators of
+/// mazes, caves and terrain only change the selected cells, so edits can be
+/// kept to part of a large map. Like an `EndpointMarker`, a selection is an
+/// entity of its own; a grid has at most one that counts, the first one found.
+/// With the visualizer the selected cells are tinted in the theme's selection
+/// color.
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
+pub struct CellSelection {
+    pub grid: Entity,
+    pub cells: HashSet<CellPos>,
+}
+
+impl CellSelection {
+    pub fn new(grid: Entity, cells: impl IntoIterator<Item = CellPos>) -> Self {
+        CellSelection { grid, cells: cells.into_iter().collect() }
+    }
+
+    /// The rectangle with corners `from` and `to`, both included.
+    pub fn rectangle(grid: Entity, from: CellPos, to: CellPos) -> Self {
+        let (min_x, max_x, min_y, max_y) = (from.0.min(to.0), from.0.max(to.0), from.1.min(to.1), from.1.max(to.1));
+        CellSelection::new(grid, (min_y..=max_y).flat_map(|y| (min_x..=max_x).map(move |x| CellPos(x, y))))
+    }
+
+    /// The bottom left and top right corners of the smallest rectangle holding
+    /// every selected cell, if any are.
+    pub fn bounds(&self) -> Option<(CellPos, CellPos)> {
+        let xs = || self.cells.iter().map(|cell_pos| cell_pos.0);
+        let ys = || self.cells.iter().map(|cell_pos| cell_pos.1);
+        Some((CellPos(xs().min()?, ys().min()?), CellPos(xs().max()?, ys().max()?)))
+    }
+}
+
+/// The selection of `grid` among `selections`, if any.
+pub fn selection_of<'a>(
+    selections: impl IntoIterator<Item = &'a CellSelection>,
+    grid: Entity,
+) -> Option<&'a CellSelection> {
+    selections.into_iter().find(|selection| selection.grid == grid)
+}
+
+#[cfg(feature = "visualizer")]
+impl CellOverlay for CellSelection {
+    fn grid(&self) -> Entity {
+        self.grid
+    }
+
+    fn cell_tint(&self, _cell_pos: CellPos, base: Color, theme: &GridTheme) -> Option<Color> {
+        Some(view::mix(base, theme.selection, 0.4))
+    }
+
+    fn tinted_cells(&self) -> Option<Vec<CellPos>> {
+        Some(self.cells.iter().copied().collect())
+    }
+}
+
+/// A component painting over the cell colors of a grid.
+#[cfg(feature = "visualizer")]
+pub trait CellOverlay: Component {
+    /// The grid entity whose cells are painted.
+    fn grid(&self) -> Entity;
+
+    /// Color painted over `cell_pos` on top of `base`, if any, in the colors of `theme`
+    /// where it has one for what is painted.
+    fn cell_tint(&self, cell_pos: CellPos, base: Color, theme: &GridTheme) -> Option<Color>;
+
+    /// The only cells `cell_tint` can return a color for, so painting skips the
+    /// rest of the grid. `None`, the default, visits every cell.
+    fn tinted_cells(&self) -> Option<Vec<CellPos>> {
+        None
+    }
+}
+
+/// Paints every `O` over the cell colors; runs after the views reset them.
+#[cfg(feature = "visualizer")]
+pub(crate) fn tint_overlay_cells<O: CellOverlay>(
+    theme: Res<GridTheme>,
+    overlays: Query<&O>,
+    mut cell_colors: Query<&mut CellColors>,
+) {
+    for overlay in &overlays {
+        let Ok(mut colors) = cell_colors.get_mut(overlay.grid()) else {
+            continue;
+        };
+
+        let tint = |cell_pos, base| overlay.cell_tint(cell_pos, base, &theme);
+        match overlay.tinted_cells() {
+            Some(cells) => colors.tint_cells(cells, tint),
+            None => colors.tint(tint),
+        }
+    }
+}
+
+/// Registers editor add-ons on an app using `AStarPlugin`.
+pub trait EditorAppExt {
+    /// Adds `tool` to the [`EditorTools`] list.
+    fn add_editor_tool(&mut self, tool: impl EditorTool) -> &mut Self;
+
+    /// Paints every `O` over the cells of its grid. Needs the visualizer.
+    #[cfg(feature = "visualizer")]
+    fn add_cell_overlay<O: CellOverlay>(&mut self) -> &mut Self;
+
+    /// Shows `section` in the `ControlPanel` window, below the built-in controls
+    /// and the sections added before it.
+    #[cfg(feature = "egui")]
+    fn add_panel_section(&mut self, section: impl PanelSection) -> &mut Self;
+}
+
+impl EditorAppExt for App {
+    fn add_editor_tool(&mut self, tool: impl EditorTool) -> &mut Self {
+        self.init_resource::<EditorTools>();
+        self.world.resource_mut::<EditorTools>().add(tool);
+        self
+    }
+
+    #[cfg(feature = "visualizer")]
+    fn add_cell_overlay<O: CellOverlay>(&mut self) -> &mut Self {
+        self.add_system(
+            tint_overlay_cells::<O>
+                .label(ViewSyncSet)
+                .after(GridEditSet)
+                .after(PathComputeSet)
+                .after(view::update_cells::<GridEditor>)
+                .after(view::update_cells::<GridView>),
+        )
+    }
+
+    #[cfg(feature = "egui")]
+    fn add_panel_section(&mut self, section: impl PanelSection) -> &mut Self {
+        self.init_resource::<PanelSections>();
+        self.world.resource_mut::<PanelSections>().add(section);
+        self
+    }
+}
@@ -0,0 +1,124 @@
+//! Flow fields drawn as arrows over their grid.
+//!
+//! A [`FlowArrows`] draws an arrow in every cell of its [`FlowField`] pointing
+//! at the cell to step to next. Zoomed out, arrows are only drawn every few
+//! cells and grow to match, so they never crowd closer than `min_spacing`
+//! screen pixels and the field stays readable on large grids.
+
+use bevy::{
+    prelude::*,
+    sprite::{MaterialMesh2dBundle, Mesh2dHandle},
+};
+
+use crate::{grid::Grids, minimap::MainCameraFilter, pathfinding::FlowField, view::arrow_mesh};
+
+/// How far above the grid texture flow arrows are drawn, below path lines.
+const ARROW_DEPTH: f32 = 0.5;
+
+/// Draws the steps of `field` as arrows over the texture of `grid`.
+#[derive(Component, Debug, Clone)]
+pub struct FlowArrows {
+    pub grid: Entity,
+    pub field: FlowField,
+    pub color: Color,
+    /// Fewest screen pixels between two arrows, thinning them out when zoomed out.
+    pub min_spacing: f32,
+}
+
+impl FlowArrows {
+    pub fn new(grid: Entity, field: FlowField) -> Self {
+        FlowArrows { grid, field, color: Color::WHITE, min_spacing: 12.0 }
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_min_spacing(mut self, min_spacing: f32) -> Self {
+        self.min_spacing = min_spacing;
+        self
+    }
+
+    /// Cells between drawn arrows, in both directions, when a cell is drawn
+    /// `cell_pixels` wide.
+    fn stride(&self, cell_pixels: f32) -> u32 {
+        (self.min_spacing / cell_pixels).ceil().max(1.0) as u32
+    }
+}
+
+/// On the mesh drawn for the [`FlowArrows`] on `arrows`, a child of its grid entity.
+#[derive(Component, Debug, Clone)]
+pub struct FlowArrowMesh {
+    pub arrows: Entity,
+    /// Cells between the drawn arrows.
+    stride: u32,
+    mesh: Handle<Mesh>,
+    material: Handle<ColorMaterial>,
+}
+
+/// Redraws flow arrows that changed, or whose spacing changed with the zoom of
+/// the 2D camera.
+pub(crate) fn draw_flow_arrows(
+    mut commands: Commands,
+    grids: Grids,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    cameras: Query<&OrthographicProjection, MainCameraFilter>,
+    flows: Query<(&FlowArrows, ChangeTrackers<FlowArrows>, Entity)>,
+    mut drawn: Query<(&mut FlowArrowMesh, Entity)>,
+) {
+    // A world unit is a cell, so a cell is drawn `1 / scale` pixels wide.
+    let cell_pixels = cameras.iter().next().map_or(1.0, |projection| 1.0 / projection.scale);
+
+    for (arrows, tracker, arrows_entity) in &flows {
+        let stride = arrows.stride(cell_pixels);
+        let mut existing = drawn.iter_mut().find(|(mesh, _)| mesh.arrows == arrows_entity);
+        if existing.as_ref().is_some_and(|(mesh, _)| mesh.stride == stride) && !tracker.is_changed() {
+            continue;
+        }
+        let Ok(grid) = grids.get(arrows.grid) else {
+            continue;
+        };
+
+        let step = stride as i32;
+        let steps = arrows.field.steps().filter(|&(cell_pos, _)| cell_pos.0 % step == 0 && cell_pos.1 % step == 0);
+        let mesh = arrow_mesh(grid, steps, stride as f32);
+
+        if let Some((drawn_mesh, _)) = &mut existing {
+            drawn_mesh.stride = stride;
+            if let Some(arrow_mesh) = meshes.get_mut(&drawn_mesh.mesh) {
+                *arrow_mesh = mesh;
+            }
+            if let Some(material) = materials.get_mut(&drawn_mesh.material) {
+                material.color = arrows.color;
+            }
+            continue;
+        }
+
+        let (mesh, material) = (meshes.add(mesh), materials.add(ColorMaterial::from(arrows.color)));
+        let mesh_entity = commands
+            .spawn(MaterialMesh2dBundle {
+                mesh: Mesh2dHandle(mesh.clone()),
+                material: material.clone(),
+                transform: Transform::from_xyz(0.0, 0.0, ARROW_DEPTH),
+                ..default()
+            })
+            .insert((FlowArrowMesh { arrows: arrows_entity, stride, mesh, material }, Name::new("Flow arrows")))
+            .id();
+        commands.entity(arrows.grid).add_child(mesh_entity);
+    }
+}
+
+/// Removes the meshes of flow arrows that are gone.
+pub(crate) fn erase_flow_arrows(
+    mut commands: Commands,
+    drawn: Query<(&FlowArrowMesh, Entity)>,
+    flows: Query<(), With<FlowArrows>>,
+) {
+    for (mesh, mesh_entity) in &drawn {
+        if !flows.contains(mesh.arrows) {
+            commands.entity(mesh_entity).despawn_recursive();
+        }
+    }
+}
@@ -0,0 +1,279 @@
+//! The search algorithms over any graph, not just grids of cells.
+//!
+//! A [`SearchGraph`] lists the successors of a node with the cost of each edge,
+//! and may estimate the cost left to a goal. [`GraphSearch`] runs any
+//! [`Planner`] over it, so navmesh polygons or waypoint graphs get the same
+//! A*, Dijkstra and greedy best-first as grids do; grid searches are a
+//! [`GraphSearch`] over the grid's cells.
+
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap, HashSet},
+    fmt::Debug,
+    hash::Hash,
+};
+
+use crate::pathfinding::Planner;
+
+/// A graph searches can run on.
+pub trait SearchGraph {
+    type Node: Copy + Eq + Hash + Debug;
+
+    /// Nodes reachable from `node` in one step, with the cost of each step.
+    /// Costs must not be negative.
+    fn successors(&self, node: Self::Node) -> impl Iterator<Item = (Self::Node, f32)> + '_;
+
+    /// Estimated cost from `node` to `goal`. A* only finds the cheapest path while
+    /// this never exceeds the real cost. Zero by default, which makes A* explore
+    /// like Dijkstra.
+    fn heuristic(&self, _node: Self::Node, _goal: Self::Node) -> f32 {
+        0.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphPath<N> {
+    /// Every node from the start to the goal, both included.
+    pub nodes: Vec<N>,
+    pub cost: f32,
+    pub nodes_expanded: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GraphStepResult<N> {
+    /// The expansion budget ran out before the search finished.
+    Running,
+    Found(GraphPath<N>),
+    NoPath,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct OpenNode<N> {
+    pub(crate) priority: f32,
+    pub(crate) g_score: f32,
+    pub(crate) node: N,
+}
+
+impl<N> PartialEq for OpenNode<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<N> Eq for OpenNode<N> {}
+
+impl<N> PartialOrd for OpenNode<N> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<N> Ord for OpenNode<N> {
+    // Reversed so the `BinaryHeap` pops the lowest priority first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.priority.total_cmp(&self.priority)
+    }
+}
+
+/// A search over a [`SearchGraph`] that can be advanced a few expansions at a
+/// time and inspected in between. It doesn't borrow the graph; every call to
+/// [`GraphSearch::step`] must be given the same one.
+#[derive(Debug, Clone)]
+pub struct GraphSearch<N> {
+    planner: Planner,
+    start: N,
+    goal: N,
+
+    open_set: BinaryHeap<OpenNode<N>>,
+    closed_set: HashSet<N>,
+    came_from: HashMap<N, N>,
+    g_score: HashMap<N, f32>,
+
+    nodes_expanded: usize,
+    /// Every node expanded, in order.
+    expansion_order: Vec<N>,
+    /// Most nodes waiting to be expanded at once so far.
+    peak_open: usize,
+    result: Option<GraphStepResult<N>>,
+}
+
+impl<N: Copy + Eq + Hash + Debug> GraphSearch<N> {
+    pub fn new(planner: Planner, start: N, goal: N) -> Self {
+        // The start is expanded first whatever its priority, so no estimate is needed yet.
+        GraphSearch {
+            planner,
+            start,
+            goal,
+            open_set: BinaryHeap::from([OpenNode { priority: 0.0, g_score: 0.0, node: start }]),
+            closed_set: HashSet::new(),
+            came_from: HashMap::new(),
+            g_score: HashMap::from([(start, 0.0)]),
+            nodes_expanded: 0,
+            expansion_order: Vec::new(),
+            peak_open: 1,
+            result: None,
+        }
+    }
+
+    pub fn planner(&self) -> Planner {
+        self.planner
+    }
+
+    pub fn start(&self) -> N {
+        self.start
+    }
+
+    pub fn goal(&self) -> N {
+        self.goal
+    }
+
+    pub fn nodes_expanded(&self) -> usize {
+        self.nodes_expanded
+    }
+
+    /// Every node expanded so far, in the order they were expanded. A node that
+    /// was reopened after a cheaper way to it turned up appears once per expansion.
+    pub fn expansion_order(&self) -> &[N] {
+        &self.expansion_order
+    }
+
+    /// Nodes waiting to be expanded, without duplicates.
+    pub fn open_len(&self) -> usize {
+        self.g_score.len() - self.closed_set.len()
+    }
+
+    /// Most nodes that were waiting to be expanded at once so far.
+    pub fn peak_open(&self) -> usize {
+        self.peak_open
+    }
+
+    /// The final result, once the search has finished.
+    pub fn result(&self) -> Option<&GraphStepResult<N>> {
+        self.result.as_ref()
+    }
+
+    /// Nodes waiting to be expanded. May contain duplicates of nodes whose cost
+    /// improved after they were first queued.
+    pub fn open_nodes(&self) -> impl Iterator<Item = N> + '_ {
+        self.open_set.iter().map(|open| open.node)
+    }
+
+    pub fn is_open(&self, node: N) -> bool {
+        self.g_score.contains_key(&node) && !self.closed_set.contains(&node)
+    }
+
+    pub fn is_closed(&self, node: N) -> bool {
+        self.closed_set.contains(&node)
+    }
+
+    pub fn closed_nodes(&self) -> impl Iterator<Item = N> + '_ {
+        self.closed_set.iter().copied()
+    }
+
+    /// Every node the search has reached, open or closed.
+    pub fn reached_nodes(&self) -> impl Iterator<Item = N> + '_ {
+        self.g_score.keys().copied()
+    }
+
+    pub fn g_score(&self, node: N) -> Option<f32> {
+        self.g_score.get(&node).copied()
+    }
+
+    /// The node `node` was best reached from so far; `None` for the start and
+    /// for nodes not reached yet.
+    pub fn came_from(&self, node: N) -> Option<N> {
+        self.came_from.get(&node).copied()
+    }
+
+    /// Every reached node but the start, with the node it was best reached from.
+    pub fn parents(&self) -> impl Iterator<Item = (N, N)> + '_ {
+        self.came_from.iter().map(|(&node, &parent)| (node, parent))
+    }
+
+    /// Pops stale entries off the open set and returns the next node to expand.
+    fn peek_frontier(&mut self) -> Option<OpenNode<N>> {
+        while let Some(&open) = self.open_set.peek() {
+            if open.g_score <= self.g_score[&open.node] && !self.closed_set.contains(&open.node) {
+                return Some(open);
+            }
+            self.open_set.pop();
+        }
+        None
+    }
+
+    /// The most promising frontier node, where the search would continue from.
+    pub fn best_frontier(&mut self) -> Option<N> {
+        self.peek_frontier().map(|open| open.node)
+    }
+
+    /// Expands up to `max_expansions` nodes.
+    pub fn step(&mut self, graph: &impl SearchGraph<Node = N>, max_expansions: usize) -> GraphStepResult<N> {
+        if let Some(result) = &self.result {
+            return result.clone();
+        }
+
+        for _ in 0..max_expansions {
+            let Some(OpenNode { g_score, node, .. }) = self.peek_frontier() else {
+                self.result = Some(GraphStepResult::NoPath);
+                return GraphStepResult::NoPath;
+            };
+            self.open_set.pop();
+
+            if node == self.goal {
+                let path = self.path_to(node);
+                self.result = Some(GraphStepResult::Found(path.clone()));
+                return GraphStepResult::Found(path);
+            }
+
+            self.closed_set.insert(node);
+            self.nodes_expanded += 1;
+            self.expansion_order.push(node);
+
+            for (neighbor, step_cost) in graph.successors(node) {
+                let tentative_g_score = g_score + step_cost;
+
+                if self.g_score.get(&neighbor).is_none_or(|&g| tentative_g_score < g) {
+                    self.closed_set.remove(&neighbor);
+                    self.came_from.insert(neighbor, node);
+                    self.g_score.insert(neighbor, tentative_g_score);
+                    self.open_set.push(OpenNode {
+                        priority: self.planner.priority(tentative_g_score, graph.heuristic(neighbor, self.goal)),
+                        g_score: tentative_g_score,
+                        node: neighbor,
+                    });
+                }
+            }
+            self.peak_open = self.peak_open.max(self.open_len());
+        }
+
+        GraphStepResult::Running
+    }
+
+    /// Runs until the search finishes.
+    pub fn run(&mut self, graph: &impl SearchGraph<Node = N>) -> GraphStepResult<N> {
+        self.step(graph, usize::MAX)
+    }
+
+    /// The path this search found so far to `end`, which must have been reached.
+    pub fn path_to(&self, end: N) -> GraphPath<N> {
+        let mut nodes = vec![end];
+        let mut current = end;
+
+        while let Some(&previous) = self.came_from.get(&current) {
+            nodes.push(previous);
+            current = previous;
+        }
+        nodes.reverse();
+
+        GraphPath { nodes, cost: self.g_score[&end], nodes_expanded: self.nodes_expanded }
+    }
+}
+
+/// Searches `graph` from `start` to `goal` with `planner`, returning `None` if
+/// the goal can't be reached.
+pub fn find_graph_path<G: SearchGraph>(graph: &G, planner: Planner, start: G::Node, goal: G::Node) -> Option<GraphPath<G::Node>> {
+    match GraphSearch::new(planner, start, goal).run(graph) {
+        GraphStepResult::Found(path) => Some(path),
+        _ => None,
+    }
+}
@@ -0,0 +1,832 @@
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt::Display, sync::Arc,
+};
+
+use itertools::{Either, Itertools};
+
+use bevy::{ecs::system::SystemParam, prelude::*, reflect::TypeUuid};
+
+use crate::{
+    clearance::Clearance,
+    layer::{GridLayer, Layers},
+    regions::Regions,
+    storage::{CellMut, CellStorage, Chunks, StorageKind},
+};
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Component, Reflect, FromReflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CellPos(pub i32, pub i32);
+
+#[derive(Component, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Cell {
+    pub is_wall: bool,
+}
+
+impl Cell {
+    pub const fn floor() -> Cell {
+        Cell { is_wall: false }
+    }
+
+    pub const fn wall() -> Cell {
+        Cell { is_wall: true }
+    }
+}
+
+/// One end of a portal: stepping onto the entry cell lets a pathfinder jump to
+/// `exit` for `cost`, regardless of the distance between them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Portal {
+    pub exit: CellPos,
+    pub cost: f32,
+}
+
+/// Orthogonal step directions. North is towards increasing `y`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    East,
+    West,
+    North,
+    South,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 4] = [Direction::East, Direction::West, Direction::North, Direction::South];
+
+    pub fn step(self, cell_pos: CellPos) -> CellPos {
+        let CellPos(x, y) = cell_pos;
+        match self {
+            Direction::East => CellPos(x + 1, y),
+            Direction::West => CellPos(x - 1, y),
+            Direction::North => CellPos(x, y + 1),
+            Direction::South => CellPos(x, y - 1),
+        }
+    }
+
+    pub fn opposite(self) -> Direction {
+        match self {
+            Direction::East => Direction::West,
+            Direction::West => Direction::East,
+            Direction::North => Direction::South,
+            Direction::South => Direction::North,
+        }
+    }
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// The directions a cell can be left through. Entering a cell is never
+/// restricted, so taking an exit away from one side of a pair of cells makes
+/// the edge between them one-way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Exits(u8);
+
+impl Exits {
+    pub const ALL: Exits = Exits(0b1111);
+    pub const NONE: Exits = Exits(0);
+
+    pub fn only(direction: Direction) -> Exits {
+        Exits(direction.bit())
+    }
+
+    pub fn with(self, direction: Direction) -> Exits {
+        Exits(self.0 | direction.bit())
+    }
+
+    pub fn without(self, direction: Direction) -> Exits {
+        Exits(self.0 & !direction.bit())
+    }
+
+    pub fn contains(self, direction: Direction) -> bool {
+        self.0 & direction.bit() != 0
+    }
+}
+
+impl Default for Exits {
+    fn default() -> Self {
+        Exits::ALL
+    }
+}
+
+/// Which cells count as adjacent, i.e. which moves a single step can make.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Connectivity {
+    /// Orthogonal steps only, each costing `1.0`.
+    #[default]
+    Four,
+    /// Orthogonal steps plus diagonals costing `SQRT_2`. A diagonal can't cut a
+    /// corner: both orthogonal cells beside it must be floor.
+    Eight,
+    /// Six neighbors on hexagons laid out in rows, each step costing `1.0`. Odd
+    /// rows sit half a cell east of even rows.
+    Hex,
+}
+
+impl Connectivity {
+    pub const ALL: [Connectivity; 3] = [Connectivity::Four, Connectivity::Eight, Connectivity::Hex];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Connectivity::Four => "Four",
+            Connectivity::Eight => "Eight",
+            Connectivity::Hex => "Hex",
+        }
+    }
+
+    const FOUR: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+    const EIGHT: [(i32, i32); 8] = [(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (-1, 1), (1, -1), (-1, -1)];
+    const HEX_EVEN_ROW: [(i32, i32); 6] = [(1, 0), (-1, 0), (0, 1), (-1, 1), (0, -1), (-1, -1)];
+    const HEX_ODD_ROW: [(i32, i32); 6] = [(1, 0), (-1, 0), (1, 1), (0, 1), (1, -1), (0, -1)];
+
+    /// Offsets from `cell_pos` to each of its adjacent cells.
+    pub fn offsets(self, cell_pos: CellPos) -> &'static [(i32, i32)] {
+        match self {
+            Connectivity::Four => &Self::FOUR,
+            Connectivity::Eight => &Self::EIGHT,
+            Connectivity::Hex if cell_pos.1.rem_euclid(2) == 0 => &Self::HEX_EVEN_ROW,
+            Connectivity::Hex => &Self::HEX_ODD_ROW,
+        }
+    }
+
+    /// Geometric cost of a single step by `offset`.
+    pub fn step_length(self, offset: (i32, i32)) -> f32 {
+        match (self, offset) {
+            (Connectivity::Eight, (dx, dy)) if dx != 0 && dy != 0 => std::f32::consts::SQRT_2,
+            _ => 1.0,
+        }
+    }
+
+    /// Fewest steps from `from` to `to` on an empty grid.
+    pub fn steps_between(self, from: CellPos, to: CellPos) -> u32 {
+        let (CellPos(x0, y0), CellPos(x1, y1)) = (from, to);
+        let (dx, dy) = ((x1 - x0).unsigned_abs(), (y1 - y0).unsigned_abs());
+
+        match self {
+            Connectivity::Four => dx + dy,
+            Connectivity::Eight => dx.max(dy),
+            Connectivity::Hex => {
+                // Through cube coordinates, undoing the half-cell shift of odd rows.
+                let cube_x = |x: i32, y: i32| x - (y - (y & 1)) / 2;
+                let (q0, q1) = (cube_x(x0, y0), cube_x(x1, y1));
+                let (dq, dr) = (q1 - q0, y1 - y0);
+                (dq.unsigned_abs() + dr.unsigned_abs() + (dq + dr).unsigned_abs()) / 2
+            }
+        }
+    }
+
+    /// Cheapest cost from `from` to `to` on an empty grid, a lower bound for
+    /// searches whose step costs are at least the step lengths.
+    pub fn distance(self, from: CellPos, to: CellPos) -> f32 {
+        match self {
+            Connectivity::Eight => {
+                let (CellPos(x0, y0), CellPos(x1, y1)) = (from, to);
+                let (dx, dy) = ((x1 - x0).unsigned_abs(), (y1 - y0).unsigned_abs());
+                let (short, long) = (dx.min(dy) as f32, dx.max(dy) as f32);
+                long - short + short * std::f32::consts::SQRT_2
+            }
+            _ => self.steps_between(from, to) as f32,
+        }
+    }
+}
+
+/// What lies beyond the stored cells of a grid.
+#[derive(Clone, Default)]
+pub enum Boundary {
+    /// Nothing: the border acts as an implicit wall.
+    #[default]
+    Solid,
+    /// A ring of floor `margin` cells wide around the grid, so paths can leave
+    /// the map. Kept finite so searches for unreachable goals still end.
+    Open { margin: u32 },
+    /// Cells in every direction without end, starting out as `fill`. Cells past
+    /// the border can be written as well and are kept in chunks, so the stored
+    /// grid is only the part allocated up front. Searches must be bounded, e.g.
+    /// with `AStar::with_search_radius`, or one for an unreachable goal never
+    /// ends. Portals and exits can only be placed on stored cells.
+    Unbounded { fill: Cell },
+    /// Asks a callback for the cell at each position outside the grid; `None`
+    /// means the position doesn't exist. Only finitely many positions should
+    /// exist, for the same reason as the `Open` margin.
+    Custom(Arc<dyn Fn(CellPos) -> Option<Cell> + Send + Sync>),
+}
+
+impl std::fmt::Debug for Boundary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Boundary::Solid => write!(f, "Solid"),
+            Boundary::Open { margin } => f.debug_struct("Open").field("margin", margin).finish(),
+            Boundary::Unbounded { fill } => f.debug_struct("Unbounded").field("fill", fill).finish(),
+            Boundary::Custom(_) => write!(f, "Custom(..)"),
+        }
+    }
+}
+
+/// A map of cells. Stored in `Assets<Grid>` and shown by the `GridEditor` and
+/// `GridView` entities holding a handle to it.
+#[derive(Debug, Clone, TypeUuid)]
+#[uuid = "5d3b0a3e-8f3c-4d8e-9a57-3c1f0b6e2a41"]
+pub struct Grid {
+    width: u32,
+    height: u32,
+    boundary: Boundary,
+    connectivity: Connectivity,
+    /// Stored row by row inside a one-cell ring of walls, so the neighbors of any
+    /// stored cell can be read without bounds checks.
+    cells: CellStorage,
+    /// Index offsets to the neighbor in each of `Direction::ALL`.
+    neighbor_offsets: [isize; 4],
+    /// Cells written past the border of an unbounded grid.
+    chunks: Chunks,
+    portals: HashMap<CellPos, Portal>,
+    /// Cells that can't be left in every direction. Absent cells have all exits.
+    exits: HashMap<CellPos, Exits>,
+    layers: Layers,
+    /// What the walls were last generated from.
+    seed: Option<u64>,
+}
+
+/// Shows a grid. Several views, and an editor, can show the same grid asset.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct GridView {
+    pub grid: Handle<Grid>,
+}
+
+impl GridView {
+    pub fn new(grid: Handle<Grid>) -> Self {
+        GridView { grid }
+    }
+}
+
+/// Shows a grid and takes edits such as `ResizeGrid`, `BulkEdit`,
+/// `RandomizeWalls`, `GenerateMaze`, `GenerateCave` and `UseTool`, applied to
+/// the grid asset through `Assets<Grid>`.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct GridEditor {
+    pub grid: Handle<Grid>,
+}
+
+impl GridEditor {
+    pub fn new(grid: Handle<Grid>) -> Self {
+        GridEditor { grid }
+    }
+}
+
+/// Components that hold a grid handle, so systems can handle editors and views
+/// the same way.
+pub trait GridHandle: Component {
+    fn handle(&self) -> &Handle<Grid>;
+}
+
+impl GridHandle for GridEditor {
+    fn handle(&self) -> &Handle<Grid> {
+        &self.grid
+    }
+}
+
+impl GridHandle for GridView {
+    fn handle(&self) -> &Handle<Grid> {
+        &self.grid
+    }
+}
+
+/// Grids modified since the reader last ran, by any system.
+pub(crate) fn modified_grids<'a>(asset_events: &'a mut EventReader<AssetEvent<Grid>>) -> HashSet<&'a Handle<Grid>> {
+    asset_events
+        .iter()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { handle } => Some(handle),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Matches modifications of grid assets with the `CellChangeEvent`s announcing
+/// them, so systems following cell events can tell when a grid was changed some
+/// other way, e.g. resized, and what they derived from it must be rebuilt.
+#[derive(Default)]
+pub(crate) struct UnannouncedChanges {
+    announced: HashMap<Handle<Grid>, usize>,
+}
+
+impl UnannouncedChanges {
+    pub(crate) fn announce(&mut self, handle: &Handle<Grid>) {
+        *self.announced.entry(handle.clone()).or_default() += 1;
+    }
+
+    /// Grids modified more often than announced. The modifications of a frame
+    /// are only seen the frame after, so cell events must be announced before
+    /// this is asked, in the same run.
+    pub(crate) fn unannounced(&mut self, asset_events: &mut EventReader<AssetEvent<Grid>>) -> HashSet<Handle<Grid>> {
+        let mut modifications: HashMap<&Handle<Grid>, usize> = HashMap::new();
+        for event in asset_events.iter() {
+            if let AssetEvent::Modified { handle } = event {
+                *modifications.entry(handle).or_default() += 1;
+            }
+        }
+
+        let mut unannounced = HashSet::new();
+        for (handle, count) in modifications {
+            let announced = self.announced.remove(handle).unwrap_or(0);
+            if count > announced {
+                unannounced.insert(handle.clone());
+            } else if announced > count {
+                self.announced.insert(handle.clone(), announced - count);
+            }
+        }
+        unannounced
+    }
+}
+
+/// Looks up the grid held by a specific `GridEditor` or `GridView` entity.
+#[derive(SystemParam)]
+pub struct Grids<'w, 's> {
+    editors: Query<'w, 's, &'static GridEditor>,
+    views: Query<'w, 's, &'static GridView>,
+    assets: Res<'w, Assets<Grid>>,
+    regions: Query<'w, 's, &'static Regions>,
+    clearance: Query<'w, 's, &'static Clearance>,
+}
+
+impl Grids<'_, '_> {
+    /// The grid of `entity`. Fails as well while the asset it refers to is
+    /// missing, e.g. still loading.
+    pub fn get(&self, entity: Entity) -> Result<&Grid, GridNotFound> {
+        self.handle(entity)
+            .and_then(|handle| self.assets.get(handle))
+            .ok_or(GridNotFound { entity })
+    }
+
+    pub fn handle(&self, entity: Entity) -> Option<&Handle<Grid>> {
+        match self.editors.get(entity) {
+            Ok(editor) => Some(editor.handle()),
+            Err(_) => self.views.get(entity).ok().map(GridHandle::handle),
+        }
+    }
+
+    /// Region labels of the grid held by `entity`, if they are being maintained.
+    pub fn regions(&self, entity: Entity) -> Option<&Regions> {
+        self.regions.get(entity).ok()
+    }
+
+    /// Clearance of the grid held by `entity`, if it is being maintained.
+    pub fn clearance(&self, entity: Entity) -> Option<&Clearance> {
+        self.clearance.get(entity).ok()
+    }
+
+    /// The grids of every editor and view; a grid shown by several of them is
+    /// yielded once for each.
+    pub fn iter(&self) -> impl Iterator<Item = &Grid> {
+        self.editors
+            .iter()
+            .map(GridHandle::handle)
+            .chain(self.views.iter().map(GridHandle::handle))
+            .filter_map(|handle| self.assets.get(handle))
+    }
+}
+
+/// Sent when a cell of the grid held by `grid` changes, so systems that derive
+/// data from the grid can update just that cell. Systems editing a grid asset
+/// directly should send one per changed cell; otherwise such data is rebuilt
+/// from scratch.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CellChangeEvent {
+    pub grid: Entity,
+    pub cell_pos: CellPos,
+    pub old: Cell,
+    pub new: Cell,
+}
+
+#[derive(Debug)]
+pub struct OutOfBounds {
+    pub cell_pos: CellPos,
+}
+
+impl Display for OutOfBounds {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let cell_pos = self.cell_pos;
+        write!(f, "out of bounds cell position: {cell_pos:?}")
+    }
+}
+impl Error for OutOfBounds {}
+
+#[derive(Debug)]
+pub struct GridNotFound {
+    pub entity: Entity,
+}
+
+impl Display for GridNotFound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let entity = self.entity;
+        write!(f, "entity {entity:?} has no grid")
+    }
+}
+impl Error for GridNotFound {}
+
+impl Grid {
+    pub fn new(width: u32, height: u32) -> Self {
+        Grid::with_storage(width, height, StorageKind::Cells)
+    }
+
+    pub fn with_storage(width: u32, height: u32, storage: StorageKind) -> Self {
+        let mut grid = Grid {
+            width: 0,
+            height: 0,
+            boundary: Boundary::Solid,
+            connectivity: Connectivity::Four,
+            cells: CellStorage::new(storage, 0, Cell { is_wall: true }),
+            neighbor_offsets: [0; 4],
+            chunks: Chunks::default(),
+            portals: HashMap::new(),
+            exits: HashMap::new(),
+            layers: Layers::default(),
+            seed: None,
+        };
+
+        grid.resize(width, height, Cell { is_wall: false });
+        grid
+    }
+
+    fn stride(width: u32) -> usize {
+        width as usize + 2
+    }
+
+    fn padded_index(width: u32, x: u32, y: u32) -> usize {
+        (y as usize + 1) * Self::stride(width) + x as usize + 1
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn storage_kind(&self) -> StorageKind {
+        self.cells.kind()
+    }
+
+    /// The seed the walls were last laid out from by the randomizer or a map
+    /// generator, if they were. Saved in map files, so a map can be generated
+    /// again with `MapRng::reseed`.
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn set_seed(&mut self, seed: Option<u64>) {
+        self.seed = seed;
+    }
+
+    pub fn with_boundary(mut self, boundary: Boundary) -> Self {
+        self.set_boundary(boundary);
+        self
+    }
+
+    /// Changes what lies beyond the stored cells, forgetting any cells written
+    /// there under an unbounded boundary.
+    pub fn set_boundary(&mut self, boundary: Boundary) -> &mut Self {
+        self.boundary = boundary;
+        self.chunks.clear();
+        self
+    }
+
+    pub fn boundary(&self) -> &Boundary {
+        &self.boundary
+    }
+
+    pub fn with_connectivity(mut self, connectivity: Connectivity) -> Self {
+        self.connectivity = connectivity;
+        self
+    }
+
+    pub fn set_connectivity(&mut self, connectivity: Connectivity) -> &mut Self {
+        self.connectivity = connectivity;
+        self
+    }
+
+    /// How searches on this grid move between cells.
+    pub fn connectivity(&self) -> Connectivity {
+        self.connectivity
+    }
+
+    /// Whether `cell_pos` is one of the cells stored in the grid, as opposed to
+    /// one supplied by the boundary.
+    pub fn in_bounds(&self, cell_pos: CellPos) -> bool {
+        let CellPos(x, y) = cell_pos;
+        x >= 0 && x < self.width as i32 && y >= 0 && y < self.height as i32
+    }
+
+    /// Whether a cell exists at `cell_pos`, either stored or beyond the border.
+    pub fn contains_pos(&self, cell_pos: CellPos) -> bool {
+        self.in_bounds(cell_pos) || self.boundary_cell(cell_pos).is_some()
+    }
+
+    fn boundary_cell(&self, cell_pos: CellPos) -> Option<Cell> {
+        match &self.boundary {
+            Boundary::Solid => None,
+            Boundary::Open { margin } => {
+                let CellPos(x, y) = cell_pos;
+                let margin = *margin as i32;
+                let within = |v: i32, size: u32| v >= -margin && v < size as i32 + margin;
+
+                (within(x, self.width) && within(y, self.height)).then_some(Cell { is_wall: false })
+            }
+            Boundary::Custom(cell_at) => cell_at(cell_pos),
+            Boundary::Unbounded { fill } => Some(self.chunks.get(cell_pos).unwrap_or(*fill)),
+        }
+    }
+
+    /// Index of a stored cell. Cells supplied by the boundary can be read but
+    /// have no storage, so they are out of bounds here.
+    fn cell_pos_to_index(&self, cell_pos: CellPos) -> Result<usize, OutOfBounds> {
+        if !self.in_bounds(cell_pos) {
+            return Err(OutOfBounds { cell_pos });
+        }
+        let CellPos(x, y) = cell_pos;
+
+        Ok(Self::padded_index(self.width, x as u32, y as u32))
+    }
+
+    fn stored_cell(&self, index: usize) -> Cell {
+        #[cfg(feature = "unchecked-indexing")]
+        // SAFETY: callers pass the index of a stored cell or of one of its
+        // neighbors, which at worst lands on the padding ring.
+        let cell = unsafe { self.cells.get_unchecked(index) };
+        #[cfg(not(feature = "unchecked-indexing"))]
+        let cell = self.cells.get(index);
+
+        cell
+    }
+
+    /// The cell at `cell_pos`, falling back to the boundary outside the grid.
+    pub fn cell(&self, cell_pos: CellPos) -> Result<Cell, OutOfBounds> {
+        match self.cell_pos_to_index(cell_pos) {
+            Ok(index) => Ok(self.cells.get(index)),
+            Err(err) => self.boundary_cell(cell_pos).ok_or(err),
+        }
+    }
+
+    pub fn iter_cell_pos(&self) -> impl Iterator<Item = (CellPos, Cell)> + '_ {
+        (0..self.width)
+            .cartesian_product(0..self.height)
+            .map(|(x, y)| {
+                let cell_pos = CellPos(x as i32, y as i32);
+                let cell = self
+                    .cell(cell_pos)
+                    .expect("Internal iterator operating on known size");
+
+                (cell_pos, cell)
+            })
+    }
+
+    pub fn set_cell(&mut self, cell_pos: CellPos, cell: Cell) -> Result<&mut Self, OutOfBounds> {
+        *self.cell_mut(cell_pos)? = cell;
+        Ok(self)
+    }
+
+    /// Write access to a stored cell, or on unbounded grids to any cell.
+    pub fn cell_mut(&mut self, cell_pos: CellPos) -> Result<CellMut<'_>, OutOfBounds> {
+        match (self.cell_pos_to_index(cell_pos), &self.boundary) {
+            (Ok(index), _) => Ok(CellMut::new(&mut self.cells, index)),
+            (Err(_), &Boundary::Unbounded { fill }) => Ok(self.chunks.cell_mut(cell_pos, self.cells.kind(), fill)),
+            (Err(err), _) => Err(err),
+        }
+    }
+
+    /// Bottom left cell of each chunk of cells written past the border of an
+    /// unbounded grid. Chunks are `Chunks::SIZE` cells wide and high.
+    pub(crate) fn chunk_origins(&self) -> impl Iterator<Item = CellPos> + '_ {
+        self.chunks.origins()
+    }
+
+    /// Changes the grid dimensions, keeping the contents of every cell that lies
+    /// inside both the old and the new bounds. Newly exposed cells are set to `fill`.
+    ///
+    /// Unbounded grids only move their border: exposed cells keep the value they
+    /// had past it, `fill` is ignored, and cells left outside are kept in chunks.
+    pub fn resize(&mut self, new_width: u32, new_height: u32, fill: Cell) -> &mut Self {
+        let stride = Self::stride(new_width);
+        let len = stride * (new_height as usize + 2);
+        let mut cells = CellStorage::new(self.cells.kind(), len, Cell { is_wall: true });
+        let unbounded = match self.boundary {
+            Boundary::Unbounded { fill } => Some(fill),
+            _ => None,
+        };
+
+        for y in 0..new_height {
+            for x in 0..new_width {
+                let cell = match (x < self.width && y < self.height, unbounded) {
+                    (true, _) => self.cells.get(Self::padded_index(self.width, x, y)),
+                    (false, Some(_)) => self.cell(CellPos(x as i32, y as i32)).expect("unbounded grids have every cell"),
+                    (false, None) => fill,
+                };
+                cells.set(Self::padded_index(new_width, x, y), cell);
+            }
+        }
+
+        if let Some(unbounded_fill) = unbounded {
+            for y in 0..self.height {
+                for x in 0..self.width {
+                    if x >= new_width || y >= new_height {
+                        let cell = self.cells.get(Self::padded_index(self.width, x, y));
+                        *self.chunks.cell_mut(CellPos(x as i32, y as i32), self.cells.kind(), unbounded_fill) = cell;
+                    }
+                }
+            }
+        }
+
+        self.width = new_width;
+        self.height = new_height;
+        self.cells = cells;
+        self.neighbor_offsets = [1, -1, stride as isize, -(stride as isize)];
+
+        let (width, height) = (new_width as i32, new_height as i32);
+        let in_bounds = |CellPos(x, y): CellPos| x < width && y < height;
+        self.portals.retain(|&entry, portal| in_bounds(entry) && in_bounds(portal.exit));
+        self.exits.retain(|&cell_pos, _| in_bounds(cell_pos));
+        self.layers.resize(new_width, new_height);
+        self
+    }
+
+    /// Adds a layer of per-cell values filled with `fill`, replacing and
+    /// returning any layer with the same value type.
+    pub fn add_layer<T: Clone + Send + Sync + 'static>(&mut self, fill: T) -> Option<GridLayer<T>> {
+        self.layers.insert(GridLayer::new(self.width, self.height, fill))
+    }
+
+    pub fn remove_layer<T: Clone + Send + Sync + 'static>(&mut self) -> Option<GridLayer<T>> {
+        self.layers.remove()
+    }
+
+    pub fn layer<T: Clone + Send + Sync + 'static>(&self) -> Option<&GridLayer<T>> {
+        self.layers.get()
+    }
+
+    pub fn layer_mut<T: Clone + Send + Sync + 'static>(&mut self) -> Option<&mut GridLayer<T>> {
+        self.layers.get_mut()
+    }
+
+    /// Links `a` and `b` as the two ends of a portal usable in both directions,
+    /// replacing any portal either of them was part of.
+    pub fn link_portals(&mut self, a: CellPos, b: CellPos, cost: f32) -> Result<&mut Self, OutOfBounds> {
+        self.cell_pos_to_index(a)?;
+        self.cell_pos_to_index(b)?;
+
+        self.unlink_portal(a);
+        self.unlink_portal(b);
+        self.portals.insert(a, Portal { exit: b, cost });
+        self.portals.insert(b, Portal { exit: a, cost });
+        Ok(self)
+    }
+
+    /// Removes the portal starting at `cell_pos` along with its return direction.
+    pub fn unlink_portal(&mut self, cell_pos: CellPos) -> Option<Portal> {
+        let portal = self.portals.remove(&cell_pos)?;
+        self.portals.remove(&portal.exit);
+        Some(portal)
+    }
+
+    pub fn portal(&self, cell_pos: CellPos) -> Option<Portal> {
+        self.portals.get(&cell_pos).copied()
+    }
+
+    pub fn portals(&self) -> impl Iterator<Item = (CellPos, Portal)> + '_ {
+        self.portals.iter().map(|(&entry, &portal)| (entry, portal))
+    }
+
+    /// Restricts the directions `cell_pos` can be left through.
+    pub fn set_exits(&mut self, cell_pos: CellPos, exits: Exits) -> Result<&mut Self, OutOfBounds> {
+        self.cell_pos_to_index(cell_pos)?;
+
+        match exits {
+            Exits::ALL => self.exits.remove(&cell_pos),
+            _ => self.exits.insert(cell_pos, exits),
+        };
+        Ok(self)
+    }
+
+    pub fn exits(&self, cell_pos: CellPos) -> Exits {
+        if self.exits.is_empty() {
+            return Exits::ALL;
+        }
+        self.exits.get(&cell_pos).copied().unwrap_or_default()
+    }
+
+    /// Makes the edge between `from` and its neighbor in `direction` one-way, by
+    /// removing the exit leading back from that neighbor.
+    pub fn make_one_way(&mut self, from: CellPos, direction: Direction) -> Result<&mut Self, OutOfBounds> {
+        let to = direction.step(from);
+        let exits = self.exits(to).without(direction.opposite());
+        self.set_exits(to, exits)
+    }
+
+    /// Positions orthogonally adjacent to `cell_pos` that lie inside the grid and
+    /// that `cell_pos` has an exit towards.
+    pub fn adjacent(&self, cell_pos: CellPos) -> impl Iterator<Item = CellPos> + '_ {
+        let exits = self.exits(cell_pos);
+
+        Direction::ALL
+            .into_iter()
+            .filter(move |&direction| exits.contains(direction))
+            .map(move |direction| direction.step(cell_pos))
+            .filter(|&neighbor| self.contains_pos(neighbor))
+    }
+
+    /// Cells a single step from `cell_pos` can land on under `connectivity`,
+    /// with the geometric length of the step. A step needs an exit for each axis
+    /// it moves along, so one-way exits also apply to diagonal and hex steps.
+    ///
+    /// Every search explores through this, using the grid's own connectivity.
+    pub fn neighbors(&self, cell_pos: CellPos, connectivity: Connectivity) -> impl Iterator<Item = (CellPos, f32)> + '_ {
+        match connectivity {
+            Connectivity::Four => Either::Left(self.passable_neighbors(cell_pos).map(|neighbor| (neighbor, 1.0))),
+            _ => {
+                let exits = self.exits(cell_pos);
+                let steps = connectivity
+                    .offsets(cell_pos)
+                    .iter()
+                    .filter(move |&&(dx, dy)| Self::exits_allow(exits, dx, dy))
+                    .filter(move |&&offset| self.can_step(cell_pos, offset, connectivity))
+                    .map(move |&(dx, dy)| (CellPos(cell_pos.0 + dx, cell_pos.1 + dy), connectivity.step_length((dx, dy))));
+                Either::Right(steps)
+            }
+        }
+    }
+
+    /// Cells a step could join `cell_pos` to in either direction: like
+    /// [`Grid::neighbors`] but regardless of exits, so the relation is symmetric.
+    pub fn touching(&self, cell_pos: CellPos, connectivity: Connectivity) -> impl Iterator<Item = CellPos> + '_ {
+        connectivity
+            .offsets(cell_pos)
+            .iter()
+            .filter(move |&&offset| self.can_step(cell_pos, offset, connectivity))
+            .map(move |&(dx, dy)| CellPos(cell_pos.0 + dx, cell_pos.1 + dy))
+    }
+
+    fn is_floor(&self, cell_pos: CellPos) -> bool {
+        self.cell(cell_pos).is_ok_and(|cell| !cell.is_wall)
+    }
+
+    fn exits_allow(exits: Exits, dx: i32, dy: i32) -> bool {
+        let horizontal = match dx.signum() {
+            1 => exits.contains(Direction::East),
+            -1 => exits.contains(Direction::West),
+            _ => true,
+        };
+        let vertical = match dy.signum() {
+            1 => exits.contains(Direction::North),
+            -1 => exits.contains(Direction::South),
+            _ => true,
+        };
+        horizontal && vertical
+    }
+
+    /// Whether the step by `offset` lands on floor without cutting a corner.
+    fn can_step(&self, cell_pos: CellPos, offset: (i32, i32), connectivity: Connectivity) -> bool {
+        let CellPos(x, y) = cell_pos;
+        let (dx, dy) = offset;
+
+        if !self.is_floor(CellPos(x + dx, y + dy)) {
+            return false;
+        }
+
+        match connectivity {
+            Connectivity::Eight if dx != 0 && dy != 0 => {
+                self.is_floor(CellPos(x + dx, y)) && self.is_floor(CellPos(x, y + dy))
+            }
+            _ => true,
+        }
+    }
+
+    /// Adjacent cells a step from `cell_pos` can land on: they exist, aren't
+    /// walls, and `cell_pos` has an exit towards them. This is the hot loop of
+    /// every four-connected search, so stored cells on a solid-bounded grid are
+    /// checked through index offsets into the padded storage rather than by
+    /// position.
+    pub fn passable_neighbors(&self, cell_pos: CellPos) -> impl Iterator<Item = CellPos> + '_ {
+        let exits = self.exits(cell_pos);
+        let index = match self.boundary {
+            Boundary::Solid => self.cell_pos_to_index(cell_pos).ok(),
+            _ => None,
+        };
+
+        Direction::ALL
+            .into_iter()
+            .zip(self.neighbor_offsets)
+            .filter(move |&(direction, _)| exits.contains(direction))
+            .filter_map(move |(direction, offset)| {
+                let neighbor = direction.step(cell_pos);
+                let passable = match index {
+                    Some(index) => !self.stored_cell(index.wrapping_add_signed(offset)).is_wall,
+                    None => self.cell(neighbor).is_ok_and(|cell| !cell.is_wall),
+                };
+                passable.then_some(neighbor)
+            })
+    }
+}
@@ -0,0 +1,148 @@
+//! Lines between the cells of grids.
+//!
+//! Every drawn grid gets a child mesh of thin lines along the edges of its
+//! cells, a single draw call however large the grid, so single cells can be
+//! told apart while editing. Lines are off until [`GridLineStyle::visible`] is
+//! set, and hidden while zoomed out so far that they would only darken the grid.
+
+use bevy::{
+    prelude::*,
+    render::mesh::PrimitiveTopology,
+    sprite::{MaterialMesh2dBundle, Mesh2dHandle},
+};
+
+use crate::{
+    grid::{CellPos, Connectivity, Grid, Grids},
+    minimap::MainCameraFilter,
+    view::{cell_transform, grid_bounds, GridTexture},
+};
+
+/// How far above the grid texture lines are drawn, below overlays.
+const LINE_DEPTH: f32 = 0.25;
+
+/// Whether and how the lines between cells are drawn.
+#[derive(Resource, Debug, Clone)]
+pub struct GridLineStyle {
+    pub visible: bool,
+    pub color: Color,
+    /// Lines are hidden while cells are drawn fewer screen pixels wide than this.
+    pub min_cell_pixels: f32,
+}
+
+impl Default for GridLineStyle {
+    fn default() -> Self {
+        GridLineStyle { visible: false, color: Color::rgba(0.0, 0.0, 0.0, 0.5), min_cell_pixels: 8.0 }
+    }
+}
+
+/// On the lines drawn for `grid`, a child of the grid entity.
+#[derive(Component, Debug, Clone)]
+pub struct GridLines {
+    pub grid: Entity,
+    /// Size and connectivity of the grid the lines were drawn for.
+    layout: (u32, u32, Connectivity),
+    mesh: Handle<Mesh>,
+    material: Handle<ColorMaterial>,
+}
+
+fn layout(grid: &Grid) -> (u32, u32, Connectivity) {
+    (grid.width(), grid.height(), grid.connectivity())
+}
+
+/// A line list along the edges of every cell of `grid`.
+fn grid_line_mesh(grid: &Grid) -> Mesh {
+    let (min, max) = grid_bounds(grid);
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut line = |from: Vec2, to: Vec2| positions.extend([from.extend(0.0).to_array(), to.extend(0.0).to_array()]);
+
+    for y in 0..=grid.height() {
+        line(Vec2::new(min.x, min.y + y as f32), Vec2::new(max.x, min.y + y as f32));
+    }
+    match grid.connectivity() {
+        // Shifted rows don't line up, so each row gets its own edges.
+        Connectivity::Hex => {
+            for y in 0..grid.height() {
+                let left = cell_transform(grid, CellPos(0, y as i32)).translation.x - 0.5;
+                let (bottom, top) = (min.y + y as f32, min.y + y as f32 + 1.0);
+                for x in 0..=grid.width() {
+                    line(Vec2::new(left + x as f32, bottom), Vec2::new(left + x as f32, top));
+                }
+            }
+        }
+        _ => {
+            for x in 0..=grid.width() {
+                line(Vec2::new(min.x + x as f32, min.y), Vec2::new(min.x + x as f32, max.y));
+            }
+        }
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::LineList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 0.0, 1.0]; positions.len()]);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0.0, 0.0]; positions.len()]);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh
+}
+
+/// Draws the lines of drawn grids once they are switched on, redraws them when
+/// a grid changes size or connectivity, and shows or hides them with the style
+/// and the zoom of the 2D camera.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn draw_grid_lines(
+    mut commands: Commands,
+    style: Res<GridLineStyle>,
+    grids: Grids,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    cameras: Query<&OrthographicProjection, MainCameraFilter>,
+    drawn_grids: Query<Entity, With<GridTexture>>,
+    mut lines: Query<(&mut GridLines, &mut Visibility)>,
+) {
+    // A world unit is a cell, so a cell is drawn `1 / scale` pixels wide.
+    let cell_pixels = cameras.iter().next().map_or(1.0, |projection| 1.0 / projection.scale);
+    let is_visible = style.visible && cell_pixels >= style.min_cell_pixels;
+
+    for grid_entity in &drawn_grids {
+        let existing = lines.iter_mut().find(|(lines, _)| lines.grid == grid_entity);
+        if existing.is_none() && !style.visible {
+            continue;
+        }
+        let Ok(grid) = grids.get(grid_entity) else {
+            continue;
+        };
+
+        let Some((mut lines, mut visibility)) = existing else {
+            let material = materials.add(ColorMaterial::from(style.color));
+            let mesh = meshes.add(grid_line_mesh(grid));
+            let lines_entity = commands
+                .spawn(MaterialMesh2dBundle {
+                    mesh: Mesh2dHandle(mesh.clone()),
+                    material: material.clone(),
+                    transform: Transform::from_xyz(0.0, 0.0, LINE_DEPTH),
+                    visibility: Visibility { is_visible },
+                    ..default()
+                })
+                .insert((
+                    GridLines { grid: grid_entity, layout: layout(grid), mesh, material },
+                    Name::new("Grid lines"),
+                ))
+                .id();
+            commands.entity(grid_entity).add_child(lines_entity);
+            continue;
+        };
+
+        if lines.layout != layout(grid) {
+            lines.layout = layout(grid);
+            if let Some(mesh) = meshes.get_mut(&lines.mesh) {
+                *mesh = grid_line_mesh(grid);
+            }
+        }
+        if style.is_changed() {
+            if let Some(material) = materials.get_mut(&lines.material) {
+                material.color = style.color;
+            }
+        }
+        if visibility.is_visible != is_visible {
+            visibility.is_visible = is_visible;
+        }
+    }
+}
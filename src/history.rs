@@ -0,0 +1,523 @@
+//! Grid persistence as an append-only log of cell edits with periodic snapshots.
+//!
+//! The same [`GridHistory`] backs the edit timeline (rebuild the grid as it
+//! was at any sequence number), syncing (ship the edits a peer hasn't seen),
+//! and crash recovery ([`GridLogFile`] appends every edit to disk as it
+//! happens). Compaction folds old edits into a single snapshot so the log
+//! doesn't grow without bound.
+//!
+//! Snapshots are written as embedded map files, so a log keeps everything a
+//! [`MapFormat`] does: portals, exits, boundary, chunks and registered layers.
+
+use std::{
+    error::Error,
+    fmt::Display,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    grid::{Cell, CellPos, Grid, OutOfBounds},
+    map_file::{MapError, MapFormat},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GridEdit {
+    pub seq: u64,
+    pub cell_pos: CellPos,
+    pub cell: Cell,
+}
+
+/// The full grid state before the edit numbered `seq` was applied.
+#[derive(Debug, Clone)]
+struct Snapshot {
+    seq: u64,
+    grid: Grid,
+}
+
+#[derive(Debug)]
+pub enum LogError {
+    Io(io::Error),
+    Parse { line: usize, message: String },
+    OutOfBounds(OutOfBounds),
+}
+
+impl Display for LogError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LogError::Io(err) => write!(f, "grid log io error: {err}"),
+            LogError::Parse { line, message } => write!(f, "grid log line {line}: {message}"),
+            LogError::OutOfBounds(err) => write!(f, "grid log edit: {err}"),
+        }
+    }
+}
+impl Error for LogError {}
+
+impl From<io::Error> for LogError {
+    fn from(err: io::Error) -> Self {
+        LogError::Io(err)
+    }
+}
+
+impl From<OutOfBounds> for LogError {
+    fn from(err: OutOfBounds) -> Self {
+        LogError::OutOfBounds(err)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GridHistory {
+    /// Sorted by `seq`; the first one is the oldest state still available.
+    snapshots: Vec<Snapshot>,
+    /// Every edit since the first snapshot, in order.
+    edits: Vec<GridEdit>,
+    current: Grid,
+    next_seq: u64,
+    snapshot_interval: usize,
+}
+
+impl GridHistory {
+    pub const DEFAULT_SNAPSHOT_INTERVAL: usize = 1024;
+
+    pub fn new(grid: Grid) -> Self {
+        GridHistory {
+            snapshots: vec![Snapshot { seq: 0, grid: grid.clone() }],
+            edits: Vec::new(),
+            current: grid,
+            next_seq: 0,
+            snapshot_interval: Self::DEFAULT_SNAPSHOT_INTERVAL,
+        }
+    }
+
+    /// Takes a snapshot every `interval` edits, trading memory for faster seeking.
+    pub fn with_snapshot_interval(mut self, interval: usize) -> Self {
+        self.snapshot_interval = interval.max(1);
+        self
+    }
+
+    pub fn current(&self) -> &Grid {
+        &self.current
+    }
+
+    /// Sequence number the next recorded edit will get.
+    pub fn next_seq(&self) -> u64 {
+        self.next_seq
+    }
+
+    /// Oldest sequence number that [`GridHistory::state_at`] can still rebuild.
+    pub fn first_seq(&self) -> u64 {
+        self.snapshots[0].seq
+    }
+
+    pub fn record(&mut self, cell_pos: CellPos, cell: Cell) -> Result<GridEdit, OutOfBounds> {
+        self.current.set_cell(cell_pos, cell)?;
+
+        let edit = GridEdit { seq: self.next_seq, cell_pos, cell };
+        self.edits.push(edit);
+        self.next_seq += 1;
+
+        let last_snapshot = self.snapshots.last().expect("history always has a base snapshot");
+        if (self.next_seq - last_snapshot.seq) as usize >= self.snapshot_interval {
+            self.take_snapshot();
+        }
+
+        Ok(edit)
+    }
+
+    /// Snapshots the current state, unless the latest snapshot already matches it.
+    pub fn take_snapshot(&mut self) {
+        let last_snapshot = self.snapshots.last().expect("history always has a base snapshot");
+        if last_snapshot.seq != self.next_seq {
+            self.snapshots.push(Snapshot { seq: self.next_seq, grid: self.current.clone() });
+        }
+    }
+
+    /// Applies an edit received from elsewhere (e.g. a peer), keeping its sequence number.
+    /// Edits older than what this history has already seen are ignored.
+    pub fn apply_remote(&mut self, edit: GridEdit) -> Result<bool, OutOfBounds> {
+        if edit.seq < self.next_seq {
+            return Ok(false);
+        }
+        self.next_seq = edit.seq;
+        self.record(edit.cell_pos, edit.cell)?;
+        Ok(true)
+    }
+
+    /// Edits with a sequence number of at least `seq`, for bringing a peer up to date.
+    pub fn edits_since(&self, seq: u64) -> &[GridEdit] {
+        let start = self.edits.partition_point(|edit| edit.seq < seq);
+        &self.edits[start..]
+    }
+
+    /// The grid as it was before the edit numbered `seq` was applied, or `None` if
+    /// that point has been compacted away.
+    pub fn state_at(&self, seq: u64) -> Option<Grid> {
+        if seq < self.first_seq() {
+            return None;
+        }
+        let seq = seq.min(self.next_seq);
+
+        let snapshot_index = self.snapshots.partition_point(|snapshot| snapshot.seq <= seq) - 1;
+        let snapshot = &self.snapshots[snapshot_index];
+
+        let mut grid = snapshot.grid.clone();
+        for edit in self.edits_since(snapshot.seq).iter().take_while(|edit| edit.seq < seq) {
+            grid.set_cell(edit.cell_pos, edit.cell)
+                .expect("edits were validated when recorded");
+        }
+
+        Some(grid)
+    }
+
+    /// Forgets everything before the newest snapshot at or before `keep_from`,
+    /// so only history from that snapshot onwards remains.
+    pub fn compact(&mut self, keep_from: u64) {
+        let keep_index = self
+            .snapshots
+            .partition_point(|snapshot| snapshot.seq <= keep_from)
+            .saturating_sub(1);
+
+        self.snapshots.drain(..keep_index);
+        let first_seq = self.first_seq();
+        self.edits.retain(|edit| edit.seq >= first_seq);
+    }
+
+    /// Writes the oldest snapshot followed by every edit since, with the crate's
+    /// default [`MapFormat`].
+    pub fn write_to(&self, writer: impl Write) -> io::Result<()> {
+        self.write_with(&MapFormat::new(), writer)
+    }
+
+    /// Like [`write_to`](Self::write_to), saving the snapshot with `format` so
+    /// the layers registered with it are kept too.
+    pub fn write_with(&self, format: &MapFormat, writer: impl Write) -> io::Result<()> {
+        let mut writer = BufWriter::new(writer);
+        write_snapshot(&mut writer, format, &self.snapshots[0])?;
+        for edit in &self.edits {
+            write_edit(&mut writer, edit)?;
+        }
+        writer.flush()
+    }
+
+    /// Reads a history written by [`write_to`](Self::write_to). A final line that
+    /// doesn't parse was cut short by a crash mid-write, and is left out.
+    pub fn read_from(reader: impl BufRead) -> Result<Self, LogError> {
+        Self::read_with(&MapFormat::new(), reader)
+    }
+
+    /// Reads a history written by [`write_with`](Self::write_with) and the same format.
+    pub fn read_with(format: &MapFormat, reader: impl BufRead) -> Result<Self, LogError> {
+        Self::read_truncated(format, reader).map(|(history, _)| history)
+    }
+
+    /// The history, and whether its final line was cut short.
+    fn read_truncated(format: &MapFormat, reader: impl BufRead) -> Result<(Self, bool), LogError> {
+        let mut history: Option<GridHistory> = None;
+        let mut truncated = false;
+        let mut lines = reader.lines().enumerate().peekable();
+
+        while let Some((index, line)) = lines.next() {
+            let line = line?;
+            let parse_error = |message: &str| LogError::Parse { line: index + 1, message: message.to_string() };
+            let last = lines.peek().is_none();
+
+            let mut fields = line.split_whitespace();
+            match (fields.next(), history.as_mut()) {
+                (None, _) => continue,
+                (Some("snapshot"), None) => {
+                    let snapshot = read_snapshot(format, &line, index + 1, &mut lines)?;
+                    let mut parsed = GridHistory::new(snapshot.grid);
+                    parsed.snapshots[0].seq = snapshot.seq;
+                    parsed.next_seq = snapshot.seq;
+                    history = Some(parsed);
+                }
+                (Some("edit"), Some(history)) => {
+                    let edit = match parse_edit(fields) {
+                        Some(edit) => edit,
+                        None if last => {
+                            truncated = true;
+                            break;
+                        }
+                        None => return Err(parse_error("malformed edit")),
+                    };
+                    if !history.apply_remote(edit)? {
+                        return Err(parse_error("edit out of sequence"));
+                    }
+                }
+                (Some("snapshot"), Some(_)) => return Err(parse_error("unexpected second snapshot")),
+                (Some("edit"), None) => return Err(parse_error("edit before the first snapshot")),
+                (Some(_), Some(_)) if last => {
+                    truncated = true;
+                    break;
+                }
+                (Some(_), _) => return Err(parse_error("unknown record")),
+            }
+        }
+
+        match history {
+            Some(history) => Ok((history, truncated)),
+            None => Err(LogError::Parse { line: 0, message: "missing snapshot".to_string() }),
+        }
+    }
+}
+
+/// A [`GridHistory`] mirrored to an append-only file, so every recorded edit
+/// survives a crash. Compaction rewrites the file once it holds more than
+/// `max_edits` edits.
+///
+/// Snapshots go through a [`MapFormat`] and share its limits: custom boundary
+/// callbacks come back as solid boundaries, and layers the format doesn't
+/// register are dropped at the next compaction. Use
+/// [`create_with`](Self::create_with) and [`open_with`](Self::open_with) to
+/// keep a game's own layers.
+pub struct GridLogFile {
+    path: PathBuf,
+    file: File,
+    history: GridHistory,
+    format: MapFormat,
+    max_edits: usize,
+}
+
+impl GridLogFile {
+    /// Starts a fresh log at `path` containing only `grid`, replacing any existing file.
+    pub fn create(path: impl AsRef<Path>, grid: Grid) -> Result<Self, LogError> {
+        Self::create_with(path, grid, MapFormat::new())
+    }
+
+    /// Like [`create`](Self::create), saving snapshots with `format`.
+    pub fn create_with(path: impl AsRef<Path>, grid: Grid, format: MapFormat) -> Result<Self, LogError> {
+        let path = path.as_ref().to_path_buf();
+        let history = GridHistory::new(grid);
+        history.write_with(&format, File::create(&path)?)?;
+        Self::from_history(path, history, format)
+    }
+
+    /// Recovers the history stored at `path`, replaying every edit written before
+    /// the last shutdown. An edit cut short by a crash is dropped, and the file
+    /// rewritten without it so new edits start on a line of their own.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, LogError> {
+        Self::open_with(path, MapFormat::new())
+    }
+
+    /// Like [`open`](Self::open), for a log created with
+    /// [`create_with`](Self::create_with) and the same `format`.
+    pub fn open_with(path: impl AsRef<Path>, format: MapFormat) -> Result<Self, LogError> {
+        let path = path.as_ref().to_path_buf();
+        let (history, truncated) = GridHistory::read_truncated(&format, BufReader::new(File::open(&path)?))?;
+        let mut log = Self::from_history(path, history, format)?;
+        if truncated {
+            log.rewrite()?;
+        }
+        Ok(log)
+    }
+
+    fn from_history(path: PathBuf, history: GridHistory, format: MapFormat) -> Result<Self, LogError> {
+        let file = OpenOptions::new().append(true).open(&path)?;
+        Ok(GridLogFile {
+            path,
+            file,
+            history,
+            format,
+            max_edits: 4 * GridHistory::DEFAULT_SNAPSHOT_INTERVAL,
+        })
+    }
+
+    pub fn with_max_edits(mut self, max_edits: usize) -> Self {
+        self.max_edits = max_edits;
+        self
+    }
+
+    pub fn history(&self) -> &GridHistory {
+        &self.history
+    }
+
+    pub fn record(&mut self, cell_pos: CellPos, cell: Cell) -> Result<GridEdit, LogError> {
+        let edit = self.history.record(cell_pos, cell)?;
+        write_edit(&mut self.file, &edit)?;
+        self.file.flush()?;
+
+        if self.history.edits.len() > self.max_edits {
+            self.compact()?;
+        }
+
+        Ok(edit)
+    }
+
+    /// Folds all history into a snapshot of the current state and rewrites the file
+    /// to match. The new file is written next to the old one and renamed over it, so
+    /// a crash mid-compaction leaves the previous log intact.
+    pub fn compact(&mut self) -> Result<(), LogError> {
+        self.history.take_snapshot();
+        self.history.compact(self.history.next_seq());
+        self.rewrite()
+    }
+
+    /// Replaces the file with the history as it is, the same crash-safe way.
+    fn rewrite(&mut self) -> Result<(), LogError> {
+        let tmp_path = self.path.with_extension("compacting");
+        self.history.write_with(&self.format, File::create(&tmp_path)?)?;
+        fs::rename(&tmp_path, &self.path)?;
+
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        Ok(())
+    }
+}
+
+fn cell_char(cell: Cell) -> char {
+    match cell.is_wall {
+        true => '#',
+        false => '.',
+    }
+}
+
+fn parse_cell(c: char) -> Option<Cell> {
+    match c {
+        '#' => Some(Cell { is_wall: true }),
+        '.' => Some(Cell { is_wall: false }),
+        _ => None,
+    }
+}
+
+/// Writes `snapshot <seq> <lines>` followed by that many lines of map file.
+fn write_snapshot(writer: &mut impl Write, format: &MapFormat, snapshot: &Snapshot) -> io::Result<()> {
+    let mut map = Vec::new();
+    format.save(&snapshot.grid, &mut map)?;
+    let lines = map.iter().filter(|&&byte| byte == b'\n').count();
+
+    writeln!(writer, "snapshot {} {lines}", snapshot.seq)?;
+    writer.write_all(&map)
+}
+
+fn write_edit(writer: &mut impl Write, edit: &GridEdit) -> io::Result<()> {
+    let CellPos(x, y) = edit.cell_pos;
+    writeln!(writer, "edit {} {x} {y} {}", edit.seq, cell_char(edit.cell))
+}
+
+/// Reads the snapshot starting at `header`, line `line` of the log, taking the
+/// map file lines that follow it from `lines`. Logs written before snapshots
+/// were map files hold the whole grid on the header line, as
+/// `snapshot <seq> <width> <height> <cells>`, which the map format's version 1
+/// migration still reads.
+fn read_snapshot(
+    format: &MapFormat,
+    header: &str,
+    line: usize,
+    lines: &mut impl Iterator<Item = (usize, io::Result<String>)>,
+) -> Result<Snapshot, LogError> {
+    let malformed = |message: &str| LogError::Parse { line, message: message.to_string() };
+    let fields: Vec<_> = header.split_whitespace().skip(1).collect();
+    let seq = fields.first().and_then(|seq| seq.parse().ok()).ok_or_else(|| malformed("malformed snapshot"))?;
+
+    let (map, first_line) = match fields.len() {
+        2 => {
+            let count: usize = fields[1].parse().map_err(|_| malformed("malformed snapshot"))?;
+            let mut map = String::new();
+            for _ in 0..count {
+                let (_, text) = lines.next().ok_or_else(|| malformed("snapshot cut short"))?;
+                map.push_str(&text?);
+                map.push('\n');
+            }
+            (map, line + 1)
+        }
+        _ => (header.to_string(), line),
+    };
+
+    let grid = format.load(map.as_bytes()).map_err(|err| match err {
+        MapError::Io(err) => LogError::Io(err),
+        MapError::Parse { line: 0, message } => LogError::Parse { line, message: format!("snapshot: {message}") },
+        MapError::Parse { line: map_line, message } => LogError::Parse {
+            line: first_line + map_line - 1,
+            message: format!("snapshot: {message}"),
+        },
+        MapError::OutOfBounds(err) => LogError::OutOfBounds(err),
+    })?;
+
+    Ok(Snapshot { seq, grid })
+}
+
+fn parse_edit<'a>(mut fields: impl Iterator<Item = &'a str>) -> Option<GridEdit> {
+    let seq = fields.next()?.parse().ok()?;
+    let x = fields.next()?.parse().ok()?;
+    let y = fields.next()?.parse().ok()?;
+    let cell = parse_cell(fields.next()?.chars().next()?)?;
+
+    Some(GridEdit { seq, cell_pos: CellPos(x, y), cell })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        grid::{Boundary, Connectivity, Direction, Exits},
+        storage::StorageKind,
+        terrain::Terrain,
+        test_util::assert_same_grid,
+    };
+
+    fn example_grid() -> Grid {
+        let mut grid = Grid::with_storage(6, 4, StorageKind::Bitset).with_connectivity(Connectivity::Eight);
+        grid.set_seed(Some(5));
+        grid.set_boundary(Boundary::Open { margin: 1 });
+        grid.set_cell(CellPos(2, 1), Cell::wall()).unwrap();
+        grid.link_portals(CellPos(0, 0), CellPos(5, 3), 2.5).unwrap();
+        grid.set_exits(CellPos(3, 2), Exits::ALL.without(Direction::North)).unwrap();
+        grid.add_layer(Terrain::PLAIN);
+        grid.layer_mut::<Terrain>().unwrap().set(CellPos(4, 0), Terrain(2)).unwrap();
+        grid
+    }
+
+    #[test]
+    fn snapshots_keep_the_whole_grid() {
+        let grid = example_grid();
+        let mut history = GridHistory::new(grid.clone());
+        history.record(CellPos(1, 1), Cell::wall()).unwrap();
+        history.record(CellPos(2, 1), Cell::floor()).unwrap();
+
+        let mut file = Vec::new();
+        history.write_to(&mut file).unwrap();
+        let loaded = GridHistory::read_from(file.as_slice()).unwrap();
+
+        assert_eq!(loaded.next_seq(), history.next_seq());
+        assert_eq!(loaded.edits_since(0), history.edits_since(0));
+        assert_same_grid(&loaded.state_at(0).unwrap(), &grid);
+        assert_same_grid(loaded.current(), history.current());
+    }
+
+    #[test]
+    fn snapshots_keep_layers_registered_with_the_format() {
+        let mut grid = example_grid();
+        grid.add_layer(0.0f32);
+        grid.layer_mut::<f32>().unwrap().set(CellPos(1, 2), 0.75).unwrap();
+        let format = MapFormat::new().with_layer::<f32>("danger");
+
+        let mut file = Vec::new();
+        GridHistory::new(grid).write_with(&format, &mut file).unwrap();
+        let loaded = GridHistory::read_with(&format, file.as_slice()).unwrap();
+
+        assert_eq!(loaded.current().layer::<f32>().unwrap().get(CellPos(1, 2)).unwrap(), &0.75);
+    }
+
+    #[test]
+    fn single_line_snapshots_still_load() {
+        let text = "snapshot 3 3 2 .#....\nedit 3 0 1 #\n";
+        let loaded = GridHistory::read_from(text.as_bytes()).unwrap();
+
+        assert_eq!((loaded.first_seq(), loaded.next_seq()), (3, 4));
+        assert_eq!(loaded.current().cell(CellPos(1, 0)).unwrap(), Cell::wall());
+        assert_eq!(loaded.current().cell(CellPos(0, 1)).unwrap(), Cell::wall());
+    }
+
+    #[test]
+    fn snapshots_cut_short_are_refused() {
+        let mut file = Vec::new();
+        GridHistory::new(example_grid()).write_to(&mut file).unwrap();
+        let text = String::from_utf8(file).unwrap();
+        let cut: Vec<_> = text.lines().take(3).collect();
+
+        assert!(matches!(
+            GridHistory::read_from(cut.join("\n").as_bytes()),
+            Err(LogError::Parse { line: 1, .. }),
+        ));
+    }
+}
@@ -0,0 +1,125 @@
+//! Grids drawn as images: floor plans, screenshots and downloaded test maps.
+//!
+//! With the `image` cargo feature, [`ImageImport`] turns an image into a grid
+//! with a cell per pixel, the top row of the image becoming the top row of the
+//! grid. A pixel matching a color of the palette becomes what the palette says,
+//! a wall, floor, or floor of some [`Terrain`], so the costs set for it in
+//! `TerrainCosts` apply. Any other pixel is a wall if it's darker than the
+//! threshold and floor otherwise, which reads black-on-white plans as they are
+//! drawn. Transparent pixels are floor.
+//!
+//! Images with several pixels to a cell can be brought down to one with
+//! `resample::downscale`, which keeps the walls but not the terrain.
+
+use std::path::Path;
+
+use image::{DynamicImage, ImageResult, RgbaImage};
+
+use crate::{
+    grid::{Cell, CellPos, Grid},
+    terrain::Terrain,
+};
+
+/// What an imported pixel becomes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PixelKind {
+    Wall,
+    Floor,
+    /// Floor, with this kind of terrain.
+    Terrain(Terrain),
+}
+
+/// How [`ImageImport::import`] reads pixels, see the module docs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageImport {
+    /// Pixels darker than this, from `0.0` for black to `1.0` for white, are
+    /// walls unless the palette says otherwise.
+    pub threshold: f32,
+    /// Colors, as RGB, and what pixels of those colors become. The first color
+    /// matched wins.
+    pub palette: Vec<([u8; 3], PixelKind)>,
+    /// Largest difference in any one channel for a pixel to match a palette
+    /// color, so colors blurred by compression or scaling still match.
+    pub tolerance: u8,
+}
+
+impl Default for ImageImport {
+    fn default() -> Self {
+        ImageImport { threshold: 0.5, palette: Vec::new(), tolerance: 8 }
+    }
+}
+
+impl ImageImport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Makes pixels of `color` into `kind`, e.g. blue ones into water.
+    pub fn with_color(mut self, color: [u8; 3], kind: PixelKind) -> Self {
+        self.palette.push((color, kind));
+        self
+    }
+
+    pub fn with_tolerance(mut self, tolerance: u8) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// What a pixel of `rgba` becomes.
+    pub fn pixel_kind(&self, [r, g, b, a]: [u8; 4]) -> PixelKind {
+        if a < 128 {
+            return PixelKind::Floor;
+        }
+        let matches = |color: &[u8; 3]| color.iter().zip([r, g, b]).all(|(&c, p)| c.abs_diff(p) <= self.tolerance);
+        if let Some(&(_, kind)) = self.palette.iter().find(|(color, _)| matches(color)) {
+            return kind;
+        }
+        let brightness = (0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32) / 255.0;
+        match brightness < self.threshold {
+            true => PixelKind::Wall,
+            false => PixelKind::Floor,
+        }
+    }
+
+    /// A grid the size of `image`, a cell per pixel. It only has a terrain
+    /// layer if some pixel became terrain.
+    pub fn import(&self, image: &DynamicImage) -> Grid {
+        self.import_rgba(&image.to_rgba8())
+    }
+
+    pub fn import_rgba(&self, image: &RgbaImage) -> Grid {
+        let (width, height) = (image.width(), image.height());
+        let mut grid = Grid::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                // Images count rows from the top, grids from the bottom.
+                let cell_pos = CellPos(x as i32, (height - 1 - y) as i32);
+                let (cell, terrain) = match self.pixel_kind(image.get_pixel(x, y).0) {
+                    PixelKind::Wall => (Cell::wall(), None),
+                    PixelKind::Floor => (Cell::floor(), None),
+                    PixelKind::Terrain(terrain) => (Cell::floor(), Some(terrain)),
+                };
+                grid.set_cell(cell_pos, cell).expect("pixels are within the grid");
+                if let Some(terrain) = terrain {
+                    if grid.layer::<Terrain>().is_none() {
+                        grid.add_layer(Terrain::PLAIN);
+                    }
+                    let layer = grid.layer_mut::<Terrain>().expect("layer was just added");
+                    layer.set(cell_pos, terrain).expect("pixels are within the grid");
+                }
+            }
+        }
+        grid
+    }
+
+    /// Reads the PNG image at `path`, or one in another format the `image` crate
+    /// is built with, and imports it.
+    pub fn load(&self, path: impl AsRef<Path>) -> ImageResult<Grid> {
+        Ok(self.import(&image::open(path)?))
+    }
+}
@@ -0,0 +1,205 @@
+//! A record of every cell change made to a grid, with who made it and when.
+//!
+//! Unlike [`GridHistory`](crate::history::GridHistory), which keeps only the new
+//! cell of each edit and rebuilds past states from snapshots, every
+//! [`JournalEntry`] also keeps the cell it replaced. That makes entries
+//! reversible on their own: a range of them can be undone, replayed onto another
+//! copy of the grid, or folded into a [`GridDiff`] without touching the grid.
+//!
+//! Only cells are journaled; portals, exits and layers aren't.
+
+use std::{collections::HashMap, ops::Range};
+
+use bevy::prelude::*;
+
+use crate::grid::{Cell, CellPos, Grid, OutOfBounds};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JournalEntry {
+    pub seq: u64,
+    /// The entity that made the change, if any; `None` for changes made by the
+    /// app itself, such as loading a map.
+    pub author: Option<Entity>,
+    /// Seconds since startup, as given by `Time::elapsed_seconds_f64`.
+    pub time: f64,
+    pub cell_pos: CellPos,
+    pub old: Cell,
+    pub new: Cell,
+}
+
+/// Every cell change made to the grid of the entity it is inserted on, in order.
+///
+/// Entries are kept until [`truncate_before`](Self::truncate_before) drops them.
+/// An `UndoStack` on the same entity does so for entries older than the oldest
+/// action it keeps; without one, the app has to truncate the journal itself.
+#[derive(Component, Debug, Clone, Default)]
+pub struct GridJournal {
+    entries: Vec<JournalEntry>,
+    first_seq: u64,
+    next_seq: u64,
+}
+
+impl GridJournal {
+    pub fn new() -> Self {
+        GridJournal::default()
+    }
+
+    /// Sets `cell_pos` on `grid` to `cell` and journals the change. Setting a
+    /// cell to the value it already has isn't journaled.
+    pub fn record(
+        &mut self,
+        grid: &mut Grid,
+        author: Option<Entity>,
+        time: f64,
+        cell_pos: CellPos,
+        cell: Cell,
+    ) -> Result<Option<JournalEntry>, OutOfBounds> {
+        let old = grid.cell(cell_pos)?;
+        grid.set_cell(cell_pos, cell)?;
+        Ok(self.log(author, time, cell_pos, old, cell))
+    }
+
+    /// Journals a change already made to the grid some other way.
+    pub fn log(&mut self, author: Option<Entity>, time: f64, cell_pos: CellPos, old: Cell, new: Cell) -> Option<JournalEntry> {
+        if old == new {
+            return None;
+        }
+
+        let entry = JournalEntry { seq: self.next_seq, author, time, cell_pos, old, new };
+        self.entries.push(entry);
+        self.next_seq += 1;
+        Some(entry)
+    }
+
+    /// Sequence number the next journaled change will get.
+    pub fn next_seq(&self) -> u64 {
+        self.next_seq
+    }
+
+    /// Oldest sequence number still journaled; the entries before it were truncated.
+    pub fn first_seq(&self) -> u64 {
+        self.first_seq
+    }
+
+    pub fn entries(&self) -> &[JournalEntry] {
+        &self.entries
+    }
+
+    /// Entries with a sequence number in `seqs`.
+    pub fn range(&self, seqs: Range<u64>) -> &[JournalEntry] {
+        let start = self.entries.partition_point(|entry| entry.seq < seqs.start);
+        let end = self.entries.partition_point(|entry| entry.seq < seqs.end);
+        &self.entries[start..end.max(start)]
+    }
+
+    pub fn by_author(&self, author: Entity) -> impl Iterator<Item = &JournalEntry> + '_ {
+        self.entries.iter().filter(move |entry| entry.author == Some(author))
+    }
+
+    /// The net change made by the entries in `seqs`.
+    pub fn diff(&self, seqs: Range<u64>) -> GridDiff {
+        GridDiff::from_entries(self.range(seqs))
+    }
+
+    /// Forgets entries older than `seq`.
+    pub fn truncate_before(&mut self, seq: u64) {
+        let start = self.entries.partition_point(|entry| entry.seq < seq);
+        self.entries.drain(..start);
+        self.first_seq = self.first_seq.max(seq.min(self.next_seq));
+    }
+}
+
+/// Applies `entries` to `grid` in order, e.g. to bring a copy of the grid up to date.
+pub fn replay(grid: &mut Grid, entries: &[JournalEntry]) -> Result<(), OutOfBounds> {
+    for entry in entries {
+        grid.set_cell(entry.cell_pos, entry.new)?;
+    }
+    Ok(())
+}
+
+/// Undoes `entries` on `grid`, newest first, restoring the cells they replaced.
+pub fn revert(grid: &mut Grid, entries: &[JournalEntry]) -> Result<(), OutOfBounds> {
+    for entry in entries.iter().rev() {
+        grid.set_cell(entry.cell_pos, entry.old)?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CellDiff {
+    pub cell_pos: CellPos,
+    pub old: Cell,
+    pub new: Cell,
+}
+
+/// The cells that differ between two grid states, sorted by row then column.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct GridDiff {
+    changes: Vec<CellDiff>,
+}
+
+impl GridDiff {
+    /// Compares the cells `old` and `new` both have. Cells outside either grid are
+    /// left out, so diffing grids of different sizes only covers their overlap.
+    pub fn between(old: &Grid, new: &Grid) -> Self {
+        let changes = old
+            .iter_cell_pos()
+            .filter(|&(cell_pos, _)| new.in_bounds(cell_pos))
+            .filter_map(|(cell_pos, old_cell)| {
+                let new_cell = new.cell(cell_pos).expect("checked to be in bounds");
+                (old_cell != new_cell).then_some(CellDiff { cell_pos, old: old_cell, new: new_cell })
+            })
+            .collect();
+
+        GridDiff::sorted(changes)
+    }
+
+    /// Folds journal entries into one change per cell, dropping cells that ended
+    /// up as they started.
+    pub fn from_entries<'a>(entries: impl IntoIterator<Item = &'a JournalEntry>) -> Self {
+        let mut changes: HashMap<CellPos, CellDiff> = HashMap::new();
+        for entry in entries {
+            changes
+                .entry(entry.cell_pos)
+                .and_modify(|change| change.new = entry.new)
+                .or_insert(CellDiff { cell_pos: entry.cell_pos, old: entry.old, new: entry.new });
+        }
+
+        GridDiff::sorted(changes.into_values().filter(|change| change.old != change.new).collect())
+    }
+
+    fn sorted(mut changes: Vec<CellDiff>) -> Self {
+        changes.sort_by_key(|change| (change.cell_pos.1, change.cell_pos.0));
+        GridDiff { changes }
+    }
+
+    pub fn changes(&self) -> &[CellDiff] {
+        &self.changes
+    }
+
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// The diff that undoes this one.
+    pub fn inverse(&self) -> GridDiff {
+        let changes = self
+            .changes
+            .iter()
+            .map(|change| CellDiff { cell_pos: change.cell_pos, old: change.new, new: change.old })
+            .collect();
+        GridDiff { changes }
+    }
+
+    /// Sets every changed cell of `grid` to its new value.
+    pub fn apply(&self, grid: &mut Grid) -> Result<(), OutOfBounds> {
+        for change in &self.changes {
+            grid.set_cell(change.cell_pos, change.new)?;
+        }
+        Ok(())
+    }
+}
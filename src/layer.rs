@@ -0,0 +1,170 @@
+//! Typed per-cell data stored alongside a grid's cells.
+//!
+//! A [`GridLayer`] holds one value per cell, laid out like the grid itself. A
+//! grid carries at most one layer per value type and resizes its layers along
+//! with its cells, so they never fall out of alignment.
+
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+};
+
+use itertools::Itertools;
+
+use crate::grid::{CellPos, OutOfBounds};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GridLayer<T> {
+    width: u32,
+    height: u32,
+    values: Vec<T>,
+    /// Value given to cells exposed by a resize.
+    fill: T,
+}
+
+impl<T: Clone> GridLayer<T> {
+    pub fn new(width: u32, height: u32, fill: T) -> Self {
+        GridLayer {
+            width,
+            height,
+            values: vec![fill.clone(); (width * height) as usize],
+            fill,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn fill(&self) -> &T {
+        &self.fill
+    }
+
+    fn index(&self, cell_pos: CellPos) -> Result<usize, OutOfBounds> {
+        let CellPos(x, y) = cell_pos;
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return Err(OutOfBounds { cell_pos });
+        }
+
+        Ok((self.width * y as u32 + x as u32) as usize)
+    }
+
+    pub fn get(&self, cell_pos: CellPos) -> Result<&T, OutOfBounds> {
+        let index = self.index(cell_pos)?;
+        Ok(&self.values[index])
+    }
+
+    pub fn get_mut(&mut self, cell_pos: CellPos) -> Result<&mut T, OutOfBounds> {
+        let index = self.index(cell_pos)?;
+        Ok(&mut self.values[index])
+    }
+
+    pub fn set(&mut self, cell_pos: CellPos, value: T) -> Result<&mut Self, OutOfBounds> {
+        *self.get_mut(cell_pos)? = value;
+        Ok(self)
+    }
+
+    /// Every value with its position, in the same order as `Grid::iter_cell_pos`.
+    pub fn iter(&self) -> impl Iterator<Item = (CellPos, &T)> + '_ {
+        (0..self.width)
+            .cartesian_product(0..self.height)
+            .map(|(x, y)| {
+                let cell_pos = CellPos(x as i32, y as i32);
+                (cell_pos, &self.values[(self.width * y + x) as usize])
+            })
+    }
+
+    /// Changes the layer dimensions the same way `Grid::resize` does, filling
+    /// new cells with the layer's fill value.
+    pub fn resize(&mut self, new_width: u32, new_height: u32) -> &mut Self {
+        let mut values = vec![self.fill.clone(); (new_width * new_height) as usize];
+
+        for y in 0..self.height.min(new_height) {
+            for x in 0..self.width.min(new_width) {
+                values[(new_width * y + x) as usize] = self.values[(self.width * y + x) as usize].clone();
+            }
+        }
+
+        self.width = new_width;
+        self.height = new_height;
+        self.values = values;
+        self
+    }
+}
+
+/// Object-safe view of a `GridLayer<T>`, so a grid can hold layers of any type.
+trait AnyLayer: Any + Send + Sync {
+    fn clone_box(&self) -> Box<dyn AnyLayer>;
+    fn resize(&mut self, new_width: u32, new_height: u32);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+impl<T: Clone + Send + Sync + 'static> AnyLayer for GridLayer<T> {
+    fn clone_box(&self) -> Box<dyn AnyLayer> {
+        Box::new(self.clone())
+    }
+
+    fn resize(&mut self, new_width: u32, new_height: u32) {
+        GridLayer::resize(self, new_width, new_height);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+/// The layers carried by a grid, keyed by value type.
+#[derive(Default)]
+pub(crate) struct Layers(HashMap<TypeId, Box<dyn AnyLayer>>);
+
+impl Layers {
+    pub(crate) fn insert<T: Clone + Send + Sync + 'static>(&mut self, layer: GridLayer<T>) -> Option<GridLayer<T>> {
+        let previous = self.0.insert(TypeId::of::<T>(), Box::new(layer))?;
+        previous.into_any().downcast().ok().map(|layer| *layer)
+    }
+
+    pub(crate) fn remove<T: Clone + Send + Sync + 'static>(&mut self) -> Option<GridLayer<T>> {
+        let layer = self.0.remove(&TypeId::of::<T>())?;
+        layer.into_any().downcast().ok().map(|layer| *layer)
+    }
+
+    pub(crate) fn get<T: Clone + Send + Sync + 'static>(&self) -> Option<&GridLayer<T>> {
+        self.0.get(&TypeId::of::<T>())?.as_any().downcast_ref()
+    }
+
+    pub(crate) fn get_mut<T: Clone + Send + Sync + 'static>(&mut self) -> Option<&mut GridLayer<T>> {
+        self.0.get_mut(&TypeId::of::<T>())?.as_any_mut().downcast_mut()
+    }
+
+    pub(crate) fn resize(&mut self, new_width: u32, new_height: u32) {
+        for layer in self.0.values_mut() {
+            layer.resize(new_width, new_height);
+        }
+    }
+}
+
+impl Clone for Layers {
+    fn clone(&self) -> Self {
+        Layers(self.0.iter().map(|(&type_id, layer)| (type_id, layer.clone_box())).collect())
+    }
+}
+
+impl std::fmt::Debug for Layers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Layers({})", self.0.len())
+    }
+}
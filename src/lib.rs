@@ -0,0 +1,501 @@
+use bevy::{asset::AssetPlugin, prelude::*};
+
+pub mod bindings;
+pub mod builder;
+#[cfg(feature = "visualizer")]
+pub mod camera;
+#[cfg(feature = "visualizer")]
+pub mod capture;
+pub mod cave;
+pub mod clearance;
+pub mod commands;
+#[cfg(feature = "visualizer")]
+pub mod compare;
+pub mod console;
+pub mod dungeon;
+pub mod editor;
+#[cfg(feature = "visualizer")]
+pub mod flow_view;
+pub mod graph;
+pub mod grid;
+#[cfg(feature = "visualizer")]
+pub mod grid_lines;
+pub mod history;
+#[cfg(feature = "image")]
+pub mod image_import;
+pub mod journal;
+pub mod layer;
+pub mod map_file;
+pub mod markers;
+pub mod maze;
+#[cfg(feature = "visualizer")]
+pub mod minimap;
+pub mod moving_ai;
+pub mod noise;
+pub mod occupancy;
+pub mod path_export;
+#[cfg(feature = "visualizer")]
+pub mod path_line;
+#[cfg(feature = "visualizer")]
+pub mod painting;
+#[cfg(feature = "visualizer")]
+pub mod palette;
+#[cfg(feature = "egui")]
+pub mod panel;
+pub mod pathfinding;
+pub mod pattern;
+#[cfg(feature = "visualizer")]
+pub mod playback;
+#[cfg(feature = "visualizer")]
+pub mod race;
+#[cfg(feature = "visualizer")]
+pub mod range;
+pub mod regions;
+pub mod request;
+pub mod resample;
+#[cfg(feature = "serde")]
+pub mod saved_map;
+#[cfg(feature = "visualizer")]
+pub mod search_stats;
+#[cfg(feature = "visualizer")]
+pub mod search_view;
+pub mod seed;
+pub mod soak;
+pub mod storage;
+pub mod streaming;
+pub mod summary;
+pub mod terrain;
+#[cfg(test)]
+mod test_util;
+#[cfg(feature = "visualizer")]
+pub mod theme;
+pub mod threat;
+#[cfg(feature = "visualizer")]
+pub mod tooltip;
+pub mod undo;
+#[cfg(feature = "visualizer")]
+pub mod view;
+#[cfg(feature = "visualizer")]
+pub mod walker;
+pub mod wfc;
+
+pub mod prelude {
+    pub use crate::{
+        bindings::{InputBindings, KeyBinding, KeyChord},
+        builder::GridBuilder,
+        cave::{cave, drunkard_walk, DrunkardWalk, GenerateCave},
+        clearance::{AgentClearance, Clearance},
+        commands::{GridCommandsExt, PathCommandsExt},
+        console::{report_errors, AssetLoadFailed, ErrorConsole, ErrorReport},
+        dungeon::{bsp_dungeon, dungeon, room_cells, Dungeon, DungeonRoom, GenerateBspDungeon, GenerateDungeon},
+        editor::{
+            selection_of, BulkEdit, CellSelection, EditorAppExt, EditorTool, EditorTools, PaintCell, RandomizeWalls,
+            ResizeGrid, SetCellEvent, SetTerrain, ToggleWall, Unsolvable, UseTool, UseToolEvent,
+        },
+        graph::{find_graph_path, GraphPath, GraphSearch, GraphStepResult, SearchGraph},
+        grid::{
+            Boundary, Cell, CellChangeEvent, CellPos, Connectivity, Direction, Exits, Grid, GridEditor, GridHandle,
+            GridNotFound, GridView, Grids, OutOfBounds, Portal,
+        },
+        history::{GridEdit, GridHistory, GridLogFile, LogError},
+        journal::{replay, revert, CellDiff, GridDiff, GridJournal, JournalEntry},
+        layer::GridLayer,
+        map_file::{LayerValue, MapError, MapFormat},
+        markers::{marked_cell, Endpoint, EndpointMarker, MarkedPath, MarkerNotFound},
+        maze::{maze, GenerateMaze, MazeAlgorithm},
+        noise::{FractalNoise, GenerateTerrain},
+        occupancy::{
+            find_timed_path, AvoidOccupied, CellReserved, OccupancySchedule, OccupiedPolicy, Reservations, TimedPath,
+        },
+        path_export::{PathExport, PathExportFile, PathExportPlugin},
+        pathfinding::{
+            AStar, FlowField, Heuristic, MovementRange, PartialPath, Path, Pathfinder, PathfinderState, Planner, Search, SearchMask,
+            SearchOutcome, StepCost, StepResult, UniformCost,
+        },
+        pattern::{CellPattern, PatternError, PatternLibrary},
+        regions::Regions,
+        request::{
+            Algorithm, ComputedPath, PathCache, PathFailed, PathFailedEvent, PathFailure, PathFoundEvent, PathPending,
+            PathRequest, PathScheduler, Pathfinders, PathfinderAppExt, ScheduledSearch, SolveMode,
+        },
+        resample::{downscale, resample, upscale},
+        seed::MapRng,
+        soak::{MetricGrowing, SoakMonitor, SoakMonitorPlugin},
+        storage::{CellMut, StorageKind},
+        streaming::{PathStream, StreamFailed, StreamStatus},
+        summary::GridSummary,
+        terrain::{CostProfile, CostProfiles, Terrain, TerrainCost, TerrainCosts, TerrainRule},
+        threat::{attack_area, ThreatMap, ThreatSource},
+        undo::{UndoPlugin, UndoStack},
+        wfc::{collapse, CollapseError, GenerateWfc, TileSet},
+        AStarPlugin, GridAnalysisSet, GridEditSet, PathComputeSet, ViewSyncSet,
+    };
+
+    #[cfg(feature = "visualizer")]
+    pub use crate::{
+        camera::{GridCamera, GridCameraPlugin},
+        capture::SearchRecorder,
+        compare::{CompareCommandsExt, SplitScreen, SplitScreenBundle, SplitScreenPlugin},
+        console::ErrorConsolePlugin,
+        editor::CellOverlay,
+        flow_view::{FlowArrowMesh, FlowArrows},
+        grid_lines::{GridLineStyle, GridLines},
+        markers::{MarkerDrag, MarkerPlacementPlugin},
+        minimap::{MainViewOutline, Minimap, MinimapBundle, MinimapPlugin, MINIMAP_LAYER},
+        painting::{fill_region, BrushShape, FillTooLarge, PaintTool, Symmetry, WallBrush, WallPaintingPlugin},
+        palette::{TerrainKind, TerrainPalette, TerrainPalettePlugin},
+        path_line::{PathLine, PathLineStyle},
+        playback::{PlaybackControlsPlugin, VisualizationClock},
+        race::{Race, RaceOutcome, Racer},
+        range::RangeHighlight,
+        search_stats::{SearchStats, SearchStatsPlugin},
+        search_view::{SearchColoring, SearchTree, SearchVisualizer},
+        theme::GridTheme,
+        threat::ThreatOverlay,
+        tooltip::CellTooltipPlugin,
+        view::{cell_color, cell_pos_at, cell_transform, CellColors, GridTexture},
+        walker::{PathWalker, WalkerBundle},
+    };
+
+    #[cfg(feature = "egui")]
+    pub use crate::panel::{ControlPanel, ControlPanelPlugin, PanelSection, PanelSections};
+    #[cfg(feature = "image")]
+    pub use crate::image_import::{ImageImport, PixelKind};
+    #[cfg(feature = "serde")]
+    pub use crate::saved_map::{MapEncoding, MapFile, MapFilePlugin, SavedMap, SavedMapError};
+}
+
+#[cfg(feature = "visualizer")]
+use editor::EditorAppExt;
+use grid::{CellPos, Grid};
+#[cfg(feature = "visualizer")]
+use grid::{GridEditor, GridView};
+
+/// Applies `ResizeGrid`, `BulkEdit`, `RandomizeWalls`, `GenerateMaze`,
+/// `GenerateCave`, `DrunkardWalk`, `GenerateDungeon`, `GenerateBspDungeon`,
+/// `GenerateWfc`, `SetTerrain`, `GenerateTerrain`, `UseTool`, `UseToolEvent` and
+/// `SetCellEvent` to grid editors. Runs first, so edits sent before it are searched and drawn
+/// the same frame.
+#[derive(SystemLabel, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GridEditSet;
+
+/// Brings `Regions` and `Clearance` up to date with the frame's edits, in
+/// [`GridAnalysisSet`], and drops the cached paths they invalidated, then answers
+/// `PathRequest`s and advances scheduled searches and `PathStream`s. Races
+/// advance in it too, without waiting for the rest. Runs after [`GridEditSet`];
+/// read `PathFoundEvent`s and `ComputedPath`s after it.
+#[derive(SystemLabel, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PathComputeSet;
+
+/// Brings `Regions` and `Clearance` up to date with the frame's edits. Part of
+/// [`PathComputeSet`], and runs before the systems of it that read them.
+#[derive(SystemLabel, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GridAnalysisSet;
+
+/// Draws, lays out and tints grid textures, overlays included. Runs after
+/// [`GridEditSet`] and [`PathComputeSet`], so it draws the frame's edits and searches.
+#[derive(SystemLabel, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ViewSyncSet;
+
+/// Registers the grid types and the systems that keep grid views in sync with their grids.
+/// Grids are assets, so the plugin adds Bevy's `AssetPlugin` unless the app already has
+/// one, as it does with `DefaultPlugins`.
+/// Errors from these systems are collected in the [`console::ErrorConsole`] resource; add
+/// `console::ErrorConsolePlugin` to show them in the window.
+///
+/// Every subsystem is enabled by default; the `with_*` methods switch off the ones an
+/// app doesn't need, and configure the path request resources the plugin inserts.
+/// Textures, overlays, races and the console window only exist with the `visualizer`
+/// cargo feature, which is on by default; without it the `visualizer` and `races`
+/// switches do nothing.
+#[derive(Debug, Clone)]
+pub struct AStarPlugin {
+    /// Applies `ResizeGrid`, `BulkEdit`, `RandomizeWalls`, `GenerateMaze`, `GenerateCave`, `DrunkardWalk`,
+    /// `GenerateDungeon`, `GenerateBspDungeon`, `GenerateWfc`, `SetTerrain`, `GenerateTerrain`, `UseTool`,
+    /// `UseToolEvent` and `SetCellEvent` requests to grid editors, the random ones seeded from the `MapRng`.
+    pub editor: bool,
+    /// Draws each grid as a single texture, a pixel per cell, kept in sync with the grid, with
+    /// `RangeHighlight`s, `ThreatOverlay`s and `SearchVisualizer`s painted on top, draws
+    /// lines between cells when the `GridLineStyle` asks for them, `FlowArrows` and every
+    /// `ComputedPath` as lines, and moves `PathWalker`s along theirs.
+    pub visualizer: bool,
+    /// Maintains `Regions` on grid editors, so unreachable queries fail fast.
+    pub regions: bool,
+    /// Maintains `Clearance` on grid editors, for agents bigger than a cell.
+    pub clearance: bool,
+    /// Answers every `PathRequest` with a `ComputedPath` or `PathFailed`, and the
+    /// matching `PathFoundEvent` or `PathFailedEvent`, running async ones on the
+    /// `AsyncComputeTaskPool` and scheduled ones within the `PathScheduler` budget.
+    pub requests: bool,
+    /// Plans the next segment of every `PathStream` as it drains.
+    pub streaming: bool,
+    /// Advances every `Race`, and tints its cells when the visualizer is on.
+    pub races: bool,
+    /// Inserted as the `PathScheduler` resource when `requests` is on: the
+    /// scheduled search budget, the default `SolveMode` and the async task limit.
+    pub scheduler: request::PathScheduler,
+    /// Paths the `PathCache` keeps at most.
+    pub cache_capacity: usize,
+}
+
+impl Default for AStarPlugin {
+    fn default() -> Self {
+        AStarPlugin {
+            editor: true,
+            visualizer: true,
+            regions: true,
+            clearance: true,
+            requests: true,
+            streaming: true,
+            races: true,
+            scheduler: request::PathScheduler::default(),
+            cache_capacity: request::PathCache::default().capacity,
+        }
+    }
+}
+
+impl AStarPlugin {
+    /// Everything but the visualizer and races, for apps running on `MinimalPlugins`
+    /// without a window, such as servers and integration tests.
+    pub fn headless() -> Self {
+        AStarPlugin::default().with_visualizer(false).with_races(false)
+    }
+
+    pub fn with_editor(mut self, editor: bool) -> Self {
+        self.editor = editor;
+        self
+    }
+
+    pub fn with_visualizer(mut self, visualizer: bool) -> Self {
+        self.visualizer = visualizer;
+        self
+    }
+
+    pub fn with_regions(mut self, regions: bool) -> Self {
+        self.regions = regions;
+        self
+    }
+
+    pub fn with_clearance(mut self, clearance: bool) -> Self {
+        self.clearance = clearance;
+        self
+    }
+
+    pub fn with_requests(mut self, requests: bool) -> Self {
+        self.requests = requests;
+        self
+    }
+
+    pub fn with_streaming(mut self, streaming: bool) -> Self {
+        self.streaming = streaming;
+        self
+    }
+
+    pub fn with_races(mut self, races: bool) -> Self {
+        self.races = races;
+        self
+    }
+
+    pub fn with_scheduler(mut self, scheduler: request::PathScheduler) -> Self {
+        self.scheduler = scheduler;
+        self
+    }
+
+    pub fn with_cache_capacity(mut self, capacity: usize) -> Self {
+        self.cache_capacity = capacity;
+        self
+    }
+
+    /// Mode of the path requests that don't name one.
+    pub fn with_default_mode(mut self, mode: request::SolveMode) -> Self {
+        self.scheduler.default_mode = mode;
+        self
+    }
+
+    /// Runs at most `max_tasks` async searches at once; more async requests wait.
+    pub fn with_async_solver(mut self, max_tasks: usize) -> Self {
+        self.scheduler.max_async_tasks = Some(max_tasks);
+        self
+    }
+}
+
+impl Plugin for AStarPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<AssetServer>() {
+            app.add_plugin(AssetPlugin::default());
+        }
+
+        app
+            .register_type::<CellPos>()
+            .register_type::<Vec<CellPos>>()
+            .register_type::<grid::Connectivity>()
+            .register_type::<grid::GridEditor>()
+            .register_type::<grid::GridView>()
+            .register_type::<summary::GridSummary>()
+            .register_type::<terrain::Terrain>()
+            .register_type::<dungeon::DungeonRoom>()
+            .register_type::<pathfinding::Path>()
+            .register_type::<pathfinding::Planner>()
+            .register_type::<pathfinding::Heuristic>()
+            .register_type::<request::SolveMode>()
+            .register_type::<request::PathFailure>()
+            .register_type::<request::PathRequest>()
+            .register_type::<request::ComputedPath>()
+            .register_type::<request::PathFailed>()
+            .register_type::<request::PathScheduler>()
+            .register_type::<request::PathCache>()
+            .register_type::<markers::Endpoint>()
+            .register_type::<markers::EndpointMarker>()
+            .register_type::<bindings::KeyChord>()
+            .register_type::<bindings::KeyBinding>()
+            .register_type::<bindings::InputBindings>()
+            .add_asset::<Grid>()
+            .add_event::<grid::CellChangeEvent>()
+            .init_resource::<console::ErrorConsole>()
+            .add_system(console::report_failed_grid_loads)
+            .add_system(summary::summarize_grids::<grid::GridEditor>)
+            .add_system(summary::summarize_grids::<grid::GridView>);
+
+        if self.editor {
+            app
+                .init_resource::<editor::EditorTools>()
+                .init_resource::<seed::MapRng>()
+                .add_event::<editor::SetCellEvent>()
+                .add_event::<editor::UseToolEvent>()
+                .add_system_set(
+                    SystemSet::new()
+                        .label(GridEditSet)
+                        .with_system(editor::resize_grid)
+                        .with_system(editor::apply_bulk_edits)
+                        .with_system(editor::randomize_walls)
+                        .with_system(maze::generate_mazes)
+                        .with_system(cave::generate_caves)
+                        .with_system(cave::generate_drunkard_walks)
+                        .with_system(dungeon::generate_dungeons)
+                        .with_system(dungeon::generate_bsp_dungeons)
+                        .with_system(wfc::generate_wfc)
+                        .with_system(editor::set_terrain)
+                        .with_system(noise::generate_terrain)
+                        .with_system(editor::use_tools)
+                        .with_system(editor::apply_cell_edits)
+                        .with_system(undo::apply_undo_requests.before(editor::apply_cell_edits)),
+                )
+                .add_system(undo::record_undo_actions.after(GridEditSet));
+        }
+
+        #[cfg(feature = "visualizer")]
+        if self.visualizer {
+            app
+                .init_resource::<theme::GridTheme>()
+                .init_resource::<path_line::PathLineStyle>()
+                .init_resource::<grid_lines::GridLineStyle>()
+                .init_resource::<playback::VisualizationClock>()
+                .init_resource::<search_stats::SearchStats>()
+                .add_system_set(
+                    SystemSet::new()
+                        .label(ViewSyncSet)
+                        .after(GridEditSet)
+                        .after(PathComputeSet)
+                        .with_system(view::update_cells::<GridEditor>.after(view::relayout_cells::<GridEditor>))
+                        .with_system(view::update_cells::<GridView>.after(view::relayout_cells::<GridView>))
+                        .with_system(view::grid_added::<GridEditor>)
+                        .with_system(view::grid_added::<GridView>)
+                        .with_system(view::relayout_cells::<GridEditor>)
+                        .with_system(view::relayout_cells::<GridView>)
+                        .with_system(grid_lines::draw_grid_lines)
+                        .with_system(path_line::draw_path_lines)
+                        .with_system(path_line::erase_path_lines)
+                        .with_system(search_view::draw_search_trees)
+                        .with_system(search_view::erase_search_trees)
+                        .with_system(search_stats::update_search_stats)
+                        .with_system(flow_view::draw_flow_arrows)
+                        .with_system(flow_view::erase_flow_arrows)
+                        .with_system(walker::follow_computed_paths)
+                        .with_system(walker::walk_paths.after(walker::follow_computed_paths))
+                        .with_system(threat::update_threat_overlays)
+                        .with_system(
+                            threat::tint_threat_cells
+                                .after(threat::update_threat_overlays)
+                                .after(view::update_cells::<GridEditor>)
+                                .after(view::update_cells::<GridView>),
+                        ),
+                )
+                // After every overlay, wherever it was scheduled in the update.
+                .add_system_to_stage(CoreStage::PostUpdate, view::upload_cell_textures)
+                .add_system_to_stage(CoreStage::PostUpdate, capture::record_searches.after(view::upload_cell_textures))
+                .add_system_to_stage(CoreStage::PreUpdate, playback::advance_visualization_clock)
+                .add_system(search_view::step_search_visualizers.label(PathComputeSet).after(GridEditSet))
+                .add_cell_overlay::<range::RangeHighlight>()
+                .add_cell_overlay::<markers::EndpointMarker>()
+                .add_cell_overlay::<editor::CellSelection>()
+                .add_cell_overlay::<search_view::SearchVisualizer>();
+        }
+
+        if self.regions {
+            app.add_system_set(
+                SystemSet::new()
+                    .label(PathComputeSet)
+                    .label(GridAnalysisSet)
+                    .after(GridEditSet)
+                    .with_system(regions::label_new_grids)
+                    .with_system(regions::update_regions),
+            );
+        }
+
+        if self.clearance {
+            app.add_system_set(
+                SystemSet::new()
+                    .label(PathComputeSet)
+                    .label(GridAnalysisSet)
+                    .after(GridEditSet)
+                    .with_system(clearance::measure_new_grids)
+                    .with_system(clearance::update_clearance),
+            );
+        }
+
+        if self.requests {
+            app
+                .add_event::<request::PathFoundEvent>()
+                .add_event::<request::PathFailedEvent>()
+                .insert_resource(self.scheduler.clone())
+                .insert_resource(request::PathCache::new(self.cache_capacity))
+                .init_resource::<request::Pathfinders>()
+                .init_resource::<terrain::TerrainCosts>()
+                .init_resource::<terrain::CostProfiles>()
+                .add_system_set(
+                    SystemSet::new()
+                        .label(PathComputeSet)
+                        .after(GridEditSet)
+                        .with_system(request::solve_path_requests.after(GridAnalysisSet))
+                        .with_system(request::invalidate_cached_paths.before(request::solve_path_requests))
+                        .with_system(request::collect_async_paths)
+                        .with_system(request::advance_scheduled_searches.after(GridAnalysisSet)),
+                )
+                .add_system(markers::request_marked_paths.after(GridEditSet));
+        }
+
+        if self.streaming {
+            app.add_system(
+                streaming::prefetch_path_streams.label(PathComputeSet).after(GridEditSet).after(GridAnalysisSet),
+            );
+        }
+
+        #[cfg(feature = "visualizer")]
+        if self.races {
+            app.add_system(race::advance_races.label(PathComputeSet).after(GridEditSet));
+        }
+
+        #[cfg(feature = "visualizer")]
+        if self.races && self.visualizer {
+            app.add_system(
+                race::tint_race_cells
+                    .label(ViewSyncSet)
+                    .after(GridEditSet)
+                    .after(PathComputeSet)
+                    .after(race::advance_races)
+                    .after(view::update_cells::<GridEditor>)
+                    .after(view::update_cells::<GridView>),
+            );
+        }
+    }
+}
@@ -0,0 +1,606 @@
+//! Saving and loading whole maps: cells, portals, exits, boundary, connectivity, the seed
+//! the map was generated from and any typed layers the game registers.
+//!
+//! A map file is a version header followed by one record per line. Readers
+//! skip record kinds they don't know, so files written by newer builds still
+//! load in older ones, minus the parts those builds can't represent. Files
+//! written by older builds are brought up to date by running the migrations
+//! between their version and [`MapFormat::VERSION`] before any record is read.
+
+use std::{
+    error::Error,
+    fmt::Display,
+    io::{self, BufRead, BufWriter, Write},
+};
+
+use crate::{
+    dungeon::DungeonRoom,
+    grid::{Boundary, Cell, CellPos, Connectivity, Direction, Exits, Grid, OutOfBounds},
+    storage::{Chunks, StorageKind},
+    terrain::Terrain,
+};
+
+/// A per-cell value that can be written into a map file as a single word.
+pub trait LayerValue: Clone + Send + Sync + 'static {
+    /// Must not contain whitespace.
+    fn encode(&self) -> String;
+    fn decode(word: &str) -> Option<Self>;
+}
+
+macro_rules! layer_value_via_str {
+    ($($ty:ty),*) => {
+        $(
+            impl LayerValue for $ty {
+                fn encode(&self) -> String {
+                    self.to_string()
+                }
+
+                fn decode(word: &str) -> Option<Self> {
+                    word.parse().ok()
+                }
+            }
+        )*
+    };
+}
+
+layer_value_via_str!(bool, u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+
+impl LayerValue for Terrain {
+    fn encode(&self) -> String {
+        self.0.encode()
+    }
+
+    fn decode(word: &str) -> Option<Self> {
+        u8::decode(word).map(Terrain)
+    }
+}
+
+impl LayerValue for DungeonRoom {
+    fn encode(&self) -> String {
+        self.0.encode()
+    }
+
+    fn decode(word: &str) -> Option<Self> {
+        u16::decode(word).map(DungeonRoom)
+    }
+}
+
+#[derive(Debug)]
+pub enum MapError {
+    Io(io::Error),
+    Parse { line: usize, message: String },
+    OutOfBounds(OutOfBounds),
+}
+
+impl Display for MapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MapError::Io(err) => write!(f, "map file io error: {err}"),
+            MapError::Parse { line, message } => write!(f, "map file line {line}: {message}"),
+            MapError::OutOfBounds(err) => write!(f, "map file record: {err}"),
+        }
+    }
+}
+impl Error for MapError {}
+
+impl From<io::Error> for MapError {
+    fn from(err: io::Error) -> Self {
+        MapError::Io(err)
+    }
+}
+
+impl From<OutOfBounds> for MapError {
+    fn from(err: OutOfBounds) -> Self {
+        MapError::OutOfBounds(err)
+    }
+}
+
+/// Most cells a loaded map can have, 4096 by 4096, so a corrupt or crafted
+/// size can't make loading allocate gigabytes.
+pub(crate) const MAX_CELLS: u64 = 1 << 24;
+
+/// Whether a map of `width` by `height` is small enough to load.
+pub(crate) fn size_fits(width: u32, height: u32) -> bool {
+    width.checked_mul(height).is_some_and(|cells| u64::from(cells) <= MAX_CELLS)
+}
+
+/// How one layer type is written and read back, under a name stable across builds.
+struct LayerCodec {
+    name: String,
+    save: fn(&Grid) -> Option<String>,
+    load: fn(&mut Grid, &[&str]) -> Option<()>,
+}
+
+/// One line of a map file, with the line number it came from.
+#[derive(Debug, Clone)]
+struct Record {
+    line: usize,
+    text: String,
+}
+
+/// Rewrites the records of a file from one version into the next.
+type Migration = fn(Vec<Record>) -> Result<Vec<Record>, MapError>;
+
+/// Record kinds that describe the grid itself, apart from `storage` and `size`.
+const KNOWN_RECORDS: [&str; 8] = ["boundary", "connectivity", "seed", "cells", "chunk", "portal", "exits", "layer"];
+
+/// `MIGRATIONS[n]` upgrades version `n + 1` to version `n + 2`.
+const MIGRATIONS: [Migration; 1] = [migrate_v1_snapshot];
+
+/// The records and layers a map file is made of. Layers are only saved and
+/// loaded if they were registered with [`MapFormat::with_layer`], apart from
+/// the crate's own `Terrain` and `DungeonRoom` layers, registered by default as
+/// `terrain` and `room`. Custom boundary callbacks can't be saved and come back
+/// as solid boundaries. Cells written past the border of an unbounded grid are
+/// saved in chunks.
+pub struct MapFormat {
+    layers: Vec<LayerCodec>,
+}
+
+impl Default for MapFormat {
+    fn default() -> Self {
+        MapFormat { layers: Vec::new() }.with_layer::<Terrain>("terrain").with_layer::<DungeonRoom>("room")
+    }
+}
+
+impl MapFormat {
+    pub const VERSION: u32 = 2;
+    const HEADER: &'static str = "a_star_map";
+
+    pub fn new() -> Self {
+        MapFormat::default()
+    }
+
+    /// Saves and loads the grid's `GridLayer<T>` under `name`, in place of any
+    /// layer registered under that name before.
+    pub fn with_layer<T: LayerValue>(mut self, name: &str) -> Self {
+        assert!(
+            !name.is_empty() && !name.contains(char::is_whitespace),
+            "layer names must be single words, got {name:?}",
+        );
+
+        self.layers.retain(|codec| codec.name != name);
+        self.layers.push(LayerCodec {
+            name: name.to_string(),
+            save: save_layer::<T>,
+            load: load_layer::<T>,
+        });
+        self
+    }
+
+    pub fn save(&self, grid: &Grid, writer: impl Write) -> io::Result<()> {
+        let mut writer = BufWriter::new(writer);
+
+        writeln!(writer, "{} {}", Self::HEADER, Self::VERSION)?;
+        // Read before the size, which is where the grid gets allocated.
+        let storage = match grid.storage_kind() {
+            StorageKind::Cells => "cells",
+            StorageKind::Bitset => "bitset",
+        };
+        writeln!(writer, "storage {storage}")?;
+        writeln!(writer, "size {} {}", grid.width(), grid.height())?;
+
+        match grid.boundary() {
+            Boundary::Open { margin } => writeln!(writer, "boundary open {margin}")?,
+            Boundary::Unbounded { fill } => writeln!(writer, "boundary unbounded {}", cell_char(*fill))?,
+            Boundary::Solid | Boundary::Custom(_) => writeln!(writer, "boundary solid")?,
+        }
+
+        let connectivity = match grid.connectivity() {
+            Connectivity::Four => "four",
+            Connectivity::Eight => "eight",
+            Connectivity::Hex => "hex",
+        };
+        writeln!(writer, "connectivity {connectivity}")?;
+
+        if let Some(seed) = grid.seed() {
+            writeln!(writer, "seed {seed}")?;
+        }
+
+        let cells: String = (0..grid.height() as i32)
+            .flat_map(|y| (0..grid.width() as i32).map(move |x| CellPos(x, y)))
+            .map(|cell_pos| cell_char(grid.cell(cell_pos).expect("iterating inside the grid")))
+            .collect();
+        writeln!(writer, "cells {cells}")?;
+
+        // Cells written past the border of an unbounded grid, one chunk per line.
+        // Written after the boundary, which loading them depends on.
+        for CellPos(x0, y0) in grid.chunk_origins() {
+            let cells: String = (y0..y0 + Chunks::SIZE)
+                .flat_map(|y| (x0..x0 + Chunks::SIZE).map(move |x| CellPos(x, y)))
+                .map(|cell_pos| cell_char(grid.cell(cell_pos).expect("unbounded grids have every cell")))
+                .collect();
+            writeln!(writer, "chunk {x0} {y0} {} {cells}", Chunks::SIZE)?;
+        }
+
+        // Portals are stored in both directions; write each pair once.
+        for (CellPos(ax, ay), portal) in grid.portals() {
+            let CellPos(bx, by) = portal.exit;
+            if (ax, ay) <= (bx, by) {
+                writeln!(writer, "portal {ax} {ay} {bx} {by} {}", portal.cost)?;
+            }
+        }
+
+        for (cell_pos, _) in grid.iter_cell_pos() {
+            let exits = grid.exits(cell_pos);
+            if exits != Exits::ALL {
+                let CellPos(x, y) = cell_pos;
+                writeln!(writer, "exits {x} {y} {}", exits_word(exits))?;
+            }
+        }
+
+        for codec in &self.layers {
+            if let Some(values) = (codec.save)(grid) {
+                writeln!(writer, "layer {} {values}", codec.name)?;
+            }
+        }
+
+        writer.flush()
+    }
+
+    pub fn load(&self, reader: impl BufRead) -> Result<Grid, MapError> {
+        let mut records = Vec::new();
+        for (index, line) in reader.lines().enumerate() {
+            let text = line?;
+            if !text.trim().is_empty() {
+                records.push(Record { line: index + 1, text });
+            }
+        }
+
+        let version = read_version(&mut records)?;
+        for migration in MIGRATIONS.iter().skip(version.saturating_sub(1) as usize) {
+            records = migration(records)?;
+        }
+
+        self.build(&records)
+    }
+
+    fn build(&self, records: &[Record]) -> Result<Grid, MapError> {
+        let mut grid: Option<Grid> = None;
+        let mut storage = StorageKind::Cells;
+
+        for record in records {
+            let parse_error = |message: &str| MapError::Parse { line: record.line, message: message.to_string() };
+            let mut fields = record.text.split_whitespace();
+            let kind = fields.next().expect("blank lines are dropped when reading");
+
+            if kind == "storage" {
+                // Storage kinds added later fall back to the default layout.
+                storage = match fields.next() {
+                    Some("bitset") => StorageKind::Bitset,
+                    _ => StorageKind::Cells,
+                };
+                continue;
+            }
+
+            if kind == "size" {
+                if grid.is_some() {
+                    return Err(parse_error("unexpected second size"));
+                }
+                let size = parse_size(fields).ok_or_else(|| parse_error("malformed size"))?;
+                if !size_fits(size.0, size.1) {
+                    return Err(parse_error(&format!("size {}x{} is over {MAX_CELLS} cells", size.0, size.1)));
+                }
+                grid = Some(Grid::with_storage(size.0, size.1, storage));
+                continue;
+            }
+
+            // Written by a newer format version.
+            if !KNOWN_RECORDS.contains(&kind) {
+                continue;
+            }
+
+            let Some(grid) = grid.as_mut() else {
+                return Err(parse_error("record before the map size"));
+            };
+
+            match kind {
+                "boundary" => {
+                    let boundary = parse_boundary(fields).ok_or_else(|| parse_error("malformed boundary"))?;
+                    grid.set_boundary(boundary);
+                }
+                "connectivity" => {
+                    let connectivity = match fields.next() {
+                        Some("four") => Connectivity::Four,
+                        Some("eight") => Connectivity::Eight,
+                        Some("hex") => Connectivity::Hex,
+                        _ => return Err(parse_error("malformed connectivity")),
+                    };
+                    grid.set_connectivity(connectivity);
+                }
+                "seed" => {
+                    let seed = fields.next().and_then(|seed| seed.parse().ok());
+                    grid.set_seed(Some(seed.ok_or_else(|| parse_error("malformed seed"))?));
+                }
+                "cells" => {
+                    parse_cells(grid, fields.next().unwrap_or("")).ok_or_else(|| parse_error("malformed cells"))?;
+                }
+                "chunk" => {
+                    parse_chunk(grid, fields).ok_or_else(|| parse_error("malformed chunk"))?;
+                }
+                "portal" => {
+                    let (a, b, cost) = parse_portal(fields).ok_or_else(|| parse_error("malformed portal"))?;
+                    grid.link_portals(a, b, cost)?;
+                }
+                "exits" => {
+                    let (cell_pos, exits) = parse_exits(fields).ok_or_else(|| parse_error("malformed exits"))?;
+                    grid.set_exits(cell_pos, exits)?;
+                }
+                "layer" => {
+                    let name = fields.next().ok_or_else(|| parse_error("layer without a name"))?;
+                    let Some(codec) = self.layers.iter().find(|codec| codec.name == name) else {
+                        // Registered by some other build of the game; nothing to load it into.
+                        continue;
+                    };
+                    let values: Vec<_> = fields.collect();
+                    (codec.load)(grid, &values).ok_or_else(|| parse_error("malformed layer"))?;
+                }
+                _ => unreachable!("checked against KNOWN_RECORDS"),
+            }
+        }
+
+        grid.ok_or(MapError::Parse { line: 0, message: "missing map size".to_string() })
+    }
+}
+
+/// Takes the header off `records` and returns the version it names. Files
+/// without a header predate it and are version 1.
+fn read_version(records: &mut Vec<Record>) -> Result<u32, MapError> {
+    let Some(first) = records.first() else {
+        return Err(MapError::Parse { line: 0, message: "empty map file".to_string() });
+    };
+
+    let mut fields = first.text.split_whitespace();
+    if fields.next() != Some(MapFormat::HEADER) {
+        return Ok(1);
+    }
+
+    let version = fields
+        .next()
+        .and_then(|version| version.parse().ok())
+        .ok_or_else(|| MapError::Parse { line: first.line, message: "malformed header".to_string() })?;
+    records.remove(0);
+    Ok(version)
+}
+
+/// Version 1 files are a single `GridHistory` snapshot line:
+/// `snapshot <seq> <width> <height> <cells>`.
+fn migrate_v1_snapshot(records: Vec<Record>) -> Result<Vec<Record>, MapError> {
+    let mut migrated = Vec::with_capacity(records.len() + 1);
+
+    for record in records {
+        let mut fields = record.text.split_whitespace();
+        if fields.next() != Some("snapshot") {
+            migrated.push(record);
+            continue;
+        }
+
+        let malformed = || MapError::Parse { line: record.line, message: "malformed snapshot".to_string() };
+        let _seq = fields.next().ok_or_else(malformed)?;
+        let width = fields.next().ok_or_else(malformed)?;
+        let height = fields.next().ok_or_else(malformed)?;
+        let cells = fields.next().unwrap_or("");
+
+        migrated.push(Record { line: record.line, text: format!("size {width} {height}") });
+        migrated.push(Record { line: record.line, text: format!("cells {cells}") });
+    }
+
+    Ok(migrated)
+}
+
+fn save_layer<T: LayerValue>(grid: &Grid) -> Option<String> {
+    let layer = grid.layer::<T>()?;
+    let mut words = vec![layer.fill().encode()];
+
+    for y in 0..layer.height() as i32 {
+        for x in 0..layer.width() as i32 {
+            words.push(layer.get(CellPos(x, y)).expect("iterating inside the layer").encode());
+        }
+    }
+
+    Some(words.join(" "))
+}
+
+fn load_layer<T: LayerValue>(grid: &mut Grid, words: &[&str]) -> Option<()> {
+    let (fill, values) = words.split_first()?;
+    if values.len() != (grid.width() * grid.height()) as usize {
+        return None;
+    }
+
+    let width = grid.width();
+    grid.add_layer(T::decode(fill)?);
+    let layer = grid.layer_mut::<T>().expect("layer was just added");
+
+    for (index, word) in values.iter().enumerate() {
+        let cell_pos = CellPos((index as u32 % width) as i32, (index as u32 / width) as i32);
+        layer.set(cell_pos, T::decode(word)?).ok()?;
+    }
+
+    Some(())
+}
+
+pub(crate) fn cell_char(cell: Cell) -> char {
+    match cell.is_wall {
+        true => '#',
+        false => '.',
+    }
+}
+
+fn parse_size<'a>(mut fields: impl Iterator<Item = &'a str>) -> Option<(u32, u32)> {
+    Some((fields.next()?.parse().ok()?, fields.next()?.parse().ok()?))
+}
+
+fn parse_boundary<'a>(mut fields: impl Iterator<Item = &'a str>) -> Option<Boundary> {
+    match fields.next()? {
+        "solid" => Some(Boundary::Solid),
+        "open" => Some(Boundary::Open { margin: fields.next()?.parse().ok()? }),
+        "unbounded" => Some(Boundary::Unbounded { fill: parse_cell(fields.next()?.chars().next()?)? }),
+        _ => None,
+    }
+}
+
+fn parse_cells(grid: &mut Grid, cells: &str) -> Option<()> {
+    let width = grid.width();
+    if cells.chars().count() != (width * grid.height()) as usize {
+        return None;
+    }
+
+    for (index, c) in cells.chars().enumerate() {
+        let cell_pos = CellPos((index as u32 % width) as i32, (index as u32 / width) as i32);
+        grid.set_cell(cell_pos, parse_cell(c)?).ok()?;
+    }
+
+    Some(())
+}
+
+pub(crate) fn parse_cell(c: char) -> Option<Cell> {
+    match c {
+        '#' => Some(Cell { is_wall: true }),
+        '.' => Some(Cell { is_wall: false }),
+        _ => None,
+    }
+}
+
+/// A square of cells from its bottom left corner, row by row. Only unbounded
+/// grids can store cells past their border.
+fn parse_chunk<'a>(grid: &mut Grid, mut fields: impl Iterator<Item = &'a str>) -> Option<()> {
+    let x0: i32 = fields.next()?.parse().ok()?;
+    let y0: i32 = fields.next()?.parse().ok()?;
+    let size: i32 = fields.next()?.parse().ok()?;
+    let cells = fields.next()?;
+    // Chunks past the last cell coordinates can't be written.
+    if size <= 0 || x0.checked_add(size).is_none() || y0.checked_add(size).is_none() {
+        return None;
+    }
+    if cells.chars().count() != size.checked_mul(size)? as usize {
+        return None;
+    }
+
+    for (index, c) in (0..).zip(cells.chars()) {
+        let cell_pos = CellPos(x0 + index % size, y0 + index / size);
+        grid.set_cell(cell_pos, parse_cell(c)?).ok()?;
+    }
+
+    Some(())
+}
+
+fn parse_portal<'a>(mut fields: impl Iterator<Item = &'a str>) -> Option<(CellPos, CellPos, f32)> {
+    let mut number = || fields.next()?.parse().ok();
+    let a = CellPos(number()?, number()?);
+    let b = CellPos(number()?, number()?);
+    let cost = fields.next()?.parse().ok()?;
+    Some((a, b, cost))
+}
+
+const DIRECTION_CHARS: [(Direction, char); 4] = [
+    (Direction::East, 'E'),
+    (Direction::West, 'W'),
+    (Direction::North, 'N'),
+    (Direction::South, 'S'),
+];
+
+/// The open directions as letters, or `-` for a cell that can't be left.
+pub(crate) fn exits_word(exits: Exits) -> String {
+    let word: String = DIRECTION_CHARS
+        .iter()
+        .filter(|&&(direction, _)| exits.contains(direction))
+        .map(|&(_, c)| c)
+        .collect();
+
+    match word.is_empty() {
+        true => "-".to_string(),
+        false => word,
+    }
+}
+
+fn parse_exits<'a>(mut fields: impl Iterator<Item = &'a str>) -> Option<(CellPos, Exits)> {
+    let x = fields.next()?.parse().ok()?;
+    let y = fields.next()?.parse().ok()?;
+    Some((CellPos(x, y), parse_exits_word(fields.next()?)?))
+}
+
+/// The exits [`exits_word`] wrote.
+pub(crate) fn parse_exits_word(word: &str) -> Option<Exits> {
+    let mut exits = Exits::NONE;
+    for c in word.chars().filter(|&c| c != '-') {
+        let &(direction, _) = DIRECTION_CHARS.iter().find(|&&(_, letter)| letter == c)?;
+        exits = exits.with(direction);
+    }
+    Some(exits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::assert_same_grid;
+
+    fn round_trip(format: &MapFormat, grid: &Grid) -> Grid {
+        let mut file = Vec::new();
+        format.save(grid, &mut file).unwrap();
+        format.load(file.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn saved_maps_load_back_the_same() {
+        let mut grid = Grid::with_storage(6, 4, StorageKind::Bitset).with_connectivity(Connectivity::Eight);
+        grid.set_boundary(Boundary::Open { margin: 2 });
+        grid.set_seed(Some(42));
+        for cell_pos in [CellPos(1, 0), CellPos(1, 1), CellPos(4, 3)] {
+            grid.set_cell(cell_pos, Cell::wall()).unwrap();
+        }
+        grid.link_portals(CellPos(0, 0), CellPos(5, 3), 2.5).unwrap();
+        grid.set_exits(CellPos(2, 2), Exits::ALL.without(Direction::North)).unwrap();
+
+        grid.add_layer(Terrain::PLAIN);
+        grid.layer_mut::<Terrain>().unwrap().set(CellPos(3, 1), Terrain(2)).unwrap();
+        grid.add_layer(DungeonRoom::NONE);
+        grid.layer_mut::<DungeonRoom>().unwrap().set(CellPos(0, 3), DungeonRoom(7)).unwrap();
+        grid.add_layer(0.5_f32);
+        grid.layer_mut::<f32>().unwrap().set(CellPos(5, 0), 1.25).unwrap();
+
+        let format = MapFormat::new().with_layer::<f32>("danger");
+        let loaded = round_trip(&format, &grid);
+        assert_same_grid(&loaded, &grid);
+        let danger = |grid: &Grid, cell_pos| grid.layer::<f32>().and_then(|layer| layer.get(cell_pos).ok()).copied();
+        for (cell_pos, _) in grid.iter_cell_pos() {
+            assert_eq!(danger(&loaded, cell_pos), danger(&grid, cell_pos));
+        }
+
+        // Layers this build didn't register are skipped.
+        let mut file = Vec::new();
+        format.save(&grid, &mut file).unwrap();
+        assert!(MapFormat::new().load(file.as_slice()).unwrap().layer::<f32>().is_none());
+    }
+
+    #[test]
+    fn cells_past_the_border_load_back_the_same() {
+        let mut grid = Grid::new(3, 3);
+        grid.set_boundary(Boundary::Unbounded { fill: Cell::floor() });
+        for cell_pos in [CellPos(-5, 2), CellPos(40, -17), CellPos(1, 1)] {
+            grid.set_cell(cell_pos, Cell::wall()).unwrap();
+        }
+
+        assert_same_grid(&round_trip(&MapFormat::new(), &grid), &grid);
+    }
+
+    #[test]
+    fn version_1_snapshots_are_migrated() {
+        let grid = MapFormat::new().load("snapshot 7 3 2 #.#..#\n".as_bytes()).unwrap();
+
+        assert_eq!((grid.width(), grid.height()), (3, 2));
+        assert_eq!(grid.iter_cell_pos().filter(|(_, cell)| cell.is_wall).count(), 3);
+        for cell_pos in [CellPos(0, 0), CellPos(2, 0), CellPos(2, 1)] {
+            assert!(grid.cell(cell_pos).unwrap().is_wall, "{cell_pos:?}");
+        }
+    }
+
+    #[test]
+    fn oversized_maps_are_refused() {
+        let text = format!("{} {}\nsize 100000 100000\n", MapFormat::HEADER, MapFormat::VERSION);
+        assert!(matches!(MapFormat::new().load(text.as_bytes()), Err(MapError::Parse { line: 2, .. })));
+
+        let chunk = format!("chunk {} 0 16 {}", i32::MAX - 3, ".".repeat(256));
+        let text = format!("{} {}\nsize 4 4\nboundary unbounded .\n{chunk}\n", MapFormat::HEADER, MapFormat::VERSION);
+        assert!(matches!(MapFormat::new().load(text.as_bytes()), Err(MapError::Parse { line: 4, .. })));
+    }
+}
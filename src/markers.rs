@@ -0,0 +1,300 @@
+//! Start and goal markers placed on grids.
+//!
+//! An [`EndpointMarker`] entity marks where paths on a grid start or end. Paths
+//! asked for with `PathCommandsExt::request_marked_path`, and the searches the
+//! control panel starts, go between the markers of their grid, so moving a
+//! marker is all it takes to try another route. With the visualizer the marked
+//! cells are drawn in the theme's start and goal colors, and
+//! [`MarkerPlacementPlugin`] moves the markers to the cell under the cursor with
+//! Shift+S and Shift+G, or along with the mouse while one is dragged.
+//!
+//! An entity with a [`MarkedPath`] asks for the path between the markers of its
+//! grid again whenever a marker moves or the grid is edited, a few times a
+//! second at most, so the path drawn follows a dragged marker.
+
+use std::{error::Error, fmt::Display, time::Duration};
+
+use bevy::prelude::*;
+#[cfg(feature = "visualizer")]
+use bevy::{ecs::system::SystemParam, input::InputSystem};
+
+use crate::{
+    grid::{modified_grids, CellPos, Grid, Grids},
+    request::PathRequest,
+};
+#[cfg(feature = "visualizer")]
+use crate::{
+    bindings::InputBindings,
+    editor::CellOverlay,
+    grid::GridEditor,
+    minimap::MainCameraFilter,
+    theme::GridTheme,
+    view::{cell_pos_at, cursor_world_position, mix},
+};
+
+/// Which end of a path an [`EndpointMarker`] marks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Reflect, FromReflect)]
+pub enum Endpoint {
+    Start,
+    Goal,
+}
+
+impl Endpoint {
+    pub fn name(self) -> &'static str {
+        match self {
+            Endpoint::Start => "start",
+            Endpoint::Goal => "goal",
+        }
+    }
+}
+
+/// Marks `cell_pos` on `grid` as where paths start or end. A grid has at most
+/// one marker of each kind that counts, the first one found.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+#[reflect(Component)]
+pub struct EndpointMarker {
+    pub grid: Entity,
+    pub endpoint: Endpoint,
+    pub cell_pos: CellPos,
+}
+
+impl EndpointMarker {
+    pub fn start(grid: Entity, cell_pos: CellPos) -> Self {
+        EndpointMarker { grid, endpoint: Endpoint::Start, cell_pos }
+    }
+
+    pub fn goal(grid: Entity, cell_pos: CellPos) -> Self {
+        EndpointMarker { grid, endpoint: Endpoint::Goal, cell_pos }
+    }
+}
+
+// Like `PathRequest`, built by the inspector before there is a grid to point at.
+impl FromWorld for EndpointMarker {
+    fn from_world(_world: &mut World) -> Self {
+        EndpointMarker::start(Entity::from_raw(u32::MAX), CellPos(0, 0))
+    }
+}
+
+/// The cell marked as `endpoint` on `grid` among `markers`, if any.
+pub fn marked_cell<'a>(
+    markers: impl IntoIterator<Item = &'a EndpointMarker>,
+    grid: Entity,
+    endpoint: Endpoint,
+) -> Option<CellPos> {
+    markers
+        .into_iter()
+        .find(|marker| marker.grid == grid && marker.endpoint == endpoint)
+        .map(|marker| marker.cell_pos)
+}
+
+/// Why a path between markers was not requested.
+#[derive(Debug)]
+pub struct MarkerNotFound {
+    pub grid: Entity,
+    pub endpoint: Endpoint,
+}
+
+impl Display for MarkerNotFound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (grid, endpoint) = (self.grid, self.endpoint.name());
+        write!(f, "grid {grid:?} has no {endpoint} marker")
+    }
+}
+impl Error for MarkerNotFound {}
+
+/// Keeps a `PathRequest` on its entity for the path between the markers of
+/// `grid`, asked again when they move or the grid is edited.
+#[derive(Component, Debug, Clone)]
+pub struct MarkedPath {
+    pub grid: Entity,
+    /// Least time between two requests, so dragging a marker across the grid
+    /// doesn't search once per cell passed over.
+    pub interval: Duration,
+    /// Time since startup of the last request, as given by `Time::elapsed`.
+    last_request: Option<Duration>,
+    outdated: bool,
+}
+
+impl MarkedPath {
+    pub fn new(grid: Entity) -> Self {
+        MarkedPath { grid, interval: Duration::from_millis(50), last_request: None, outdated: true }
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+}
+
+pub(crate) fn request_marked_paths(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut asset_events: EventReader<AssetEvent<Grid>>,
+    grids: Grids,
+    moved: Query<&EndpointMarker, Changed<EndpointMarker>>,
+    markers: Query<&EndpointMarker>,
+    mut paths: Query<(&mut MarkedPath, Entity)>,
+) {
+    let modified = modified_grids(&mut asset_events);
+    let now = time.elapsed();
+
+    for (mut marked, entity) in &mut paths {
+        let grid = marked.grid;
+        let edited = grids.handle(grid).is_some_and(|handle| modified.contains(handle));
+        if !marked.outdated && (edited || moved.iter().any(|marker| marker.grid == grid)) {
+            marked.outdated = true;
+        }
+        if !marked.outdated || marked.last_request.is_some_and(|last| now < last + marked.interval) {
+            continue;
+        }
+
+        // Waits for both markers, keeping the path outdated until then.
+        let marked_at = |endpoint| marked_cell(&markers, grid, endpoint);
+        let (Some(start), Some(goal)) = (marked_at(Endpoint::Start), marked_at(Endpoint::Goal)) else {
+            continue;
+        };
+        commands.entity(entity).insert(PathRequest::new(grid, start, goal));
+        marked.last_request = Some(now);
+        marked.outdated = false;
+    }
+}
+
+#[cfg(feature = "visualizer")]
+impl CellOverlay for EndpointMarker {
+    fn grid(&self) -> Entity {
+        self.grid
+    }
+
+    fn cell_tint(&self, _cell_pos: CellPos, base: Color, theme: &GridTheme) -> Option<Color> {
+        let color = match self.endpoint {
+            Endpoint::Start => theme.start,
+            Endpoint::Goal => theme.goal,
+        };
+        Some(mix(base, color, 0.9))
+    }
+
+    fn tinted_cells(&self) -> Option<Vec<CellPos>> {
+        Some(vec![self.cell_pos])
+    }
+}
+
+/// Moves the start marker of the hovered grid editor to the cell under the
+/// cursor with Shift+S, and its goal marker with Shift+G, spawning them the
+/// first time. Markers can also be dragged with the left mouse button, which
+/// then doesn't paint. The keys and button are taken from the `InputBindings`.
+#[cfg(feature = "visualizer")]
+pub struct MarkerPlacementPlugin;
+
+#[cfg(feature = "visualizer")]
+impl Plugin for MarkerPlacementPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<MarkerDrag>()
+            .init_resource::<InputBindings>()
+            .add_system(place_markers.before(crate::GridEditSet))
+            // Before the update, so a press that grabs a marker is never painted with.
+            .add_system_to_stage(CoreStage::PreUpdate, drag_markers.after(InputSystem));
+    }
+}
+
+/// The marker being dragged with the mouse, if any.
+#[cfg(feature = "visualizer")]
+#[derive(Resource, Debug, Default)]
+pub struct MarkerDrag {
+    marker: Option<Entity>,
+}
+
+#[cfg(feature = "visualizer")]
+impl MarkerDrag {
+    pub fn dragged(&self) -> Option<Entity> {
+        self.marker
+    }
+}
+
+/// The grid editor and cell under the cursor.
+#[cfg(feature = "visualizer")]
+#[derive(SystemParam)]
+struct GridCursor<'w, 's> {
+    windows: Res<'w, Windows>,
+    grids: Grids<'w, 's>,
+    cameras: Query<'w, 's, (&'static GlobalTransform, &'static OrthographicProjection), MainCameraFilter>,
+    editors: Query<'w, 's, (Entity, &'static GlobalTransform), With<GridEditor>>,
+}
+
+#[cfg(feature = "visualizer")]
+impl GridCursor<'_, '_> {
+    fn hovered(&self) -> Option<(Entity, CellPos)> {
+        let window = self.windows.get_primary()?;
+        let (camera_transform, projection) = self.cameras.iter().next()?;
+        let world = cursor_world_position(window, camera_transform, projection)?;
+
+        self.editors.iter().find_map(|(grid_entity, grid_transform)| {
+            let grid = self.grids.get(grid_entity).ok()?;
+            let cell_pos = cell_pos_at(grid, world - grid_transform.translation().truncate())?;
+            Some((grid_entity, cell_pos))
+        })
+    }
+}
+
+#[cfg(feature = "visualizer")]
+fn drag_markers(
+    buttons: Res<Input<MouseButton>>,
+    bindings: Res<InputBindings>,
+    cursor: GridCursor,
+    mut drag: ResMut<MarkerDrag>,
+    mut markers: Query<(&mut EndpointMarker, Entity)>,
+) {
+    if !buttons.pressed(bindings.drag_marker) {
+        if drag.marker.is_some() {
+            drag.marker = None;
+        }
+        return;
+    }
+    let Some((grid_entity, cell_pos)) = cursor.hovered() else {
+        return;
+    };
+
+    if buttons.just_pressed(bindings.drag_marker) {
+        drag.marker = markers
+            .iter()
+            .find(|(marker, _)| marker.grid == grid_entity && marker.cell_pos == cell_pos)
+            .map(|(_, entity)| entity);
+    }
+    let Some(Ok((mut marker, _))) = drag.marker.map(|entity| markers.get_mut(entity)) else {
+        return;
+    };
+    if marker.grid == grid_entity && marker.cell_pos != cell_pos {
+        marker.cell_pos = cell_pos;
+    }
+}
+
+#[cfg(feature = "visualizer")]
+fn place_markers(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<InputBindings>,
+    cursor: GridCursor,
+    mut markers: Query<&mut EndpointMarker>,
+) {
+    let endpoint = match (bindings.place_start.just_pressed(&keys), bindings.place_goal.just_pressed(&keys)) {
+        (true, false) => Endpoint::Start,
+        (false, true) => Endpoint::Goal,
+        _ => return,
+    };
+
+    let Some((grid_entity, cell_pos)) = cursor.hovered() else {
+        return;
+    };
+
+    let marker = markers.iter_mut().find(|marker| marker.grid == grid_entity && marker.endpoint == endpoint);
+    match marker {
+        Some(mut marker) => marker.cell_pos = cell_pos,
+        None => {
+            let name = match endpoint {
+                Endpoint::Start => "Start marker",
+                Endpoint::Goal => "Goal marker",
+            };
+            commands.spawn((Name::new(name), EndpointMarker { grid: grid_entity, endpoint, cell_pos }));
+        }
+    }
+}
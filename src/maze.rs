@@ -0,0 +1,274 @@
+//! Perfect mazes to path through.
+//!
+//! [`maze`] lays out a lattice of square rooms `corridor_width` cells wide,
+//! separated by walls one cell thick, and knocks down the walls of a random
+//! spanning tree of it: every room is reached and there is exactly one way
+//! between any two of them. The last row and column of rooms stretch to the
+//! edges of the grid, so its corners are always open. Which tree depends on the
+//! [`MazeAlgorithm`], each leaving mazes of a different texture, from the long
+//! winding corridors of the recursive backtracker to the many short dead ends
+//! of Prim's and Kruskal's algorithms.
+//!
+//! Inserting [`GenerateMaze`] on a grid editor entity lays a maze over its
+//! grid, like a `BulkEdit`, within its `CellSelection` if it has one.
+
+use std::ops::Range;
+
+use bevy::prelude::*;
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{
+    console::ErrorConsole,
+    editor::{selection_of, set_all_cells, CellSelection},
+    grid::{Cell, CellPos, Grid, GridEditor, GridNotFound},
+    journal::GridJournal,
+    pattern::CellPattern,
+    seed::MapRng,
+};
+
+/// How the walls of a [`maze`] are knocked down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum MazeAlgorithm {
+    /// A depth-first walk that knocks down the wall to a random unvisited room
+    /// and backs up when there is none: long winding corridors with few dead
+    /// ends.
+    #[default]
+    RecursiveBacktracker,
+    /// Grows the maze from a corner, opening a random wall on its edge at a
+    /// time: many short dead ends branching off paths that run out from it.
+    Prim,
+    /// Opens every wall in a random order unless both sides are already
+    /// connected: many short dead ends, evenly spread over the grid.
+    Kruskal,
+}
+
+impl MazeAlgorithm {
+    pub const ALL: [MazeAlgorithm; 3] =
+        [MazeAlgorithm::RecursiveBacktracker, MazeAlgorithm::Prim, MazeAlgorithm::Kruskal];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MazeAlgorithm::RecursiveBacktracker => "Recursive backtracker",
+            MazeAlgorithm::Prim => "Prim",
+            MazeAlgorithm::Kruskal => "Kruskal",
+        }
+    }
+}
+
+/// A `width` by `height` maze with corridors `corridor_width` cells wide, at
+/// least one, laid out by `algorithm`.
+pub fn maze(
+    width: u32,
+    height: u32,
+    corridor_width: u32,
+    algorithm: MazeAlgorithm,
+    rng: &mut impl Rng,
+) -> CellPattern {
+    let lattice = Lattice::new(width, height, corridor_width);
+    let passages = match algorithm {
+        MazeAlgorithm::RecursiveBacktracker => lattice.backtracker(rng),
+        MazeAlgorithm::Prim => lattice.prim(rng),
+        MazeAlgorithm::Kruskal => lattice.kruskal(rng),
+    };
+    lattice.carve(&passages)
+}
+
+/// Column and row of a room of the [`Lattice`].
+type Room = (u32, u32);
+
+/// The rooms of a maze, which the algorithms connect with passages and
+/// [`carve`](Self::carve) turns into cells.
+struct Lattice {
+    width: u32,
+    height: u32,
+    corridor_width: u32,
+    columns: u32,
+    rows: u32,
+}
+
+impl Lattice {
+    fn new(width: u32, height: u32, corridor_width: u32) -> Self {
+        let corridor_width = corridor_width.max(1);
+        let pitch = corridor_width + 1;
+        let (columns, rows) = (((width + 1) / pitch).max(1), ((height + 1) / pitch).max(1));
+        Lattice { width, height, corridor_width, columns, rows }
+    }
+
+    fn index(&self, (column, row): Room) -> usize {
+        (row * self.columns + column) as usize
+    }
+
+    fn neighbors(&self, (column, row): Room) -> impl Iterator<Item = Room> {
+        let (columns, rows) = (self.columns as i32, self.rows as i32);
+        [(-1, 0), (1, 0), (0, -1), (0, 1)]
+            .into_iter()
+            .map(move |(dx, dy)| (column as i32 + dx, row as i32 + dy))
+            .filter(move |&(x, y)| x >= 0 && y >= 0 && x < columns && y < rows)
+            .map(|(x, y)| (x as u32, y as u32))
+    }
+
+    fn backtracker(&self, rng: &mut impl Rng) -> Vec<(Room, Room)> {
+        let mut visited = vec![false; (self.columns * self.rows) as usize];
+        let mut passages = Vec::new();
+        let mut stack = vec![(0, 0)];
+        visited[0] = true;
+
+        while let Some(&room) = stack.last() {
+            let unvisited: Vec<Room> = self.neighbors(room).filter(|&next| !visited[self.index(next)]).collect();
+            if unvisited.is_empty() {
+                stack.pop();
+                continue;
+            }
+            let next = unvisited[rng.gen_range(0..unvisited.len())];
+            visited[self.index(next)] = true;
+            passages.push((room, next));
+            stack.push(next);
+        }
+        passages
+    }
+
+    fn prim(&self, rng: &mut impl Rng) -> Vec<(Room, Room)> {
+        let mut visited = vec![false; (self.columns * self.rows) as usize];
+        let mut passages = Vec::new();
+        // Walls between a room of the maze and one that may not be yet.
+        let mut frontier: Vec<(Room, Room)> = self.neighbors((0, 0)).map(|next| ((0, 0), next)).collect();
+        visited[0] = true;
+
+        while !frontier.is_empty() {
+            let (room, next) = frontier.swap_remove(rng.gen_range(0..frontier.len()));
+            if visited[self.index(next)] {
+                continue;
+            }
+            visited[self.index(next)] = true;
+            passages.push((room, next));
+            let unvisited = self.neighbors(next).filter(|&after| !visited[self.index(after)]);
+            frontier.extend(unvisited.map(|after| (next, after)));
+        }
+        passages
+    }
+
+    fn kruskal(&self, rng: &mut impl Rng) -> Vec<(Room, Room)> {
+        let mut walls: Vec<(Room, Room)> = (0..self.rows)
+            .flat_map(|row| (0..self.columns).map(move |column| (column, row)))
+            .flat_map(|room| [(room, (room.0 + 1, room.1)), (room, (room.0, room.1 + 1))])
+            .filter(|&(_, (column, row))| column < self.columns && row < self.rows)
+            .collect();
+        walls.shuffle(rng);
+
+        // The room each room was connected through, up to one standing for its
+        // whole part of the maze.
+        let mut parents: Vec<usize> = (0..(self.columns * self.rows) as usize).collect();
+        fn root(parents: &mut [usize], mut index: usize) -> usize {
+            while parents[index] != index {
+                parents[index] = parents[parents[index]];
+                index = parents[index];
+            }
+            index
+        }
+
+        let mut passages = Vec::new();
+        for (room, next) in walls {
+            let (a, b) = (root(&mut parents, self.index(room)), root(&mut parents, self.index(next)));
+            if a != b {
+                parents[a] = b;
+                passages.push((room, next));
+            }
+        }
+        passages
+    }
+
+    /// The cells of the room `index` out of `count` along a side `size` long.
+    fn span(&self, index: u32, count: u32, size: u32) -> Range<u32> {
+        let start = index * (self.corridor_width + 1);
+        let end = if index + 1 == count { size } else { start + self.corridor_width };
+        start.min(size)..end.min(size)
+    }
+
+    /// Walls everywhere but the rooms and the `passages` between them.
+    fn carve(&self, passages: &[(Room, Room)]) -> CellPattern {
+        let mut pattern = CellPattern::new(self.width, self.height, Cell::wall());
+        let mut open = |xs: Range<u32>, ys: Range<u32>| {
+            for y in ys {
+                for x in xs.clone() {
+                    let cell_pos = CellPos(x as i32, y as i32);
+                    pattern.set_cell(cell_pos, Cell::floor()).expect("spans are clamped to the pattern");
+                }
+            }
+        };
+
+        let columns = |column| self.span(column, self.columns, self.width);
+        let rows = |row| self.span(row, self.rows, self.height);
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                open(columns(column), rows(row));
+            }
+        }
+        // The wall between two rooms is just before the farther one starts.
+        let pitch = self.corridor_width + 1;
+        for &((column, row), (next_column, next_row)) in passages {
+            if next_column != column {
+                let x = column.max(next_column) * pitch - 1;
+                open(x..x + 1, rows(row));
+            } else {
+                let y = row.max(next_row) * pitch - 1;
+                open(columns(column), y..y + 1);
+            }
+        }
+        pattern
+    }
+}
+
+/// Inserted on a grid editor entity to replace its walls with a [`maze`], all
+/// at once like a `BulkEdit`, within its `CellSelection` if it has one.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GenerateMaze {
+    pub corridor_width: u32,
+    pub algorithm: MazeAlgorithm,
+}
+
+impl GenerateMaze {
+    pub fn new(corridor_width: u32) -> Self {
+        GenerateMaze { corridor_width, algorithm: MazeAlgorithm::default() }
+    }
+
+    pub fn with_algorithm(mut self, algorithm: MazeAlgorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+}
+
+impl Default for GenerateMaze {
+    fn default() -> Self {
+        GenerateMaze::new(1)
+    }
+}
+
+pub(crate) fn generate_mazes(
+    mut commands: Commands,
+    mut grid_query: Query<(&GridEditor, &GenerateMaze, Option<&mut GridJournal>, Entity)>,
+    selections: Query<&CellSelection>,
+    mut assets: ResMut<Assets<Grid>>,
+    mut console: ResMut<ErrorConsole>,
+    time: Res<Time>,
+    mut map_rng: ResMut<MapRng>,
+) {
+    for (grid_editor, generate, journal, entity) in &mut grid_query {
+        commands.entity(entity).remove::<GenerateMaze>();
+
+        let Some(grid) = assets.get(&grid_editor.grid) else {
+            console.report("generate_mazes", &GridNotFound { entity });
+            continue;
+        };
+
+        let (seed, mut rng) = map_rng.next_rng();
+        let maze = maze(grid.width(), grid.height(), generate.corridor_width, generate.algorithm, &mut rng);
+        let selection = selection_of(&selections, entity);
+        let changes: Vec<(CellPos, Cell, Cell)> = grid
+            .iter_cell_pos()
+            .filter(|(cell_pos, _)| selection.is_none_or(|selection| selection.cells.contains(cell_pos)))
+            .filter_map(|(cell_pos, old)| Some((cell_pos, old, maze.cell(cell_pos)?)))
+            .filter(|(_, old, new)| old != new)
+            .collect();
+        set_all_cells(&mut assets, &grid_editor.grid, journal, time.elapsed_seconds_f64(), changes, Some(seed));
+    }
+}
@@ -0,0 +1,221 @@
+//! A corner view of a whole grid.
+//!
+//! A [`MinimapBundle`] is a second 2D camera that draws its grid into a corner
+//! of the window, zoomed out to fit, with the part of the grid the main camera
+//! sees outlined on it. The outline is on [`MINIMAP_LAYER`], so only minimaps
+//! draw it. Add [`MinimapPlugin`] to keep minimaps fitted to the window and
+//! their outlines in step with the main camera.
+
+use bevy::{
+    core_pipeline::clear_color::ClearColorConfig,
+    prelude::*,
+    render::{camera::Viewport, mesh::PrimitiveTopology, view::RenderLayers},
+    sprite::{MaterialMesh2dBundle, Mesh2dHandle},
+};
+
+use crate::{grid::Grids, view::grid_bounds};
+
+/// Render layer of the main camera outlines, which only minimaps draw.
+pub const MINIMAP_LAYER: u8 = 1;
+
+/// How far above the grid texture, its lines and walkers outlines are drawn.
+const OUTLINE_DEPTH: f32 = 10.0;
+
+/// The 2D cameras that aren't minimaps, for systems that follow the main view.
+pub(crate) type MainCameraFilter = (With<Camera2d>, Without<Minimap>);
+
+/// Fits the 2D camera it is on to a corner of the window, showing all of `grid`.
+#[derive(Component, Debug, Clone)]
+pub struct Minimap {
+    pub grid: Entity,
+    /// The camera whose view is outlined.
+    pub main_camera: Entity,
+    /// Longest side of the minimap, as a fraction of the window height.
+    pub size: f32,
+    /// Logical pixels between the minimap and the bottom right corner of the window.
+    pub margin: f32,
+    pub outline_color: Color,
+}
+
+impl Minimap {
+    pub fn new(grid: Entity, main_camera: Entity) -> Self {
+        Minimap { grid, main_camera, size: 0.25, margin: 10.0, outline_color: Color::WHITE }
+    }
+
+    pub fn with_size(mut self, size: f32) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn with_margin(mut self, margin: f32) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    pub fn with_outline_color(mut self, outline_color: Color) -> Self {
+        self.outline_color = outline_color;
+        self
+    }
+}
+
+/// A camera drawing a minimap of `grid` over the view of `main_camera`.
+#[derive(Bundle)]
+pub struct MinimapBundle {
+    pub minimap: Minimap,
+    #[bundle]
+    pub camera: Camera2dBundle,
+    pub render_layers: RenderLayers,
+}
+
+impl MinimapBundle {
+    pub fn new(grid: Entity, main_camera: Entity) -> Self {
+        MinimapBundle {
+            minimap: Minimap::new(grid, main_camera),
+            camera: Camera2dBundle {
+                // Drawn after the main camera, over its view.
+                camera: Camera { priority: 1, ..default() },
+                camera_2d: Camera2d { clear_color: ClearColorConfig::None },
+                ..default()
+            },
+            render_layers: RenderLayers::layer(0).with(MINIMAP_LAYER),
+        }
+    }
+}
+
+/// Fits every [`Minimap`] to its corner and outlines what its main camera sees.
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_system(fit_minimaps)
+            .add_system(outline_main_views)
+            .add_system(erase_main_view_outlines);
+    }
+}
+
+/// On the outline of the main camera's view drawn for the [`Minimap`] on `minimap`.
+#[derive(Component, Debug, Clone)]
+pub struct MainViewOutline {
+    pub minimap: Entity,
+    material: Handle<ColorMaterial>,
+}
+
+fn fit_minimaps(
+    windows: Res<Windows>,
+    grids: Grids,
+    grid_transforms: Query<&GlobalTransform>,
+    mut minimaps: Query<(&Minimap, &mut Camera, &mut Transform, &mut OrthographicProjection)>,
+) {
+    let Some(window) = windows.get_primary() else {
+        return;
+    };
+    let scale_factor = window.scale_factor() as f32;
+
+    for (minimap, mut camera, mut transform, mut projection) in &mut minimaps {
+        let Ok(grid) = grids.get(minimap.grid) else {
+            continue;
+        };
+        let (min, max) = grid_bounds(grid);
+        let extent = max - min;
+
+        // In logical pixels, keeping the grid's aspect ratio.
+        let side = minimap.size * window.height();
+        let size = extent / extent.max_element() * side;
+
+        let physical_size = UVec2::new((size.x * scale_factor).max(1.0) as u32, (size.y * scale_factor).max(1.0) as u32);
+        let margin = (minimap.margin * scale_factor) as u32;
+        let physical_position = UVec2::new(
+            window.physical_width().saturating_sub(physical_size.x + margin),
+            window.physical_height().saturating_sub(physical_size.y + margin),
+        );
+
+        let fitted = camera.viewport.as_ref().is_some_and(|viewport| {
+            viewport.physical_position == physical_position && viewport.physical_size == physical_size
+        });
+        if !fitted {
+            camera.viewport = Some(Viewport { physical_position, physical_size, ..default() });
+        }
+
+        let scale = extent.max_element() / side;
+        if projection.scale != scale {
+            projection.scale = scale;
+        }
+
+        let origin = grid_transforms.get(minimap.grid).map_or(Vec3::ZERO, GlobalTransform::translation).truncate();
+        let center = origin + (min + max) / 2.0;
+        if center != transform.translation.truncate() {
+            transform.translation = center.extend(transform.translation.z);
+        }
+    }
+}
+
+/// A unit square around the origin, scaled to the main camera's view.
+fn outline_mesh() -> Mesh {
+    let positions = vec![[-0.5, -0.5, 0.0], [0.5, -0.5, 0.0], [0.5, 0.5, 0.0], [-0.5, 0.5, 0.0], [-0.5, -0.5, 0.0]];
+
+    let mut mesh = Mesh::new(PrimitiveTopology::LineStrip);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 0.0, 1.0]; positions.len()]);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0.0, 0.0]; positions.len()]);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh
+}
+
+/// Moves each minimap's outline over what its main camera sees, drawing it first if needed.
+fn outline_main_views(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    main_cameras: Query<(&GlobalTransform, &OrthographicProjection), MainCameraFilter>,
+    minimaps: Query<(&Minimap, ChangeTrackers<Minimap>, Entity)>,
+    mut outlines: Query<(&MainViewOutline, &mut Transform)>,
+) {
+    for (minimap, tracker, minimap_entity) in &minimaps {
+        let Ok((camera_transform, projection)) = main_cameras.get(minimap.main_camera) else {
+            continue;
+        };
+        let center = camera_transform.translation().truncate();
+        let size = Vec2::new(projection.right - projection.left, projection.top - projection.bottom) * projection.scale;
+        let transform = Transform::from_translation(center.extend(OUTLINE_DEPTH)).with_scale(size.extend(1.0));
+
+        match outlines.iter_mut().find(|(outline, _)| outline.minimap == minimap_entity) {
+            Some((outline, mut outline_transform)) => {
+                if *outline_transform != transform {
+                    *outline_transform = transform;
+                }
+                if tracker.is_changed() {
+                    if let Some(material) = materials.get_mut(&outline.material) {
+                        material.color = minimap.outline_color;
+                    }
+                }
+            }
+            None => {
+                let material = materials.add(ColorMaterial::from(minimap.outline_color));
+                commands.spawn((
+                    MaterialMesh2dBundle {
+                        mesh: Mesh2dHandle(meshes.add(outline_mesh())),
+                        material: material.clone(),
+                        transform,
+                        ..default()
+                    },
+                    MainViewOutline { minimap: minimap_entity, material },
+                    RenderLayers::layer(MINIMAP_LAYER),
+                    Name::new("Main view outline"),
+                ));
+            }
+        }
+    }
+}
+
+/// Removes the outlines of minimaps that are gone.
+fn erase_main_view_outlines(
+    mut commands: Commands,
+    outlines: Query<(&MainViewOutline, Entity)>,
+    minimaps: Query<(), With<Minimap>>,
+) {
+    for (outline, outline_entity) in &outlines {
+        if !minimaps.contains(outline.minimap) {
+            commands.entity(outline_entity).despawn_recursive();
+        }
+    }
+}
@@ -0,0 +1,357 @@
+//! The `.map` format of the MovingAI grid pathfinding benchmarks.
+//!
+//! A map file is a short header, `type octile`, `height` and `width` lines and
+//! a `map` line, then one line of symbols per row, top row first. `.` and `G`
+//! are open ground and `S` swamp, all read as floor; `@` and `O` are out of
+//! bounds, `T` trees and `W` water, all read as walls, as the benchmarks treat
+//! them. Octile maps allow diagonal moves that don't cut corners, so they load
+//! with eight-way connectivity.
+//!
+//! Saving writes floor as `.` and walls as `@`, always as `type octile`: exits,
+//! portals, layers and the connectivity of the grid aren't kept. `MapFormat`
+//! saves those.
+//!
+//! MovingAI coordinates count rows from the top, and the grid's from the
+//! bottom; [`cell_pos`] and [`map_coords`] convert between them.
+//!
+//! A `.scen` file lists queries on a map, each with the length of the shortest
+//! path, grouped into buckets of similar lengths. [`run_scenarios`] answers them
+//! all with a [`Pathfinder`], checks its paths against those lengths and times
+//! it per bucket: the usual way to validate and benchmark grid pathfinders.
+
+use std::{
+    error::Error,
+    fmt::Display,
+    io::{self, BufRead, BufWriter, Write},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    grid::{Cell, CellPos, Connectivity, Grid},
+    map_file::MapError,
+    pathfinding::{Pathfinder, SearchOutcome},
+};
+
+/// The cell at column `x` and row `y` from the top of a map file, on `grid`
+/// loaded from it.
+pub fn cell_pos(grid: &Grid, x: i32, y: i32) -> CellPos {
+    CellPos(x, grid.height() as i32 - 1 - y)
+}
+
+/// The column and row from the top of `cell_pos`, in a map file saved from `grid`.
+pub fn map_coords(grid: &Grid, CellPos(x, y): CellPos) -> (i32, i32) {
+    (x, grid.height() as i32 - 1 - y)
+}
+
+fn parse_symbol(symbol: char) -> Option<Cell> {
+    match symbol {
+        '.' | 'G' | 'S' => Some(Cell::floor()),
+        '@' | 'O' | 'T' | 'W' => Some(Cell::wall()),
+        _ => None,
+    }
+}
+
+/// Reads a map file, see the module docs.
+pub fn load_map(reader: impl BufRead) -> Result<Grid, MapError> {
+    let mut lines = reader.lines().enumerate().map(|(index, line)| line.map(|text| (index + 1, text)));
+    let parse_error = |line: usize, message: String| MapError::Parse { line, message };
+
+    let (mut width, mut height, mut line) = (None, None, 0);
+    loop {
+        let Some(next) = lines.next() else {
+            return Err(parse_error(line, "missing map line".to_string()));
+        };
+        let (number, text) = next?;
+        line = number;
+        let mut fields = text.split_whitespace();
+        match (fields.next(), fields.next()) {
+            (Some("map"), None) => break,
+            (Some("width"), Some(value)) => width = value.parse::<u32>().ok(),
+            (Some("height"), Some(value)) => height = value.parse::<u32>().ok(),
+            // `type` is always octile, and blank lines don't matter.
+            (Some("type"), Some(_)) | (None, _) => {}
+            _ => return Err(parse_error(line, format!("unexpected header line {text:?}"))),
+        }
+    }
+    let (Some(width), Some(height)) = (width, height) else {
+        return Err(parse_error(line, "missing or malformed width or height".to_string()));
+    };
+
+    let mut grid = Grid::new(width, height);
+    grid.set_connectivity(Connectivity::Eight);
+    for y in 0..height as i32 {
+        let Some(next) = lines.next() else {
+            return Err(parse_error(line, format!("expected {height} rows, got {y}")));
+        };
+        let (number, text) = next?;
+        line = number;
+        let row = text.trim_end();
+        if row.chars().count() != width as usize {
+            return Err(parse_error(line, format!("expected a row of {width} cells")));
+        }
+        for (x, symbol) in (0..).zip(row.chars()) {
+            let cell = parse_symbol(symbol).ok_or_else(|| parse_error(line, format!("unexpected symbol {symbol:?}")))?;
+            grid.set_cell(cell_pos(&grid, x, y), cell)?;
+        }
+    }
+
+    for next in lines {
+        let (line, text) = next?;
+        if !text.trim().is_empty() {
+            return Err(parse_error(line, format!("expected {height} rows, got more")));
+        }
+    }
+    Ok(grid)
+}
+
+/// Writes the cells of `grid` as a map file, see the module docs.
+pub fn save_map(grid: &Grid, writer: impl Write) -> io::Result<()> {
+    let mut writer = BufWriter::new(writer);
+    let (width, height) = (grid.width(), grid.height());
+
+    writeln!(writer, "type octile")?;
+    writeln!(writer, "height {height}")?;
+    writeln!(writer, "width {width}")?;
+    writeln!(writer, "map")?;
+    for y in 0..height as i32 {
+        let row: String = (0..width as i32)
+            .map(|x| match grid.cell(cell_pos(grid, x, y)).expect("rows are kept in the grid").is_wall {
+                true => '@',
+                false => '.',
+            })
+            .collect();
+        writeln!(writer, "{row}")?;
+    }
+
+    writer.flush()
+}
+
+/// One query of a scenario file, in MovingAI coordinates.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scenario {
+    pub bucket: u32,
+    /// Path of the map file, as written in the scenario file.
+    pub map: String,
+    pub map_width: u32,
+    pub map_height: u32,
+    pub start: (i32, i32),
+    pub goal: (i32, i32),
+    /// Length of the shortest path with octile moves.
+    pub optimal_length: f64,
+}
+
+fn parse_scenario(fields: &[&str]) -> Option<Scenario> {
+    let &[bucket, map, map_width, map_height, start_x, start_y, goal_x, goal_y, optimal_length] = fields else {
+        return None;
+    };
+    Some(Scenario {
+        bucket: bucket.parse().ok()?,
+        map: map.to_string(),
+        map_width: map_width.parse().ok()?,
+        map_height: map_height.parse().ok()?,
+        start: (start_x.parse().ok()?, start_y.parse().ok()?),
+        goal: (goal_x.parse().ok()?, goal_y.parse().ok()?),
+        optimal_length: optimal_length.parse().ok()?,
+    })
+}
+
+/// Reads the queries of a scenario file.
+pub fn load_scenarios(reader: impl BufRead) -> Result<Vec<Scenario>, MapError> {
+    let mut scenarios = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let text = line?;
+        let fields: Vec<&str> = text.split_whitespace().collect();
+        match fields[..] {
+            [] | ["version", _] => continue,
+            _ => {
+                let message = "malformed scenario".to_string();
+                scenarios.push(parse_scenario(&fields).ok_or(MapError::Parse { line: index + 1, message })?);
+            }
+        }
+    }
+    Ok(scenarios)
+}
+
+/// Returned by [`run_scenarios`] for a scenario made for a map of another size
+/// than the grid.
+#[derive(Debug)]
+pub struct WrongMap {
+    pub map: String,
+    pub map_width: u32,
+    pub map_height: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Display for WrongMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let WrongMap { map, map_width, map_height, width, height } = self;
+        write!(f, "scenario for {map}, {map_width}x{map_height}, run on a {width}x{height} grid")
+    }
+}
+
+impl Error for WrongMap {}
+
+/// How a pathfinder did on the scenarios of one bucket.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BucketReport {
+    pub bucket: u32,
+    pub queries: usize,
+    /// Queries the pathfinder found a path for.
+    pub solved: usize,
+    /// Solved queries whose path was as short as the optimal length.
+    pub optimal: usize,
+    /// Most any path was longer than the optimal length.
+    pub worst_excess: f64,
+    pub nodes_expanded: usize,
+    /// Time spent searching, over all the queries.
+    pub time: Duration,
+}
+
+/// How a pathfinder did on a scenario file, bucket by bucket.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BenchmarkReport {
+    /// In increasing order of bucket.
+    pub buckets: Vec<BucketReport>,
+}
+
+impl BenchmarkReport {
+    pub fn queries(&self) -> usize {
+        self.buckets.iter().map(|bucket| bucket.queries).sum()
+    }
+
+    /// Whether every query was solved with a path of the optimal length.
+    pub fn all_optimal(&self) -> bool {
+        self.buckets.iter().all(|bucket| bucket.optimal == bucket.queries)
+    }
+}
+
+impl Display for BenchmarkReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{:>6} {:>8} {:>8} {:>8} {:>12} {:>12} {:>12}",
+            "bucket", "queries", "solved", "optimal", "worst excess", "avg nodes", "avg time",
+        )?;
+        for bucket in &self.buckets {
+            writeln!(
+                f,
+                "{:>6} {:>8} {:>8} {:>8} {:>12.4} {:>12} {:>12?}",
+                bucket.bucket,
+                bucket.queries,
+                bucket.solved,
+                bucket.optimal,
+                bucket.worst_excess,
+                bucket.nodes_expanded / bucket.queries.max(1),
+                bucket.time / bucket.queries.max(1) as u32,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Answers every scenario on `grid`, loaded from their map, with `pathfinder`.
+/// A path counts as optimal when its cost is within float rounding of the
+/// optimal length, which assumes the step costs of an eight-connected grid
+/// without terrain.
+pub fn run_scenarios(
+    grid: &Grid,
+    scenarios: &[Scenario],
+    pathfinder: &mut impl Pathfinder,
+) -> Result<BenchmarkReport, WrongMap> {
+    let mut report = BenchmarkReport::default();
+    for scenario in scenarios {
+        if (scenario.map_width, scenario.map_height) != (grid.width(), grid.height()) {
+            return Err(WrongMap {
+                map: scenario.map.clone(),
+                map_width: scenario.map_width,
+                map_height: scenario.map_height,
+                width: grid.width(),
+                height: grid.height(),
+            });
+        }
+
+        let ((start_x, start_y), (goal_x, goal_y)) = (scenario.start, scenario.goal);
+        let (start, goal) = (cell_pos(grid, start_x, start_y), cell_pos(grid, goal_x, goal_y));
+        let started = Instant::now();
+        let outcome = pathfinder.search(grid, start, goal);
+        let time = started.elapsed();
+
+        let index = match report.buckets.binary_search_by_key(&scenario.bucket, |bucket| bucket.bucket) {
+            Ok(index) => index,
+            Err(index) => {
+                report.buckets.insert(index, BucketReport { bucket: scenario.bucket, ..BucketReport::default() });
+                index
+            }
+        };
+        let bucket = &mut report.buckets[index];
+        bucket.queries += 1;
+        bucket.time += time;
+        match outcome {
+            SearchOutcome::Found(path) => {
+                let excess = path.cost as f64 - scenario.optimal_length;
+                bucket.solved += 1;
+                bucket.optimal += usize::from(excess <= 1e-4 * scenario.optimal_length + 1e-3);
+                bucket.worst_excess = bucket.worst_excess.max(excess);
+                bucket.nodes_expanded += path.nodes_expanded;
+            }
+            SearchOutcome::NoPath { nodes_expanded } => bucket.nodes_expanded += nodes_expanded,
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pathfinding::Planner;
+
+    const MAP: &str = "type octile\nheight 3\nwidth 4\nmap\n.@..\n.@T.\n....\n";
+
+    #[test]
+    fn saved_maps_load_back_the_same() {
+        let grid = load_map(MAP.as_bytes()).unwrap();
+        assert_eq!((grid.width(), grid.height()), (4, 3));
+        assert_eq!(grid.connectivity(), Connectivity::Eight);
+        assert!(grid.cell(cell_pos(&grid, 1, 0)).unwrap().is_wall);
+        assert!(grid.cell(cell_pos(&grid, 2, 1)).unwrap().is_wall);
+        assert!(!grid.cell(cell_pos(&grid, 1, 2)).unwrap().is_wall);
+
+        let mut file = Vec::new();
+        save_map(&grid, &mut file).unwrap();
+        assert_eq!(String::from_utf8(file).unwrap(), MAP.replace('T', "@"));
+    }
+
+    #[test]
+    fn map_coordinates_count_rows_from_the_top() {
+        let grid = load_map(MAP.as_bytes()).unwrap();
+        assert_eq!(cell_pos(&grid, 1, 0), CellPos(1, 2));
+        assert_eq!(map_coords(&grid, CellPos(1, 2)), (1, 0));
+    }
+
+    #[test]
+    fn malformed_maps_name_the_line() {
+        let short_row = "type octile\nheight 2\nwidth 3\nmap\n...\n..\n";
+        assert!(matches!(load_map(short_row.as_bytes()), Err(MapError::Parse { line: 6, .. })));
+        let symbol = "type octile\nheight 1\nwidth 2\nmap\n.x\n";
+        assert!(matches!(load_map(symbol.as_bytes()), Err(MapError::Parse { line: 5, .. })));
+    }
+
+    #[test]
+    fn scenarios_are_checked_against_their_optimal_length() {
+        let grid = load_map(MAP.as_bytes()).unwrap();
+        let scen = "version 1\n0\tsmall.map\t4\t3\t0\t0\t3\t0\t7\n1\tsmall.map\t4\t3\t0\t0\t0\t2\t2\n";
+        let scenarios = load_scenarios(scen.as_bytes()).unwrap();
+        assert_eq!(scenarios.len(), 2);
+        assert_eq!(scenarios[0].map, "small.map");
+        assert_eq!((scenarios[0].start, scenarios[0].goal), ((0, 0), (3, 0)));
+
+        let report = run_scenarios(&grid, &scenarios, &mut Planner::AStar).unwrap();
+        assert_eq!(report.queries(), 2);
+        assert_eq!(report.buckets.iter().map(|bucket| bucket.bucket).collect::<Vec<_>>(), [0, 1]);
+        assert!(report.all_optimal(), "{report}");
+
+        let wrong = load_scenarios("0 big.map 8 8 0 0 1 1 1.41421356\n".as_bytes()).unwrap();
+        assert!(run_scenarios(&grid, &wrong, &mut Planner::AStar).is_err());
+    }
+}
@@ -0,0 +1,165 @@
+//! Terrain laid out from noise.
+//!
+//! [`FractalNoise`] sums a few octaves of Perlin gradient noise, each finer and
+//! fainter than the last, into smooth rolling values with detail at every
+//! scale. Inserting [`GenerateTerrain`] on a grid editor entity samples it at
+//! every cell and splits the cells into bands of terrain by how high they come
+//! out, so the ground shifts from one kind to the next the way real landscapes
+//! do. With a `TerrainCost`, the costs of the kinds turn that into a landscape
+//! of costs for weighted searches to wind through: by default the four kinds of
+//! the `TerrainPalette`, from road up to water.
+
+use bevy::prelude::*;
+
+use crate::{
+    console::ErrorConsole,
+    editor::{selection_of, CellSelection},
+    grid::{CellPos, Grid, GridEditor, GridNotFound},
+    terrain::Terrain,
+};
+
+/// Perlin noise summed over `octaves`, seeded so the same seed gives the same
+/// values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FractalNoise {
+    pub seed: u32,
+    /// Size in cells of the broadest features.
+    pub scale: f32,
+    /// Layers of noise summed, each at twice the frequency of the last.
+    pub octaves: u32,
+    /// How much fainter each octave is than the last; lower is smoother.
+    pub persistence: f32,
+}
+
+impl FractalNoise {
+    pub fn new(seed: u32) -> Self {
+        FractalNoise { seed, scale: 16.0, octaves: 4, persistence: 0.5 }
+    }
+
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_octaves(mut self, octaves: u32) -> Self {
+        self.octaves = octaves;
+        self
+    }
+
+    pub fn with_persistence(mut self, persistence: f32) -> Self {
+        self.persistence = persistence;
+        self
+    }
+
+    /// The noise at `cell_pos`, roughly between -1 and 1.
+    pub fn sample(&self, cell_pos: CellPos) -> f32 {
+        let (mut x, mut y) = (cell_pos.0 as f32 / self.scale.max(1.0), cell_pos.1 as f32 / self.scale.max(1.0));
+        let (mut sum, mut amplitude, mut total) = (0.0, 1.0, 0.0);
+        for octave in 0..self.octaves.max(1) {
+            sum += amplitude * perlin(self.seed.wrapping_add(octave), x, y);
+            total += amplitude;
+            amplitude *= self.persistence;
+            (x, y) = (x * 2.0, y * 2.0);
+        }
+        sum / total
+    }
+}
+
+/// Perlin noise at `(x, y)`: zero on whole coordinates, sloping along a
+/// gradient picked from `seed` and the coordinates.
+fn perlin(seed: u32, x: f32, y: f32) -> f32 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let (ix, iy) = (x0 as i32, y0 as i32);
+
+    // Dot product of the offset from a corner with the corner's gradient.
+    let corner = |dx: i32, dy: i32| {
+        let angle = hash(seed, ix + dx, iy + dy) as f32 / u32::MAX as f32 * std::f32::consts::TAU;
+        angle.cos() * (fx - dx as f32) + angle.sin() * (fy - dy as f32)
+    };
+    let fade = |t: f32| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+
+    let (u, v) = (fade(fx), fade(fy));
+    let bottom = lerp(corner(0, 0), corner(1, 0), u);
+    let top = lerp(corner(0, 1), corner(1, 1), u);
+    // Scaled up from about ±0.71, the most a unit gradient reaches.
+    lerp(bottom, top, v) * std::f32::consts::SQRT_2
+}
+
+fn hash(seed: u32, x: i32, y: i32) -> u32 {
+    let mut hash = seed ^ (x as u32).wrapping_mul(0x27d4_eb2d) ^ (y as u32).wrapping_mul(0x1656_67b1);
+    hash = (hash ^ (hash >> 15)).wrapping_mul(0x85eb_ca6b);
+    hash = (hash ^ (hash >> 13)).wrapping_mul(0xc2b2_ae35);
+    hash ^ (hash >> 16)
+}
+
+/// Inserted on a grid editor entity to lay out its terrain from `noise`, within
+/// its `CellSelection` if it has one. The cells are sorted by their noise and
+/// split into as many equal shares as there are `bands`, lowest first, so
+/// every kind covers as much ground whatever the noise. Like `SetTerrain`, it
+/// adds a terrain layer if the grid has none, and isn't journaled.
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct GenerateTerrain {
+    pub noise: FractalNoise,
+    pub bands: Vec<Terrain>,
+}
+
+impl GenerateTerrain {
+    /// Terrain from noise seeded with `seed`, in the bands of the default
+    /// `TerrainPalette`.
+    pub fn new(seed: u32) -> Self {
+        GenerateTerrain { noise: FractalNoise::new(seed), bands: (1..=4).map(Terrain).collect() }
+    }
+
+    pub fn with_noise(mut self, noise: FractalNoise) -> Self {
+        self.noise = noise;
+        self
+    }
+
+    pub fn with_bands(mut self, bands: impl IntoIterator<Item = Terrain>) -> Self {
+        self.bands = bands.into_iter().collect();
+        self
+    }
+}
+
+pub(crate) fn generate_terrain(
+    mut commands: Commands,
+    grid_query: Query<(&GridEditor, &GenerateTerrain, Entity)>,
+    selections: Query<&CellSelection>,
+    mut assets: ResMut<Assets<Grid>>,
+    mut console: ResMut<ErrorConsole>,
+) {
+    for (grid_editor, generate, entity) in &grid_query {
+        commands.entity(entity).remove::<GenerateTerrain>();
+
+        let Some(grid) = assets.get(&grid_editor.grid) else {
+            console.report("generate_terrain", &GridNotFound { entity });
+            continue;
+        };
+
+        let selection = selection_of(&selections, entity);
+        let mut cells: Vec<(CellPos, f32)> = grid
+            .iter_cell_pos()
+            .map(|(cell_pos, _)| cell_pos)
+            .filter(|cell_pos| selection.is_none_or(|selection| selection.cells.contains(cell_pos)))
+            .map(|cell_pos| (cell_pos, generate.noise.sample(cell_pos)))
+            .collect();
+        if cells.is_empty() || generate.bands.is_empty() {
+            continue;
+        }
+        cells.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+
+        let grid = assets.get_mut(&grid_editor.grid).expect("read above");
+        if grid.layer::<Terrain>().is_none() {
+            grid.add_layer(Terrain::PLAIN);
+        }
+        if let Some(layer) = grid.layer_mut::<Terrain>() {
+            let count = cells.len();
+            for (rank, (cell_pos, _)) in cells.into_iter().enumerate() {
+                let terrain = generate.bands[rank * generate.bands.len() / count];
+                layer.set(cell_pos, terrain).expect("iterated from the grid");
+            }
+        }
+    }
+}
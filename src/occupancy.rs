@@ -0,0 +1,290 @@
+//! Cells that are blocked by other agents or only at certain times.
+//!
+//! [`Reservations`] track which agent stands on which cell, as in turn-based
+//! games where every unit holds the cell it occupies. [`Reservations::avoiding`]
+//! turns them into a step cost, so a planner can route around other units or
+//! merely prefer to.
+//!
+//! An [`OccupancySchedule`] records when predictable moving obstacles (doors,
+//! patrolling hazards) occupy each cell, measured in ticks where one tick is one
+//! step of an agent. [`find_timed_path`] searches over cells and ticks together,
+//! so an agent can wait for a door to open or let a patrol pass instead of
+//! treating their cells as permanent walls.
+//!
+//! Only the cells themselves are reserved: an agent and an obstacle swapping
+//! places between two ticks isn't detected. Portals are not taken by timed
+//! searches.
+
+use std::{
+    cmp::Reverse,
+    collections::{hash_map::Entry, BinaryHeap, HashMap, HashSet},
+    error::Error,
+    fmt::Display,
+    ops::Range,
+};
+
+use bevy::prelude::*;
+
+use crate::{
+    grid::{CellPos, Grid},
+    pathfinding::{StepCost, UniformCost},
+};
+
+/// Returned when reserving a cell another agent already holds.
+#[derive(Debug)]
+pub struct CellReserved {
+    pub cell_pos: CellPos,
+    pub by: Entity,
+}
+
+impl Display for CellReserved {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let CellReserved { cell_pos, by } = self;
+        write!(f, "cell {cell_pos:?} is reserved by {by:?}")
+    }
+}
+
+impl Error for CellReserved {}
+
+/// The cell every agent on a grid stands on. Each agent holds at most one cell
+/// and each cell at most one agent.
+#[derive(Component, Debug, Clone, Default)]
+pub struct Reservations {
+    occupants: HashMap<CellPos, Entity>,
+    cells: HashMap<Entity, CellPos>,
+}
+
+impl Reservations {
+    pub fn new() -> Self {
+        Reservations::default()
+    }
+
+    /// Places `agent` on `cell_pos`, releasing the cell it held before. Used both
+    /// to place an agent and to move it; on failure the agent keeps its cell.
+    pub fn reserve(&mut self, agent: Entity, cell_pos: CellPos) -> Result<&mut Self, CellReserved> {
+        match self.occupants.get(&cell_pos) {
+            Some(&by) if by != agent => return Err(CellReserved { cell_pos, by }),
+            _ => {}
+        }
+
+        if let Some(previous) = self.cells.insert(agent, cell_pos) {
+            self.occupants.remove(&previous);
+        }
+        self.occupants.insert(cell_pos, agent);
+        Ok(self)
+    }
+
+    /// Frees the cell held by `agent`, returning it.
+    pub fn release(&mut self, agent: Entity) -> Option<CellPos> {
+        let cell_pos = self.cells.remove(&agent)?;
+        self.occupants.remove(&cell_pos);
+        Some(cell_pos)
+    }
+
+    pub fn occupant(&self, cell_pos: CellPos) -> Option<Entity> {
+        self.occupants.get(&cell_pos).copied()
+    }
+
+    pub fn cell_of(&self, agent: Entity) -> Option<CellPos> {
+        self.cells.get(&agent).copied()
+    }
+
+    pub fn is_occupied(&self, cell_pos: CellPos) -> bool {
+        self.occupants.contains_key(&cell_pos)
+    }
+
+    /// Every reserved cell with the agent holding it.
+    pub fn iter(&self) -> impl Iterator<Item = (CellPos, Entity)> + '_ {
+        self.occupants.iter().map(|(&cell_pos, &agent)| (cell_pos, agent))
+    }
+
+    /// A step cost for planning `agent`'s moves around the other agents. Cells
+    /// held by `agent` itself never count as occupied.
+    pub fn avoiding(&self, agent: Entity, policy: OccupiedPolicy) -> AvoidOccupied<'_> {
+        AvoidOccupied { reservations: self, agent, policy, cost: UniformCost }
+    }
+}
+
+/// How a planner treats cells other agents stand on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OccupiedPolicy {
+    /// Occupied cells can't be entered.
+    Block,
+    /// Entering an occupied cell costs this much extra, e.g. to plan through a
+    /// unit expected to move out of the way.
+    Penalize(f32),
+}
+
+/// Step cost from [`Reservations::avoiding`]. Steps onto free cells are priced
+/// by `cost`; occupied cells are refused or penalized.
+pub struct AvoidOccupied<'a, C: StepCost = UniformCost> {
+    reservations: &'a Reservations,
+    agent: Entity,
+    policy: OccupiedPolicy,
+    cost: C,
+}
+
+impl<'a, C: StepCost> AvoidOccupied<'a, C> {
+    pub fn with_cost<D: StepCost>(self, cost: D) -> AvoidOccupied<'a, D> {
+        AvoidOccupied {
+            reservations: self.reservations,
+            agent: self.agent,
+            policy: self.policy,
+            cost,
+        }
+    }
+}
+
+impl<C: StepCost> StepCost for AvoidOccupied<'_, C> {
+    fn step_cost(&self, grid: &Grid, from: CellPos, to: CellPos) -> Option<f32> {
+        let cost = self.cost.step_cost(grid, from, to)?;
+
+        match (self.reservations.occupant(to), self.policy) {
+            (Some(occupant), OccupiedPolicy::Block) if occupant != self.agent => None,
+            (Some(occupant), OccupiedPolicy::Penalize(penalty)) if occupant != self.agent => Some(cost + penalty),
+            _ => Some(cost),
+        }
+    }
+}
+
+#[derive(Component, Debug, Clone, Default)]
+pub struct OccupancySchedule {
+    /// Sorted, non-overlapping tick ranges during which each cell is blocked.
+    blocked: HashMap<CellPos, Vec<Range<u32>>>,
+}
+
+impl OccupancySchedule {
+    pub fn new() -> Self {
+        OccupancySchedule::default()
+    }
+
+    /// Blocks `cell_pos` for the ticks in `ticks`, merging with the intervals
+    /// already scheduled there.
+    pub fn block(&mut self, cell_pos: CellPos, ticks: Range<u32>) -> &mut Self {
+        if ticks.is_empty() {
+            return self;
+        }
+
+        let intervals = self.blocked.entry(cell_pos).or_default();
+        intervals.push(ticks);
+        intervals.sort_by_key(|interval| interval.start);
+
+        let mut merged: Vec<Range<u32>> = Vec::with_capacity(intervals.len());
+        for interval in intervals.drain(..) {
+            match merged.last_mut() {
+                Some(last) if interval.start <= last.end => last.end = last.end.max(interval.end),
+                _ => merged.push(interval),
+            }
+        }
+        *intervals = merged;
+        self
+    }
+
+    /// Blocks every cell of `route` for the one tick an obstacle starting at
+    /// `start_tick` spends there, e.g. a patrol walking its beat.
+    pub fn block_route(&mut self, route: &[CellPos], start_tick: u32) -> &mut Self {
+        for (tick, &cell_pos) in (start_tick..).zip(route) {
+            self.block(cell_pos, tick..tick + 1);
+        }
+        self
+    }
+
+    /// Removes every interval scheduled on `cell_pos`.
+    pub fn clear(&mut self, cell_pos: CellPos) -> &mut Self {
+        self.blocked.remove(&cell_pos);
+        self
+    }
+
+    pub fn intervals(&self, cell_pos: CellPos) -> &[Range<u32>] {
+        self.blocked.get(&cell_pos).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn is_blocked(&self, cell_pos: CellPos, tick: u32) -> bool {
+        let intervals = self.intervals(cell_pos);
+        // Index of the first interval starting after `tick`; only the one before it can hold it.
+        let after = intervals.partition_point(|interval| interval.start <= tick);
+        after > 0 && intervals[after - 1].contains(&tick)
+    }
+}
+
+/// A path through time: the agent is on `cells[i]` at tick `start_tick + i`.
+/// Consecutive repeats of a cell are waits.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimedPath {
+    pub start_tick: u32,
+    pub cells: Vec<CellPos>,
+}
+
+impl TimedPath {
+    /// Tick on which the goal is reached.
+    pub fn arrival_tick(&self) -> u32 {
+        self.start_tick + self.cells.len() as u32 - 1
+    }
+
+    pub fn cell_at(&self, tick: u32) -> Option<CellPos> {
+        let index = tick.checked_sub(self.start_tick)?;
+        self.cells.get(index as usize).copied()
+    }
+}
+
+/// Finds the earliest arrival at `goal` when leaving `start` on `start_tick`,
+/// moving or waiting one tick at a time around the walls of `grid` and the cells
+/// `schedule` blocks. Gives up on plans arriving later than `max_tick`.
+pub fn find_timed_path(
+    grid: &Grid,
+    schedule: &OccupancySchedule,
+    start: CellPos,
+    goal: CellPos,
+    start_tick: u32,
+    max_tick: u32,
+) -> Option<TimedPath> {
+    if !grid.contains_pos(start) || !grid.contains_pos(goal) || schedule.is_blocked(start, start_tick) {
+        return None;
+    }
+
+    let connectivity = grid.connectivity();
+    let heuristic = |cell_pos: CellPos| connectivity.steps_between(cell_pos, goal);
+
+    // Ordered by estimated arrival, then by latest tick so deeper plans are tried first.
+    let mut open_set = BinaryHeap::from([Reverse((start_tick + heuristic(start), Reverse(start_tick), start.0, start.1))]);
+    let mut closed_set = HashSet::new();
+    let mut came_from: HashMap<(CellPos, u32), CellPos> = HashMap::new();
+
+    while let Some(Reverse((_, Reverse(tick), x, y))) = open_set.pop() {
+        let cell_pos = CellPos(x, y);
+
+        if cell_pos == goal {
+            let mut cells = vec![goal];
+            let mut state = (goal, tick);
+            while let Some(&previous) = came_from.get(&state) {
+                cells.push(previous);
+                state = (previous, state.1 - 1);
+            }
+            cells.reverse();
+            return Some(TimedPath { start_tick, cells });
+        }
+
+        if !closed_set.insert((cell_pos, tick)) || tick >= max_tick {
+            continue;
+        }
+
+        let next_tick = tick + 1;
+        let moves = grid
+            .neighbors(cell_pos, connectivity)
+            .map(|(neighbor, _)| neighbor)
+            .chain([cell_pos]);
+
+        for next in moves {
+            if schedule.is_blocked(next, next_tick) || closed_set.contains(&(next, next_tick)) {
+                continue;
+            }
+
+            if let Entry::Vacant(entry) = came_from.entry((next, next_tick)) {
+                entry.insert(cell_pos);
+                open_set.push(Reverse((next_tick + heuristic(next), Reverse(next_tick), next.0, next.1)));
+            }
+        }
+    }
+
+    None
+}
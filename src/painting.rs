@@ -0,0 +1,957 @@
+//! Painting walls with the mouse.
+//!
+//! With [`WallPaintingPlugin`], holding the left mouse button over a grid
+//! editor paints walls and holding the right one erases them, through the main
+//! 2D camera. The cells are set with `SetCellEvent`s before the grid systems
+//! run, so searches, paths and views follow the brush the same frame. Fast
+//! strokes are filled in cell by cell rather than leaving gaps.
+//!
+//! The [`WallBrush`] resource sets the size and shape of the brush, and the
+//! cells it would paint are highlighted under the cursor. Left and right
+//! bracket shrink and grow it, and backslash switches between a square and a
+//! circle. Its [`PaintTool`] can also draw straight lines and rectangles from
+//! where a drag starts to where it is released, shown as a ghost while
+//! dragging, and flood fill the region around a clicked cell. It can select a
+//! rectangle too, or with the magic wand the region a fill would paint, as the
+//! grid's `CellSelection`; Escape drops it. Ctrl+C copies the selection to the
+//! brush's clipboard, and the stamp puts the clipboard down wherever it is
+//! clicked after R turns or M mirrors it; Ctrl+V picks the stamp. P picks it
+//! too, with the next shape of the `PatternLibrary` on the clipboard. Keys 1 to
+//! 8 pick the brush, line, rectangle, filled rectangle, fill, selection, wand
+//! and stamp. Shift with 1 to 8 picks one of the `EditorTools` instead, in the
+//! order they're listed, which the left button then uses on every cell the
+//! brush enters.
+//! With its `terrain` set, every tool but the stamp paints that terrain instead
+//! of walls, and the right button clears it back to plain. With a [`Symmetry`]
+//! set, which Y cycles through, the painting tools also paint the mirror images
+//! of their cells across the center lines of the grid, left to right, top to
+//! bottom or both, as symmetric competitive maps are built.
+//!
+//! Ctrl+Backspace clears every wall of the hovered grid editor, Ctrl+F fills it
+//! with walls and Ctrl+I swaps its walls and floor, as one `BulkEdit` each.
+//! Ctrl+T sets its terrain to the brush's, Ctrl+R lays out its walls at random,
+//! to the brush's `wall_density`, and Ctrl+M lays a maze over it, with the
+//! brush's `maze_algorithm` and corridors its `corridor_width` wide. While the
+//! grid has a selection, all of them only change the selected cells.
+//!
+//! Those are the default controls; the keys and buttons used are taken from the
+//! `InputBindings` resource.
+//!
+//! Grid editors with an [`UndoStack`] record everything painted while a mouse
+//! button is held as one action, so a whole stroke is undone at once.
+//! Presses that grab a start or goal marker of the `MarkerPlacementPlugin`
+//! drag the marker instead of painting.
+
+use std::{
+    collections::{HashSet, VecDeque},
+    error::Error,
+    fmt::Display,
+};
+
+use bevy::prelude::*;
+
+use crate::{
+    bindings::InputBindings,
+    console::ErrorConsole,
+    editor::{
+        selection_of, BulkEdit, CellSelection, EditorTools, RandomizeWalls, SetCellEvent, SetTerrain, UseToolEvent,
+    },
+    grid::{Cell, CellPos, Connectivity, Grid, GridEditor, Grids},
+    markers::MarkerDrag,
+    maze::{GenerateMaze, MazeAlgorithm},
+    minimap::MainCameraFilter,
+    pattern::{CellPattern, PatternLibrary},
+    terrain::Terrain,
+    undo::UndoStack,
+    view::{cell_pos_at, cursor_world_position, mix, upload_cell_textures, CellColors},
+    GridEditSet,
+};
+
+/// The outline of the cells a [`WallBrush`] paints around the cursor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BrushShape {
+    #[default]
+    Square,
+    /// The cells whose centers are within about `radius` of the cursor cell.
+    Circle,
+}
+
+/// The lines through the center of a grid a [`WallBrush`] mirrors what it
+/// paints across.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Symmetry {
+    #[default]
+    Off,
+    /// Mirrors left to right, across the vertical center line.
+    LeftRight,
+    /// Mirrors top to bottom, across the horizontal center line.
+    TopBottom,
+    /// Mirrors across both center lines, painting each cell four times.
+    FourFold,
+}
+
+impl Symmetry {
+    pub const ALL: [Symmetry; 4] = [Symmetry::Off, Symmetry::LeftRight, Symmetry::TopBottom, Symmetry::FourFold];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Symmetry::Off => "Off",
+            Symmetry::LeftRight => "Left-right",
+            Symmetry::TopBottom => "Top-bottom",
+            Symmetry::FourFold => "Four-fold",
+        }
+    }
+
+    /// `cell_pos` and its mirror images on `grid`, each once.
+    pub fn images(self, grid: &Grid, cell_pos: CellPos) -> Vec<CellPos> {
+        let CellPos(x, y) = cell_pos;
+        let (mirror_x, mirror_y) = (grid.width() as i32 - 1 - x, grid.height() as i32 - 1 - y);
+        let mut images = match self {
+            Symmetry::Off => vec![cell_pos],
+            Symmetry::LeftRight => vec![cell_pos, CellPos(mirror_x, y)],
+            Symmetry::TopBottom => vec![cell_pos, CellPos(x, mirror_y)],
+            Symmetry::FourFold => {
+                vec![cell_pos, CellPos(mirror_x, y), CellPos(x, mirror_y), CellPos(mirror_x, mirror_y)]
+            }
+        };
+        // Cells on a center line are their own image.
+        images.sort_by_key(|&CellPos(x, y)| (x, y));
+        images.dedup();
+        images
+    }
+
+    /// `cells` and their mirror images on `grid`, each once.
+    pub fn mirror(self, grid: &Grid, cells: impl IntoIterator<Item = CellPos>) -> Vec<CellPos> {
+        if self == Symmetry::Off {
+            return cells.into_iter().collect();
+        }
+
+        let mut seen = HashSet::new();
+        cells
+            .into_iter()
+            .flat_map(|cell_pos| self.images(grid, cell_pos))
+            .filter(|&cell_pos| seen.insert(cell_pos))
+            .collect()
+    }
+}
+
+/// What dragging over a grid editor draws.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PaintTool {
+    /// Paints wherever the cursor goes while the button is held.
+    #[default]
+    Brush,
+    /// A straight line from the cell the drag started on to the one it ends on.
+    Line,
+    /// A rectangle with its corners where the drag starts and ends, either
+    /// `filled` or just its outline.
+    Rectangle { filled: bool },
+    /// Every cell connected to the clicked one with the same wall and terrain,
+    /// up to `WallBrush::max_fill` cells.
+    Fill,
+    /// Selects the rectangle dragged out as the grid's `CellSelection`, for
+    /// Ctrl+C to copy to the clipboard and the bulk edits to keep to.
+    Select,
+    /// Selects the cells the fill would paint from the clicked one.
+    Wand,
+    /// Stamps `WallBrush::clipboard` centered on the clicked cell.
+    Stamp,
+    /// Uses the selected tool of the `EditorTools` on each cell the brush
+    /// enters. Picked with the editor tool keys, so it isn't in `ALL`.
+    Editor,
+}
+
+impl PaintTool {
+    pub const ALL: [PaintTool; 8] = [
+        PaintTool::Brush,
+        PaintTool::Line,
+        PaintTool::Rectangle { filled: false },
+        PaintTool::Rectangle { filled: true },
+        PaintTool::Fill,
+        PaintTool::Select,
+        PaintTool::Wand,
+        PaintTool::Stamp,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PaintTool::Brush => "Brush",
+            PaintTool::Line => "Line",
+            PaintTool::Rectangle { filled: false } => "Rectangle",
+            PaintTool::Rectangle { filled: true } => "Filled rectangle",
+            PaintTool::Fill => "Fill",
+            PaintTool::Select => "Select",
+            PaintTool::Wand => "Magic wand",
+            PaintTool::Stamp => "Stamp",
+            PaintTool::Editor => "Editor tool",
+        }
+    }
+
+    /// The cells a drag from `from` to `to` draws, before the brush widens them.
+    /// The brush only ever draws `to`, the fill and the wand start from it and
+    /// the stamp is centered on it.
+    pub fn cells(self, from: CellPos, to: CellPos) -> Vec<CellPos> {
+        let (CellPos(x0, y0), CellPos(x1, y1)) = (from, to);
+        let (min_x, max_x, min_y, max_y) = (x0.min(x1), x0.max(x1), y0.min(y1), y0.max(y1));
+
+        match self {
+            PaintTool::Brush | PaintTool::Fill | PaintTool::Wand | PaintTool::Stamp | PaintTool::Editor => vec![to],
+            PaintTool::Line => line_cells(from, to),
+            PaintTool::Rectangle { filled: true } | PaintTool::Select => {
+                (min_y..=max_y).flat_map(|y| (min_x..=max_x).map(move |x| CellPos(x, y))).collect()
+            }
+            PaintTool::Rectangle { filled: false } => (min_y..=max_y)
+                .flat_map(|y| (min_x..=max_x).map(move |x| CellPos(x, y)))
+                .filter(|&CellPos(x, y)| x == min_x || x == max_x || y == min_y || y == max_y)
+                .collect(),
+        }
+    }
+}
+
+/// Why a fill was not applied.
+#[derive(Debug)]
+pub struct FillTooLarge {
+    pub start: CellPos,
+    pub max_cells: usize,
+}
+
+impl Display for FillTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (CellPos(x, y), max_cells) = (self.start, self.max_cells);
+        write!(f, "the region around ({x}, {y}) has more than {max_cells} cells to fill")
+    }
+}
+impl Error for FillTooLarge {}
+
+/// The cells connected to `start` that have the same wall and terrain, `start`
+/// included, through orthogonal steps or hex neighbors. Diagonals are left out
+/// so a fill doesn't leak through the corners of a wall. Fails once the region
+/// grows past `max_cells`.
+pub fn fill_region(grid: &Grid, start: CellPos, max_cells: usize) -> Result<Vec<CellPos>, FillTooLarge> {
+    let key = |cell_pos| grid.cell(cell_pos).ok().map(|cell| (cell, Terrain::at(grid, cell_pos)));
+    let Some(start_key) = key(start) else {
+        return Ok(Vec::new());
+    };
+    let connectivity = match grid.connectivity() {
+        Connectivity::Hex => Connectivity::Hex,
+        _ => Connectivity::Four,
+    };
+
+    let mut region = vec![start];
+    let mut reached = HashSet::from([start]);
+    let mut queue = VecDeque::from([start]);
+
+    while let Some(cell_pos) = queue.pop_front() {
+        for &(dx, dy) in connectivity.offsets(cell_pos) {
+            let neighbor = CellPos(cell_pos.0 + dx, cell_pos.1 + dy);
+            if !grid.in_bounds(neighbor) || key(neighbor) != Some(start_key) || !reached.insert(neighbor) {
+                continue;
+            }
+            if region.len() == max_cells {
+                return Err(FillTooLarge { start, max_cells });
+            }
+
+            region.push(neighbor);
+            queue.push_back(neighbor);
+        }
+    }
+    Ok(region)
+}
+
+/// The cells of the Bresenham line from `from` to `to`, both included.
+fn line_cells(from: CellPos, to: CellPos) -> Vec<CellPos> {
+    let (CellPos(mut x, mut y), CellPos(x1, y1)) = (from, to);
+    let (dx, dy) = ((x1 - x).abs(), -(y1 - y).abs());
+    let (step_x, step_y) = ((x1 - x).signum(), (y1 - y).signum());
+    let mut error = dx + dy;
+    let mut cells = vec![from];
+
+    while (x, y) != (x1, y1) {
+        if 2 * error >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if 2 * error <= dx {
+            error += dx;
+            y += step_y;
+        }
+        cells.push(CellPos(x, y));
+    }
+    cells
+}
+
+/// How much of the grid a [`WallPaintingPlugin`] stroke paints.
+#[derive(Resource, Debug, Clone)]
+pub struct WallBrush {
+    /// Cells painted on each side of the cursor cell; `0` paints just that cell.
+    pub radius: u32,
+    pub shape: BrushShape,
+    pub tool: PaintTool,
+    /// Terrain every tool but the stamp paints with the left button, and clears
+    /// back to plain with the right; `None` paints walls and floor instead.
+    pub terrain: Option<Terrain>,
+    /// Most cells a single fill changes; larger regions are left alone and
+    /// reported to the `ErrorConsole`.
+    pub max_fill: usize,
+    /// Color the cells under the brush, or the ghost of a line or rectangle,
+    /// are highlighted with.
+    pub preview_color: Color,
+    /// Cells copied from a selection, stamped by the stamp tool.
+    pub clipboard: Option<CellPattern>,
+    /// Share of the cells Ctrl+R turns into walls.
+    pub wall_density: f64,
+    /// Width in cells of the corridors of the mazes Ctrl+M generates.
+    pub corridor_width: u32,
+    /// How the mazes Ctrl+M generates are laid out.
+    pub maze_algorithm: MazeAlgorithm,
+    /// Lines every tool but the selections and the stamp mirrors across.
+    pub symmetry: Symmetry,
+    /// Selection made this frame, set on its grid before the grids are edited.
+    new_selection: Option<CellSelection>,
+    /// The grid editor and cell under the cursor, as of the last frame.
+    hovered: Option<(Entity, CellPos)>,
+    /// The cells highlighted on the grid editor, as of the last frame.
+    preview: Option<(Entity, Vec<CellPos>)>,
+    /// Terrain painted this frame, set on the grids before they are edited.
+    terrain_edits: Vec<(Entity, CellPos, Terrain)>,
+}
+
+impl Default for WallBrush {
+    fn default() -> Self {
+        WallBrush {
+            radius: 0,
+            shape: BrushShape::Square,
+            tool: PaintTool::Brush,
+            terrain: None,
+            max_fill: 65_536,
+            preview_color: Color::WHITE,
+            clipboard: None,
+            wall_density: 0.3,
+            corridor_width: 1,
+            maze_algorithm: MazeAlgorithm::RecursiveBacktracker,
+            symmetry: Symmetry::Off,
+            new_selection: None,
+            hovered: None,
+            preview: None,
+            terrain_edits: Vec::new(),
+        }
+    }
+}
+
+impl WallBrush {
+    /// Largest radius the bracket keys grow the brush to.
+    pub const MAX_RADIUS: u32 = 32;
+
+    pub fn with_radius(mut self, radius: u32) -> Self {
+        self.radius = radius;
+        self
+    }
+
+    pub fn with_shape(mut self, shape: BrushShape) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    pub fn with_tool(mut self, tool: PaintTool) -> Self {
+        self.tool = tool;
+        self
+    }
+
+    pub fn with_terrain(mut self, terrain: Option<Terrain>) -> Self {
+        self.terrain = terrain;
+        self
+    }
+
+    pub fn with_max_fill(mut self, max_fill: usize) -> Self {
+        self.max_fill = max_fill;
+        self
+    }
+
+    pub fn with_clipboard(mut self, clipboard: CellPattern) -> Self {
+        self.clipboard = Some(clipboard);
+        self
+    }
+
+    pub fn with_wall_density(mut self, wall_density: f64) -> Self {
+        self.wall_density = wall_density;
+        self
+    }
+
+    pub fn with_corridor_width(mut self, corridor_width: u32) -> Self {
+        self.corridor_width = corridor_width;
+        self
+    }
+
+    pub fn with_maze_algorithm(mut self, maze_algorithm: MazeAlgorithm) -> Self {
+        self.maze_algorithm = maze_algorithm;
+        self
+    }
+
+    pub fn with_symmetry(mut self, symmetry: Symmetry) -> Self {
+        self.symmetry = symmetry;
+        self
+    }
+
+    /// The grid editor and cell under the cursor, if any.
+    pub fn hovered(&self) -> Option<(Entity, CellPos)> {
+        self.hovered
+    }
+
+    /// The cells of `grid` the brush covers when centered on `center`.
+    pub fn cells(&self, grid: &Grid, center: CellPos) -> Vec<CellPos> {
+        let radius = self.radius as i32;
+        let CellPos(x, y) = center;
+
+        (-radius..=radius)
+            .flat_map(|dy| (-radius..=radius).map(move |dx| (dx, dy)))
+            .filter(|&(dx, dy)| match self.shape {
+                BrushShape::Square => true,
+                // `r * (r + 1)` rather than `r * r` rounds the edge out, so small circles aren't diamonds.
+                BrushShape::Circle => dx * dx + dy * dy <= radius * (radius + 1),
+            })
+            .map(|(dx, dy)| CellPos(x + dx, y + dy))
+            .filter(|&cell_pos| grid.in_bounds(cell_pos))
+            .collect()
+    }
+
+    /// The cells of `grid` a drag from `from` to `to` draws with the current
+    /// tool, widened by the brush unless it selects, each once.
+    pub fn stroke_cells(&self, grid: &Grid, from: CellPos, to: CellPos) -> Vec<CellPos> {
+        if self.tool == PaintTool::Select {
+            return self.tool.cells(from, to).into_iter().filter(|&cell_pos| grid.in_bounds(cell_pos)).collect();
+        }
+
+        let mut seen = HashSet::new();
+        self.tool
+            .cells(from, to)
+            .into_iter()
+            .flat_map(|center| self.cells(grid, center))
+            .filter(|&cell_pos| seen.insert(cell_pos))
+            .collect()
+    }
+
+    /// The cells of `grid` the clipboard sets when stamped centered on `center`,
+    /// with the cell each is set to.
+    pub fn stamp_cells(&self, grid: &Grid, center: CellPos) -> Vec<(CellPos, Cell)> {
+        let Some(clipboard) = &self.clipboard else {
+            return Vec::new();
+        };
+
+        let origin = CellPos(center.0 - clipboard.width() as i32 / 2, center.1 - clipboard.height() as i32 / 2);
+        clipboard.cells_at(origin).filter(|&(cell_pos, _)| grid.in_bounds(cell_pos)).collect()
+    }
+}
+
+impl WallBrush {
+    /// What the left button, or the right one when `erase`, paints with.
+    fn ink(&self, erase: bool) -> Ink {
+        match (self.terrain, erase) {
+            (None, false) => Ink::Cell(Cell::wall()),
+            (None, true) => Ink::Cell(Cell::floor()),
+            (Some(terrain), false) => Ink::Terrain(terrain),
+            (Some(_), true) => Ink::Terrain(Terrain::PLAIN),
+        }
+    }
+
+    /// Paints `cells` of `grid`, held by `grid_entity`, with `ink`, along with
+    /// their mirror images under the brush's symmetry.
+    fn paint(
+        &mut self,
+        grid_entity: Entity,
+        grid: &Grid,
+        cells: impl IntoIterator<Item = CellPos>,
+        ink: Ink,
+        edits: &mut EventWriter<SetCellEvent>,
+    ) {
+        let mut cells = self.symmetry.mirror(grid, cells);
+        cells.retain(|&cell_pos| ink.changes(grid, cell_pos));
+
+        match ink {
+            Ink::Cell(cell) => {
+                for cell_pos in cells {
+                    edits.send(SetCellEvent::new(grid_entity, cell_pos, cell));
+                }
+            }
+            Ink::Terrain(terrain) => {
+                self.terrain_edits.extend(cells.into_iter().map(|cell_pos| (grid_entity, cell_pos, terrain)));
+            }
+        }
+    }
+}
+
+/// What a stroke puts down: walls or floor, or terrain under the cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Ink {
+    Cell(Cell),
+    Terrain(Terrain),
+}
+
+impl Ink {
+    /// Whether painting `cell_pos` changes `grid`; cells beyond it never do.
+    fn changes(self, grid: &Grid, cell_pos: CellPos) -> bool {
+        match self {
+            Ink::Cell(cell) => grid.cell(cell_pos).is_ok_and(|current| current != cell),
+            Ink::Terrain(terrain) => grid.in_bounds(cell_pos) && Terrain::at(grid, cell_pos) != terrain,
+        }
+    }
+}
+
+/// Paints walls with the left mouse button and floor with the right, with the
+/// [`WallBrush`] resource.
+pub struct WallPaintingPlugin;
+
+impl Plugin for WallPaintingPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<WallBrush>()
+            .init_resource::<PatternLibrary>()
+            .init_resource::<InputBindings>()
+            .add_system(brush_keys)
+            .add_system(clipboard_keys)
+            .add_system(bulk_edit_keys.before(GridEditSet))
+            .add_system(paint_walls.after(brush_keys).before(GridEditSet))
+            .add_system(fill_regions.after(paint_walls).before(GridEditSet))
+            .add_system(paint_terrain.after(paint_walls).after(fill_regions).before(GridEditSet))
+            .add_system(apply_selection.after(paint_walls).after(fill_regions).before(GridEditSet))
+            .add_system(group_strokes.before(GridEditSet))
+            // After every overlay, wherever it was scheduled in the update.
+            .add_system_to_stage(CoreStage::PostUpdate, preview_brush.before(upload_cell_textures));
+    }
+}
+
+fn brush_keys(
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<InputBindings>,
+    mut brush: ResMut<WallBrush>,
+    tools: Option<ResMut<EditorTools>>,
+) {
+    for (binding, tool) in bindings.paint_tools.iter().zip(PaintTool::ALL) {
+        if binding.just_pressed(&keys) {
+            brush.tool = tool;
+        }
+    }
+    if let Some(mut tools) = tools {
+        let picked = bindings.editor_tools.iter().zip(tools.names()).find(|(binding, _)| binding.just_pressed(&keys));
+        if let Some((_, name)) = picked {
+            let name = name.to_string();
+            tools.select(&name);
+            brush.tool = PaintTool::Editor;
+            info!("tool: {name}");
+        }
+    }
+    if bindings.shrink_brush.just_pressed(&keys) {
+        brush.radius = brush.radius.saturating_sub(1);
+    }
+    if bindings.grow_brush.just_pressed(&keys) {
+        brush.radius = (brush.radius + 1).min(WallBrush::MAX_RADIUS);
+    }
+    if bindings.switch_brush_shape.just_pressed(&keys) {
+        brush.shape = match brush.shape {
+            BrushShape::Square => BrushShape::Circle,
+            BrushShape::Circle => BrushShape::Square,
+        };
+    }
+    if bindings.cycle_symmetry.just_pressed(&keys) {
+        let index = Symmetry::ALL.iter().position(|&symmetry| symmetry == brush.symmetry).unwrap_or(0);
+        brush.symmetry = Symmetry::ALL[(index + 1) % Symmetry::ALL.len()];
+        info!("symmetry: {}", brush.symmetry.name());
+    }
+}
+
+/// Copies the selection of the hovered grid, or of any grid, with Ctrl+C and
+/// picks the stamp with Ctrl+V; P picks the stamp with the next pattern of the
+/// library instead. R turns and M mirrors the clipboard while stamping. Escape
+/// drops every selection.
+#[allow(clippy::too_many_arguments)]
+fn clipboard_keys(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<InputBindings>,
+    grids: Grids,
+    selections: Query<(&CellSelection, Entity)>,
+    library: Res<PatternLibrary>,
+    mut brush: ResMut<WallBrush>,
+    mut next_pattern: Local<usize>,
+) {
+    if bindings.copy_selection.just_pressed(&keys) {
+        let all = || selections.iter().map(|(selection, _)| selection);
+        let hovered = brush.hovered.and_then(|(grid_entity, _)| selection_of(all(), grid_entity));
+        // A wand's selection is copied with the rest of its bounding rectangle.
+        if let Some(selection) = hovered.or_else(|| all().next()) {
+            if let (Some((from, to)), Ok(grid)) = (selection.bounds(), grids.get(selection.grid)) {
+                brush.clipboard = Some(CellPattern::copy(grid, from, to));
+            }
+        }
+    }
+    if bindings.clear_selection.just_pressed(&keys) {
+        for (_, entity) in &selections {
+            commands.entity(entity).despawn();
+        }
+    }
+    if bindings.pick_stamp.just_pressed(&keys) && brush.clipboard.is_some() {
+        brush.tool = PaintTool::Stamp;
+    }
+
+    if bindings.next_pattern.just_pressed(&keys) && !library.is_empty() {
+        *next_pattern %= library.len();
+        if let Some((name, pattern)) = library.nth(*next_pattern) {
+            info!("stamping {name}");
+            brush.clipboard = Some(pattern.clone());
+            brush.tool = PaintTool::Stamp;
+        }
+        *next_pattern += 1;
+    }
+
+    if brush.tool != PaintTool::Stamp {
+        return;
+    }
+    if bindings.rotate_stamp.just_pressed(&keys) {
+        brush.clipboard = brush.clipboard.as_ref().map(CellPattern::rotated);
+    }
+    if bindings.mirror_stamp.just_pressed(&keys) {
+        brush.clipboard = brush.clipboard.as_ref().map(CellPattern::mirrored);
+    }
+}
+
+/// Edits the whole hovered grid, or its selection, with Ctrl+Backspace, Ctrl+F
+/// and Ctrl+I, sets its terrain to the brush's with Ctrl+T, randomizes its
+/// walls with Ctrl+R and lays a maze over it with Ctrl+M.
+fn bulk_edit_keys(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<InputBindings>,
+    brush: Res<WallBrush>,
+) {
+    let Some((grid_entity, _)) = brush.hovered else {
+        return;
+    };
+
+    let edit_bindings = [&bindings.clear_walls, &bindings.fill_walls, &bindings.invert_walls];
+    for (binding, edit) in edit_bindings.into_iter().zip(BulkEdit::ALL) {
+        if binding.just_pressed(&keys) {
+            commands.entity(grid_entity).insert(edit);
+        }
+    }
+    if bindings.set_terrain.just_pressed(&keys) {
+        commands.entity(grid_entity).insert(SetTerrain(brush.terrain.unwrap_or(Terrain::PLAIN)));
+    }
+    if bindings.randomize_walls.just_pressed(&keys) {
+        commands.entity(grid_entity).insert(RandomizeWalls::new(brush.wall_density));
+    }
+    if bindings.generate_maze.just_pressed(&keys) {
+        let maze = GenerateMaze::new(brush.corridor_width).with_algorithm(brush.maze_algorithm);
+        commands.entity(grid_entity).insert(maze);
+    }
+}
+
+/// Keeps each stroke one undoable action, from the press of a mouse button to
+/// the release of the last one held.
+fn group_strokes(buttons: Res<Input<MouseButton>>, bindings: Res<InputBindings>, mut stacks: Query<&mut UndoStack>) {
+    let buttons_used = [bindings.paint, bindings.erase];
+    if buttons_used.into_iter().any(|button| buttons.just_pressed(button)) {
+        for mut stack in &mut stacks {
+            stack.begin_action();
+        }
+    } else if buttons_used.into_iter().any(|button| buttons.just_released(button)) && !buttons.any_pressed(buttons_used) {
+        for mut stack in &mut stacks {
+            stack.end_action();
+        }
+    }
+}
+
+/// A line or rectangle being dragged out.
+#[derive(Debug, Clone, Copy)]
+struct Drag {
+    grid: Entity,
+    from: CellPos,
+    to: CellPos,
+    ink: Ink,
+}
+
+/// What the mouse was doing last frame.
+#[derive(Default)]
+struct Stroke {
+    /// The grid editor and position the brush was over while painting.
+    last_position: Option<(Entity, Vec2)>,
+    drag: Option<Drag>,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn paint_walls(
+    windows: Res<Windows>,
+    buttons: Res<Input<MouseButton>>,
+    bindings: Res<InputBindings>,
+    grids: Grids,
+    cameras: Query<(&GlobalTransform, &OrthographicProjection), MainCameraFilter>,
+    editors: Query<(Entity, &GlobalTransform), With<GridEditor>>,
+    interactions: Query<&Interaction>,
+    mut brush: ResMut<WallBrush>,
+    mut stroke: Local<Stroke>,
+    mut edits: EventWriter<SetCellEvent>,
+    mut tool_events: EventWriter<UseToolEvent>,
+    marker_drag: Option<Res<MarkerDrag>>,
+) {
+    let world = windows.get_primary().and_then(|window| {
+        let (camera_transform, projection) = cameras.iter().next()?;
+        cursor_world_position(window, camera_transform, projection)
+    });
+    // Buttons under the cursor take the clicks, and hide the preview.
+    let over_ui = interactions.iter().any(|interaction| *interaction != Interaction::None);
+    let hovered = world.filter(|_| !over_ui).and_then(|world| {
+        editors.iter().find_map(|(grid_entity, grid_transform)| {
+            let grid = grids.get(grid_entity).ok()?;
+            let position = world - grid_transform.translation().truncate();
+            let cell_pos = cell_pos_at(grid, position)?;
+            Some((grid_entity, grid, position, cell_pos))
+        })
+    });
+
+    let hovered_cell = hovered.map(|(grid_entity, _, _, cell_pos)| (grid_entity, cell_pos));
+    if brush.hovered != hovered_cell {
+        brush.hovered = hovered_cell;
+    }
+    // A dragged start or goal marker takes the button it was grabbed with.
+    let dragging_marker = marker_drag.is_some_and(|drag| drag.dragged().is_some());
+    let ink = match (buttons.pressed(bindings.paint) && !dragging_marker, buttons.pressed(bindings.erase)) {
+        (true, false) => Some(brush.ink(false)),
+        (false, true) => Some(brush.ink(true)),
+        _ => None,
+    };
+
+    let preview = match brush.tool {
+        PaintTool::Fill | PaintTool::Wand => {
+            (stroke.last_position, stroke.drag) = (None, None);
+            hovered_cell.map(|(grid_entity, cell_pos)| (grid_entity, vec![cell_pos]))
+        }
+        PaintTool::Stamp => {
+            (stroke.last_position, stroke.drag) = (None, None);
+            let stamp = hovered.map(|(grid_entity, grid, _, cell_pos)| (grid_entity, grid, brush.stamp_cells(grid, cell_pos)));
+            let stamped = buttons.just_pressed(bindings.paint) && !dragging_marker;
+            if let (true, Some((grid_entity, grid, cells))) = (stamped, &stamp) {
+                for &(cell_pos, cell) in cells {
+                    if grid.cell(cell_pos).is_ok_and(|current| current != cell) {
+                        edits.send(SetCellEvent::new(*grid_entity, cell_pos, cell));
+                    }
+                }
+            }
+            stamp.map(|(grid_entity, _, cells)| (grid_entity, cells.into_iter().map(|(cell_pos, _)| cell_pos).collect()))
+        }
+        PaintTool::Brush => {
+            stroke.drag = None;
+            if let (Some(ink), Some((grid_entity, grid, position, _))) = (ink, hovered) {
+                let cells = stroke_path(&brush, grid_entity, grid, position, ink, &mut stroke.last_position);
+                brush.paint(grid_entity, grid, cells, ink, &mut edits);
+            } else {
+                stroke.last_position = None;
+            }
+            hovered.map(|(grid_entity, grid, _, cell_pos)| (grid_entity, brush.cells(grid, cell_pos)))
+        }
+        PaintTool::Editor => {
+            stroke.drag = None;
+            let used = buttons.pressed(bindings.paint) && !dragging_marker;
+            if let (true, Some((grid_entity, grid, position, _))) = (used, hovered) {
+                let from = match stroke.last_position {
+                    Some((entity, from)) if entity == grid_entity => Some(from),
+                    _ => None,
+                };
+                // Only the cells the brush wasn't over last frame, so tools that
+                // toggle don't flicker while the cursor rests.
+                let before: HashSet<CellPos> = match from.and_then(|from| cell_pos_at(grid, from)) {
+                    Some(center) => brush.cells(grid, center).into_iter().collect(),
+                    None => HashSet::new(),
+                };
+                let entered: Vec<CellPos> = swept_cells(&brush, grid, from.unwrap_or(position), position)
+                    .into_iter()
+                    .filter(|cell_pos| !before.contains(cell_pos))
+                    .collect();
+                for cell_pos in brush.symmetry.mirror(grid, entered) {
+                    tool_events.send(UseToolEvent::new(grid_entity, cell_pos));
+                }
+                stroke.last_position = Some((grid_entity, position));
+            } else {
+                stroke.last_position = None;
+            }
+            hovered.map(|(grid_entity, grid, _, cell_pos)| (grid_entity, brush.cells(grid, cell_pos)))
+        }
+        PaintTool::Line | PaintTool::Rectangle { .. } | PaintTool::Select => {
+            stroke.last_position = None;
+            match (stroke.drag, ink, hovered) {
+                (None, Some(ink), Some((grid_entity, _, _, cell_pos))) => {
+                    stroke.drag = Some(Drag { grid: grid_entity, from: cell_pos, to: cell_pos, ink });
+                }
+                (Some(drag), Some(_), Some((grid_entity, _, _, cell_pos))) if drag.grid == grid_entity => {
+                    stroke.drag = Some(Drag { to: cell_pos, ..drag });
+                }
+                // Released, wherever the cursor is: draw up to the last cell it was over.
+                (Some(drag), None, _) if brush.tool == PaintTool::Select => {
+                    stroke.drag = None;
+                    if let Ok(grid) = grids.get(drag.grid) {
+                        let cells = brush.stroke_cells(grid, drag.from, drag.to);
+                        brush.new_selection = Some(CellSelection::new(drag.grid, cells));
+                    }
+                }
+                (Some(drag), None, _) => {
+                    stroke.drag = None;
+                    if let Ok(grid) = grids.get(drag.grid) {
+                        let mut cells = brush.stroke_cells(grid, drag.from, drag.to);
+                        cells.retain(|&cell_pos| drag.ink.changes(grid, cell_pos));
+                        brush.paint(drag.grid, grid, cells, drag.ink, &mut edits);
+                    }
+                }
+                _ => {}
+            }
+
+            match (stroke.drag, hovered) {
+                (Some(drag), _) => grids.get(drag.grid).ok().map(|grid| (drag.grid, brush.stroke_cells(grid, drag.from, drag.to))),
+                (None, Some((grid_entity, grid, _, cell_pos))) => Some((grid_entity, brush.cells(grid, cell_pos))),
+                (None, None) => None,
+            }
+        }
+    };
+
+    let preview = match brush.tool {
+        PaintTool::Select | PaintTool::Wand | PaintTool::Stamp => preview,
+        _ => preview.map(|(grid_entity, cells)| match grids.get(grid_entity) {
+            Ok(grid) => (grid_entity, brush.symmetry.mirror(grid, cells)),
+            Err(_) => (grid_entity, cells),
+        }),
+    };
+    if brush.preview != preview {
+        brush.preview = preview;
+    }
+}
+
+/// Fills the region around the hovered cell when the fill tool is clicked, or
+/// selects it with the wand.
+fn fill_regions(
+    buttons: Res<Input<MouseButton>>,
+    bindings: Res<InputBindings>,
+    grids: Grids,
+    mut brush: ResMut<WallBrush>,
+    mut console: ResMut<ErrorConsole>,
+    mut edits: EventWriter<SetCellEvent>,
+    marker_drag: Option<Res<MarkerDrag>>,
+) {
+    let (tool @ (PaintTool::Fill | PaintTool::Wand), Some((grid_entity, start))) = (brush.tool, brush.hovered)
+    else {
+        return;
+    };
+    if marker_drag.is_some_and(|drag| drag.dragged().is_some()) {
+        return;
+    }
+    let ink = match (buttons.just_pressed(bindings.paint), buttons.just_pressed(bindings.erase)) {
+        (true, false) => brush.ink(false),
+        (false, true) => brush.ink(true),
+        _ => return,
+    };
+    let grid = match grids.get(grid_entity) {
+        Ok(grid) => grid,
+        Err(error) => {
+            console.report("fill_regions", &error);
+            return;
+        }
+    };
+
+    match fill_region(grid, start, brush.max_fill) {
+        Ok(region) if tool == PaintTool::Wand => brush.new_selection = Some(CellSelection::new(grid_entity, region)),
+        Ok(region) => brush.paint(grid_entity, grid, region, ink, &mut edits),
+        Err(error) => console.report("fill_regions", &error),
+    }
+}
+
+/// Sets the selection made this frame on its grid, replacing the one it had.
+fn apply_selection(
+    mut commands: Commands,
+    mut brush: ResMut<WallBrush>,
+    mut selections: Query<&mut CellSelection>,
+) {
+    if brush.new_selection.is_none() {
+        return;
+    }
+    let Some(selection) = brush.new_selection.take() else {
+        return;
+    };
+
+    match selections.iter_mut().find(|current| current.grid == selection.grid) {
+        Some(mut current) => *current = selection,
+        None => {
+            commands.spawn((Name::new("Selection"), selection));
+        }
+    }
+}
+
+/// Sets the terrain painted this frame. Terrain has no edit events, so it is
+/// set on the assets and the grids' systems rebuild what they derive from it.
+fn paint_terrain(mut brush: ResMut<WallBrush>, editors: Query<&GridEditor>, mut assets: ResMut<Assets<Grid>>) {
+    if brush.terrain_edits.is_empty() {
+        return;
+    }
+
+    for (grid_entity, cell_pos, terrain) in brush.terrain_edits.drain(..) {
+        let Some(grid) = editors.get(grid_entity).ok().and_then(|editor| assets.get_mut(&editor.grid)) else {
+            continue;
+        };
+        if grid.layer::<Terrain>().is_none() {
+            grid.add_layer(Terrain::PLAIN);
+        }
+        if let Some(layer) = grid.layer_mut::<Terrain>() {
+            let _ = layer.set(cell_pos, terrain);
+        }
+    }
+}
+
+/// The cells `ink` changes under the brush from where it was last frame to
+/// `position` on `grid`.
+fn stroke_path(
+    brush: &WallBrush,
+    grid_entity: Entity,
+    grid: &Grid,
+    position: Vec2,
+    ink: Ink,
+    last_position: &mut Option<(Entity, Vec2)>,
+) -> Vec<CellPos> {
+    let from = match *last_position {
+        Some((entity, from)) if entity == grid_entity => from,
+        _ => position,
+    };
+    let mut painted = swept_cells(brush, grid, from, position);
+    painted.retain(|&cell_pos| ink.changes(grid, cell_pos));
+
+    *last_position = Some((grid_entity, position));
+    painted
+}
+
+/// The cells the brush covers moving in a straight line from `from` to `to`,
+/// each once, in half-cell steps so no cell is skipped.
+fn swept_cells(brush: &WallBrush, grid: &Grid, from: Vec2, to: Vec2) -> Vec<CellPos> {
+    let steps = ((to - from).length() * 2.0).ceil() as usize;
+    let mut seen: HashSet<CellPos> = HashSet::new();
+    let mut swept = Vec::new();
+
+    for step in 0..=steps {
+        let t = if steps == 0 { 1.0 } else { step as f32 / steps as f32 };
+        let Some(center) = cell_pos_at(grid, from.lerp(to, t)) else {
+            continue;
+        };
+
+        for cell_pos in brush.cells(grid, center) {
+            if seen.insert(cell_pos) {
+                swept.push(cell_pos);
+            }
+        }
+    }
+    swept
+}
+
+/// Highlights the cells the brush or the dragged shape would paint, over every
+/// other overlay.
+fn preview_brush(brush: Res<WallBrush>, mut cell_colors: Query<&mut CellColors>) {
+    let Some((grid_entity, cells)) = &brush.preview else {
+        return;
+    };
+    let Ok(mut colors) = cell_colors.get_mut(*grid_entity) else {
+        return;
+    };
+
+    colors.tint_cells(cells.iter().copied(), |_, base| Some(mix(base, brush.preview_color, 0.5)));
+}
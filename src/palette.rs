@@ -0,0 +1,237 @@
+//! A palette of terrain kinds to paint with.
+//!
+//! The [`TerrainPalette`] names a few kinds of terrain and gives each a color
+//! and a cost, which [`TerrainPalettePlugin`] copies into the `GridTheme` and
+//! the `TerrainCosts` whenever the palette changes. The plugin shows a swatch
+//! per kind in the bottom right corner, plus one for walls: clicking one sets
+//! what the `WallBrush` paints, and T (`InputBindings::cycle_terrain`) cycles
+//! through them. Paths asked for with
+//! `PathRequest`s then route around the expensive ground as it is painted.
+
+use bevy::prelude::*;
+
+use crate::{
+    bindings::InputBindings,
+    painting::WallBrush,
+    terrain::{Terrain, TerrainCosts},
+    theme::GridTheme,
+};
+
+/// A kind of terrain in the [`TerrainPalette`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TerrainKind {
+    pub name: &'static str,
+    pub terrain: Terrain,
+    pub color: Color,
+    /// Base cost of entering it, as set in the `TerrainCosts`.
+    pub cost: f32,
+}
+
+impl TerrainKind {
+    pub fn new(name: &'static str, terrain: Terrain, color: Color, cost: f32) -> Self {
+        TerrainKind { name, terrain, color, cost }
+    }
+}
+
+/// The kinds of terrain offered for painting, in the order they are listed.
+#[derive(Resource, Debug, Clone)]
+pub struct TerrainPalette {
+    pub kinds: Vec<TerrainKind>,
+}
+
+impl Default for TerrainPalette {
+    /// Road, grass, mud and water, from cheapest to dearest. Road costs as much
+    /// as plain ground, since cheaper steps would throw off A*'s heuristic.
+    fn default() -> Self {
+        TerrainPalette {
+            kinds: vec![
+                TerrainKind::new("road", Terrain(1), Color::rgb(0.55, 0.55, 0.5), 1.0),
+                TerrainKind::new("grass", Terrain(2), Color::rgb(0.3, 0.65, 0.25), 1.5),
+                TerrainKind::new("mud", Terrain(3), Color::rgb(0.45, 0.3, 0.15), 3.0),
+                TerrainKind::new("water", Terrain(4), Color::rgb(0.2, 0.5, 0.9), 6.0),
+            ],
+        }
+    }
+}
+
+impl TerrainPalette {
+    /// An empty palette, to add kinds to with [`with_kind`](Self::with_kind).
+    pub fn new() -> Self {
+        TerrainPalette { kinds: Vec::new() }
+    }
+
+    pub fn with_kind(mut self, kind: TerrainKind) -> Self {
+        self.kinds.push(kind);
+        self
+    }
+
+    pub fn kind(&self, terrain: Terrain) -> Option<&TerrainKind> {
+        self.kinds.iter().find(|kind| kind.terrain == terrain)
+    }
+}
+
+/// Applies the [`TerrainPalette`] to the theme and terrain costs, and shows it
+/// as swatches that pick what the `WallBrush` paints. Goes with the
+/// `WallPaintingPlugin`.
+pub struct TerrainPalettePlugin {
+    /// Font used by the swatches, relative to the assets folder.
+    pub font: &'static str,
+}
+
+impl Default for TerrainPalettePlugin {
+    fn default() -> Self {
+        TerrainPalettePlugin { font: "fonts/FiraMono-Medium.ttf" }
+    }
+}
+
+impl Plugin for TerrainPalettePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<TerrainPalette>()
+            .init_resource::<WallBrush>()
+            .init_resource::<InputBindings>()
+            .insert_resource(PaletteFont(self.font))
+            .add_system(apply_palette.before(crate::GridEditSet))
+            .add_system(palette_keys)
+            .add_system(palette_buttons)
+            .add_system(update_swatches.after(apply_palette).after(palette_keys).after(palette_buttons));
+    }
+}
+
+#[derive(Resource)]
+struct PaletteFont(&'static str);
+
+/// A swatch painting with `terrain`, or walls when `None`.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+struct Swatch {
+    terrain: Option<Terrain>,
+}
+
+/// Copies the palette into the theme and costs, and respawns the swatches.
+fn apply_palette(
+    mut commands: Commands,
+    palette: Res<TerrainPalette>,
+    asset_server: Res<AssetServer>,
+    font: Res<PaletteFont>,
+    mut theme: ResMut<GridTheme>,
+    costs: Option<ResMut<TerrainCosts>>,
+    swatch_rows: Query<Entity, With<SwatchRow>>,
+) {
+    if !palette.is_changed() {
+        return;
+    }
+
+    for kind in &palette.kinds {
+        theme.terrain.insert(kind.terrain, kind.color);
+    }
+    if let Some(mut costs) = costs {
+        for kind in &palette.kinds {
+            costs.set(kind.terrain, kind.cost);
+        }
+    }
+
+    for row in &swatch_rows {
+        commands.entity(row).despawn_recursive();
+    }
+    let style = TextStyle {
+        font: asset_server.load(font.0),
+        font_size: 14.0,
+        color: Color::WHITE,
+    };
+    let swatches = std::iter::once((Swatch { terrain: None }, theme.wall))
+        .chain(palette.kinds.iter().map(|kind| (Swatch { terrain: Some(kind.terrain) }, kind.color)));
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    right: Val::Px(10.0),
+                    bottom: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            },
+            background_color: Color::NONE.into(),
+            ..default()
+        })
+        .insert((SwatchRow, Name::new("Terrain palette")))
+        .with_children(|parent| {
+            for (swatch, color) in swatches {
+                parent
+                    .spawn(ButtonBundle {
+                        style: Style {
+                            margin: UiRect::all(Val::Px(2.0)),
+                            padding: UiRect::all(Val::Px(4.0)),
+                            ..default()
+                        },
+                        background_color: color.into(),
+                        ..default()
+                    })
+                    .insert(swatch)
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section("", style.clone()));
+                    });
+            }
+        });
+}
+
+/// The row of swatches.
+#[derive(Component)]
+struct SwatchRow;
+
+fn palette_keys(
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<InputBindings>,
+    palette: Res<TerrainPalette>,
+    mut brush: ResMut<WallBrush>,
+) {
+    if !bindings.cycle_terrain.just_pressed(&keys) {
+        return;
+    }
+
+    // Walls, then each kind in order, then walls again.
+    let position = match brush.terrain {
+        None => Some(0),
+        Some(terrain) => palette.kinds.iter().position(|kind| kind.terrain == terrain).map(|index| index + 1),
+    };
+    brush.terrain = position.and_then(|index| palette.kinds.get(index)).map(|kind| kind.terrain);
+}
+
+fn palette_buttons(mut brush: ResMut<WallBrush>, swatches: Query<(&Swatch, &Interaction), Changed<Interaction>>) {
+    for (swatch, &interaction) in &swatches {
+        if interaction == Interaction::Clicked && brush.terrain != swatch.terrain {
+            brush.terrain = swatch.terrain;
+        }
+    }
+}
+
+/// Labels each swatch, marking the one the brush paints with.
+fn update_swatches(
+    brush: Res<WallBrush>,
+    palette: Res<TerrainPalette>,
+    swatches: Query<(&Swatch, &Children)>,
+    added: Query<(), Added<Swatch>>,
+    mut texts: Query<&mut Text>,
+) {
+    if !brush.is_changed() && added.is_empty() {
+        return;
+    }
+
+    for (swatch, children) in &swatches {
+        let name = match swatch.terrain {
+            None => "walls",
+            Some(terrain) => palette.kind(terrain).map_or("?", |kind| kind.name),
+        };
+        let label = match swatch.terrain == brush.terrain {
+            true => format!("[{name}]"),
+            false => name.to_string(),
+        };
+
+        for &child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(child) {
+                text.sections[0].value = label.clone();
+            }
+        }
+    }
+}
@@ -0,0 +1,440 @@
+//! An egui window for configuring searches at runtime.
+//!
+//! [`ControlPanelPlugin`] shows a window to pick the planner, heuristic and
+//! connectivity of a grid, set the speed of the [`VisualizationClock`], clear,
+//! fill or invert the grid, randomize its walls to a chosen density, keeping
+//! the start and goal clear and connected if asked, lay a maze over it with a
+//! chosen algorithm and corridor width, grow a cave over it or dig one with
+//! drunkard's walks, carve a dungeon of rooms into it, scattered or by binary
+//! space partitioning, piece rooms and corridors together over it by wave
+//! function collapse, lay out its terrain from noise, or start a new search on
+//! it. The Reseed button starts the `MapRng` the randomizer and generators draw
+//! from over from the seed typed in, and the seed the grid was generated from
+//! is shown next to it. It needs the `egui` cargo feature, and adds
+//! `EguiPlugin` unless the app already has it, as it does with the inspector's
+//! `WorldInspectorPlugin`.
+//!
+//! The start and goal of the searches follow the grid's [`EndpointMarker`]s
+//! when it has them, and editing them in the window moves the markers.
+//!
+//! The window also picks the editor tool from the `EditorTools`, when the app
+//! has them, and shows the [`PanelSection`]s other crates add with
+//! `EditorAppExt::add_panel_section`, each under its own collapsible header.
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext, EguiPlugin};
+
+use crate::{
+    cave::{DrunkardWalk, GenerateCave},
+    dungeon::{GenerateBspDungeon, GenerateDungeon},
+    editor::{BulkEdit, EditorTools, RandomizeWalls},
+    grid::{CellPos, Connectivity, Grid, GridEditor},
+    markers::{Endpoint, EndpointMarker},
+    maze::{GenerateMaze, MazeAlgorithm},
+    noise::{FractalNoise, GenerateTerrain},
+    pathfinding::{Heuristic, Planner, Search},
+    playback::VisualizationClock,
+    search_view::SearchVisualizer,
+    seed::MapRng,
+    wfc::GenerateWfc,
+};
+
+/// What the [`ControlPanelPlugin`] window edits and searches with.
+#[derive(Resource, Debug, Clone)]
+pub struct ControlPanel {
+    /// Grid editor the panel acts on; the first one found when `None`.
+    pub grid: Option<Entity>,
+    pub planner: Planner,
+    pub heuristic: Heuristic,
+    /// Endpoints of the searches the panel starts, unless the grid has markers.
+    /// Cells beyond the grid are moved to its edge, so the default goal is the
+    /// far corner.
+    pub start: CellPos,
+    pub goal: CellPos,
+    /// Share of the cells made walls when randomizing.
+    pub wall_density: f64,
+    /// Whether randomizing leaves the start and goal as floor, along with the
+    /// cells within `clear_radius` steps of them.
+    pub keep_ends_clear: bool,
+    pub clear_radius: u32,
+    /// Whether randomizing leaves a path between the start and goal, opening
+    /// walls if it has to.
+    pub keep_ends_connected: bool,
+    /// Width in cells of the corridors of generated mazes.
+    pub corridor_width: u32,
+    pub maze_algorithm: MazeAlgorithm,
+    /// Share of the cells walled off before a cave is smoothed.
+    pub cave_fill_ratio: f64,
+    /// Rounds of smoothing of generated caves.
+    pub cave_iterations: u32,
+    /// Walkers digging out drunkard's walk caves, and the steps each takes.
+    pub walkers: u32,
+    pub walker_steps: u32,
+    /// Most rooms of generated dungeons.
+    pub dungeon_rooms: u32,
+    /// Fewest and most cells across the leaves of BSP dungeons.
+    pub min_leaf: u32,
+    pub max_leaf: u32,
+    /// Size in cells of the broadest features of terrain generated from noise.
+    pub terrain_scale: f32,
+    /// Octaves of the noise terrain is generated from.
+    pub terrain_octaves: u32,
+    /// Seed the Reseed button starts the `MapRng` over from; a random one when
+    /// it isn't a number.
+    pub seed: String,
+    /// Expansions per tick of the searches the panel starts.
+    pub expansions_per_tick: usize,
+}
+
+impl Default for ControlPanel {
+    fn default() -> Self {
+        ControlPanel {
+            grid: None,
+            planner: Planner::AStar,
+            heuristic: Heuristic::default(),
+            start: CellPos(0, 0),
+            goal: CellPos(i32::MAX, i32::MAX),
+            wall_density: 0.25,
+            keep_ends_clear: true,
+            clear_radius: 1,
+            keep_ends_connected: true,
+            corridor_width: 1,
+            maze_algorithm: MazeAlgorithm::default(),
+            cave_fill_ratio: 0.45,
+            cave_iterations: 4,
+            walkers: 8,
+            walker_steps: 400,
+            dungeon_rooms: 12,
+            min_leaf: 6,
+            max_leaf: 16,
+            terrain_scale: 16.0,
+            terrain_octaves: 4,
+            seed: String::new(),
+            expansions_per_tick: 4,
+        }
+    }
+}
+
+impl ControlPanel {
+    fn endpoint_mut(&mut self, endpoint: Endpoint) -> &mut CellPos {
+        match endpoint {
+            Endpoint::Start => &mut self.start,
+            Endpoint::Goal => &mut self.goal,
+        }
+    }
+}
+
+/// Controls another crate adds to the [`ControlPanel`] window.
+pub trait PanelSection: Send + Sync + 'static {
+    /// Shown on the header of the section.
+    fn name(&self) -> &str;
+
+    /// Draws the section's controls for the grid editor `grid_entity`, holding
+    /// `grid`. Changes to the grid go through `commands`, as requests on the
+    /// grid editor or `SetCellEvent`s.
+    fn show(&mut self, ui: &mut egui::Ui, grid_entity: Entity, grid: &Grid, commands: &mut Commands);
+}
+
+/// The sections of the [`ControlPanel`] window added by other crates, in the
+/// order they are shown.
+#[derive(Resource, Default)]
+pub struct PanelSections {
+    sections: Vec<Box<dyn PanelSection>>,
+}
+
+impl PanelSections {
+    pub fn add(&mut self, section: impl PanelSection) -> &mut Self {
+        self.sections.push(Box::new(section));
+        self
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> + '_ {
+        self.sections.iter().map(|section| section.name())
+    }
+
+    pub fn len(&self) -> usize {
+        self.sections.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sections.is_empty()
+    }
+}
+
+/// Shows the [`ControlPanel`] window.
+pub struct ControlPanelPlugin;
+
+impl Plugin for ControlPanelPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugin(EguiPlugin);
+        }
+
+        app.init_resource::<ControlPanel>()
+            .init_resource::<PanelSections>()
+            .init_resource::<VisualizationClock>()
+            .init_resource::<MapRng>()
+            .add_system(show_control_panel);
+    }
+}
+
+/// A button pressed in the panel, applied once the window is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PanelAction {
+    Edit(BulkEdit),
+    Randomize,
+    Maze,
+    Cave,
+    DrunkardWalk,
+    Dungeon,
+    BspDungeon,
+    Wfc,
+    Terrain,
+    Search,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn show_control_panel(
+    mut egui_context: ResMut<EguiContext>,
+    mut panel: ResMut<ControlPanel>,
+    mut clock: ResMut<VisualizationClock>,
+    mut grids: ResMut<Assets<Grid>>,
+    editors: Query<(&GridEditor, Entity)>,
+    mut visualizers: Query<&mut SearchVisualizer>,
+    mut commands: Commands,
+    mut markers: Query<&mut EndpointMarker>,
+    mut map_rng: ResMut<MapRng>,
+    tools: Option<ResMut<EditorTools>>,
+    mut sections: ResMut<PanelSections>,
+) {
+    let editor = match panel.grid {
+        Some(entity) => editors.get(entity).ok(),
+        None => editors.iter().next(),
+    };
+    let Some((editor, grid_entity)) = editor else {
+        return;
+    };
+    let Some(grid) = grids.get(&editor.grid) else {
+        return;
+    };
+
+    let panel = &mut *panel;
+    for marker in &markers {
+        if marker.grid == grid_entity {
+            *panel.endpoint_mut(marker.endpoint) = marker.cell_pos;
+        }
+    }
+    let mut connectivity = grid.connectivity();
+    let max = CellPos(grid.width() as i32 - 1, grid.height() as i32 - 1);
+    panel.start = clamp_cell_pos(panel.start, max);
+    panel.goal = clamp_cell_pos(panel.goal, max);
+    let mut action = None;
+
+    egui::Window::new("Control panel").resizable(false).show(egui_context.ctx_mut(), |ui| {
+        egui::ComboBox::from_label("Planner").selected_text(panel.planner.name()).show_ui(ui, |ui| {
+            for planner in Planner::ALL {
+                ui.selectable_value(&mut panel.planner, planner, planner.name());
+            }
+        });
+        egui::ComboBox::from_label("Heuristic").selected_text(panel.heuristic.name()).show_ui(ui, |ui| {
+            for heuristic in Heuristic::ALL {
+                ui.selectable_value(&mut panel.heuristic, heuristic, heuristic.name());
+            }
+        });
+        egui::ComboBox::from_label("Connectivity").selected_text(connectivity.name()).show_ui(ui, |ui| {
+            for option in Connectivity::ALL {
+                ui.selectable_value(&mut connectivity, option, option.name());
+            }
+        });
+        if let Some(mut tools) = tools {
+            let selected = tools.selected().map_or(String::new(), |tool| tool.name().to_string());
+            let mut picked = selected.clone();
+            egui::ComboBox::from_label("Tool").selected_text(selected.as_str()).show_ui(ui, |ui| {
+                for name in tools.names() {
+                    ui.selectable_value(&mut picked, name.to_string(), name);
+                }
+            });
+            // Only borrowed mutably on change, like the grid.
+            if picked != selected {
+                tools.select(&picked);
+            }
+        }
+
+        ui.separator();
+        let (min_speed, max_speed) = VisualizationClock::SPEED_RANGE;
+        ui.add(
+            egui::Slider::new(&mut clock.ticks_per_second, min_speed..=max_speed)
+                .logarithmic(true)
+                .text("ticks per second"),
+        );
+        ui.add(egui::Slider::new(&mut panel.expansions_per_tick, 1..=256).logarithmic(true).text("expansions per tick"));
+        ui.horizontal(|ui| {
+            if ui.button(if clock.paused { "Play" } else { "Pause" }).clicked() {
+                clock.toggle();
+            }
+            if ui.button("Step").clicked() {
+                clock.step();
+            }
+        });
+
+        ui.separator();
+        for (label, cell_pos) in [("Start", &mut panel.start), ("Goal", &mut panel.goal)] {
+            ui.horizontal(|ui| {
+                ui.label(label);
+                ui.add(egui::DragValue::new(&mut cell_pos.0).clamp_range(0..=max.0).prefix("x: "));
+                ui.add(egui::DragValue::new(&mut cell_pos.1).clamp_range(0..=max.1).prefix("y: "));
+            });
+        }
+        ui.add(egui::Slider::new(&mut panel.wall_density, 0.0..=0.9).text("wall density"));
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut panel.keep_ends_clear, "keep start and goal clear");
+            ui.add(egui::DragValue::new(&mut panel.clear_radius).clamp_range(0..=8).prefix("radius: "));
+        });
+        ui.checkbox(&mut panel.keep_ends_connected, "keep start and goal connected");
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_label("Maze").selected_text(panel.maze_algorithm.name()).show_ui(ui, |ui| {
+                for algorithm in MazeAlgorithm::ALL {
+                    ui.selectable_value(&mut panel.maze_algorithm, algorithm, algorithm.name());
+                }
+            });
+            ui.add(egui::DragValue::new(&mut panel.corridor_width).clamp_range(1..=8).prefix("corridor width: "));
+        });
+        ui.horizontal(|ui| {
+            ui.add(egui::Slider::new(&mut panel.cave_fill_ratio, 0.3..=0.6).text("cave fill"));
+            ui.add(egui::DragValue::new(&mut panel.cave_iterations).clamp_range(0..=12).prefix("iterations: "));
+        });
+        ui.horizontal(|ui| {
+            ui.add(egui::DragValue::new(&mut panel.walkers).clamp_range(1..=64).prefix("walkers: "));
+            ui.add(egui::DragValue::new(&mut panel.walker_steps).clamp_range(1..=100_000).prefix("steps: "));
+            ui.add(egui::DragValue::new(&mut panel.dungeon_rooms).clamp_range(1..=64).prefix("rooms: "));
+        });
+        ui.horizontal(|ui| {
+            ui.add(egui::DragValue::new(&mut panel.min_leaf).clamp_range(3..=32).prefix("min leaf: "));
+            let min_leaf = panel.min_leaf;
+            ui.add(egui::DragValue::new(&mut panel.max_leaf).clamp_range(min_leaf..=64).prefix("max leaf: "));
+        });
+        ui.horizontal(|ui| {
+            ui.add(egui::Slider::new(&mut panel.terrain_scale, 2.0..=64.0).logarithmic(true).text("terrain scale"));
+            ui.add(egui::DragValue::new(&mut panel.terrain_octaves).clamp_range(1..=8).prefix("octaves: "));
+        });
+        ui.horizontal(|ui| {
+            ui.label("Seed");
+            ui.text_edit_singleline(&mut panel.seed);
+            if ui.button("Reseed").clicked() {
+                let seed = panel.seed.trim().parse().unwrap_or_else(|_| rand::random());
+                map_rng.reseed(seed);
+                panel.seed = seed.to_string();
+            }
+            if let Some(seed) = grid.seed() {
+                ui.label(format!("map seed: {seed}"));
+            }
+        });
+
+        ui.horizontal(|ui| {
+            for edit in BulkEdit::ALL {
+                if ui.button(edit.name()).clicked() {
+                    action = Some(PanelAction::Edit(edit));
+                }
+            }
+        });
+        ui.horizontal(|ui| {
+            let buttons = [
+                ("Randomize", PanelAction::Randomize),
+                ("Maze", PanelAction::Maze),
+                ("Cave", PanelAction::Cave),
+                ("Drunkard's walk", PanelAction::DrunkardWalk),
+                ("Dungeon", PanelAction::Dungeon),
+                ("BSP dungeon", PanelAction::BspDungeon),
+                ("Wave collapse", PanelAction::Wfc),
+                ("Terrain", PanelAction::Terrain),
+                ("Search", PanelAction::Search),
+            ];
+            for (label, pressed) in buttons {
+                if ui.button(label).clicked() {
+                    action = Some(pressed);
+                }
+            }
+        });
+
+        for section in &mut sections.sections {
+            egui::CollapsingHeader::new(section.name().to_string()).show(ui, |ui| {
+                section.show(ui, grid_entity, grid, &mut commands);
+            });
+        }
+    });
+
+    match action {
+        Some(PanelAction::Edit(edit)) => {
+            commands.entity(grid_entity).insert(edit);
+        }
+        Some(PanelAction::Randomize) => {
+            let mut randomize = RandomizeWalls::new(panel.wall_density);
+            if panel.keep_ends_clear {
+                randomize = randomize.with_clear_cells([panel.start, panel.goal], panel.clear_radius);
+            }
+            if panel.keep_ends_connected {
+                randomize = randomize.with_connected(panel.start, panel.goal);
+            }
+            commands.entity(grid_entity).insert(randomize);
+        }
+        Some(PanelAction::Maze) => {
+            let maze = GenerateMaze::new(panel.corridor_width).with_algorithm(panel.maze_algorithm);
+            commands.entity(grid_entity).insert(maze);
+        }
+        Some(PanelAction::Cave) => {
+            commands.entity(grid_entity).insert(GenerateCave::new(panel.cave_fill_ratio, panel.cave_iterations));
+        }
+        Some(PanelAction::DrunkardWalk) => {
+            commands.entity(grid_entity).insert(DrunkardWalk::new(panel.walkers, panel.walker_steps));
+        }
+        Some(PanelAction::Dungeon) => {
+            commands.entity(grid_entity).insert(GenerateDungeon::new(panel.dungeon_rooms));
+        }
+        Some(PanelAction::BspDungeon) => {
+            commands.entity(grid_entity).insert(GenerateBspDungeon::new(panel.min_leaf, panel.max_leaf));
+        }
+        Some(PanelAction::Wfc) => {
+            commands.entity(grid_entity).insert(GenerateWfc::default());
+        }
+        Some(PanelAction::Terrain) => {
+            let seed = map_rng.next_seed() as u32;
+            let noise = FractalNoise::new(seed).with_scale(panel.terrain_scale).with_octaves(panel.terrain_octaves);
+            commands.entity(grid_entity).insert(GenerateTerrain::new(seed).with_noise(noise));
+        }
+        Some(PanelAction::Search) => {
+            let new_visualizer = || {
+                let search = Search::new(grid, panel.planner, panel.start, panel.goal).with_heuristic(panel.heuristic);
+                SearchVisualizer::new(grid_entity, search).with_expansions_per_tick(panel.expansions_per_tick)
+            };
+
+            let mut replaced = false;
+            for mut visualizer in visualizers.iter_mut().filter(|visualizer| visualizer.grid == grid_entity) {
+                let (coloring, show_tree, trail) = (visualizer.coloring, visualizer.show_tree, visualizer.trail);
+                *visualizer = new_visualizer().with_coloring(coloring).with_tree(show_tree).with_trail(trail);
+                replaced = true;
+            }
+            if !replaced {
+                commands.spawn((Name::new("Search"), new_visualizer()));
+            }
+        }
+        None => {}
+    }
+
+    for mut marker in &mut markers {
+        let cell_pos = *panel.endpoint_mut(marker.endpoint);
+        if marker.grid == grid_entity && marker.cell_pos != cell_pos {
+            marker.cell_pos = cell_pos;
+        }
+    }
+
+    // Only borrowed mutably on change, as that redraws every view of the grid.
+    if connectivity != grid.connectivity() {
+        if let Some(grid) = grids.get_mut(&editor.grid) {
+            grid.set_connectivity(connectivity);
+        }
+    }
+}
+
+fn clamp_cell_pos(cell_pos: CellPos, max: CellPos) -> CellPos {
+    CellPos(cell_pos.0.clamp(0, max.0.max(0)), cell_pos.1.clamp(0, max.1.max(0)))
+}
@@ -0,0 +1,170 @@
+//! Paths written out as JSON or CSV, for plotting and post-processing.
+//!
+//! A [`PathExport`] holds the cells of a path with what each step into them
+//! cost, the total cost and the search's stats. As JSON it's a single object:
+//!
+//! ```text
+//! {"cost": 2.4142137, "steps": 2, "nodes_expanded": 5,
+//!  "cells": [[0, 0], [1, 0], [2, 1]], "step_costs": [1, 1.4142135]}
+//! ```
+//!
+//! As CSV it's a row per cell, with the cost of the step into it, empty for the
+//! start, and the cost so far: `step,x,y,step_cost,total_cost`. The stats are
+//! left out. A step whose cost can't be worked out, because the cost function
+//! forbids it since the search, is `null` or empty.
+//!
+//! [`PathExportPlugin`] writes the first `ComputedPath` of the app to its
+//! [`PathExportFile`] on Ctrl+E, by default; see `InputBindings`. Its step costs
+//! are the `TerrainCosts` of the terrain entered, as for paths asked for
+//! without a cost profile.
+
+use std::{
+    fmt::Write as _,
+    fs, io,
+    path::{Path as FilePath, PathBuf},
+};
+
+use bevy::prelude::*;
+
+use crate::{
+    bindings::InputBindings,
+    console::ErrorConsole,
+    grid::{CellPos, Grid, Grids},
+    pathfinding::{grid_successors, Path, StepCost},
+    request::ComputedPath,
+    terrain::{TerrainCost, TerrainCosts},
+};
+
+/// `null` for costs that are missing or aren't numbers JSON can hold.
+fn json_number(value: Option<f32>) -> String {
+    value.filter(|value| value.is_finite()).map_or("null".to_string(), |value| value.to_string())
+}
+
+/// A path ready to be written out, see the module docs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathExport {
+    pub cells: Vec<CellPos>,
+    /// Cost of each step, from `cells[i]` to `cells[i + 1]`, if it's allowed.
+    pub step_costs: Vec<Option<f32>>,
+    pub cost: f32,
+    pub nodes_expanded: usize,
+}
+
+impl PathExport {
+    /// The steps of `path` costed by `cost` on `grid`, portals included.
+    pub fn new(path: &Path, grid: &Grid, cost: &impl StepCost) -> Self {
+        let step_costs = path
+            .cells
+            .windows(2)
+            .map(|step| {
+                grid_successors(grid, cost, step[0])
+                    .filter(|&(next, _)| next == step[1])
+                    .map(|(_, cost)| cost)
+                    .reduce(f32::min)
+            })
+            .collect();
+        PathExport { cells: path.cells.clone(), step_costs, cost: path.cost, nodes_expanded: path.nodes_expanded }
+    }
+
+    pub fn to_json(&self) -> String {
+        let cells: Vec<String> = self.cells.iter().map(|CellPos(x, y)| format!("[{x}, {y}]")).collect();
+        let step_costs: Vec<String> = self.step_costs.iter().map(|&cost| json_number(cost)).collect();
+        format!(
+            "{{\"cost\": {}, \"steps\": {}, \"nodes_expanded\": {}, \"cells\": [{}], \"step_costs\": [{}]}}\n",
+            json_number(Some(self.cost)),
+            self.step_costs.len(),
+            self.nodes_expanded,
+            cells.join(", "),
+            step_costs.join(", "),
+        )
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = "step,x,y,step_cost,total_cost\n".to_string();
+        let mut total = Some(0.0);
+        for (step, &CellPos(x, y)) in self.cells.iter().enumerate() {
+            let step_cost = step.checked_sub(1).and_then(|index| self.step_costs[index]);
+            if step > 0 {
+                total = total.zip(step_cost).map(|(total, cost)| total + cost);
+            }
+            let [step_cost, total] = [step_cost, total].map(|cost| cost.map_or(String::new(), |cost| cost.to_string()));
+            writeln!(csv, "{step},{x},{y},{step_cost},{total}").expect("writing to a string can't fail");
+        }
+        csv
+    }
+
+    /// Writes the path to `path`, as CSV if it ends in `.csv` and JSON if not.
+    pub fn save(&self, path: impl AsRef<FilePath>) -> io::Result<()> {
+        let path = path.as_ref();
+        let csv = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("csv"));
+        fs::write(path, if csv { self.to_csv() } else { self.to_json() })
+    }
+}
+
+/// Where the [`PathExportPlugin`] writes paths.
+#[derive(Resource, Debug, Clone, PartialEq, Eq)]
+pub struct PathExportFile {
+    pub path: PathBuf,
+}
+
+/// Writes the first computed path to the [`PathExportFile`] on the
+/// `export_path` binding.
+#[derive(Debug, Clone)]
+pub struct PathExportPlugin {
+    /// Where paths go until the `PathExportFile` is changed; `path.json` by default.
+    pub path: PathBuf,
+}
+
+impl Default for PathExportPlugin {
+    fn default() -> Self {
+        PathExportPlugin { path: PathBuf::from("path.json") }
+    }
+}
+
+impl PathExportPlugin {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        PathExportPlugin { path: path.into() }
+    }
+}
+
+impl Plugin for PathExportPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<InputBindings>()
+            .init_resource::<TerrainCosts>()
+            .insert_resource(PathExportFile { path: self.path.clone() })
+            .add_system(export_path_keys);
+    }
+}
+
+fn export_path_keys(
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<InputBindings>,
+    export_file: Res<PathExportFile>,
+    computed_paths: Query<&ComputedPath>,
+    grids: Grids,
+    costs: Res<TerrainCosts>,
+    mut console: ResMut<ErrorConsole>,
+) {
+    if !bindings.export_path.just_pressed(&keys) {
+        return;
+    }
+    let Some(computed) = computed_paths.iter().next() else {
+        info!("no path to export");
+        return;
+    };
+    let grid = match grids.get(computed.grid) {
+        Ok(grid) => grid,
+        Err(error) => {
+            console.report("export_path_keys", &error);
+            return;
+        }
+    };
+
+    let cost = TerrainCost::new(costs.clone(), default());
+    let path = &export_file.path;
+    match PathExport::new(&computed.path, grid, &cost).save(path) {
+        Ok(()) => info!("exported path to {}", path.display()),
+        Err(error) => console.report("export_path_keys", &error),
+    }
+}
@@ -0,0 +1,120 @@
+//! Found paths drawn as lines over their grid.
+//!
+//! Every [`ComputedPath`] is drawn over the texture of its grid as a line
+//! through the centers of its cells, with a marker on its start and its goal.
+//! The line is redrawn when the path is replanned or the [`GridTheme`] changes,
+//! and goes away with the path.
+
+use bevy::{
+    prelude::*,
+    render::mesh::PrimitiveTopology,
+    sprite::{MaterialMesh2dBundle, Mesh2dHandle},
+};
+
+use crate::{grid::Grids, request::ComputedPath, theme::GridTheme, view::cell_transform};
+
+/// How far above the grid texture lines are drawn.
+const LINE_DEPTH: f32 = 1.0;
+
+/// Marker size of every path line; their colors are the path, start and goal
+/// colors of the [`GridTheme`].
+#[derive(Resource, Debug, Clone)]
+pub struct PathLineStyle {
+    /// Side of the start and goal markers, in cells.
+    pub marker_size: f32,
+}
+
+impl Default for PathLineStyle {
+    fn default() -> Self {
+        PathLineStyle { marker_size: 0.5 }
+    }
+}
+
+/// On the line drawn for the [`ComputedPath`] of `path_entity`, a child of the
+/// path's grid entity.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathLine {
+    pub path_entity: Entity,
+}
+
+/// Draws new and replanned paths, replacing their previous line, and every
+/// path again when the style or theme changes.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn draw_path_lines(
+    mut commands: Commands,
+    style: Res<PathLineStyle>,
+    theme: Res<GridTheme>,
+    grids: Grids,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    paths: Query<(&ComputedPath, ChangeTrackers<ComputedPath>, Entity)>,
+    lines: Query<(&PathLine, Entity)>,
+) {
+    let restyled = style.is_changed() || theme.is_changed();
+
+    for (computed, tracker, path_entity) in &paths {
+        if !tracker.is_changed() && !restyled {
+            continue;
+        }
+
+        for (line, line_entity) in &lines {
+            if line.path_entity == path_entity {
+                commands.entity(line_entity).despawn_recursive();
+            }
+        }
+
+        let Ok(grid) = grids.get(computed.grid) else {
+            continue;
+        };
+        let cells = &computed.path.cells;
+        let (Some(&start), Some(&goal)) = (cells.first(), cells.last()) else {
+            continue;
+        };
+
+        let positions: Vec<[f32; 3]> =
+            cells.iter().map(|&cell_pos| cell_transform(grid, cell_pos).translation.to_array()).collect();
+        let mut mesh = Mesh::new(PrimitiveTopology::LineStrip);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 0.0, 1.0]; positions.len()]);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0.0, 0.0]; positions.len()]);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+
+        let marker = |cell_pos, color| SpriteBundle {
+            transform: cell_transform(grid, cell_pos),
+            sprite: Sprite {
+                color,
+                custom_size: Some(Vec2::splat(style.marker_size)),
+                ..default()
+            },
+            ..default()
+        };
+
+        let line_entity = commands
+            .spawn(MaterialMesh2dBundle {
+                mesh: Mesh2dHandle(meshes.add(mesh)),
+                material: materials.add(ColorMaterial::from(theme.path)),
+                transform: Transform::from_xyz(0.0, 0.0, LINE_DEPTH),
+                ..default()
+            })
+            .insert((PathLine { path_entity }, Name::new("Path line")))
+            .with_children(|parent| {
+                parent.spawn(marker(start, theme.start));
+                parent.spawn(marker(goal, theme.goal));
+            })
+            .id();
+        commands.entity(computed.grid).add_child(line_entity);
+    }
+}
+
+/// Removes the lines of paths that are gone, whether replaced by a `PathFailed`,
+/// removed, or despawned with their entity.
+pub(crate) fn erase_path_lines(
+    mut commands: Commands,
+    lines: Query<(&PathLine, Entity)>,
+    paths: Query<(), With<ComputedPath>>,
+) {
+    for (line, line_entity) in &lines {
+        if !paths.contains(line.path_entity) {
+            commands.entity(line_entity).despawn_recursive();
+        }
+    }
+}
@@ -0,0 +1,880 @@
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use bevy::prelude::{Entity, FromReflect, Reflect};
+
+use crate::{
+    graph::{GraphPath, GraphSearch, GraphStepResult, OpenNode, SearchGraph},
+    grid::{CellPos, Connectivity, Grid, GridNotFound, Grids},
+};
+
+/// Cost of moving between two adjacent cells, or `None` if the move is not allowed.
+/// Searches never step onto walls, so `to` is always a floor cell. The cost is
+/// scaled by the length of the step, so diagonal steps on eight-connected grids
+/// cost `SQRT_2` times as much.
+///
+/// Costs should be at least `1.0` per step, since the A* heuristic assumes as much.
+pub trait StepCost {
+    fn step_cost(&self, grid: &Grid, from: CellPos, to: CellPos) -> Option<f32>;
+}
+
+/// Every step onto a floor cell costs `1.0`; walls can't be entered.
+pub struct UniformCost;
+
+impl StepCost for UniformCost {
+    fn step_cost(&self, _grid: &Grid, _from: CellPos, _to: CellPos) -> Option<f32> {
+        Some(1.0)
+    }
+}
+
+impl<F> StepCost for F
+where
+    F: Fn(&Grid, CellPos, CellPos) -> Option<f32>,
+{
+    fn step_cost(&self, grid: &Grid, from: CellPos, to: CellPos) -> Option<f32> {
+        self(grid, from, to)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Reflect, FromReflect)]
+pub struct Path {
+    /// Every cell from the start to the goal, both included.
+    pub cells: Vec<CellPos>,
+    pub cost: f32,
+    pub nodes_expanded: usize,
+}
+
+/// Result of a search with a node budget: either the full path to the goal, or a
+/// prefix heading towards it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartialPath {
+    pub path: Path,
+    pub reaches_goal: bool,
+}
+
+/// How a search orders its frontier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Reflect, FromReflect)]
+pub enum Planner {
+    /// Cost so far plus the heuristic: optimal, and usually fast.
+    AStar,
+    /// Cost so far only: optimal, but explores in every direction.
+    Dijkstra,
+    /// Heuristic only: fast, but paths are not guaranteed to be the cheapest.
+    GreedyBestFirst,
+}
+
+impl Planner {
+    pub const ALL: [Planner; 3] = [Planner::AStar, Planner::Dijkstra, Planner::GreedyBestFirst];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Planner::AStar => "A*",
+            Planner::Dijkstra => "Dijkstra",
+            Planner::GreedyBestFirst => "Greedy best-first",
+        }
+    }
+
+    pub(crate) fn priority(self, g_score: f32, h_score: f32) -> f32 {
+        match self {
+            Planner::AStar => g_score + h_score,
+            Planner::Dijkstra => g_score,
+            Planner::GreedyBestFirst => h_score,
+        }
+    }
+}
+
+/// Estimate of the cost left from a cell to the goal, which steers A* and
+/// greedy best-first. A* only guarantees the cheapest path while the estimate
+/// never exceeds the real cost; every heuristic here stays below it on square
+/// grids whose step costs are at least the step lengths, except where noted.
+///
+/// On hex grids `Manhattan`, `Euclidean` and `Octile` overestimate, since a
+/// diagonal hex step moves a row and a column at once for the cost of one, so
+/// searches on hex grids use `Connectivity` whatever heuristic they're given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Reflect, FromReflect)]
+pub enum Heuristic {
+    /// The cheapest cost on an empty grid with the grid's own connectivity: the
+    /// tightest estimate that never overestimates, hex grids included.
+    #[default]
+    Connectivity,
+    /// `dx + dy`. Overestimates diagonal moves on eight-connected grids.
+    Manhattan,
+    /// Straight-line distance.
+    Euclidean,
+    /// `max(dx, dy)`, as if diagonal steps cost the same as straight ones.
+    Chebyshev,
+    /// Straight steps plus `SQRT_2` per diagonal step.
+    Octile,
+}
+
+impl Heuristic {
+    pub const ALL: [Heuristic; 5] = [
+        Heuristic::Connectivity,
+        Heuristic::Manhattan,
+        Heuristic::Euclidean,
+        Heuristic::Chebyshev,
+        Heuristic::Octile,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Heuristic::Connectivity => "Connectivity",
+            Heuristic::Manhattan => "Manhattan",
+            Heuristic::Euclidean => "Euclidean",
+            Heuristic::Chebyshev => "Chebyshev",
+            Heuristic::Octile => "Octile",
+        }
+    }
+
+    /// Estimated cost from `from` to `to` on a grid with `connectivity`.
+    pub fn estimate(self, connectivity: Connectivity, from: CellPos, to: CellPos) -> f32 {
+        let (CellPos(x0, y0), CellPos(x1, y1)) = (from, to);
+        let (dx, dy) = ((x1 - x0).unsigned_abs() as f32, (y1 - y0).unsigned_abs() as f32);
+
+        match self {
+            Heuristic::Connectivity => connectivity.distance(from, to),
+            Heuristic::Manhattan => dx + dy,
+            Heuristic::Euclidean => dx.hypot(dy),
+            Heuristic::Chebyshev => dx.max(dy),
+            Heuristic::Octile => Connectivity::Eight.distance(from, to),
+        }
+    }
+}
+
+/// Outcome of a [`Pathfinder`] search.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SearchOutcome {
+    Found(Path),
+    /// The goal can't be reached; `nodes_expanded` cells were expanded finding out.
+    NoPath { nodes_expanded: usize },
+}
+
+/// A path search algorithm that `PathRequest`s can be answered with. The
+/// [`Planner`]s are built in; others are registered with the plugin through
+/// `PathfinderAppExt::add_pathfinder` and requested by name.
+pub trait Pathfinder: Send + Sync + 'static {
+    /// Used to request the pathfinder, and shown next to its results.
+    fn name(&self) -> &str;
+
+    /// Searches for a path from `start` to `goal`. Walls can't be entered, and
+    /// portals should be taken into account like the built-in searches do.
+    fn search(&mut self, grid: &Grid, start: CellPos, goal: CellPos) -> SearchOutcome;
+}
+
+impl Pathfinder for Planner {
+    fn name(&self) -> &str {
+        Planner::name(*self)
+    }
+
+    fn search(&mut self, grid: &Grid, start: CellPos, goal: CellPos) -> SearchOutcome {
+        Search::new(grid, *self, start, goal).finish(grid, &UniformCost)
+    }
+}
+
+/// Restricts a search to part of the grid: cells outside the mask are treated as
+/// walls. A search whose start or goal lies outside its mask finds no path.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SearchMask {
+    /// Every cell between the two corners, both included.
+    Rect { min: CellPos, max: CellPos },
+    Cells(HashSet<CellPos>),
+}
+
+impl SearchMask {
+    /// The rectangle spanned by two opposite corners, given in any order.
+    pub fn rect(a: CellPos, b: CellPos) -> SearchMask {
+        let (CellPos(x0, y0), CellPos(x1, y1)) = (a, b);
+        SearchMask::Rect {
+            min: CellPos(x0.min(x1), y0.min(y1)),
+            max: CellPos(x0.max(x1), y0.max(y1)),
+        }
+    }
+
+    /// Every cell within `radius` steps of `center` along both axes, e.g. to
+    /// bound a replan around an edited cell.
+    pub fn around(center: CellPos, radius: u32) -> SearchMask {
+        let CellPos(x, y) = center;
+        let radius = radius as i32;
+        SearchMask::rect(CellPos(x - radius, y - radius), CellPos(x + radius, y + radius))
+    }
+
+    pub fn contains(&self, cell_pos: CellPos) -> bool {
+        match self {
+            SearchMask::Rect { min, max } => {
+                let CellPos(x, y) = cell_pos;
+                (min.0..=max.0).contains(&x) && (min.1..=max.1).contains(&y)
+            }
+            SearchMask::Cells(cells) => cells.contains(&cell_pos),
+        }
+    }
+}
+
+impl FromIterator<CellPos> for SearchMask {
+    fn from_iter<I: IntoIterator<Item = CellPos>>(cells: I) -> Self {
+        SearchMask::Cells(cells.into_iter().collect())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StepResult {
+    /// The expansion budget ran out before the search finished.
+    Running,
+    Found(Path),
+    NoPath,
+}
+
+/// Estimates the cost left to the goal of a grid search, taking portals into account.
+#[derive(Debug, Clone)]
+struct GridEstimate {
+    connectivity: Connectivity,
+    heuristic: Heuristic,
+    portal_entries: Vec<CellPos>,
+    portal_exits: Vec<CellPos>,
+    cheapest_jump: f32,
+    /// Lower bound on the cost left after taking any portal: the cheapest jump plus
+    /// the distance from the closest exit to the goal.
+    portal_exit_bound: f32,
+}
+
+impl GridEstimate {
+    fn new(grid: &Grid, heuristic: Heuristic, goal: CellPos) -> Self {
+        let mut estimate = GridEstimate {
+            connectivity: grid.connectivity(),
+            heuristic,
+            portal_entries: Vec::new(),
+            portal_exits: Vec::new(),
+            cheapest_jump: f32::INFINITY,
+            portal_exit_bound: f32::INFINITY,
+        };
+
+        for (entry, portal) in grid.portals() {
+            estimate.portal_entries.push(entry);
+            estimate.portal_exits.push(portal.exit);
+            estimate.cheapest_jump = estimate.cheapest_jump.min(portal.cost);
+        }
+        estimate.bound_portal_exits(goal);
+        estimate
+    }
+
+    fn with_heuristic(mut self, heuristic: Heuristic, goal: CellPos) -> Self {
+        self.heuristic = match self.connectivity {
+            Connectivity::Hex => Heuristic::Connectivity,
+            Connectivity::Four | Connectivity::Eight => heuristic,
+        };
+        self.bound_portal_exits(goal);
+        self
+    }
+
+    fn bound_portal_exits(&mut self, goal: CellPos) {
+        let closest_exit = self
+            .portal_exits
+            .iter()
+            .map(|&exit| self.heuristic.estimate(self.connectivity, exit, goal))
+            .fold(f32::INFINITY, f32::min);
+
+        self.portal_exit_bound = self.cheapest_jump + closest_exit;
+    }
+
+    /// The heuristic's estimate of the cost to the goal, lowered where walking to a
+    /// portal could be cheaper, so portals don't make it overestimate.
+    fn h_score(&self, from: CellPos, goal: CellPos) -> f32 {
+        let direct = self.heuristic.estimate(self.connectivity, from, goal);
+
+        if self.portal_entries.is_empty() {
+            return direct;
+        }
+
+        let via_portal = self
+            .portal_entries
+            .iter()
+            .map(|&entry| self.heuristic.estimate(self.connectivity, from, entry))
+            .fold(f32::INFINITY, f32::min)
+            + self.portal_exit_bound;
+
+        direct.min(via_portal)
+    }
+}
+
+/// Neighbors the cost function allows stepping onto, plus the far end of a
+/// portal standing on `cell_pos`.
+pub(crate) fn grid_successors<'a>(grid: &'a Grid, cost: &'a impl StepCost, cell_pos: CellPos) -> impl Iterator<Item = (CellPos, f32)> + 'a {
+    let steps = grid.neighbors(cell_pos, grid.connectivity()).filter_map(move |(neighbor, length)| {
+        let step_cost = cost.step_cost(grid, cell_pos, neighbor)?;
+        Some((neighbor, length * step_cost))
+    });
+
+    let jump = grid
+        .portal(cell_pos)
+        .filter(|portal| grid.cell(portal.exit).is_ok_and(|cell| !cell.is_wall))
+        .map(|portal| (portal.exit, portal.cost));
+
+    steps.chain(jump)
+}
+
+/// The cells of a grid as a [`SearchGraph`], with a search's step costs, mask and
+/// estimate.
+struct GridGraph<'a, C: StepCost> {
+    grid: &'a Grid,
+    cost: &'a C,
+    mask: Option<&'a SearchMask>,
+    estimate: &'a GridEstimate,
+}
+
+impl<C: StepCost> SearchGraph for GridGraph<'_, C> {
+    type Node = CellPos;
+
+    fn successors(&self, cell_pos: CellPos) -> impl Iterator<Item = (CellPos, f32)> + '_ {
+        grid_successors(self.grid, self.cost, cell_pos)
+            .filter(|&(neighbor, _)| self.mask.is_none_or(|mask| mask.contains(neighbor)))
+    }
+
+    fn heuristic(&self, cell_pos: CellPos, goal: CellPos) -> f32 {
+        self.estimate.h_score(cell_pos, goal)
+    }
+}
+
+impl From<GraphPath<CellPos>> for Path {
+    fn from(path: GraphPath<CellPos>) -> Self {
+        Path { cells: path.nodes, cost: path.cost, nodes_expanded: path.nodes_expanded }
+    }
+}
+
+/// A search that can be advanced a few expansions at a time and inspected in
+/// between. It doesn't borrow the grid, so it can live in a component across
+/// frames; every call to [`Search::step`] must be given the same grid.
+#[derive(Debug, Clone)]
+pub struct Search {
+    graph_search: GraphSearch<CellPos>,
+    estimate: GridEstimate,
+    mask: Option<SearchMask>,
+    result: Option<StepResult>,
+}
+
+impl Search {
+    pub fn new(grid: &Grid, planner: Planner, start: CellPos, goal: CellPos) -> Self {
+        let mut search = Search {
+            graph_search: GraphSearch::new(planner, start, goal),
+            estimate: GridEstimate::new(grid, Heuristic::Connectivity, goal),
+            mask: None,
+            result: None,
+        };
+
+        if !grid.contains_pos(start) || !grid.contains_pos(goal) {
+            search.result = Some(StepResult::NoPath);
+        }
+
+        search
+    }
+
+    /// Keeps the search inside `mask`. Must be applied before the first step.
+    pub fn with_mask(mut self, mask: SearchMask) -> Self {
+        if !mask.contains(self.start()) || !mask.contains(self.goal()) {
+            self.result = Some(StepResult::NoPath);
+        }
+        self.mask = Some(mask);
+        self
+    }
+
+    /// Estimates the cost left with `heuristic` instead of the grid's connectivity,
+    /// except on hex grids, see [`Heuristic`]. Must be applied before the first step.
+    pub fn with_heuristic(mut self, heuristic: Heuristic) -> Self {
+        let goal = self.goal();
+        self.estimate = self.estimate.with_heuristic(heuristic, goal);
+        self
+    }
+
+    pub fn mask(&self) -> Option<&SearchMask> {
+        self.mask.as_ref()
+    }
+
+    pub fn planner(&self) -> Planner {
+        self.graph_search.planner()
+    }
+
+    pub fn heuristic(&self) -> Heuristic {
+        self.estimate.heuristic
+    }
+
+    pub fn start(&self) -> CellPos {
+        self.graph_search.start()
+    }
+
+    pub fn goal(&self) -> CellPos {
+        self.graph_search.goal()
+    }
+
+    pub fn nodes_expanded(&self) -> usize {
+        self.graph_search.nodes_expanded()
+    }
+
+    /// Every cell expanded so far, in the order they were expanded.
+    pub fn expansion_order(&self) -> &[CellPos] {
+        self.graph_search.expansion_order()
+    }
+
+    /// Cells waiting to be expanded, without duplicates.
+    pub fn open_len(&self) -> usize {
+        self.graph_search.open_len()
+    }
+
+    /// Most cells that were waiting to be expanded at once so far.
+    pub fn peak_open(&self) -> usize {
+        self.graph_search.peak_open()
+    }
+
+    /// The final result, once the search has finished.
+    pub fn result(&self) -> Option<&StepResult> {
+        self.result.as_ref()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.result.is_some()
+    }
+
+    /// Cells waiting to be expanded. May contain duplicates of cells whose cost
+    /// improved after they were first queued.
+    pub fn open_cells(&self) -> impl Iterator<Item = CellPos> + '_ {
+        self.graph_search.open_nodes()
+    }
+
+    pub fn is_open(&self, cell_pos: CellPos) -> bool {
+        self.graph_search.is_open(cell_pos)
+    }
+
+    pub fn is_closed(&self, cell_pos: CellPos) -> bool {
+        self.graph_search.is_closed(cell_pos)
+    }
+
+    pub fn closed_cells(&self) -> impl Iterator<Item = CellPos> + '_ {
+        self.graph_search.closed_nodes()
+    }
+
+    /// Every cell the search has reached, open or closed.
+    pub fn reached_cells(&self) -> impl Iterator<Item = CellPos> + '_ {
+        self.graph_search.reached_nodes()
+    }
+
+    pub fn g_score(&self, cell_pos: CellPos) -> Option<f32> {
+        self.graph_search.g_score(cell_pos)
+    }
+
+    /// The cell `cell_pos` was best reached from so far; `None` for the start and
+    /// for cells not reached yet.
+    pub fn came_from(&self, cell_pos: CellPos) -> Option<CellPos> {
+        self.graph_search.came_from(cell_pos)
+    }
+
+    /// Every reached cell but the start, with the cell it was best reached from:
+    /// the search tree.
+    pub fn parents(&self) -> impl Iterator<Item = (CellPos, CellPos)> + '_ {
+        self.graph_search.parents()
+    }
+
+    /// The search's estimate of the cost left from `cell_pos` to the goal.
+    pub fn h_score(&self, cell_pos: CellPos) -> f32 {
+        self.estimate.h_score(cell_pos, self.goal())
+    }
+
+    /// Cost so far plus the estimate left, for cells the search has reached.
+    pub fn f_score(&self, cell_pos: CellPos) -> Option<f32> {
+        Some(self.g_score(cell_pos)? + self.h_score(cell_pos))
+    }
+
+    /// The most promising frontier cell, where the search would continue from.
+    pub fn best_frontier(&mut self) -> Option<CellPos> {
+        self.graph_search.best_frontier()
+    }
+
+    /// Expands up to `max_expansions` cells.
+    pub fn step(&mut self, grid: &Grid, cost: &impl StepCost, max_expansions: usize) -> StepResult {
+        if let Some(result) = &self.result {
+            return result.clone();
+        }
+
+        let graph = GridGraph { grid, cost, mask: self.mask.as_ref(), estimate: &self.estimate };
+        let result = match self.graph_search.step(&graph, max_expansions) {
+            GraphStepResult::Running => return StepResult::Running,
+            GraphStepResult::Found(path) => StepResult::Found(path.into()),
+            GraphStepResult::NoPath => StepResult::NoPath,
+        };
+
+        self.result = Some(result.clone());
+        result
+    }
+
+    /// Runs until the search finishes.
+    pub fn run(&mut self, grid: &Grid, cost: &impl StepCost) -> StepResult {
+        self.step(grid, cost, usize::MAX)
+    }
+
+    /// Runs until the search finishes, as the outcome of a [`Pathfinder`] search.
+    pub fn finish(&mut self, grid: &Grid, cost: &impl StepCost) -> SearchOutcome {
+        match self.run(grid, cost) {
+            StepResult::Found(path) => SearchOutcome::Found(path),
+            _ => SearchOutcome::NoPath { nodes_expanded: self.nodes_expanded() },
+        }
+    }
+
+    /// The path this search found so far to `end`, which must have been reached.
+    pub fn path_to(&self, end: CellPos) -> Path {
+        self.graph_search.path_to(end).into()
+    }
+}
+
+/// A [`Search`] together with the grid and step costs it runs on, so it can be
+/// stepped by expansion count alone, e.g. one expansion per key press while
+/// debugging. Borrows both, so unlike [`Search`] it can't be kept across frames.
+pub struct PathfinderState<'a, C: StepCost = UniformCost> {
+    grid: &'a Grid,
+    cost: &'a C,
+    search: Search,
+}
+
+impl<'a, C: StepCost> PathfinderState<'a, C> {
+    pub fn new(grid: &'a Grid, cost: &'a C, search: Search) -> Self {
+        PathfinderState { grid, cost, search }
+    }
+
+    pub fn grid(&self) -> &'a Grid {
+        self.grid
+    }
+
+    /// The search so far, for its open and closed sets, scores and result.
+    pub fn search(&self) -> &Search {
+        &self.search
+    }
+
+    pub fn into_search(self) -> Search {
+        self.search
+    }
+
+    pub fn open_cells(&self) -> impl Iterator<Item = CellPos> + '_ {
+        self.search.open_cells()
+    }
+
+    pub fn closed_cells(&self) -> impl Iterator<Item = CellPos> + '_ {
+        self.search.closed_cells()
+    }
+
+    /// The most promising frontier cell, where the next step continues from.
+    pub fn best_frontier(&mut self) -> Option<CellPos> {
+        self.search.best_frontier()
+    }
+
+    /// Expands up to `max_expansions` cells.
+    pub fn step(&mut self, max_expansions: usize) -> StepResult {
+        self.search.step(self.grid, self.cost, max_expansions)
+    }
+
+    /// Runs until the search finishes.
+    pub fn run(&mut self) -> StepResult {
+        self.search.run(self.grid, self.cost)
+    }
+}
+
+pub struct AStar<'a, C: StepCost = UniformCost> {
+    grid: &'a Grid,
+    cost: C,
+    heuristic: Heuristic,
+    mask: Option<SearchMask>,
+    search_radius: Option<u32>,
+}
+
+impl<'a> AStar<'a> {
+    pub fn new(grid: &'a Grid) -> AStar<'a> {
+        AStar::with_cost(grid, UniformCost)
+    }
+}
+
+impl<'a, C: StepCost> AStar<'a, C> {
+    pub fn with_cost(grid: &'a Grid, cost: C) -> AStar<'a, C> {
+        AStar { grid, cost, heuristic: Heuristic::Connectivity, mask: None, search_radius: None }
+    }
+
+    pub fn with_heuristic(mut self, heuristic: Heuristic) -> Self {
+        self.heuristic = heuristic;
+        self
+    }
+
+    /// Only explores cells inside `mask`, such as a single room.
+    pub fn with_mask(mut self, mask: SearchMask) -> Self {
+        self.mask = Some(mask);
+        self
+    }
+
+    /// Only explores cells within `radius` steps of the start along both axes,
+    /// unless a mask is set. Path queries on unbounded grids need a bound like
+    /// this, or searching for an unreachable goal never ends.
+    pub fn with_search_radius(mut self, radius: u32) -> Self {
+        self.search_radius = Some(radius);
+        self
+    }
+
+    fn search(&self, start: CellPos, goal: CellPos) -> Search {
+        let search = Search::new(self.grid, Planner::AStar, start, goal).with_heuristic(self.heuristic);
+        match (&self.mask, self.search_radius) {
+            (Some(mask), _) => search.with_mask(mask.clone()),
+            (None, Some(radius)) => search.with_mask(SearchMask::around(start, radius)),
+            (None, None) => search,
+        }
+    }
+
+    /// A search from `start` to `goal` with this query's step costs, heuristic
+    /// and mask, not yet stepped.
+    pub fn start_search(&self, start: CellPos, goal: CellPos) -> PathfinderState<'_, C> {
+        PathfinderState::new(self.grid, &self.cost, self.search(start, goal))
+    }
+
+    /// Searches for the cheapest path from `start` to `goal`, returning `None` if
+    /// the goal can't be reached.
+    pub fn find_path(&mut self, start: CellPos, goal: CellPos) -> Option<Path> {
+        match self.search(start, goal).run(self.grid, &self.cost) {
+            StepResult::Found(path) => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Every cell that can be reached from `start` for at most `budget`, such
+    /// as the cells a unit can move to this turn. Explores like Dijkstra, with
+    /// the same step costs and mask as the path queries.
+    pub fn reachable_within(&self, start: CellPos, budget: f32) -> MovementRange {
+        let mut range = MovementRange {
+            start,
+            budget,
+            costs: HashMap::new(),
+            came_from: HashMap::new(),
+        };
+
+        if !self.grid.contains_pos(start) || self.mask.as_ref().is_some_and(|mask| !mask.contains(start)) {
+            return range;
+        }
+
+        let mut open_set = BinaryHeap::from([OpenNode { priority: 0.0, g_score: 0.0, node: start }]);
+        let mut closed_set = HashSet::new();
+        range.costs.insert(start, 0.0);
+
+        while let Some(OpenNode { g_score, node: cell_pos, .. }) = open_set.pop() {
+            if !closed_set.insert(cell_pos) {
+                continue;
+            }
+
+            for (neighbor, step_cost) in grid_successors(self.grid, &self.cost, cell_pos) {
+                if self.mask.as_ref().is_some_and(|mask| !mask.contains(neighbor)) {
+                    continue;
+                }
+
+                let tentative_g_score = g_score + step_cost;
+                if tentative_g_score > budget {
+                    continue;
+                }
+
+                if range.costs.get(&neighbor).is_none_or(|&g| tentative_g_score < g) {
+                    range.costs.insert(neighbor, tentative_g_score);
+                    range.came_from.insert(neighbor, cell_pos);
+                    open_set.push(OpenNode {
+                        priority: tentative_g_score,
+                        g_score: tentative_g_score,
+                        node: neighbor,
+                    });
+                }
+            }
+        }
+
+        range
+    }
+
+    /// The cheapest next step towards `goal` from every cell that can reach it,
+    /// for steering many agents to one goal without a search each. Explores
+    /// backwards from the goal like Dijkstra, with the same step costs and mask
+    /// as the path queries; portals are not taken.
+    pub fn flow_field(&self, goal: CellPos) -> FlowField {
+        let mut field = FlowField { goal, costs: HashMap::new(), next: HashMap::new() };
+
+        if !self.grid.contains_pos(goal) || self.mask.as_ref().is_some_and(|mask| !mask.contains(goal)) {
+            return field;
+        }
+
+        let mut open_set = BinaryHeap::from([OpenNode { priority: 0.0, g_score: 0.0, node: goal }]);
+        let mut closed_set = HashSet::new();
+        field.costs.insert(goal, 0.0);
+
+        while let Some(OpenNode { g_score, node: cell_pos, .. }) = open_set.pop() {
+            if !closed_set.insert(cell_pos) {
+                continue;
+            }
+
+            for &(dx, dy) in self.grid.connectivity().offsets(cell_pos) {
+                let neighbor = CellPos(cell_pos.0 + dx, cell_pos.1 + dy);
+                if !self.grid.cell(neighbor).is_ok_and(|cell| !cell.is_wall)
+                    || self.mask.as_ref().is_some_and(|mask| !mask.contains(neighbor))
+                {
+                    continue;
+                }
+
+                // The step back from the neighbor, which may cost differently or not be allowed.
+                let Some((_, step_cost)) =
+                    grid_successors(self.grid, &self.cost, neighbor).find(|&(to, _)| to == cell_pos)
+                else {
+                    continue;
+                };
+
+                let tentative_g_score = g_score + step_cost;
+                if field.costs.get(&neighbor).is_none_or(|&g| tentative_g_score < g) {
+                    field.costs.insert(neighbor, tentative_g_score);
+                    field.next.insert(neighbor, cell_pos);
+                    open_set.push(OpenNode {
+                        priority: tentative_g_score,
+                        g_score: tentative_g_score,
+                        node: neighbor,
+                    });
+                }
+            }
+        }
+
+        field
+    }
+
+    /// Like [`AStar::find_path`], but gives up after expanding `max_expansions`
+    /// nodes and returns the path to the most promising frontier cell (lowest
+    /// f-score) instead. Returns `None` if the goal is unreachable.
+    pub fn find_partial_path(&mut self, start: CellPos, goal: CellPos, max_expansions: usize) -> Option<PartialPath> {
+        let mut state = self.start_search(start, goal);
+
+        match state.step(max_expansions.max(1)) {
+            StepResult::Found(path) => Some(PartialPath { path, reaches_goal: true }),
+            StepResult::NoPath => None,
+            StepResult::Running => {
+                let frontier = state.best_frontier()?;
+                Some(PartialPath { path: state.search().path_to(frontier), reaches_goal: false })
+            }
+        }
+    }
+}
+
+/// Every cell reachable from a start for at most a movement budget, from
+/// [`AStar::reachable_within`].
+#[derive(Debug, Clone)]
+pub struct MovementRange {
+    start: CellPos,
+    budget: f32,
+    costs: HashMap<CellPos, f32>,
+    came_from: HashMap<CellPos, CellPos>,
+}
+
+impl MovementRange {
+    pub fn start(&self) -> CellPos {
+        self.start
+    }
+
+    pub fn budget(&self) -> f32 {
+        self.budget
+    }
+
+    pub fn contains(&self, cell_pos: CellPos) -> bool {
+        self.costs.contains_key(&cell_pos)
+    }
+
+    /// Cheapest cost of reaching `cell_pos`, if it is in range.
+    pub fn cost(&self, cell_pos: CellPos) -> Option<f32> {
+        self.costs.get(&cell_pos).copied()
+    }
+
+    /// Every cell in range with its cost, the start included, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (CellPos, f32)> + '_ {
+        self.costs.iter().map(|(&cell_pos, &cost)| (cell_pos, cost))
+    }
+
+    pub fn len(&self) -> usize {
+        self.costs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.costs.is_empty()
+    }
+
+    /// The cheapest path from the start to `cell_pos`, if it is in range.
+    pub fn path_to(&self, cell_pos: CellPos) -> Option<Path> {
+        let cost = self.cost(cell_pos)?;
+
+        let mut cells = vec![cell_pos];
+        let mut current = cell_pos;
+        while let Some(&previous) = self.came_from.get(&current) {
+            cells.push(previous);
+            current = previous;
+        }
+        cells.reverse();
+
+        Some(Path { cells, cost, nodes_expanded: self.costs.len() })
+    }
+}
+
+/// The cheapest next step towards a goal from every cell that can reach it, from
+/// [`AStar::flow_field`].
+#[derive(Debug, Clone)]
+pub struct FlowField {
+    goal: CellPos,
+    costs: HashMap<CellPos, f32>,
+    next: HashMap<CellPos, CellPos>,
+}
+
+impl FlowField {
+    pub fn goal(&self) -> CellPos {
+        self.goal
+    }
+
+    pub fn contains(&self, cell_pos: CellPos) -> bool {
+        self.costs.contains_key(&cell_pos)
+    }
+
+    /// Cheapest cost of reaching the goal from `cell_pos`, if it can.
+    pub fn cost(&self, cell_pos: CellPos) -> Option<f32> {
+        self.costs.get(&cell_pos).copied()
+    }
+
+    /// The cell to step to from `cell_pos`, `None` on the goal and on cells that
+    /// can't reach it.
+    pub fn next(&self, cell_pos: CellPos) -> Option<CellPos> {
+        self.next.get(&cell_pos).copied()
+    }
+
+    /// Every cell that can reach the goal with its next step, the goal excluded,
+    /// in no particular order.
+    pub fn steps(&self) -> impl Iterator<Item = (CellPos, CellPos)> + '_ {
+        self.next.iter().map(|(&cell_pos, &next)| (cell_pos, next))
+    }
+
+    pub fn len(&self) -> usize {
+        self.costs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.costs.is_empty()
+    }
+
+    /// The cheapest path from `cell_pos` to the goal, if it can reach it.
+    pub fn path_from(&self, cell_pos: CellPos) -> Option<Path> {
+        let cost = self.cost(cell_pos)?;
+
+        let mut cells = vec![cell_pos];
+        let mut current = cell_pos;
+        while let Some(next) = self.next(current) {
+            cells.push(next);
+            current = next;
+        }
+
+        Some(Path { cells, cost, nodes_expanded: self.costs.len() })
+    }
+}
+
+impl Grids<'_, '_> {
+    /// Runs A* on the grid held by `grid_entity`, skipping the search when its
+    /// region labels already rule the goal out.
+    pub fn find_path(&self, grid_entity: Entity, start: CellPos, goal: CellPos) -> Result<Option<Path>, GridNotFound> {
+        let grid = self.get(grid_entity)?;
+
+        if self.regions(grid_entity).is_some_and(|regions| !regions.is_reachable(start, goal)) {
+            return Ok(None);
+        }
+
+        Ok(AStar::new(grid).find_path(start, goal))
+    }
+}
@@ -0,0 +1,283 @@
+//! Rectangles of cells lifted out of a grid, to be stamped elsewhere.
+//!
+//! A [`CellPattern`] keeps only cells, not terrain or other layers, and can be
+//! turned and mirrored before it is stamped. The wall painting tools use one as
+//! their clipboard.
+//!
+//! The [`PatternLibrary`] resource holds named patterns to stamp, such as walls
+//! and rooms to build test maps from. It starts with a few shapes, and more are
+//! read from pattern files: each pattern is a `[name]` line followed by its rows
+//! from top to bottom, `#` for walls and `.` for floor. Blank lines and lines
+//! starting with `//` are skipped.
+//!
+//! ```text
+//! // A wall with a gap in the middle.
+//! [gate]
+//! ###.###
+//! ```
+
+use std::{
+    error::Error,
+    fmt::Display,
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::Path,
+};
+
+use bevy::prelude::*;
+
+use crate::grid::{Cell, CellPos, Grid, OutOfBounds};
+
+/// A rectangle of cells, indexed from its bottom left corner.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CellPattern {
+    width: u32,
+    height: u32,
+    cells: Vec<Cell>,
+}
+
+impl CellPattern {
+    /// A pattern drawn as `rows` from top to bottom, `#` for walls and `.` for
+    /// floor, as in pattern files. Rows must all be as wide.
+    pub fn from_rows<'a>(rows: impl IntoIterator<Item = &'a str>) -> Result<Self, String> {
+        let rows: Vec<&str> = rows.into_iter().collect();
+        let width = rows.first().map_or(0, |row| row.chars().count());
+        if rows.iter().any(|row| row.chars().count() != width) {
+            return Err("rows of different widths".to_string());
+        }
+
+        // Rows are drawn top first, while patterns are indexed from the bottom.
+        let mut cells = Vec::with_capacity(width * rows.len());
+        for row in rows.iter().rev() {
+            for c in row.chars() {
+                cells.push(match c {
+                    '#' => Cell::wall(),
+                    '.' => Cell::floor(),
+                    _ => return Err(format!("unexpected cell {c:?}")),
+                });
+            }
+        }
+
+        Ok(CellPattern { width: width as u32, height: rows.len() as u32, cells })
+    }
+
+    /// A `width` by `height` pattern of `fill`.
+    pub fn new(width: u32, height: u32, fill: Cell) -> Self {
+        CellPattern { width, height, cells: vec![fill; (width * height) as usize] }
+    }
+
+    /// The cells of `grid` in the rectangle with corners `from` and `to`, both
+    /// included. The part of the rectangle beyond the grid is left out.
+    pub fn copy(grid: &Grid, from: CellPos, to: CellPos) -> Self {
+        let max = CellPos(grid.width() as i32 - 1, grid.height() as i32 - 1);
+        let min_x = from.0.min(to.0).clamp(0, max.0.max(0));
+        let min_y = from.1.min(to.1).clamp(0, max.1.max(0));
+        let max_x = from.0.max(to.0).min(max.0);
+        let max_y = from.1.max(to.1).min(max.1);
+        if max_x < min_x || max_y < min_y {
+            return CellPattern::new(0, 0, Cell::floor());
+        }
+
+        let cells = (min_y..=max_y)
+            .flat_map(|y| (min_x..=max_x).map(move |x| CellPos(x, y)))
+            .map(|cell_pos| grid.cell(cell_pos).expect("clamped to the grid"))
+            .collect();
+
+        CellPattern { width: (max_x - min_x + 1) as u32, height: (max_y - min_y + 1) as u32, cells }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// The cell at `cell_pos` from the bottom left corner, if it is in the pattern.
+    pub fn cell(&self, cell_pos: CellPos) -> Option<Cell> {
+        let CellPos(x, y) = cell_pos;
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return None;
+        }
+        Some(self.cells[(y as u32 * self.width + x as u32) as usize])
+    }
+
+    pub fn set_cell(&mut self, cell_pos: CellPos, cell: Cell) -> Result<&mut Self, OutOfBounds> {
+        let CellPos(x, y) = cell_pos;
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return Err(OutOfBounds { cell_pos });
+        }
+        self.cells[(y as u32 * self.width + x as u32) as usize] = cell;
+        Ok(self)
+    }
+
+    /// The pattern turned a quarter counterclockwise, as grids are drawn with y up.
+    pub fn rotated(&self) -> Self {
+        let (width, height) = (self.height, self.width);
+        let cells = (0..height as i32)
+            .flat_map(|y| (0..width as i32).map(move |x| CellPos(x, y)))
+            .map(|CellPos(x, y)| self.cell(CellPos(y, self.height as i32 - 1 - x)).expect("in the turned bounds"))
+            .collect();
+
+        CellPattern { width, height, cells }
+    }
+
+    /// The pattern flipped left to right.
+    pub fn mirrored(&self) -> Self {
+        let cells = (0..self.height as i32)
+            .flat_map(|y| (0..self.width as i32).map(move |x| CellPos(x, y)))
+            .map(|CellPos(x, y)| self.cell(CellPos(self.width as i32 - 1 - x, y)).expect("in bounds"))
+            .collect();
+
+        CellPattern { width: self.width, height: self.height, cells }
+    }
+
+    /// Each cell of the pattern with its position once stamped with its bottom
+    /// left corner on `origin`, including positions beyond any grid.
+    pub fn cells_at(&self, origin: CellPos) -> impl Iterator<Item = (CellPos, Cell)> + '_ {
+        let width = self.width as i32;
+        self.cells.iter().enumerate().map(move |(index, &cell)| {
+            let (x, y) = (index as i32 % width, index as i32 / width);
+            (CellPos(origin.0 + x, origin.1 + y), cell)
+        })
+    }
+}
+
+/// Why a pattern file could not be read.
+#[derive(Debug)]
+pub enum PatternError {
+    Io(io::Error),
+    Parse { line: usize, message: String },
+}
+
+impl Display for PatternError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PatternError::Io(err) => write!(f, "pattern file io error: {err}"),
+            PatternError::Parse { line, message } => write!(f, "pattern file line {line}: {message}"),
+        }
+    }
+}
+impl Error for PatternError {}
+
+impl From<io::Error> for PatternError {
+    fn from(err: io::Error) -> Self {
+        PatternError::Io(err)
+    }
+}
+
+/// Named patterns to stamp, in the order they were added.
+#[derive(Resource, Debug, Clone)]
+pub struct PatternLibrary {
+    patterns: Vec<(String, CellPattern)>,
+}
+
+impl Default for PatternLibrary {
+    /// An L-shaped wall, a U-shaped trap, a corridor and a room with a door.
+    fn default() -> Self {
+        let shapes: [(&str, &[&str]); 4] = [
+            ("l-wall", &["#....", "#....", "#....", "#####"]),
+            ("u-trap", &["#...#", "#...#", "#...#", "#####"]),
+            ("corridor", &["#######", ".......", "#######"]),
+            ("room", &["###.###", "#.....#", "#.....#", "#.....#", "#######"]),
+        ];
+
+        let mut library = PatternLibrary::new();
+        for (name, rows) in shapes {
+            library.add(name, CellPattern::from_rows(rows.iter().copied()).expect("built-in patterns are valid"));
+        }
+        library
+    }
+}
+
+impl PatternLibrary {
+    /// A library without any pattern.
+    pub fn new() -> Self {
+        PatternLibrary { patterns: Vec::new() }
+    }
+
+    pub fn with_pattern(mut self, name: impl Into<String>, pattern: CellPattern) -> Self {
+        self.add(name, pattern);
+        self
+    }
+
+    /// Adds `pattern` under `name`, replacing the pattern of that name if there
+    /// is one.
+    pub fn add(&mut self, name: impl Into<String>, pattern: CellPattern) -> &mut Self {
+        let name = name.into();
+        match self.patterns.iter_mut().find(|(existing, _)| *existing == name) {
+            Some((_, existing)) => *existing = pattern,
+            None => self.patterns.push((name, pattern)),
+        }
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CellPattern> {
+        self.patterns.iter().find(|(existing, _)| existing == name).map(|(_, pattern)| pattern)
+    }
+
+    pub fn len(&self) -> usize {
+        self.patterns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// The name and pattern at `index`, in the order they were added.
+    pub fn nth(&self, index: usize) -> Option<(&str, &CellPattern)> {
+        self.patterns.get(index).map(|(name, pattern)| (name.as_str(), pattern))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &CellPattern)> + '_ {
+        self.patterns.iter().map(|(name, pattern)| (name.as_str(), pattern))
+    }
+
+    /// Adds every pattern of the pattern file read from `reader`. Nothing is
+    /// added if any of them is malformed.
+    pub fn read_from(&mut self, reader: impl BufRead) -> Result<&mut Self, PatternError> {
+        // The name of each pattern, the line it is on and the rows below it.
+        let mut blocks: Vec<(String, usize, Vec<String>)> = Vec::new();
+        for (index, text) in reader.lines().enumerate() {
+            let text = text?;
+            let text = text.trim();
+            if text.is_empty() || text.starts_with("//") {
+                continue;
+            }
+
+            if let Some(name) = text.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+                blocks.push((name.trim().to_string(), index + 1, Vec::new()));
+                continue;
+            }
+            let Some((_, _, rows)) = blocks.last_mut() else {
+                let message = "rows before the first [name]".to_string();
+                return Err(PatternError::Parse { line: index + 1, message });
+            };
+            rows.push(text.to_string());
+        }
+
+        let mut read = Vec::with_capacity(blocks.len());
+        for (name, line, rows) in blocks {
+            if rows.is_empty() {
+                return Err(PatternError::Parse { line, message: format!("pattern {name:?} has no rows") });
+            }
+            let pattern = CellPattern::from_rows(rows.iter().map(String::as_str))
+                .map_err(|message| PatternError::Parse { line, message: format!("pattern {name:?}: {message}") })?;
+            read.push((name, pattern));
+        }
+        for (name, pattern) in read {
+            self.add(name, pattern);
+        }
+        Ok(self)
+    }
+
+    /// Adds every pattern of the pattern file at `path`.
+    pub fn load(&mut self, path: impl AsRef<Path>) -> Result<&mut Self, PatternError> {
+        self.read_from(BufReader::new(File::open(path)?))
+    }
+}
@@ -0,0 +1,267 @@
+//! Playing, pausing and stepping search visualizations.
+//!
+//! [`SearchVisualizer`]s advance on the ticks of the [`VisualizationClock`]
+//! resource rather than once per frame, so the clock sets how fast every
+//! visualization plays, can pause them all, and can step them one expansion at
+//! a time. [`PlaybackControlsPlugin`] drives the clock from the keyboard and
+//! from a row of buttons in the corner of the window.
+//!
+//! [`SearchVisualizer`]: crate::search_view::SearchVisualizer
+
+use std::mem;
+
+use bevy::prelude::*;
+
+use crate::bindings::InputBindings;
+
+/// Ticks are never run more than this many to a frame, so a long frame doesn't
+/// finish every search at once.
+const MAX_TICKS_PER_FRAME: usize = 8;
+
+/// When search visualizations advance. Every tick, each visualizer runs its
+/// `expansions_per_tick`; steps run a single expansion, even while paused.
+#[derive(Resource, Debug, Clone)]
+pub struct VisualizationClock {
+    pub paused: bool,
+    /// Ticks per second while playing.
+    pub ticks_per_second: f32,
+    /// Steps asked for since the last frame.
+    steps: usize,
+    /// Ticks and steps to run this frame.
+    due: (usize, usize),
+    /// Ticks run so far towards the next whole one.
+    partial_tick: f32,
+}
+
+impl Default for VisualizationClock {
+    fn default() -> Self {
+        VisualizationClock { paused: false, ticks_per_second: 60.0, steps: 0, due: (0, 0), partial_tick: 0.0 }
+    }
+}
+
+impl VisualizationClock {
+    /// Slowest and fastest playback speeds [`VisualizationClock::faster`] and
+    /// [`VisualizationClock::slower`] go to, in ticks per second.
+    pub const SPEED_RANGE: (f32, f32) = (0.5, 960.0);
+
+    pub fn toggle(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    /// Pauses and runs a single expansion of every visualizer next frame.
+    pub fn step(&mut self) {
+        self.paused = true;
+        self.steps += 1;
+    }
+
+    /// Doubles the playback speed, up to the end of [`VisualizationClock::SPEED_RANGE`].
+    pub fn faster(&mut self) {
+        self.ticks_per_second = (self.ticks_per_second * 2.0).clamp(Self::SPEED_RANGE.0, Self::SPEED_RANGE.1);
+    }
+
+    /// Halves the playback speed, down to the start of [`VisualizationClock::SPEED_RANGE`].
+    pub fn slower(&mut self) {
+        self.ticks_per_second = (self.ticks_per_second / 2.0).clamp(Self::SPEED_RANGE.0, Self::SPEED_RANGE.1);
+    }
+
+    /// Ticks due this frame.
+    pub fn ticks(&self) -> usize {
+        self.due.0
+    }
+
+    /// Single expansions due this frame.
+    pub fn steps(&self) -> usize {
+        self.due.1
+    }
+
+    /// Expansions a visualizer running `expansions_per_tick` is due this frame.
+    pub fn expansions(&self, expansions_per_tick: usize) -> usize {
+        self.ticks() * expansions_per_tick + self.steps()
+    }
+
+    /// Works out what is due for a frame of `seconds`.
+    fn advance(&mut self, seconds: f32) {
+        let ticks = match self.paused {
+            true => {
+                self.partial_tick = 0.0;
+                0
+            }
+            false => {
+                self.partial_tick += seconds * self.ticks_per_second;
+                let ticks = self.partial_tick as usize;
+                self.partial_tick = self.partial_tick.fract();
+                ticks.min(MAX_TICKS_PER_FRAME)
+            }
+        };
+
+        self.due = (ticks, mem::take(&mut self.steps));
+    }
+}
+
+pub(crate) fn advance_visualization_clock(time: Res<Time>, mut clock: ResMut<VisualizationClock>) {
+    clock.advance(time.delta_seconds());
+}
+
+/// Controls the [`VisualizationClock`]: space plays and pauses, period steps,
+/// and minus and equals halve and double the speed, unless the `InputBindings`
+/// say otherwise. The same controls are
+/// shown as buttons in the bottom left corner, with the current speed.
+pub struct PlaybackControlsPlugin {
+    /// Font used by the buttons, relative to the assets folder.
+    pub font: &'static str,
+}
+
+impl Default for PlaybackControlsPlugin {
+    fn default() -> Self {
+        PlaybackControlsPlugin { font: "fonts/FiraMono-Medium.ttf" }
+    }
+}
+
+impl Plugin for PlaybackControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<VisualizationClock>()
+            .init_resource::<InputBindings>()
+            .insert_resource(ControlsFont(self.font))
+            .add_startup_system(spawn_controls)
+            .add_system(playback_keys)
+            .add_system(playback_buttons)
+            .add_system(update_controls.after(playback_keys).after(playback_buttons));
+    }
+}
+
+#[derive(Resource)]
+struct ControlsFont(&'static str);
+
+/// What a playback button does to the clock.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+enum PlaybackButton {
+    Toggle,
+    Step,
+    Slower,
+    Faster,
+}
+
+impl PlaybackButton {
+    const ALL: [PlaybackButton; 4] =
+        [PlaybackButton::Toggle, PlaybackButton::Step, PlaybackButton::Slower, PlaybackButton::Faster];
+
+    fn label(self, clock: &VisualizationClock) -> &'static str {
+        match self {
+            PlaybackButton::Toggle if clock.paused => "play",
+            PlaybackButton::Toggle => "pause",
+            PlaybackButton::Step => "step",
+            PlaybackButton::Slower => "-",
+            PlaybackButton::Faster => "+",
+        }
+    }
+
+    fn press(self, clock: &mut VisualizationClock) {
+        match self {
+            PlaybackButton::Toggle => clock.toggle(),
+            PlaybackButton::Step => clock.step(),
+            PlaybackButton::Slower => clock.slower(),
+            PlaybackButton::Faster => clock.faster(),
+        }
+    }
+}
+
+/// The text showing the playback speed.
+#[derive(Component)]
+struct SpeedText;
+
+fn spawn_controls(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    font: Res<ControlsFont>,
+    clock: Res<VisualizationClock>,
+) {
+    let style = TextStyle {
+        font: asset_server.load(font.0),
+        font_size: 16.0,
+        color: Color::WHITE,
+    };
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(10.0),
+                    bottom: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            },
+            background_color: Color::NONE.into(),
+            ..default()
+        })
+        .insert(Name::new("Playback controls"))
+        .with_children(|parent| {
+            for button in PlaybackButton::ALL {
+                parent
+                    .spawn(ButtonBundle {
+                        style: Style {
+                            margin: UiRect::all(Val::Px(2.0)),
+                            padding: UiRect::all(Val::Px(4.0)),
+                            ..default()
+                        },
+                        background_color: Color::DARK_GRAY.into(),
+                        ..default()
+                    })
+                    .insert(button)
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(button.label(&clock), style.clone()));
+                    });
+            }
+            parent.spawn((TextBundle::from_section("", style.clone()), SpeedText));
+        });
+}
+
+fn playback_keys(keys: Res<Input<KeyCode>>, bindings: Res<InputBindings>, mut clock: ResMut<VisualizationClock>) {
+    if bindings.toggle_playback.just_pressed(&keys) {
+        clock.toggle();
+    }
+    if bindings.step_playback.just_pressed(&keys) {
+        clock.step();
+    }
+    if bindings.slower_playback.just_pressed(&keys) {
+        clock.slower();
+    }
+    if bindings.faster_playback.just_pressed(&keys) {
+        clock.faster();
+    }
+}
+
+fn playback_buttons(
+    mut clock: ResMut<VisualizationClock>,
+    buttons: Query<(&PlaybackButton, &Interaction), Changed<Interaction>>,
+) {
+    for (&button, &interaction) in &buttons {
+        if interaction == Interaction::Clicked {
+            button.press(&mut clock);
+        }
+    }
+}
+
+fn update_controls(
+    clock: Res<VisualizationClock>,
+    buttons: Query<(&PlaybackButton, &Children)>,
+    mut texts: Query<&mut Text, Without<SpeedText>>,
+    mut speed: Query<&mut Text, With<SpeedText>>,
+) {
+    if !clock.is_changed() {
+        return;
+    }
+
+    for (&button, children) in &buttons {
+        for &child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(child) {
+                text.sections[0].value = button.label(&clock).to_string();
+            }
+        }
+    }
+    for mut text in &mut speed {
+        text.sections[0].value = format!(" {} ticks/s", clock.ticks_per_second);
+    }
+}
@@ -0,0 +1,209 @@
+//! Two planners racing on the same query, drawn over one grid view.
+//!
+//! Both searches advance one expansion at a time in lock step, so after any
+//! number of frames they have done the same amount of work. Each one tints the
+//! cells it has touched; the first to finish is announced in the log along with
+//! how far the other one got, and the loser keeps running so its final path can
+//! be compared.
+
+use bevy::prelude::*;
+
+use crate::{
+    grid::{CellPos, Grid, Grids},
+    pathfinding::{Planner, Search, StepResult, UniformCost},
+    view::{mix, CellColors},
+};
+
+#[derive(Debug, Clone)]
+pub struct Racer {
+    pub search: Search,
+    pub tint: Color,
+    /// Lock-step tick on which the search finished.
+    finished_at: Option<usize>,
+}
+
+impl Racer {
+    pub fn finished_at(&self) -> Option<usize> {
+        self.finished_at
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RaceOutcome {
+    /// Index of the racer that finished first.
+    Winner(usize),
+    /// Both finished on the same tick.
+    Tie,
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct Race {
+    pub grid: Entity,
+    /// Lock-step ticks run per frame; each tick is one expansion per racer.
+    pub ticks_per_frame: usize,
+
+    racers: [Racer; 2],
+    ticks: usize,
+    outcome: Option<RaceOutcome>,
+}
+
+impl Race {
+    pub fn new(
+        grid_entity: Entity,
+        grid: &Grid,
+        planners: [(Planner, Color); 2],
+        start: CellPos,
+        goal: CellPos,
+    ) -> Self {
+        let racers = planners.map(|(planner, tint)| Racer {
+            search: Search::new(grid, planner, start, goal),
+            tint,
+            finished_at: None,
+        });
+
+        Race {
+            grid: grid_entity,
+            ticks_per_frame: 20,
+            racers,
+            ticks: 0,
+            outcome: None,
+        }
+    }
+
+    pub fn with_ticks_per_frame(mut self, ticks_per_frame: usize) -> Self {
+        self.ticks_per_frame = ticks_per_frame;
+        self
+    }
+
+    pub fn racers(&self) -> &[Racer; 2] {
+        &self.racers
+    }
+
+    pub fn ticks(&self) -> usize {
+        self.ticks
+    }
+
+    pub fn outcome(&self) -> Option<RaceOutcome> {
+        self.outcome
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.racers.iter().all(|racer| racer.search.is_finished())
+    }
+
+    /// Runs one lock-step tick: a single expansion for every racer still going.
+    pub fn tick(&mut self, grid: &Grid) {
+        if self.is_finished() {
+            return;
+        }
+
+        self.ticks += 1;
+
+        for racer in &mut self.racers {
+            if racer.search.is_finished() {
+                continue;
+            }
+            if racer.search.step(grid, &UniformCost, 1) != StepResult::Running {
+                racer.finished_at = Some(self.ticks);
+            }
+        }
+
+        if self.outcome.is_none() {
+            self.outcome = match (self.racers[0].finished_at, self.racers[1].finished_at) {
+                (Some(_), Some(_)) => Some(RaceOutcome::Tie),
+                (Some(_), None) => Some(RaceOutcome::Winner(0)),
+                (None, Some(_)) => Some(RaceOutcome::Winner(1)),
+                (None, None) => None,
+            };
+
+            if let Some(outcome) = self.outcome {
+                info!("{}", self.announcement(outcome));
+            }
+        }
+    }
+
+    fn announcement(&self, outcome: RaceOutcome) -> String {
+        let stats = |racer: &Racer| {
+            let search = &racer.search;
+            let name = search.planner().name();
+            let expanded = search.nodes_expanded();
+
+            match search.result() {
+                Some(StepResult::Found(path)) => format!(
+                    "{name}: path of {} cells, cost {}, {expanded} nodes expanded",
+                    path.cells.len(),
+                    path.cost,
+                ),
+                Some(StepResult::NoPath) => format!("{name}: no path, {expanded} nodes expanded"),
+                _ => format!("{name}: still running, {expanded} nodes expanded"),
+            }
+        };
+
+        let [first, second] = &self.racers;
+        let headline = match outcome {
+            RaceOutcome::Winner(index) => {
+                let name = self.racers[index].search.planner().name();
+                format!("{name} finished first after {} ticks", self.ticks)
+            }
+            RaceOutcome::Tie => format!("Tie after {} ticks", self.ticks),
+        };
+
+        format!("{headline} ({}; {})", stats(first), stats(second))
+    }
+
+    /// Color the race paints over `cell_pos`, if either racer has touched it.
+    pub fn cell_tint(&self, cell_pos: CellPos) -> Option<Color> {
+        let tints: Vec<_> = self
+            .racers
+            .iter()
+            .filter_map(|racer| racer_tint(racer, cell_pos))
+            .collect();
+
+        match tints.as_slice() {
+            [] => None,
+            [tint] => Some(*tint),
+            [a, b, ..] => Some(mix(*a, *b, 0.5)),
+        }
+    }
+}
+
+/// Full tint on the frontier, darker once expanded, lighter along a found path.
+fn racer_tint(racer: &Racer, cell_pos: CellPos) -> Option<Color> {
+    let search = &racer.search;
+
+    if let Some(StepResult::Found(path)) = search.result() {
+        if path.cells.contains(&cell_pos) {
+            return Some(mix(racer.tint, Color::WHITE, 0.6));
+        }
+    }
+
+    if search.is_open(cell_pos) {
+        Some(racer.tint)
+    } else if search.is_closed(cell_pos) {
+        Some(mix(racer.tint, Color::BLACK, 0.5))
+    } else {
+        None
+    }
+}
+
+pub(crate) fn advance_races(grids: Grids, mut races: Query<&mut Race>) {
+    for mut race in &mut races {
+        let Ok(grid) = grids.get(race.grid) else {
+            continue;
+        };
+
+        for _ in 0..race.ticks_per_frame {
+            race.tick(grid);
+        }
+    }
+}
+
+/// Paints race progress over the cell colors; runs after the views reset them.
+pub(crate) fn tint_race_cells(races: Query<&Race>, mut cell_colors: Query<&mut CellColors>) {
+    for race in &races {
+        if let Ok(mut colors) = cell_colors.get_mut(race.grid) {
+            let reached = race.racers.iter().flat_map(|racer| racer.search.reached_cells());
+            colors.tint_cells(reached, |cell_pos, _| race.cell_tint(cell_pos));
+        }
+    }
+}
@@ -0,0 +1,40 @@
+//! Highlighting a unit's movement range on the grid.
+
+use bevy::prelude::*;
+
+use crate::{editor::CellOverlay, grid::CellPos, pathfinding::MovementRange, theme::GridTheme, view::mix};
+
+/// Tints the cells of `range` on the texture of `grid`, fading towards the edge
+/// of the budget so the cheapest moves stand out.
+#[derive(Component, Debug, Clone)]
+pub struct RangeHighlight {
+    pub grid: Entity,
+    pub range: MovementRange,
+    pub color: Color,
+}
+
+impl RangeHighlight {
+    pub fn new(grid: Entity, range: MovementRange, color: Color) -> Self {
+        RangeHighlight { grid, range, color }
+    }
+}
+
+impl CellOverlay for RangeHighlight {
+    fn grid(&self) -> Entity {
+        self.grid
+    }
+
+    fn cell_tint(&self, cell_pos: CellPos, base: Color, _theme: &GridTheme) -> Option<Color> {
+        let cost = self.range.cost(cell_pos)?;
+        let remaining = match self.range.budget() > 0.0 {
+            true => 1.0 - cost / self.range.budget(),
+            false => 1.0,
+        };
+
+        Some(mix(base, self.color, 0.4 + 0.4 * remaining))
+    }
+
+    fn tinted_cells(&self) -> Option<Vec<CellPos>> {
+        Some(self.range.iter().map(|(cell_pos, _)| cell_pos).collect())
+    }
+}
@@ -0,0 +1,323 @@
+//! Connected-component labels for the walkable cells of a grid.
+//!
+//! Every floor cell carries a region label, and two cells can only reach each
+//! other if their labels resolve to the same region, so hopeless queries can be
+//! rejected without running a search. Opening a cell merges the regions around
+//! it through a union-find; closing one re-floods the region it belonged to,
+//! since union-find can't split.
+//!
+//! Regions ignore one-way exits, so a shared region means "maybe reachable"
+//! while different regions always mean "unreachable". Only stored cells are
+//! labelled; on grids whose boundary isn't solid, paths may leave the map, so
+//! nothing is ruled out.
+
+use std::collections::{HashMap, VecDeque};
+
+use bevy::prelude::*;
+
+use crate::grid::{Boundary, CellChangeEvent, CellPos, Connectivity, Grid, GridEditor, UnannouncedChanges};
+
+const NO_REGION: u32 = u32::MAX;
+
+#[derive(Component, Debug, Clone)]
+pub struct Regions {
+    width: u32,
+    height: u32,
+    solid_boundary: bool,
+    connectivity: Connectivity,
+    /// Raw label of every cell, or `NO_REGION` for walls.
+    labels: Vec<u32>,
+    /// Union-find forest over raw labels.
+    parents: Vec<u32>,
+    sizes: Vec<u32>,
+}
+
+impl Regions {
+    pub fn new(grid: &Grid) -> Self {
+        let mut regions = Regions {
+            width: 0,
+            height: 0,
+            solid_boundary: true,
+            connectivity: Connectivity::Four,
+            labels: Vec::new(),
+            parents: Vec::new(),
+            sizes: Vec::new(),
+        };
+        regions.rebuild(grid);
+        regions
+    }
+
+    /// Labels every cell from scratch. Needed after edits that weren't announced
+    /// with a `CellChangeEvent`, such as relinking portals.
+    pub fn rebuild(&mut self, grid: &Grid) {
+        self.width = grid.width();
+        self.height = grid.height();
+        self.solid_boundary = matches!(grid.boundary(), Boundary::Solid);
+        self.connectivity = grid.connectivity();
+        self.labels = vec![NO_REGION; (self.width * self.height) as usize];
+        self.parents.clear();
+        self.sizes.clear();
+
+        for (cell_pos, cell) in grid.iter_cell_pos() {
+            if !cell.is_wall && self.label(cell_pos) == NO_REGION {
+                let label = self.new_label();
+                self.flood(grid, cell_pos, label);
+            }
+        }
+    }
+
+    /// False if no path can exist between `start` and `goal`.
+    pub fn is_reachable(&self, start: CellPos, goal: CellPos) -> bool {
+        if !self.solid_boundary {
+            return true;
+        }
+
+        let (start, goal) = (self.label(start), self.label(goal));
+        start != NO_REGION && goal != NO_REGION && self.find(start) == self.find(goal)
+    }
+
+    /// Updates the labels after `cell_pos` changed between wall and floor.
+    pub fn cell_changed(&mut self, grid: &Grid, cell_pos: CellPos) {
+        if !grid.in_bounds(cell_pos) {
+            return;
+        }
+        let cell = grid.cell(cell_pos).expect("checked to be in bounds");
+
+        let solid_boundary = matches!(grid.boundary(), Boundary::Solid);
+        if grid.width() != self.width
+            || grid.height() != self.height
+            || solid_boundary != self.solid_boundary
+            || grid.connectivity() != self.connectivity
+        {
+            self.rebuild(grid);
+            return;
+        }
+
+        let index = self.index(cell_pos);
+        let labelled = self.labels[index] != NO_REGION;
+
+        match (cell.is_wall, labelled) {
+            (false, false) => {
+                let label = self.new_label();
+                self.labels[index] = label;
+
+                for neighbor in Self::linked(grid, cell_pos) {
+                    let neighbor_label = self.label(neighbor);
+                    if neighbor_label != NO_REGION {
+                        self.union(label, neighbor_label);
+                    }
+                }
+            }
+            (true, true) => {
+                self.labels[index] = NO_REGION;
+
+                let neighbors: Vec<_> = Self::linked(grid, cell_pos)
+                    .filter(|&neighbor| self.label(neighbor) != NO_REGION)
+                    .collect();
+
+                // Removing a dead end can't disconnect anything.
+                if neighbors.len() < 2 {
+                    return;
+                }
+
+                // Every flood gets a fresh label, so a neighbor that still carries
+                // an old one wasn't reached by the floods before it.
+                let first_fresh = self.parents.len() as u32;
+                for neighbor in neighbors {
+                    if self.label(neighbor) < first_fresh {
+                        let label = self.new_label();
+                        self.flood(grid, neighbor, label);
+                    }
+                }
+
+                // Abandoned labels pile up with every split; start over once they
+                // outnumber the cells.
+                if self.parents.len() > self.labels.len() {
+                    self.rebuild(grid);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn index(&self, cell_pos: CellPos) -> usize {
+        let CellPos(x, y) = cell_pos;
+        (self.width * y as u32 + x as u32) as usize
+    }
+
+    fn label(&self, cell_pos: CellPos) -> u32 {
+        let CellPos(x, y) = cell_pos;
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return NO_REGION;
+        }
+        self.labels[self.index(cell_pos)]
+    }
+
+    fn new_label(&mut self) -> u32 {
+        let label = self.parents.len() as u32;
+        self.parents.push(label);
+        self.sizes.push(1);
+        label
+    }
+
+    fn find(&self, mut label: u32) -> u32 {
+        while self.parents[label as usize] != label {
+            label = self.parents[label as usize];
+        }
+        label
+    }
+
+    fn union(&mut self, a: u32, b: u32) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return;
+        }
+
+        let (small, large) = match self.sizes[a as usize] < self.sizes[b as usize] {
+            true => (a, b),
+            false => (b, a),
+        };
+        self.parents[small as usize] = large;
+        self.sizes[large as usize] += self.sizes[small as usize];
+    }
+
+    /// Stored floor cells connected to `cell_pos` in either direction: every
+    /// neighbor regardless of exits, plus the far end of a portal.
+    fn linked(grid: &Grid, cell_pos: CellPos) -> impl Iterator<Item = CellPos> + '_ {
+        grid.touching(cell_pos, grid.connectivity())
+            .chain(grid.portal(cell_pos).map(|portal| portal.exit))
+            .filter(|&neighbor| grid.in_bounds(neighbor))
+            .filter(|&neighbor| grid.cell(neighbor).is_ok_and(|cell| !cell.is_wall))
+    }
+
+    fn flood(&mut self, grid: &Grid, start: CellPos, label: u32) {
+        let mut queue = VecDeque::from([start]);
+        let start_index = self.index(start);
+        self.labels[start_index] = label;
+
+        while let Some(cell_pos) = queue.pop_front() {
+            for neighbor in Self::linked(grid, cell_pos) {
+                let index = self.index(neighbor);
+                if self.labels[index] != label {
+                    self.labels[index] = label;
+                    self.sizes[label as usize] += 1;
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+    }
+}
+
+/// Labels grid editors once their grid asset is available.
+pub(crate) fn label_new_grids(
+    mut commands: Commands,
+    assets: Res<Assets<Grid>>,
+    new_grids: Query<(&GridEditor, Entity), Without<Regions>>,
+) {
+    for (grid_editor, entity) in &new_grids {
+        if let Some(grid) = assets.get(&grid_editor.grid) {
+            commands.entity(entity).insert(Regions::new(grid));
+        }
+    }
+}
+
+/// Updates the regions of edited grids: incrementally for each `CellChangeEvent`,
+/// from scratch when the grid asset was modified without one. Every editor of
+/// the changed grid asset is updated, not only the one the event names.
+pub(crate) fn update_regions(
+    assets: Res<Assets<Grid>>,
+    mut asset_events: EventReader<AssetEvent<Grid>>,
+    mut cell_events: EventReader<CellChangeEvent>,
+    mut unannounced: Local<UnannouncedChanges>,
+    editors: Query<&GridEditor>,
+    mut grids: Query<(&GridEditor, &mut Regions, Entity)>,
+) {
+    let mut editing: HashMap<Handle<Grid>, Vec<Entity>> = HashMap::new();
+    for (grid_editor, _, entity) in &grids {
+        editing.entry(grid_editor.grid.clone()).or_default().push(entity);
+    }
+
+    for event in cell_events.iter() {
+        let Ok(grid_editor) = editors.get(event.grid) else {
+            continue;
+        };
+        unannounced.announce(&grid_editor.grid);
+        let Some(grid) = assets.get(&grid_editor.grid) else {
+            continue;
+        };
+        for &entity in editing.get(&grid_editor.grid).into_iter().flatten() {
+            if let Ok((_, mut regions, _)) = grids.get_mut(entity) {
+                regions.cell_changed(grid, event.cell_pos);
+            }
+        }
+    }
+
+    let unannounced = unannounced.unannounced(&mut asset_events);
+    for (grid_editor, mut regions, _) in &mut grids {
+        if let (true, Some(grid)) = (unannounced.contains(&grid_editor.grid), assets.get(&grid_editor.grid)) {
+            regions.rebuild(grid);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::grid::Cell;
+
+    fn cells(grid: &Grid) -> Vec<CellPos> {
+        grid.iter_cell_pos().map(|(cell_pos, _)| cell_pos).collect()
+    }
+
+    /// Toggles random cells and checks after each edit that the incremental
+    /// labels split the cells into the same regions as labelling from scratch.
+    fn check_against_rebuild(connectivity: Connectivity, seed: u64) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut grid = Grid::new(16, 12);
+        grid.set_connectivity(connectivity);
+        for cell_pos in cells(&grid) {
+            grid.set_cell(cell_pos, Cell { is_wall: rng.gen_bool(0.4) }).unwrap();
+        }
+        grid.link_portals(CellPos(0, 0), CellPos(15, 11), 1.0).unwrap();
+        grid.link_portals(CellPos(3, 9), CellPos(12, 2), 1.0).unwrap();
+
+        let all = cells(&grid);
+        let mut regions = Regions::new(&grid);
+        for edit in 0..200 {
+            let cell_pos = all[rng.gen_range(0..all.len())];
+            let is_wall = grid.cell(cell_pos).unwrap().is_wall;
+            grid.set_cell(cell_pos, Cell { is_wall: !is_wall }).unwrap();
+            regions.cell_changed(&grid, cell_pos);
+
+            let rebuilt = Regions::new(&grid);
+            for &a in &all {
+                for &b in &all {
+                    assert_eq!(
+                        regions.is_reachable(a, b),
+                        rebuilt.is_reachable(a, b),
+                        "{connectivity:?} edit {edit} at {cell_pos:?}: {a:?} to {b:?}",
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn incremental_labels_match_rebuild() {
+        check_against_rebuild(Connectivity::Four, 0);
+        check_against_rebuild(Connectivity::Eight, 1);
+        check_against_rebuild(Connectivity::Hex, 2);
+    }
+
+    #[test]
+    fn open_boundaries_rule_nothing_out() {
+        let mut grid = Grid::new(3, 1);
+        grid.set_cell(CellPos(1, 0), Cell::wall()).unwrap();
+        assert!(!Regions::new(&grid).is_reachable(CellPos(0, 0), CellPos(2, 0)));
+
+        grid.set_boundary(Boundary::Open { margin: 1 });
+        assert!(Regions::new(&grid).is_reachable(CellPos(0, 0), CellPos(2, 0)));
+    }
+}
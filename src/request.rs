@@ -0,0 +1,1114 @@
+//! Path queries as components.
+//!
+//! Insert a [`PathRequest`] on any entity, such as a unit, and the plugin answers
+//! it on its next update: the request is replaced by a [`ComputedPath`] when a
+//! path is found, or by [`PathFailed`] when there is none. Inserting another
+//! request asks again and replaces the previous answer.
+//!
+//! Every answer is also sent as a [`PathFoundEvent`] or [`PathFailedEvent`], so
+//! systems can react to finished searches without polling for the components.
+//!
+//! A request still being searched is cancelled by removing its [`PathPending`]
+//! or [`ScheduledSearch`], by `PathCommandsExt::cancel_path`, or by inserting a
+//! new request. Its partial search is dropped and it is never answered.
+//!
+//! How a request is searched is set by its [`SolveMode`], or else the default
+//! mode of the [`PathScheduler`]: right away in the update, on the
+//! `AsyncComputeTaskPool` against a snapshot of the grid, or a few expansions
+//! per frame under the shared budget of the `PathScheduler`, so dozens of
+//! concurrent requests never blow the frame time.
+//!
+//! Paths found inline are kept in the [`PathCache`], so a request identical to a
+//! recent one, as when many agents share a target, is answered without a search
+//! in any mode.
+//!
+//! Requests are searched with one of the built-in [`Planner`]s, or with a
+//! [`Pathfinder`] registered through [`PathfinderAppExt::add_pathfinder`], which
+//! is then answered, cached and scheduled like the built-in ones.
+//!
+//! Steps cost what the [`TerrainCosts`] say the terrain entered costs, adjusted
+//! by the [`CostProfile`] a request names, so different kinds of units path
+//! differently over the same grid.
+
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet, VecDeque},
+    error::Error,
+    fmt::Display,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use bevy::{
+    ecs::system::SystemParam,
+    prelude::*,
+    tasks::{futures_lite::future, AsyncComputeTaskPool, Task},
+};
+
+use crate::{
+    clearance::Clearance,
+    console::ErrorConsole,
+    grid::{Boundary, CellChangeEvent, CellPos, Grid, GridEditor, GridHandle, GridView, Grids, UnannouncedChanges},
+    pathfinding::{Heuristic, Path, Pathfinder, Planner, Search, SearchMask, SearchOutcome, StepCost, StepResult},
+    terrain::{CostProfile, CostProfiles, TerrainCost, TerrainCosts},
+};
+
+/// Asks for a path on the grid held by `grid`.
+#[derive(Component, Debug, Clone, Reflect)]
+#[reflect(Component)]
+pub struct PathRequest {
+    pub grid: Entity,
+    pub start: CellPos,
+    pub goal: CellPos,
+    /// Not reflected, since custom pathfinders are named by `&'static str`.
+    #[reflect(ignore)]
+    pub algorithm: Algorithm,
+    /// Estimate planners steer by; custom pathfinders use their own.
+    pub heuristic: Heuristic,
+    /// Name of the [`CostProfile`] in [`CostProfiles`] applied on top of the base
+    /// terrain costs; `None` for the base costs alone. Custom pathfinders ignore
+    /// it. Not reflected, for the same reason as `algorithm`.
+    #[reflect(ignore)]
+    pub profile: Option<&'static str>,
+    /// `None` for the [`PathScheduler`]'s default mode, inline unless set otherwise.
+    pub mode: Option<SolveMode>,
+    /// Scheduled requests with a higher priority get the budget first, e.g. the
+    /// player's over background units'. Inline and async requests ignore it.
+    pub priority: u32,
+    /// Only explores cells within this many steps of the start along both axes,
+    /// like `AStar::with_search_radius`. Requests on unbounded grids need this
+    /// or a `mask`, or searching for an unreachable goal would never end; they
+    /// fail with [`PathFailure::Unbounded`] without. Custom pathfinders ignore it.
+    pub search_radius: Option<u32>,
+    /// Only explores cells inside the mask, such as a single room; takes the
+    /// place of `search_radius` when both are set. Paths found within a mask of
+    /// arbitrary cells aren't cached. Custom pathfinders ignore it. Not
+    /// reflected, as the mask can't be.
+    #[reflect(ignore)]
+    pub mask: Option<SearchMask>,
+    /// Radius of the agent in cells, keeping it out of cells whose
+    /// [`Clearance`] is too small for it. Uses the clearance maintained for the
+    /// grid, or measures the grid for the request when it isn't maintained.
+    /// Custom pathfinders ignore it.
+    pub radius: Option<u32>,
+}
+
+/// The search a [`PathRequest`] is answered with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Algorithm {
+    Planner(Planner),
+    /// The [`Pathfinder`] registered under this name.
+    Custom(&'static str),
+}
+
+impl From<Planner> for Algorithm {
+    fn from(planner: Planner) -> Self {
+        Algorithm::Planner(planner)
+    }
+}
+
+/// Where and when a [`PathRequest`] is searched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Reflect, FromReflect)]
+pub enum SolveMode {
+    /// Searches to the end in the update that picks the request up.
+    #[default]
+    Inline,
+    /// Searches on the `AsyncComputeTaskPool` against a copy of the grid, so a
+    /// long search doesn't stall the frame. The answer arrives in a later frame,
+    /// for the grid as it was when the request was picked up; until then the
+    /// entity has a [`PathPending`].
+    Async,
+    /// Advances the search a few expansions per frame within the
+    /// [`PathScheduler`]'s budget, against the grid as it is each frame. Until it
+    /// finishes the entity has a [`ScheduledSearch`].
+    Scheduled,
+}
+
+impl PathRequest {
+    /// Requests the cheapest path, found with A*.
+    pub fn new(grid: Entity, start: CellPos, goal: CellPos) -> Self {
+        PathRequest {
+            grid,
+            start,
+            goal,
+            algorithm: Algorithm::Planner(Planner::AStar),
+            heuristic: Heuristic::Connectivity,
+            profile: None,
+            mode: None,
+            priority: 0,
+            search_radius: None,
+            mask: None,
+            radius: None,
+        }
+    }
+
+    pub fn with_algorithm(mut self, algorithm: impl Into<Algorithm>) -> Self {
+        self.algorithm = algorithm.into();
+        self
+    }
+
+    pub fn with_heuristic(mut self, heuristic: Heuristic) -> Self {
+        self.heuristic = heuristic;
+        self
+    }
+
+    pub fn with_profile(mut self, profile: &'static str) -> Self {
+        self.profile = Some(profile);
+        self
+    }
+
+    pub fn with_mode(mut self, mode: SolveMode) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    pub fn with_priority(mut self, priority: u32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn with_search_radius(mut self, radius: u32) -> Self {
+        self.search_radius = Some(radius);
+        self
+    }
+
+    pub fn with_mask(mut self, mask: SearchMask) -> Self {
+        self.mask = Some(mask);
+        self
+    }
+
+    pub fn with_radius(mut self, radius: u32) -> Self {
+        self.radius = Some(radius);
+        self
+    }
+
+    /// The cells the search may explore, if it's bounded.
+    fn search_mask(&self) -> Option<SearchMask> {
+        match (&self.mask, self.search_radius) {
+            (Some(mask), _) => Some(mask.clone()),
+            (None, Some(radius)) => Some(SearchMask::around(self.start, radius)),
+            (None, None) => None,
+        }
+    }
+}
+
+/// The answer to a [`PathRequest`] that found a path.
+#[derive(Component, Debug, Clone, PartialEq, Reflect)]
+#[reflect(Component)]
+pub struct ComputedPath {
+    pub grid: Entity,
+    pub path: Path,
+}
+
+// The inspector builds components from scratch before filling them in, and
+// there is no grid entity to start from, so these point at a placeholder.
+impl FromWorld for PathRequest {
+    fn from_world(_world: &mut World) -> Self {
+        PathRequest::new(Entity::from_raw(u32::MAX), CellPos(0, 0), CellPos(0, 0))
+    }
+}
+
+impl FromWorld for ComputedPath {
+    fn from_world(_world: &mut World) -> Self {
+        let path = Path { cells: Vec::new(), cost: 0.0, nodes_expanded: 0 };
+        ComputedPath { grid: Entity::from_raw(u32::MAX), path }
+    }
+}
+
+impl FromWorld for PathFailed {
+    fn from_world(_world: &mut World) -> Self {
+        let reason = PathFailure::GridNotFound;
+        PathFailed { grid: Entity::from_raw(u32::MAX), start: CellPos(0, 0), goal: CellPos(0, 0), reason }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Reflect, FromReflect)]
+pub enum PathFailure {
+    /// The search ran out of cells without reaching the goal.
+    Unreachable,
+    /// The request's grid entity holds no grid.
+    GridNotFound,
+    /// No [`Pathfinder`] is registered under the requested name.
+    UnknownPathfinder,
+    /// No [`CostProfile`] is registered under the requested name.
+    UnknownCostProfile,
+    /// The grid is unbounded and the request sets no search radius or mask, so
+    /// the search might never end.
+    Unbounded,
+}
+
+/// The answer to a [`PathRequest`] that found no path.
+#[derive(Component, Debug, Clone, PartialEq, Eq, Reflect)]
+#[reflect(Component)]
+pub struct PathFailed {
+    pub grid: Entity,
+    pub start: CellPos,
+    pub goal: CellPos,
+    pub reason: PathFailure,
+}
+
+impl Display for PathFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let PathFailed { grid, start, goal, reason } = self;
+        match reason {
+            PathFailure::Unreachable => write!(f, "no path from {start:?} to {goal:?} on grid {grid:?}"),
+            PathFailure::GridNotFound => write!(f, "entity {grid:?} has no grid to find a path on"),
+            PathFailure::UnknownPathfinder => {
+                write!(f, "no pathfinder registered for the path from {start:?} to {goal:?} on grid {grid:?}")
+            }
+            PathFailure::UnknownCostProfile => {
+                write!(f, "no cost profile registered for the path from {start:?} to {goal:?} on grid {grid:?}")
+            }
+            PathFailure::Unbounded => {
+                write!(f, "unbounded search for the path from {start:?} to {goal:?} on unbounded grid {grid:?}")
+            }
+        }
+    }
+}
+
+impl Error for PathFailed {}
+
+/// Sent when a [`PathRequest`] found a path.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathFoundEvent {
+    /// The entity the request was inserted on.
+    pub entity: Entity,
+    /// Every cell from the start to the goal, both included.
+    pub path: Vec<CellPos>,
+    pub cost: f32,
+    pub nodes_expanded: usize,
+}
+
+/// Sent when a [`PathRequest`] found no path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathFailedEvent {
+    /// The entity the request was inserted on.
+    pub entity: Entity,
+    pub reason: PathFailure,
+}
+
+/// On an entity whose async [`PathRequest`] is still being searched. Removing
+/// it, or inserting a new request, cancels the search.
+#[derive(Component)]
+pub struct PathPending {
+    pub grid: Entity,
+    pub start: CellPos,
+    pub goal: CellPos,
+    task: Task<Result<Path, PathFailure>>,
+}
+
+/// On an entity whose scheduled [`PathRequest`] is still being searched.
+/// Removing it, or inserting a new request, cancels the search.
+#[derive(Component, Debug, Clone)]
+pub struct ScheduledSearch {
+    pub grid: Entity,
+    pub start: CellPos,
+    pub goal: CellPos,
+    algorithm: Algorithm,
+    /// The stepped search of a planner; custom pathfinders run all at once when
+    /// their turn comes.
+    search: Option<Search>,
+    cost: TerrainCost,
+    /// Agent radius, kept to the grid's clearance as it is each frame.
+    radius: Option<u32>,
+    priority: u32,
+    /// Order the search was scheduled in; breaks ties between equal priorities.
+    seq: u64,
+    /// Scheduler frame the search was scheduled in.
+    scheduled_at: u64,
+}
+
+impl ScheduledSearch {
+    /// The search so far, e.g. to draw its frontier. `None` for custom pathfinders.
+    pub fn search(&self) -> Option<&Search> {
+        self.search.as_ref()
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    pub fn priority(&self) -> u32 {
+        self.priority
+    }
+}
+
+/// Shares a per-frame node expansion budget between the searches of scheduled
+/// path requests, so each frame spends a bounded time on them however many there
+/// are. Searches are served by priority, then oldest first. A search's priority
+/// rises by one for every `aging_frames` frames it waits, so background requests
+/// still finish while higher-priority ones keep arriving.
+///
+/// It also sets the mode of requests that don't name one, and how many async
+/// searches may run at once.
+#[derive(Resource, Debug, Clone, Reflect)]
+#[reflect(Resource)]
+pub struct PathScheduler {
+    /// Node expansions spent per frame across all scheduled searches.
+    pub expansions_per_frame: usize,
+    /// Frames a search waits for each step its priority rises.
+    pub aging_frames: u32,
+    /// Mode of the requests whose `mode` is `None`.
+    pub default_mode: SolveMode,
+    /// Async searches running at most; further async requests wait as they are
+    /// until one finishes. `None` for no limit.
+    pub max_async_tasks: Option<usize>,
+    next_seq: u64,
+    frame: u64,
+    expanded_last_frame: usize,
+}
+
+impl Default for PathScheduler {
+    fn default() -> Self {
+        PathScheduler::new(5_000)
+    }
+}
+
+impl PathScheduler {
+    pub fn new(expansions_per_frame: usize) -> Self {
+        PathScheduler {
+            expansions_per_frame,
+            aging_frames: 30,
+            default_mode: SolveMode::Inline,
+            max_async_tasks: None,
+            next_seq: 0,
+            frame: 0,
+            expanded_last_frame: 0,
+        }
+    }
+
+    pub fn with_aging_frames(mut self, aging_frames: u32) -> Self {
+        self.aging_frames = aging_frames;
+        self
+    }
+
+    pub fn with_default_mode(mut self, mode: SolveMode) -> Self {
+        self.default_mode = mode;
+        self
+    }
+
+    pub fn with_max_async_tasks(mut self, max_tasks: usize) -> Self {
+        self.max_async_tasks = Some(max_tasks);
+        self
+    }
+
+    /// Priority `scheduled` is served with this frame, its own plus its aging.
+    pub fn effective_priority(&self, scheduled: &ScheduledSearch) -> u64 {
+        let waited = self.frame - scheduled.scheduled_at;
+        scheduled.priority as u64 + waited / self.aging_frames.max(1) as u64
+    }
+
+    /// Node expansions the scheduled searches used last frame.
+    pub fn expanded_last_frame(&self) -> usize {
+        self.expanded_last_frame
+    }
+}
+
+/// What a cached path was asked for with: the fields of its [`PathRequest`]
+/// that change which path is found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PathKey {
+    pub grid: Entity,
+    pub algorithm: Algorithm,
+    pub heuristic: Heuristic,
+    pub profile: Option<&'static str>,
+    pub start: CellPos,
+    pub goal: CellPos,
+    /// Corners of the rectangle the search was kept in, if it was.
+    pub bounds: Option<(CellPos, CellPos)>,
+    /// Agent radius; `0` for requests without one, which fit the same cells.
+    pub radius: u32,
+}
+
+impl PathKey {
+    /// The key of `request` searched within `mask`. `None` for masks of
+    /// arbitrary cells, whose paths aren't cached.
+    fn of(request: &PathRequest, mask: Option<&SearchMask>) -> Option<PathKey> {
+        let bounds = match mask {
+            None => None,
+            Some(&SearchMask::Rect { min, max }) => Some((min, max)),
+            Some(SearchMask::Cells(_)) => return None,
+        };
+        Some(PathKey {
+            grid: request.grid,
+            algorithm: request.algorithm,
+            heuristic: request.heuristic,
+            profile: request.profile,
+            start: request.start,
+            goal: request.goal,
+            bounds,
+            radius: request.radius.unwrap_or(0),
+        })
+    }
+}
+
+/// Recently found paths by the [`PathKey`] of the requests that found them.
+///
+/// A path is dropped when one of its cells changes, as announced by a
+/// `CellChangeEvent`, or for agents with a radius, a cell within that radius of
+/// it, since a new wall there narrows the path. Every path of a grid is dropped
+/// when its asset is modified without one. Changes elsewhere can open a shorter
+/// route that a cached path doesn't take, so cached paths are valid but not
+/// always the cheapest. Hits keep the `nodes_expanded` of the search that found
+/// them. Every path is dropped when the [`TerrainCosts`] or [`CostProfiles`]
+/// change.
+#[derive(Resource, Debug, Clone, Reflect)]
+#[reflect(Resource)]
+pub struct PathCache {
+    /// Paths kept at most; the oldest are dropped first.
+    pub capacity: usize,
+    #[reflect(ignore)]
+    paths: HashMap<PathKey, (u64, Path)>,
+    /// Keys in insertion order, with the insertion they belong to. Entries of
+    /// dropped or replaced paths are skipped.
+    #[reflect(ignore)]
+    order: VecDeque<(u64, PathKey)>,
+    #[reflect(ignore)]
+    by_cell: HashMap<(Entity, CellPos), HashSet<PathKey>>,
+    /// Largest agent radius of the paths cached since the last clear.
+    max_radius: u32,
+    next_insertion: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl Default for PathCache {
+    fn default() -> Self {
+        PathCache::new(1_024)
+    }
+}
+
+impl PathCache {
+    pub fn new(capacity: usize) -> Self {
+        PathCache {
+            capacity,
+            paths: HashMap::new(),
+            order: VecDeque::new(),
+            by_cell: HashMap::new(),
+            max_radius: 0,
+            next_insertion: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// The cached path, counting the lookup as a hit or a miss.
+    pub fn get(&mut self, key: &PathKey) -> Option<&Path> {
+        match self.paths.get(key) {
+            Some((_, path)) => {
+                self.hits += 1;
+                Some(path)
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn insert(&mut self, key: PathKey, path: Path) {
+        self.remove(key);
+        self.max_radius = self.max_radius.max(key.radius);
+
+        for &cell_pos in &path.cells {
+            self.by_cell.entry((key.grid, cell_pos)).or_default().insert(key);
+        }
+        let insertion = self.next_insertion;
+        self.next_insertion += 1;
+        self.paths.insert(key, (insertion, path));
+        self.order.push_back((insertion, key));
+
+        while self.paths.len() > self.capacity {
+            let Some((insertion, key)) = self.order.pop_front() else {
+                break;
+            };
+            if self.paths.get(&key).is_some_and(|&(current, _)| current == insertion) {
+                self.remove(key);
+            }
+        }
+
+        // Skipped entries pile up when paths are invalidated faster than evicted.
+        if self.order.len() > 2 * self.capacity.max(1) {
+            let paths = &self.paths;
+            self.order.retain(|(insertion, key)| paths.get(key).is_some_and(|(current, _)| current == insertion));
+        }
+    }
+
+    /// Drops every path of `grid` through `cell_pos`, and those of agents whose
+    /// radius reaches it.
+    pub fn invalidate_cell(&mut self, grid: Entity, cell_pos: CellPos) {
+        let CellPos(x, y) = cell_pos;
+        let reach = self.max_radius as i32;
+        for dy in -reach..=reach {
+            for dx in -reach..=reach {
+                let Some(keys) = self.by_cell.get(&(grid, CellPos(x + dx, y + dy))) else {
+                    continue;
+                };
+                let distance = dx.unsigned_abs().max(dy.unsigned_abs());
+                let reached: Vec<_> = keys.iter().filter(|key| key.radius >= distance).copied().collect();
+                for key in reached {
+                    self.remove(key);
+                }
+            }
+        }
+    }
+
+    /// Drops every path of `grid`.
+    pub fn invalidate_grid(&mut self, grid: Entity) {
+        let keys: Vec<_> = self.paths.keys().filter(|key| key.grid == grid).copied().collect();
+        for key in keys {
+            self.remove(key);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.paths.clear();
+        self.order.clear();
+        self.by_cell.clear();
+        self.max_radius = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    fn remove(&mut self, key: PathKey) {
+        let Some((_, path)) = self.paths.remove(&key) else {
+            return;
+        };
+        for &cell_pos in &path.cells {
+            if let Some(keys) = self.by_cell.get_mut(&(key.grid, cell_pos)) {
+                keys.remove(&key);
+                if keys.is_empty() {
+                    self.by_cell.remove(&(key.grid, cell_pos));
+                }
+            }
+        }
+    }
+}
+
+/// The custom pathfinders requests can name, besides the built-in planners.
+/// Each one answers a single request at a time, so async requests for the same
+/// pathfinder wait for each other.
+#[derive(Resource, Default)]
+pub struct Pathfinders {
+    pathfinders: Vec<(String, Arc<Mutex<dyn Pathfinder>>)>,
+}
+
+impl Pathfinders {
+    /// Registers `pathfinder` under its name, replacing any registered under the same name.
+    pub fn add(&mut self, pathfinder: impl Pathfinder) -> &mut Self {
+        let name = pathfinder.name().to_string();
+        self.pathfinders.retain(|(registered, _)| *registered != name);
+        self.pathfinders.push((name, Arc::new(Mutex::new(pathfinder))));
+        self
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> + '_ {
+        self.pathfinders.iter().map(|(name, _)| name.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.pathfinders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pathfinders.is_empty()
+    }
+
+    fn resolve(&self, algorithm: Algorithm, heuristic: Heuristic, cost: TerrainCost) -> Option<Searcher> {
+        match algorithm {
+            Algorithm::Planner(planner) => Some(Searcher::Planner(planner, heuristic, cost)),
+            Algorithm::Custom(name) => self
+                .pathfinders
+                .iter()
+                .find(|(registered, _)| registered == name)
+                .map(|(_, pathfinder)| Searcher::Custom(pathfinder.clone())),
+        }
+    }
+}
+
+/// Registers pathfinding add-ons on an app using `AStarPlugin`.
+pub trait PathfinderAppExt {
+    /// Adds `pathfinder` to the [`Pathfinders`], so requests can name it with
+    /// [`Algorithm::Custom`].
+    fn add_pathfinder(&mut self, pathfinder: impl Pathfinder) -> &mut Self;
+
+    /// Adds `profile` to the [`CostProfiles`] under `name`, so requests can name
+    /// it with [`PathRequest::with_profile`].
+    fn add_cost_profile(&mut self, name: impl Into<String>, profile: CostProfile) -> &mut Self;
+}
+
+impl PathfinderAppExt for App {
+    fn add_pathfinder(&mut self, pathfinder: impl Pathfinder) -> &mut Self {
+        self.init_resource::<Pathfinders>();
+        self.world.resource_mut::<Pathfinders>().add(pathfinder);
+        self
+    }
+
+    fn add_cost_profile(&mut self, name: impl Into<String>, profile: CostProfile) -> &mut Self {
+        self.init_resource::<CostProfiles>();
+        self.world.resource_mut::<CostProfiles>().add(name, profile);
+        self
+    }
+}
+
+/// Drops cached paths through changed cells, on every entity showing the changed
+/// grid, and every cached path of grids modified without a `CellChangeEvent`.
+/// Drops them all when step costs change. Runs before requests are answered.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn invalidate_cached_paths(
+    mut cache: ResMut<PathCache>,
+    costs: Res<TerrainCosts>,
+    profiles: Res<CostProfiles>,
+    mut asset_events: EventReader<AssetEvent<Grid>>,
+    mut cell_events: EventReader<CellChangeEvent>,
+    mut unannounced: Local<UnannouncedChanges>,
+    grids: Grids,
+    editors: Query<(&GridEditor, Entity)>,
+    views: Query<(&GridView, Entity)>,
+) {
+    if costs.is_changed() || profiles.is_changed() {
+        cache.clear();
+    }
+
+    let mut showing: HashMap<&Handle<Grid>, Vec<Entity>> = HashMap::new();
+    let handles = editors
+        .iter()
+        .map(|(editor, entity)| (editor.handle(), entity))
+        .chain(views.iter().map(|(view, entity)| (view.handle(), entity)));
+    for (handle, entity) in handles {
+        showing.entry(handle).or_default().push(entity);
+    }
+
+    for event in cell_events.iter() {
+        let Some(handle) = grids.handle(event.grid) else {
+            continue;
+        };
+        unannounced.announce(handle);
+        for &entity in showing.get(handle).into_iter().flatten() {
+            cache.invalidate_cell(entity, event.cell_pos);
+        }
+    }
+
+    for handle in unannounced.unannounced(&mut asset_events) {
+        for &entity in showing.get(&handle).into_iter().flatten() {
+            cache.invalidate_grid(entity);
+        }
+    }
+}
+
+/// Puts answers on the requesting entities and sends the matching events.
+#[derive(SystemParam)]
+pub(crate) struct PathAnswers<'w, 's> {
+    commands: Commands<'w, 's>,
+    found_events: EventWriter<'w, 's, PathFoundEvent>,
+    failed_events: EventWriter<'w, 's, PathFailedEvent>,
+}
+
+impl PathAnswers<'_, '_> {
+    fn answer(&mut self, entity: Entity, grid: Entity, start: CellPos, goal: CellPos, result: Result<Path, PathFailure>) {
+        let mut entity_commands = self.commands.entity(entity);
+        match result {
+            Ok(path) => {
+                self.found_events.send(PathFoundEvent {
+                    entity,
+                    path: path.cells.clone(),
+                    cost: path.cost,
+                    nodes_expanded: path.nodes_expanded,
+                });
+                entity_commands.remove::<PathFailed>().insert(ComputedPath { grid, path });
+            }
+            Err(reason) => {
+                self.failed_events.send(PathFailedEvent { entity, reason });
+                entity_commands.remove::<ComputedPath>().insert(PathFailed { grid, start, goal, reason });
+            }
+        }
+    }
+}
+
+/// An [`Algorithm`] resolved against the [`Pathfinders`], ready to search on any thread.
+#[derive(Clone)]
+enum Searcher {
+    Planner(Planner, Heuristic, TerrainCost),
+    Custom(Arc<Mutex<dyn Pathfinder>>),
+}
+
+impl Searcher {
+    /// Custom pathfinders search the whole grid whatever the `mask` and `agent`.
+    fn search(
+        &self,
+        grid: &Grid,
+        start: CellPos,
+        goal: CellPos,
+        mask: Option<&SearchMask>,
+        agent: Option<(&Clearance, u32)>,
+    ) -> SearchOutcome {
+        match self {
+            Searcher::Planner(planner, heuristic, cost) => {
+                planner_search(grid, *planner, *heuristic, start, goal, mask).finish(grid, &agent_cost(cost, agent))
+            }
+            Searcher::Custom(pathfinder) => {
+                // A panic in an earlier search doesn't keep the pathfinder from being used.
+                let mut pathfinder = pathfinder.lock().unwrap_or_else(PoisonError::into_inner);
+                pathfinder.search(grid, start, goal)
+            }
+        }
+    }
+}
+
+fn planner_search(
+    grid: &Grid,
+    planner: Planner,
+    heuristic: Heuristic,
+    start: CellPos,
+    goal: CellPos,
+    mask: Option<&SearchMask>,
+) -> Search {
+    let search = Search::new(grid, planner, start, goal).with_heuristic(heuristic);
+    match mask {
+        Some(mask) => search.with_mask(mask.clone()),
+        None => search,
+    }
+}
+
+/// `cost`, keeping the agent, if there is one, out of cells too narrow for it.
+fn agent_cost<'a>(cost: &'a TerrainCost, agent: Option<(&'a Clearance, u32)>) -> impl StepCost + 'a {
+    move |grid: &Grid, from: CellPos, to: CellPos| match agent {
+        Some((clearance, radius)) if !clearance.fits(to, radius) => None,
+        _ => cost.step_cost(grid, from, to),
+    }
+}
+
+/// The clearance an agent of `radius` is kept to on `grid`: the one maintained
+/// for `grid_entity`, or else `measured`, filled in from the grid.
+fn agent_clearance<'a>(
+    grids: &'a Grids,
+    grid_entity: Entity,
+    grid: &Grid,
+    radius: Option<u32>,
+    measured: &'a mut Option<Clearance>,
+) -> Option<(&'a Clearance, u32)> {
+    let radius = radius?;
+    match grids.clearance(grid_entity) {
+        Some(clearance) => Some((clearance, radius)),
+        None => Some((measured.insert(Clearance::new(grid)), radius)),
+    }
+}
+
+fn answer_of(outcome: SearchOutcome) -> Result<Path, PathFailure> {
+    match outcome {
+        SearchOutcome::Found(path) => Ok(path),
+        SearchOutcome::NoPath { .. } => Err(PathFailure::Unreachable),
+    }
+}
+
+/// Answers every new [`PathRequest`], or starts its search if it isn't solved
+/// inline. Requests naming a missing grid are
+/// reported to the [`ErrorConsole`]; unreachable goals aren't, since games ask
+/// for those routinely.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn solve_path_requests(
+    mut answers: PathAnswers,
+    grids: Grids,
+    requests: Query<(&PathRequest, Entity)>,
+    pending: Query<(), (With<PathPending>, Without<PathRequest>)>,
+    mut scheduler: ResMut<PathScheduler>,
+    mut cache: ResMut<PathCache>,
+    pathfinders: Res<Pathfinders>,
+    costs: Res<TerrainCosts>,
+    profiles: Res<CostProfiles>,
+    mut console: ResMut<ErrorConsole>,
+) {
+    let mut running = pending.iter().count();
+    for (request, entity) in &requests {
+        let &PathRequest { grid: grid_entity, start, goal, algorithm, heuristic, profile, mode, priority, radius, .. } =
+            request;
+        let mode = mode.unwrap_or(scheduler.default_mode);
+        if mode == SolveMode::Async && scheduler.max_async_tasks.is_some_and(|max_tasks| running >= max_tasks) {
+            continue;
+        }
+        answers
+            .commands
+            .entity(entity)
+            .remove::<PathRequest>()
+            .remove::<PathPending>()
+            .remove::<ScheduledSearch>();
+
+        let Ok(grid) = grids.get(grid_entity) else {
+            let reason = PathFailure::GridNotFound;
+            console.report("solve_path_requests", &PathFailed { grid: grid_entity, start, goal, reason });
+            answers.answer(entity, grid_entity, start, goal, Err(reason));
+            continue;
+        };
+
+        let mask = request.search_mask();
+        if matches!(grid.boundary(), Boundary::Unbounded { .. }) && mask.is_none() {
+            let reason = PathFailure::Unbounded;
+            console.report("solve_path_requests", &PathFailed { grid: grid_entity, start, goal, reason });
+            answers.answer(entity, grid_entity, start, goal, Err(reason));
+            continue;
+        }
+
+        // Skip the search when the region labels already rule the goal out.
+        if grids.regions(grid_entity).is_some_and(|regions| !regions.is_reachable(start, goal)) {
+            answers.answer(entity, grid_entity, start, goal, Err(PathFailure::Unreachable));
+            continue;
+        }
+
+        let key = PathKey::of(request, mask.as_ref());
+        if let Some(path) = key.and_then(|key| cache.get(&key).cloned()) {
+            answers.answer(entity, grid_entity, start, goal, Ok(path));
+            continue;
+        }
+
+        let cost_profile = match profile {
+            Some(name) => profiles.get(name).cloned(),
+            None => Some(CostProfile::default()),
+        };
+        let Some(cost_profile) = cost_profile else {
+            let reason = PathFailure::UnknownCostProfile;
+            console.report("solve_path_requests", &PathFailed { grid: grid_entity, start, goal, reason });
+            answers.answer(entity, grid_entity, start, goal, Err(reason));
+            continue;
+        };
+        let cost = TerrainCost::new(costs.clone(), cost_profile);
+
+        let Some(searcher) = pathfinders.resolve(algorithm, heuristic, cost) else {
+            let reason = PathFailure::UnknownPathfinder;
+            console.report("solve_path_requests", &PathFailed { grid: grid_entity, start, goal, reason });
+            answers.answer(entity, grid_entity, start, goal, Err(reason));
+            continue;
+        };
+
+        match mode {
+            SolveMode::Inline => {
+                let mut measured = None;
+                let agent = agent_clearance(&grids, grid_entity, grid, radius, &mut measured);
+                let result = answer_of(searcher.search(grid, start, goal, mask.as_ref(), agent));
+                if let (Ok(path), Some(key)) = (&result, key) {
+                    cache.insert(key, path.clone());
+                }
+                answers.answer(entity, grid_entity, start, goal, result);
+            }
+            SolveMode::Async => {
+                // The task searches its own copy, so the grid can be edited meanwhile.
+                let snapshot = grid.clone();
+                let mut measured = None;
+                let agent = agent_clearance(&grids, grid_entity, grid, radius, &mut measured)
+                    .map(|(clearance, radius)| (clearance.clone(), radius));
+                let task = AsyncComputeTaskPool::get().spawn(async move {
+                    let agent = agent.as_ref().map(|(clearance, radius)| (clearance, *radius));
+                    answer_of(searcher.search(&snapshot, start, goal, mask.as_ref(), agent))
+                });
+                answers.commands.entity(entity).insert(PathPending { grid: grid_entity, start, goal, task });
+                running += 1;
+            }
+            SolveMode::Scheduled => {
+                let (search, cost) = match searcher {
+                    Searcher::Planner(planner, heuristic, cost) => {
+                        (Some(planner_search(grid, planner, heuristic, start, goal, mask.as_ref())), cost)
+                    }
+                    Searcher::Custom(_) => (None, TerrainCost::default()),
+                };
+                let (seq, scheduled_at) = (scheduler.next_seq, scheduler.frame);
+                scheduler.next_seq += 1;
+                answers.commands.entity(entity).insert(ScheduledSearch {
+                    grid: grid_entity,
+                    start,
+                    goal,
+                    algorithm,
+                    search,
+                    cost,
+                    radius,
+                    priority,
+                    seq,
+                    scheduled_at,
+                });
+            }
+        }
+    }
+}
+
+/// Advances the scheduled searches in the order the [`PathScheduler`] serves them
+/// until the frame's budget is spent, and answers the ones that finish. Searches
+/// superseded by a new request are left for it to replace.
+pub(crate) fn advance_scheduled_searches(
+    mut answers: PathAnswers,
+    grids: Grids,
+    mut scheduler: ResMut<PathScheduler>,
+    mut searches: Query<(&mut ScheduledSearch, Entity), Without<PathRequest>>,
+    pathfinders: Res<Pathfinders>,
+    mut console: ResMut<ErrorConsole>,
+) {
+    scheduler.frame += 1;
+    let mut queue: Vec<_> = searches.iter_mut().collect();
+    queue.sort_by_key(|(scheduled, _)| (Reverse(scheduler.effective_priority(scheduled)), scheduled.seq));
+
+    let mut budget = scheduler.expansions_per_frame;
+    for (mut scheduled, entity) in queue {
+        if budget == 0 {
+            break;
+        }
+
+        let (grid_entity, start, goal, algorithm) = (scheduled.grid, scheduled.start, scheduled.goal, scheduled.algorithm);
+        let ScheduledSearch { search, cost, radius, .. } = &mut *scheduled;
+        let result = match (grids.get(grid_entity), search.as_mut()) {
+            (Ok(grid), Some(search)) => {
+                let mut measured = None;
+                let agent = agent_clearance(&grids, grid_entity, grid, *radius, &mut measured);
+                let expanded = search.nodes_expanded();
+                let result = search.step(grid, &agent_cost(cost, agent), budget);
+                budget = budget.saturating_sub(search.nodes_expanded() - expanded);
+
+                match result {
+                    StepResult::Running => continue,
+                    StepResult::Found(path) => Ok(path),
+                    StepResult::NoPath => Err(PathFailure::Unreachable),
+                }
+            }
+            // Custom pathfinders can't be paused, so they run to the end and are
+            // charged for everything they expanded.
+            (Ok(grid), None) => match pathfinders.resolve(algorithm, Heuristic::Connectivity, TerrainCost::default()) {
+                Some(searcher) => {
+                    let outcome = searcher.search(grid, start, goal, None, None);
+                    let expanded = match &outcome {
+                        SearchOutcome::Found(path) => path.nodes_expanded,
+                        &SearchOutcome::NoPath { nodes_expanded } => nodes_expanded,
+                    };
+                    budget = budget.saturating_sub(expanded);
+                    answer_of(outcome)
+                }
+                None => {
+                    let reason = PathFailure::UnknownPathfinder;
+                    console.report("advance_scheduled_searches", &PathFailed { grid: grid_entity, start, goal, reason });
+                    Err(reason)
+                }
+            },
+            (Err(_), _) => {
+                let reason = PathFailure::GridNotFound;
+                console.report("advance_scheduled_searches", &PathFailed { grid: grid_entity, start, goal, reason });
+                Err(reason)
+            }
+        };
+
+        answers.commands.entity(entity).remove::<ScheduledSearch>();
+        answers.answer(entity, grid_entity, start, goal, result);
+    }
+
+    scheduler.expanded_last_frame = scheduler.expansions_per_frame - budget;
+}
+
+/// Answers the async requests whose search has finished, unless a new request
+/// supersedes them.
+pub(crate) fn collect_async_paths(
+    mut answers: PathAnswers,
+    mut pending: Query<(&mut PathPending, Entity), Without<PathRequest>>,
+) {
+    for (mut pending, entity) in &mut pending {
+        let Some(result) = future::block_on(future::poll_once(&mut pending.task)) else {
+            continue;
+        };
+
+        answers.commands.entity(entity).remove::<PathPending>();
+        answers.answer(entity, pending.grid, pending.start, pending.goal, result);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    fn random_cell(rng: &mut StdRng) -> CellPos {
+        CellPos(rng.gen_range(0..10), rng.gen_range(0..10))
+    }
+
+    fn random_key(rng: &mut StdRng) -> PathKey {
+        PathKey {
+            grid: Entity::from_raw(rng.gen_range(0..2)),
+            algorithm: Algorithm::Planner(Planner::AStar),
+            heuristic: Heuristic::default(),
+            profile: None,
+            start: random_cell(rng),
+            goal: random_cell(rng),
+            bounds: None,
+            radius: rng.gen_range(0..3),
+        }
+    }
+
+    fn random_path(rng: &mut StdRng, key: &PathKey) -> Path {
+        let mut cells = vec![key.start];
+        cells.extend((0..rng.gen_range(0..6)).map(|_| random_cell(rng)));
+        cells.push(key.goal);
+        Path { cells, cost: rng.gen_range(0.0..20.0), nodes_expanded: rng.gen_range(0..100) }
+    }
+
+    /// Whether a change to `cell_pos` of `grid` drops the path cached under `key`.
+    fn reaches(key: &PathKey, path: &Path, grid: Entity, CellPos(x, y): CellPos) -> bool {
+        let radius = key.radius as i32;
+        key.grid == grid && path.cells.iter().any(|&CellPos(px, py)| (px - x).abs().max((py - y).abs()) <= radius)
+    }
+
+    /// Inserts and invalidates at random, checking after each step that the cache
+    /// holds exactly what a plain list of paths, oldest first, says it should.
+    #[test]
+    fn invalidation_and_eviction_match_a_plain_list() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut cache = PathCache::new(16);
+        let mut expected: Vec<(PathKey, Path)> = Vec::new();
+
+        for step in 0..2_000 {
+            match rng.gen_range(0..10) {
+                0..=5 => {
+                    let key = random_key(&mut rng);
+                    let path = random_path(&mut rng, &key);
+                    cache.insert(key, path.clone());
+                    expected.retain(|(cached, _)| *cached != key);
+                    expected.push((key, path));
+                    if expected.len() > cache.capacity {
+                        expected.remove(0);
+                    }
+                }
+                6..=8 => {
+                    let (grid, cell_pos) = (Entity::from_raw(rng.gen_range(0..2)), random_cell(&mut rng));
+                    cache.invalidate_cell(grid, cell_pos);
+                    expected.retain(|(key, path)| !reaches(key, path, grid, cell_pos));
+                }
+                _ => {
+                    let grid = Entity::from_raw(rng.gen_range(0..2));
+                    cache.invalidate_grid(grid);
+                    expected.retain(|(key, _)| key.grid != grid);
+                }
+            }
+
+            assert_eq!(cache.len(), expected.len(), "step {step}");
+            for (key, path) in &expected {
+                assert_eq!(cache.get(key), Some(path), "step {step}, {key:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn requests_with_arbitrary_masks_have_no_key() {
+        let request = PathRequest::new(Entity::from_raw(0), CellPos(0, 0), CellPos(3, 3));
+        let cells = SearchMask::Cells(HashSet::from([CellPos(0, 0), CellPos(3, 3)]));
+        assert!(PathKey::of(&request, Some(&cells)).is_none());
+
+        let rect = SearchMask::rect(CellPos(3, 3), CellPos(0, 0));
+        let key = PathKey::of(&request, Some(&rect)).unwrap();
+        assert_eq!(key.bounds, Some((CellPos(0, 0), CellPos(3, 3))));
+    }
+}
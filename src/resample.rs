@@ -0,0 +1,187 @@
+//! Converting maps between cell sizes.
+//!
+//! Downscaling merges each block of `factor` × `factor` cells into one. A block
+//! is floor if any of its cells is, and it keeps an exit towards a neighboring
+//! block only if one of its floor cells can step into a floor cell of that
+//! block. So any orthogonal path on the original map still exists on the
+//! downscaled one: corridors narrower than a block never disappear, and walls
+//! between blocks stay closed. The reverse doesn't hold, since floor pieces that
+//! are separate within one block become a single cell.
+//!
+//! Upscaling turns every cell into a block of identical cells, with exits on the
+//! block's edge cells, so paths are preserved both ways.
+//!
+//! Portals are moved to the blocks of their ends; on downscaling, a portal whose
+//! ends fall in the same block or in blocks that already have one is dropped.
+//! Layers, and cells written past the border of an unbounded grid, aren't
+//! resampled.
+
+use std::sync::Arc;
+
+use crate::grid::{Boundary, Cell, CellPos, Direction, Exits, Grid};
+
+/// Merges blocks of `factor` × `factor` cells into one cell, see the module docs.
+/// Blocks cut off by the edge of the grid are merged like whole ones.
+///
+/// # Panics
+///
+/// If `factor` is zero.
+pub fn downscale(grid: &Grid, factor: u32) -> Grid {
+    assert!(factor > 0, "cannot downscale by a factor of zero");
+    let block = |CellPos(x, y): CellPos| CellPos(x.div_euclid(factor as i32), y.div_euclid(factor as i32));
+
+    let (width, height) = (grid.width().div_ceil(factor), grid.height().div_ceil(factor));
+    let index = |CellPos(x, y): CellPos| (y as u32 * width + x as u32) as usize;
+
+    // Exits of each block that has floor, row by row.
+    let mut blocks: Vec<Option<Exits>> = vec![None; (width * height) as usize];
+    for (cell_pos, cell) in grid.iter_cell_pos() {
+        if cell.is_wall {
+            continue;
+        }
+        let coarse_pos = block(cell_pos);
+        let exits = blocks[index(coarse_pos)].get_or_insert(Exits::NONE);
+
+        for direction in Direction::ALL {
+            let neighbor = direction.step(cell_pos);
+            let crosses = block(neighbor) != coarse_pos;
+            let reachable = grid.exits(cell_pos).contains(direction) && grid.cell(neighbor).is_ok_and(|cell| !cell.is_wall);
+
+            if crosses && reachable {
+                *exits = exits.with(direction);
+            }
+        }
+    }
+
+    let mut coarse = Grid::with_storage(width, height, grid.storage_kind())
+        .with_boundary(downscale_boundary(grid.boundary(), factor))
+        .with_connectivity(grid.connectivity());
+
+    for y in 0..height as i32 {
+        for x in 0..width as i32 {
+            let cell_pos = CellPos(x, y);
+            match blocks[index(cell_pos)] {
+                Some(exits) => coarse.set_exits(cell_pos, exits),
+                None => coarse.set_cell(cell_pos, Cell::wall()),
+            }
+            .expect("iterating inside the grid");
+        }
+    }
+
+    let mut portals: Vec<_> = grid.portals().collect();
+    portals.sort_by_key(|&(CellPos(x, y), _)| (y, x));
+    for (entry, portal) in portals {
+        let (a, b) = (block(entry), block(portal.exit));
+        if a != b && coarse.portal(a).is_none() && coarse.portal(b).is_none() {
+            coarse.link_portals(a, b, portal.cost).expect("blocks of stored cells are stored");
+        }
+    }
+
+    coarse
+}
+
+/// Splits every cell into a block of `factor` × `factor` copies of it, see the
+/// module docs. Portals link the center cells of their blocks.
+///
+/// # Panics
+///
+/// If `factor` is zero.
+pub fn upscale(grid: &Grid, factor: u32) -> Grid {
+    assert!(factor > 0, "cannot upscale by a factor of zero");
+    let scale = factor as i32;
+    let block_cells = move |CellPos(x, y): CellPos| {
+        (0..scale).flat_map(move |dy| (0..scale).map(move |dx| CellPos(x * scale + dx, y * scale + dy)))
+    };
+    let center = |CellPos(x, y): CellPos| CellPos(x * scale + scale / 2, y * scale + scale / 2);
+
+    let mut fine = Grid::with_storage(grid.width() * factor, grid.height() * factor, grid.storage_kind())
+        .with_boundary(upscale_boundary(grid.boundary(), factor))
+        .with_connectivity(grid.connectivity());
+
+    for (cell_pos, cell) in grid.iter_cell_pos() {
+        let exits = grid.exits(cell_pos);
+
+        for fine_pos in block_cells(cell_pos) {
+            fine.set_cell(fine_pos, cell).expect("blocks of stored cells are stored");
+
+            // Only the edge cells facing a missing exit lose it.
+            let on_edge = |direction: Direction| block_cells(cell_pos).all(|other| other != direction.step(fine_pos));
+            let fine_exits = Direction::ALL
+                .into_iter()
+                .filter(|&direction| !exits.contains(direction) && on_edge(direction))
+                .fold(Exits::ALL, Exits::without);
+            fine.set_exits(fine_pos, fine_exits).expect("blocks of stored cells are stored");
+        }
+    }
+
+    for (entry, portal) in grid.portals() {
+        fine.link_portals(center(entry), center(portal.exit), portal.cost)
+            .expect("blocks of stored cells are stored");
+    }
+
+    fine
+}
+
+/// Converts a map drawn with cells `from_cell_size` wide into one with cells
+/// `to_cell_size` wide, upscaling and then downscaling by the smallest factors
+/// that get there. A map with 2 m cells becomes one with 3 m cells by upscaling
+/// by 2 and downscaling by 3.
+///
+/// # Panics
+///
+/// If either cell size is zero.
+pub fn resample(grid: &Grid, from_cell_size: u32, to_cell_size: u32) -> Grid {
+    assert!(from_cell_size > 0 && to_cell_size > 0, "cell sizes must be positive");
+    let divisor = gcd(from_cell_size, to_cell_size);
+    let (up, down) = (from_cell_size / divisor, to_cell_size / divisor);
+
+    match (up, down) {
+        (1, 1) => grid.clone(),
+        (1, _) => downscale(grid, down),
+        (_, 1) => upscale(grid, up),
+        _ => downscale(&upscale(grid, up), down),
+    }
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+    match b {
+        0 => a,
+        _ => gcd(b, a % b),
+    }
+}
+
+fn downscale_boundary(boundary: &Boundary, factor: u32) -> Boundary {
+    match boundary {
+        Boundary::Solid => Boundary::Solid,
+        Boundary::Open { margin } => Boundary::Open { margin: margin.div_ceil(factor) },
+        Boundary::Unbounded { fill } => Boundary::Unbounded { fill: *fill },
+        Boundary::Custom(cell_at) => {
+            let cell_at = cell_at.clone();
+            let scale = factor as i32;
+            Boundary::Custom(Arc::new(move |CellPos(x, y)| {
+                let cells: Vec<_> = (0..scale)
+                    .flat_map(|dy| (0..scale).map(move |dx| (dx, dy)))
+                    .filter_map(|(dx, dy)| cell_at(CellPos(x * scale + dx, y * scale + dy)))
+                    .collect();
+
+                match cells.is_empty() {
+                    true => None,
+                    false => Some(Cell { is_wall: cells.iter().all(|cell| cell.is_wall) }),
+                }
+            }))
+        }
+    }
+}
+
+fn upscale_boundary(boundary: &Boundary, factor: u32) -> Boundary {
+    match boundary {
+        Boundary::Solid => Boundary::Solid,
+        Boundary::Open { margin } => Boundary::Open { margin: margin * factor },
+        Boundary::Unbounded { fill } => Boundary::Unbounded { fill: *fill },
+        Boundary::Custom(cell_at) => {
+            let cell_at = cell_at.clone();
+            let scale = factor as i32;
+            Boundary::Custom(Arc::new(move |CellPos(x, y)| cell_at(CellPos(x.div_euclid(scale), y.div_euclid(scale)))))
+        }
+    }
+}
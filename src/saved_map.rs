@@ -0,0 +1,493 @@
+//! Grids through serde, and maps saved as RON or JSON.
+//!
+//! With the `serde` cargo feature, [`Grid`] implements `Serialize` and
+//! `Deserialize`, so it can go into any format serde supports, inside the
+//! game's own save files too. The cells are written as rows of `#` and `.`
+//! from the top down, as in pattern files, so small maps can be drawn by hand
+//! in a text editor, alongside the boundary, connectivity, portals, exits,
+//! cells past the border of unbounded grids, the seed the grid was generated
+//! from and its terrain layer. Other layers aren't kept; the line-based
+//! `MapFormat` can save those the game registers.
+//!
+//! A [`SavedMap`] is a grid with the cells of its start and goal markers,
+//! saved to and loaded from RON or JSON files. [`MapFilePlugin`] saves the
+//! first grid editor to its [`MapFile`] on Ctrl+S and loads it back on Ctrl+O,
+//! by default; see `InputBindings`. Loading replaces the grid at once, without
+//! journaling it, so it can't be undone.
+
+use std::{
+    error::Error,
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    bindings::InputBindings,
+    console::ErrorConsole,
+    grid::{Boundary, CellPos, Connectivity, Exits, Grid, GridEditor},
+    map_file::{cell_char, exits_word, parse_cell, parse_exits_word, size_fits, MAX_CELLS},
+    markers::{Endpoint, EndpointMarker},
+    pattern::CellPattern,
+    storage::{Chunks, StorageKind},
+    terrain::Terrain,
+    GridEditSet,
+};
+
+/// What a [`Grid`] is serialized as.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Grid")]
+struct GridData {
+    width: u32,
+    height: u32,
+    #[serde(default)]
+    bitset: bool,
+    #[serde(default)]
+    boundary: BoundaryData,
+    #[serde(default)]
+    connectivity: Connectivity,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+    /// Top row first, `#` for walls and `.` for floor.
+    cells: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    chunks: Vec<ChunkData>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    portals: Vec<PortalData>,
+    /// The exits of the cells that can't be left every way, as the letters of
+    /// the directions they can be, or `-` for none.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    exits: Vec<(CellPos, String)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    terrain: Option<TerrainData>,
+}
+
+/// A `Boundary`, but for custom ones, which can't be saved and come back solid.
+#[derive(Serialize, Deserialize, Default)]
+enum BoundaryData {
+    #[default]
+    Solid,
+    Open { margin: u32 },
+    Unbounded { fill: char },
+}
+
+/// Cells written past the border of an unbounded grid, from the bottom left
+/// corner of their chunk.
+#[derive(Serialize, Deserialize)]
+struct ChunkData {
+    origin: CellPos,
+    /// Top row first, like the cells of the grid.
+    cells: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PortalData {
+    a: CellPos,
+    b: CellPos,
+    cost: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TerrainData {
+    fill: u8,
+    /// Top row first.
+    rows: Vec<Vec<u8>>,
+}
+
+/// The cells from `origin` in a `width` by `height` rectangle, top row first.
+fn rows(grid: &Grid, origin: CellPos, width: i32, height: i32) -> Vec<String> {
+    let CellPos(x0, y0) = origin;
+    let cell = |x, y| cell_char(grid.cell(CellPos(x, y)).expect("rows are kept in the grid"));
+    (y0..y0 + height).rev().map(|y| (x0..x0 + width).map(|x| cell(x, y)).collect()).collect()
+}
+
+/// Sets the cells from `origin` to those of `rows`, top row first.
+fn set_rows(grid: &mut Grid, origin: CellPos, rows: &[String]) -> Result<(), String> {
+    let pattern = CellPattern::from_rows(rows.iter().map(String::as_str))?;
+    let CellPos(x0, y0) = origin;
+    if x0.checked_add(pattern.width() as i32).is_none() || y0.checked_add(pattern.height() as i32).is_none() {
+        return Err(format!("rows at {x0}, {y0} run past the last cell coordinates"));
+    }
+    for (cell_pos, cell) in pattern.cells_at(origin) {
+        grid.set_cell(cell_pos, cell).map_err(|err| err.to_string())?;
+    }
+    Ok(())
+}
+
+impl From<&Grid> for GridData {
+    fn from(grid: &Grid) -> Self {
+        let (width, height) = (grid.width() as i32, grid.height() as i32);
+        let boundary = match grid.boundary() {
+            Boundary::Open { margin } => BoundaryData::Open { margin: *margin },
+            Boundary::Unbounded { fill } => BoundaryData::Unbounded { fill: cell_char(*fill) },
+            Boundary::Solid | Boundary::Custom(_) => BoundaryData::Solid,
+        };
+        let chunks = grid
+            .chunk_origins()
+            .map(|origin| ChunkData { origin, cells: rows(grid, origin, Chunks::SIZE, Chunks::SIZE) })
+            .collect();
+        // Portals are stored in both directions; keep each pair once.
+        let portals = grid
+            .portals()
+            .filter(|&(a, portal)| (a.0, a.1) <= (portal.exit.0, portal.exit.1))
+            .map(|(a, portal)| PortalData { a, b: portal.exit, cost: portal.cost })
+            .collect();
+        let exits = grid
+            .iter_cell_pos()
+            .map(|(cell_pos, _)| (cell_pos, grid.exits(cell_pos)))
+            .filter(|&(_, exits)| exits != Exits::ALL)
+            .map(|(cell_pos, exits)| (cell_pos, exits_word(exits)))
+            .collect();
+        let terrain = grid.layer::<Terrain>().map(|layer| TerrainData {
+            fill: layer.fill().0,
+            rows: (0..height)
+                .rev()
+                .map(|y| (0..width).map(|x| layer.get(CellPos(x, y)).expect("rows are kept in the layer").0).collect())
+                .collect(),
+        });
+
+        GridData {
+            width: grid.width(),
+            height: grid.height(),
+            bitset: grid.storage_kind() == StorageKind::Bitset,
+            boundary,
+            connectivity: grid.connectivity(),
+            seed: grid.seed(),
+            cells: rows(grid, CellPos(0, 0), width, height),
+            chunks,
+            portals,
+            exits,
+            terrain,
+        }
+    }
+}
+
+impl TryFrom<GridData> for Grid {
+    type Error = String;
+
+    fn try_from(data: GridData) -> Result<Self, Self::Error> {
+        if !size_fits(data.width, data.height) {
+            return Err(format!("{}x{} is over {MAX_CELLS} cells", data.width, data.height));
+        }
+        let storage = if data.bitset { StorageKind::Bitset } else { StorageKind::Cells };
+        let mut grid = Grid::with_storage(data.width, data.height, storage);
+
+        let (width, height) = (data.width as usize, data.height as usize);
+        if data.cells.len() != height || data.cells.iter().any(|row| row.chars().count() != width) {
+            return Err(format!("cells aren't {} rows of {} cells", data.height, data.width));
+        }
+        set_rows(&mut grid, CellPos(0, 0), &data.cells)?;
+
+        // Before the chunks, which only unbounded grids can hold.
+        let boundary = match data.boundary {
+            BoundaryData::Solid => Boundary::Solid,
+            BoundaryData::Open { margin } => Boundary::Open { margin },
+            BoundaryData::Unbounded { fill } => {
+                Boundary::Unbounded { fill: parse_cell(fill).ok_or(format!("unexpected cell {fill:?}"))? }
+            }
+        };
+        grid.set_boundary(boundary).set_connectivity(data.connectivity);
+        grid.set_seed(data.seed);
+
+        for chunk in &data.chunks {
+            set_rows(&mut grid, chunk.origin, &chunk.cells)?;
+        }
+        for portal in &data.portals {
+            grid.link_portals(portal.a, portal.b, portal.cost).map_err(|err| err.to_string())?;
+        }
+        for (cell_pos, word) in &data.exits {
+            let exits = parse_exits_word(word).ok_or(format!("unexpected exits {word:?}"))?;
+            grid.set_exits(*cell_pos, exits).map_err(|err| err.to_string())?;
+        }
+
+        if let Some(terrain) = data.terrain {
+            if terrain.rows.len() != height || terrain.rows.iter().any(|row| row.len() != width) {
+                return Err(format!("terrain isn't {} rows of {} cells", data.height, data.width));
+            }
+            grid.add_layer(Terrain(terrain.fill));
+            let layer = grid.layer_mut::<Terrain>().expect("layer was just added");
+            for (y, row) in (0..data.height as i32).rev().zip(&terrain.rows) {
+                for (x, &kind) in (0..).zip(row) {
+                    layer.set(CellPos(x, y), Terrain(kind)).expect("rows are checked against the size");
+                }
+            }
+        }
+
+        Ok(grid)
+    }
+}
+
+impl Serialize for Grid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        GridData::from(self).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Grid {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Grid::try_from(GridData::deserialize(deserializer)?).map_err(de::Error::custom)
+    }
+}
+
+/// How a [`SavedMap`] is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MapEncoding {
+    #[default]
+    Ron,
+    Json,
+}
+
+impl MapEncoding {
+    /// JSON for paths ending in `.json`, RON for any other.
+    pub fn of(path: &Path) -> Self {
+        match path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("json")) {
+            true => MapEncoding::Json,
+            false => MapEncoding::Ron,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SavedMapError {
+    Io(io::Error),
+    Ron(ron::Error),
+    RonSyntax(ron::error::SpannedError),
+    Json(serde_json::Error),
+}
+
+impl Display for SavedMapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SavedMapError::Io(err) => write!(f, "saved map io error: {err}"),
+            SavedMapError::Ron(err) => write!(f, "saved map RON error: {err}"),
+            SavedMapError::RonSyntax(err) => write!(f, "saved map RON error: {err}"),
+            SavedMapError::Json(err) => write!(f, "saved map JSON error: {err}"),
+        }
+    }
+}
+impl Error for SavedMapError {}
+
+impl From<io::Error> for SavedMapError {
+    fn from(err: io::Error) -> Self {
+        SavedMapError::Io(err)
+    }
+}
+
+impl From<ron::Error> for SavedMapError {
+    fn from(err: ron::Error) -> Self {
+        SavedMapError::Ron(err)
+    }
+}
+
+impl From<ron::error::SpannedError> for SavedMapError {
+    fn from(err: ron::error::SpannedError) -> Self {
+        SavedMapError::RonSyntax(err)
+    }
+}
+
+impl From<serde_json::Error> for SavedMapError {
+    fn from(err: serde_json::Error) -> Self {
+        SavedMapError::Json(err)
+    }
+}
+
+/// A grid and the cells its start and goal markers were on, if it had them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedMap {
+    pub grid: Grid,
+    #[serde(default)]
+    pub start: Option<CellPos>,
+    #[serde(default)]
+    pub goal: Option<CellPos>,
+}
+
+impl SavedMap {
+    pub fn new(grid: Grid) -> Self {
+        SavedMap { grid, start: None, goal: None }
+    }
+
+    pub fn with_endpoints(mut self, start: Option<CellPos>, goal: Option<CellPos>) -> Self {
+        self.start = start;
+        self.goal = goal;
+        self
+    }
+
+    pub fn encode(&self, encoding: MapEncoding) -> Result<String, SavedMapError> {
+        Ok(match encoding {
+            MapEncoding::Ron => ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new())?,
+            MapEncoding::Json => serde_json::to_string_pretty(self)?,
+        })
+    }
+
+    pub fn decode(text: &str, encoding: MapEncoding) -> Result<Self, SavedMapError> {
+        Ok(match encoding {
+            MapEncoding::Ron => ron::from_str(text)?,
+            MapEncoding::Json => serde_json::from_str(text)?,
+        })
+    }
+
+    /// Writes the map to `path`, as JSON if it ends in `.json` and RON if not.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SavedMapError> {
+        let path = path.as_ref();
+        fs::write(path, self.encode(MapEncoding::of(path))?)?;
+        Ok(())
+    }
+
+    /// Reads a map [`save`](Self::save) wrote.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SavedMapError> {
+        let path = path.as_ref();
+        SavedMap::decode(&fs::read_to_string(path)?, MapEncoding::of(path))
+    }
+}
+
+/// Where the [`MapFilePlugin`] saves and loads the map.
+#[derive(Resource, Debug, Clone, PartialEq, Eq)]
+pub struct MapFile {
+    pub path: PathBuf,
+}
+
+/// Saves the first grid editor and its markers to the [`MapFile`] and loads
+/// them back, on the `save_map` and `open_map` bindings.
+#[derive(Debug, Clone)]
+pub struct MapFilePlugin {
+    /// Where the map goes until the `MapFile` is changed; `map.ron` by default.
+    pub path: PathBuf,
+}
+
+impl Default for MapFilePlugin {
+    fn default() -> Self {
+        MapFilePlugin { path: PathBuf::from("map.ron") }
+    }
+}
+
+impl MapFilePlugin {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        MapFilePlugin { path: path.into() }
+    }
+}
+
+impl Plugin for MapFilePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<InputBindings>()
+            .insert_resource(MapFile { path: self.path.clone() })
+            .add_system(map_file_keys.before(GridEditSet));
+    }
+}
+
+fn map_file_keys(
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<InputBindings>,
+    map_file: Res<MapFile>,
+    editors: Query<(&GridEditor, Entity)>,
+    mut markers: Query<&mut EndpointMarker>,
+    mut assets: ResMut<Assets<Grid>>,
+    mut console: ResMut<ErrorConsole>,
+) {
+    let save = bindings.save_map.just_pressed(&keys);
+    let open = bindings.open_map.just_pressed(&keys);
+    let Some((editor, entity)) = editors.iter().next() else {
+        return;
+    };
+    let path = &map_file.path;
+
+    if save {
+        let Some(grid) = assets.get(&editor.grid) else {
+            return;
+        };
+        let marked = |endpoint| {
+            let mut markers = markers.iter().filter(|marker| marker.grid == entity);
+            markers.find(|marker| marker.endpoint == endpoint).map(|marker| marker.cell_pos)
+        };
+        let saved = SavedMap::new(grid.clone()).with_endpoints(marked(Endpoint::Start), marked(Endpoint::Goal));
+        match saved.save(path) {
+            Ok(()) => info!("saved map to {}", path.display()),
+            Err(error) => console.report("saving map", &error),
+        }
+    }
+
+    if open {
+        let saved = match SavedMap::load(path) {
+            Ok(saved) => saved,
+            Err(error) => {
+                console.report("loading map", &error);
+                return;
+            }
+        };
+        let Some(grid) = assets.get_mut(&editor.grid) else {
+            return;
+        };
+        *grid = saved.grid;
+        for mut marker in markers.iter_mut().filter(|marker| marker.grid == entity) {
+            let cell_pos = match marker.endpoint {
+                Endpoint::Start => saved.start,
+                Endpoint::Goal => saved.goal,
+            };
+            if let Some(cell_pos) = cell_pos {
+                marker.cell_pos = cell_pos;
+            }
+        }
+        info!("loaded map from {}", path.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        grid::{Cell, Direction},
+        test_util::assert_same_grid,
+    };
+
+    fn example_grid() -> Grid {
+        let mut grid = Grid::with_storage(5, 3, StorageKind::Bitset).with_connectivity(Connectivity::Hex);
+        grid.set_seed(Some(9));
+        for cell_pos in [CellPos(0, 2), CellPos(2, 1), CellPos(4, 0)] {
+            grid.set_cell(cell_pos, Cell::wall()).unwrap();
+        }
+        grid.link_portals(CellPos(0, 0), CellPos(4, 2), 3.0).unwrap();
+        grid.set_exits(CellPos(1, 1), Exits::ALL.without(Direction::East)).unwrap();
+        grid.add_layer(Terrain::PLAIN);
+        grid.layer_mut::<Terrain>().unwrap().set(CellPos(3, 2), Terrain(4)).unwrap();
+        grid
+    }
+
+    #[test]
+    fn saved_maps_decode_to_the_same_map() {
+        let mut unbounded = Grid::new(2, 2);
+        unbounded.set_boundary(Boundary::Unbounded { fill: Cell::wall() });
+        unbounded.set_cell(CellPos(-20, 7), Cell::floor()).unwrap();
+
+        for grid in [example_grid(), unbounded] {
+            let map = SavedMap::new(grid).with_endpoints(Some(CellPos(1, 0)), None);
+            for encoding in [MapEncoding::Ron, MapEncoding::Json] {
+                let decoded = SavedMap::decode(&map.encode(encoding).unwrap(), encoding).unwrap();
+                assert_same_grid(&decoded.grid, &map.grid);
+                assert_eq!((decoded.start, decoded.goal), (map.start, map.goal));
+            }
+        }
+    }
+
+    #[test]
+    fn grid_data_converts_back_to_the_same_grid() {
+        let grid = example_grid();
+        let data = GridData::from(&grid);
+        assert_eq!(data.cells, ["#....", "..#..", "....#"]);
+        assert_same_grid(&Grid::try_from(data).unwrap(), &grid);
+    }
+
+    #[test]
+    fn oversized_grids_are_refused() {
+        let mut data = GridData::from(&Grid::new(1, 1));
+        (data.width, data.height) = (u32::MAX, 2);
+        assert!(Grid::try_from(data).is_err());
+
+        let mut data = GridData::from(&Grid::new(1, 1));
+        data.boundary = BoundaryData::Unbounded { fill: '.' };
+        data.chunks.push(ChunkData { origin: CellPos(i32::MAX, 0), cells: vec!["##".to_string()] });
+        assert!(Grid::try_from(data).is_err());
+    }
+}
@@ -0,0 +1,145 @@
+//! Live numbers on the search being visualized.
+//!
+//! The [`SearchStats`] resource follows the [`SearchVisualizer`] that stepped
+//! most recently, so it describes the search running now or, once they have all
+//! finished, the last one to run. [`SearchStatsPlugin`] shows the same numbers
+//! in the top right corner of the window.
+
+use bevy::{prelude::*, utils::Duration};
+
+use crate::{
+    pathfinding::{Planner, StepResult},
+    search_view::SearchVisualizer,
+};
+
+/// How the latest search visualization went, as of its last step.
+#[derive(Resource, Debug, Clone, Default, PartialEq)]
+pub struct SearchStats {
+    /// Entity of the visualizer, `None` until one has stepped.
+    pub visualizer: Option<Entity>,
+    pub planner: Option<Planner>,
+    pub nodes_expanded: usize,
+    /// Most cells that were waiting to be expanded at once.
+    pub peak_open: usize,
+    /// Steps along the path found, `None` while searching or without a path.
+    pub path_length: Option<usize>,
+    pub path_cost: Option<f32>,
+    /// Wall-clock time spent stepping the search, leaving out the time between ticks.
+    pub elapsed: Duration,
+    pub finished: bool,
+}
+
+impl SearchStats {
+    fn of(visualizer: &SearchVisualizer, entity: Entity) -> Self {
+        let search = &visualizer.search;
+        let path = match search.result() {
+            Some(StepResult::Found(path)) => Some(path),
+            _ => None,
+        };
+
+        SearchStats {
+            visualizer: Some(entity),
+            planner: Some(search.planner()),
+            nodes_expanded: search.nodes_expanded(),
+            peak_open: search.peak_open(),
+            path_length: path.map(|path| path.cells.len().saturating_sub(1)),
+            path_cost: path.map(|path| path.cost),
+            elapsed: visualizer.elapsed(),
+            finished: search.is_finished(),
+        }
+    }
+}
+
+pub(crate) fn update_search_stats(
+    mut stats: ResMut<SearchStats>,
+    visualizers: Query<(&SearchVisualizer, Entity), Changed<SearchVisualizer>>,
+) {
+    let Some(latest) = visualizers.iter().last().map(|(visualizer, entity)| SearchStats::of(visualizer, entity)) else {
+        return;
+    };
+
+    if *stats != latest {
+        *stats = latest;
+    }
+}
+
+/// Shows the [`SearchStats`] in the top right corner of the window.
+pub struct SearchStatsPlugin {
+    /// Font used by the overlay, relative to the assets folder.
+    pub font: &'static str,
+}
+
+impl Default for SearchStatsPlugin {
+    fn default() -> Self {
+        SearchStatsPlugin { font: "fonts/FiraMono-Medium.ttf" }
+    }
+}
+
+impl Plugin for SearchStatsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<SearchStats>()
+            .insert_resource(StatsFont(self.font))
+            .add_startup_system(spawn_stats_text)
+            .add_system(update_stats_text.after(crate::ViewSyncSet));
+    }
+}
+
+#[derive(Resource)]
+struct StatsFont(&'static str);
+
+#[derive(Component)]
+struct StatsText;
+
+fn spawn_stats_text(mut commands: Commands, asset_server: Res<AssetServer>, font: Res<StatsFont>) {
+    let style = TextStyle {
+        font: asset_server.load(font.0),
+        font_size: 14.0,
+        color: Color::WHITE,
+    };
+
+    commands.spawn((
+        TextBundle::from_section("", style).with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: Val::Px(8.0),
+                right: Val::Px(8.0),
+                ..default()
+            },
+            padding: UiRect::all(Val::Px(4.0)),
+            ..default()
+        }),
+        BackgroundColor(Color::rgba(0.0, 0.0, 0.0, 0.7)),
+        StatsText,
+        Name::new("Search stats"),
+    ));
+}
+
+/// The lines of the overlay for `stats`.
+fn describe_stats(stats: &SearchStats) -> Vec<String> {
+    let Some(planner) = stats.planner else {
+        return vec!["no search".to_string()];
+    };
+
+    let path = match (stats.path_length, stats.path_cost) {
+        (Some(length), Some(cost)) => format!("path {length} steps, cost {cost:.2}"),
+        _ if stats.finished => "no path".to_string(),
+        _ => "searching".to_string(),
+    };
+
+    vec![
+        planner.name().to_string(),
+        format!("expanded {}", stats.nodes_expanded),
+        format!("open peak {}", stats.peak_open),
+        path,
+        format!("time {:.2} ms", stats.elapsed.as_secs_f64() * 1000.0),
+    ]
+}
+
+fn update_stats_text(stats: Res<SearchStats>, mut texts: Query<&mut Text, With<StatsText>>) {
+    for mut text in &mut texts {
+        if stats.is_changed() || text.sections[0].value.is_empty() {
+            text.sections[0].value = describe_stats(&stats).join("\n");
+        }
+    }
+}
@@ -0,0 +1,333 @@
+//! Watching a single search explore the grid, a few expansions at a time.
+//!
+//! A [`SearchVisualizer`] steps its [`Search`] on every tick of the
+//! [`VisualizationClock`] and paints it over the
+//! cell colors of its grid: the frontier, the closed cells and the cell the
+//! search will expand next each get their own color, and the path once found.
+//! Switching its [`SearchColoring`] to a score instead shades every reached cell
+//! on a gradient, showing the cost landscape of the search. With `show_tree`
+//! on, every reached cell also gets an arrow pointing to the cell it was
+//! reached from, drawing the search tree. With a `trail`, freshly expanded
+//! cells glow and fade out over a few seconds, showing where the frontier is
+//! heading and how fast even at thousands of expansions a second.
+
+use std::collections::HashMap;
+
+use bevy::{
+    prelude::*,
+    sprite::{MaterialMesh2dBundle, Mesh2dHandle},
+    utils::{Duration, Instant},
+};
+
+use crate::{
+    editor::CellOverlay,
+    grid::{CellPos, Grid, Grids},
+    pathfinding::{Search, StepResult},
+    playback::VisualizationClock,
+    terrain::TerrainCost,
+    theme::GridTheme,
+    view::{arrow_mesh, mix},
+};
+
+/// How far above the grid texture search trees are drawn, below path lines.
+const TREE_DEPTH: f32 = 0.5;
+
+/// What a [`SearchVisualizer`] colors cells by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SearchColoring {
+    /// Frontier, closed cells and the next cell to expand.
+    #[default]
+    Sets,
+    /// Cost so far, on the heatmap of the [`GridTheme`] from the start to the
+    /// costliest cell reached.
+    GScore,
+    /// Cost so far plus the estimate left, on the same gradient.
+    FScore,
+}
+
+impl SearchColoring {
+    pub const ALL: [SearchColoring; 3] = [SearchColoring::Sets, SearchColoring::GScore, SearchColoring::FScore];
+
+    /// The coloring after this one, wrapping around, for cycling through them at runtime.
+    pub fn next(self) -> Self {
+        match self {
+            SearchColoring::Sets => SearchColoring::GScore,
+            SearchColoring::GScore => SearchColoring::FScore,
+            SearchColoring::FScore => SearchColoring::Sets,
+        }
+    }
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct SearchVisualizer {
+    pub grid: Entity,
+    pub search: Search,
+    /// Step costs the search runs with.
+    pub cost: TerrainCost,
+    /// Expansions run per tick of the [`VisualizationClock`]; `0` pauses the
+    /// search, e.g. to step it by hand with [`SearchVisualizer::step`].
+    pub expansions_per_tick: usize,
+    /// Can be changed at any time, even once the search has finished.
+    pub coloring: SearchColoring,
+    /// Draws an arrow from every reached cell to the cell it was reached from.
+    pub show_tree: bool,
+    pub tree_color: Color,
+    /// Seconds an expanded cell glows for, fading out; `0.0` draws no trail.
+    pub trail: f32,
+    /// When each cell of the trail was expanded, in seconds since startup.
+    trail_cells: HashMap<CellPos, f32>,
+    /// Seconds since startup as of the last step or fade.
+    trail_time: f32,
+    /// Frontier cell the search continues from, as of the last step.
+    best: Option<CellPos>,
+    /// Highest g-score and f-score of the reached cells, as of the last step.
+    max_scores: (f32, f32),
+    /// Wall-clock time spent stepping the search so far.
+    elapsed: Duration,
+}
+
+impl SearchVisualizer {
+    pub fn new(grid: Entity, search: Search) -> Self {
+        SearchVisualizer {
+            grid,
+            search,
+            cost: TerrainCost::default(),
+            expansions_per_tick: 1,
+            coloring: SearchColoring::Sets,
+            show_tree: false,
+            tree_color: Color::BLACK,
+            trail: 0.0,
+            trail_cells: HashMap::new(),
+            trail_time: 0.0,
+            best: None,
+            max_scores: (0.0, 0.0),
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    pub fn with_cost(mut self, cost: TerrainCost) -> Self {
+        self.cost = cost;
+        self
+    }
+
+    pub fn with_coloring(mut self, coloring: SearchColoring) -> Self {
+        self.coloring = coloring;
+        self
+    }
+
+    pub fn with_tree(mut self, show_tree: bool) -> Self {
+        self.show_tree = show_tree;
+        self
+    }
+
+    pub fn with_trail(mut self, trail: f32) -> Self {
+        self.trail = trail;
+        self
+    }
+
+    pub fn with_expansions_per_tick(mut self, expansions_per_tick: usize) -> Self {
+        self.expansions_per_tick = expansions_per_tick;
+        self
+    }
+
+    pub fn best(&self) -> Option<CellPos> {
+        self.best
+    }
+
+    /// Wall-clock time spent in [`SearchVisualizer::step`] so far, which leaves
+    /// out the time between ticks.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Expands up to `max_expansions` cells of the search on `grid`, which must
+    /// be the visualizer's grid.
+    pub fn step(&mut self, grid: &Grid, max_expansions: usize) -> StepResult {
+        let expanded_before = self.search.nodes_expanded();
+        let started = Instant::now();
+        let result = self.search.step(grid, &self.cost, max_expansions);
+        self.elapsed += started.elapsed();
+
+        if self.trail > 0.0 {
+            let time = self.trail_time;
+            let expanded = &self.search.expansion_order()[expanded_before..];
+            self.trail_cells.extend(expanded.iter().map(|&cell_pos| (cell_pos, time)));
+        }
+        self.best = match result {
+            StepResult::Running => self.search.best_frontier(),
+            _ => None,
+        };
+
+        let search = &self.search;
+        self.max_scores = search
+            .closed_cells()
+            .chain(search.open_cells())
+            .filter_map(|cell_pos| Some((search.g_score(cell_pos)?, search.f_score(cell_pos)?)))
+            .fold((0.0, 0.0), |(max_g, max_f), (g, f)| (f32::max(max_g, g), f32::max(max_f, f)));
+        result
+    }
+
+    /// Moves the trail on to `time`, in seconds since startup, dropping the
+    /// cells that have faded out.
+    fn fade_trail(&mut self, time: f32) {
+        let trail = self.trail;
+        self.trail_time = time;
+        self.trail_cells.retain(|_, &mut expanded_at| time - expanded_at < trail);
+    }
+
+    /// How brightly `cell_pos` glows in the trail, from `1.0` right after it was
+    /// expanded down to `0.0`.
+    fn glow(&self, cell_pos: CellPos) -> Option<f32> {
+        let expanded_at = self.trail_cells.get(&cell_pos)?;
+        Some((1.0 - (self.trail_time - expanded_at) / self.trail).clamp(0.0, 1.0))
+    }
+
+    /// Where the score of `cell_pos` falls between zero and the highest score
+    /// reached, for the score colorings.
+    fn heat(&self, cell_pos: CellPos) -> Option<f32> {
+        let (score, max) = match self.coloring {
+            SearchColoring::Sets => return None,
+            SearchColoring::GScore => (self.search.g_score(cell_pos)?, self.max_scores.0),
+            SearchColoring::FScore => (self.search.f_score(cell_pos)?, self.max_scores.1),
+        };
+
+        match max > 0.0 {
+            true => Some((score / max).min(1.0)),
+            false => Some(0.0),
+        }
+    }
+}
+
+impl CellOverlay for SearchVisualizer {
+    fn grid(&self) -> Entity {
+        self.grid
+    }
+
+    fn cell_tint(&self, cell_pos: CellPos, base: Color, theme: &GridTheme) -> Option<Color> {
+        if let Some(StepResult::Found(path)) = self.search.result() {
+            if path.cells.contains(&cell_pos) {
+                return Some(mix(base, theme.path, 0.7));
+            }
+        }
+
+        let tint = if self.coloring != SearchColoring::Sets {
+            self.heat(cell_pos).map(|heat| theme.heat(heat))
+        } else if self.best == Some(cell_pos) {
+            Some(theme.next)
+        } else if self.search.is_open(cell_pos) {
+            Some(mix(base, theme.open, 0.6))
+        } else if self.search.is_closed(cell_pos) {
+            Some(mix(base, theme.closed, 0.6))
+        } else {
+            None
+        };
+
+        match self.glow(cell_pos) {
+            Some(glow) => Some(mix(tint.unwrap_or(base), theme.trail, glow)),
+            None => tint,
+        }
+    }
+
+    fn tinted_cells(&self) -> Option<Vec<CellPos>> {
+        Some(self.search.reached_cells().collect())
+    }
+}
+
+pub(crate) fn step_search_visualizers(
+    clock: Res<VisualizationClock>,
+    time: Res<Time>,
+    grids: Grids,
+    mut visualizers: Query<&mut SearchVisualizer>,
+) {
+    let time = time.elapsed_seconds();
+
+    for mut visualizer in &mut visualizers {
+        // Fading only while there is a trail, so idle visualizers don't show up as changed.
+        if !visualizer.trail_cells.is_empty() {
+            visualizer.fade_trail(time);
+        }
+
+        let expansions = match visualizer.expansions_per_tick {
+            0 => 0,
+            expansions_per_tick => clock.expansions(expansions_per_tick),
+        };
+        if expansions == 0 || visualizer.search.is_finished() {
+            continue;
+        }
+        let Ok(grid) = grids.get(visualizer.grid) else {
+            continue;
+        };
+
+        visualizer.trail_time = time;
+        visualizer.step(grid, expansions);
+    }
+}
+
+/// On the arrows drawn for the search tree of the [`SearchVisualizer`] on
+/// `visualizer`, a child of its grid entity.
+#[derive(Component, Debug, Clone)]
+pub struct SearchTree {
+    pub visualizer: Entity,
+    mesh: Handle<Mesh>,
+    material: Handle<ColorMaterial>,
+}
+
+/// Redraws the trees of visualizers that changed, such as by stepping, and
+/// removes them from visualizers that stopped showing them.
+pub(crate) fn draw_search_trees(
+    mut commands: Commands,
+    grids: Grids,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    visualizers: Query<(&SearchVisualizer, Entity), Changed<SearchVisualizer>>,
+    trees: Query<(&SearchTree, Entity)>,
+) {
+    for (visualizer, visualizer_entity) in &visualizers {
+        let tree = trees.iter().find(|(tree, _)| tree.visualizer == visualizer_entity);
+        if !visualizer.show_tree {
+            if let Some((_, tree_entity)) = tree {
+                commands.entity(tree_entity).despawn_recursive();
+            }
+            continue;
+        }
+        let Ok(grid) = grids.get(visualizer.grid) else {
+            continue;
+        };
+
+        let mesh = arrow_mesh(grid, visualizer.search.parents(), 1.0);
+        if let Some((tree, _)) = tree {
+            if let Some(tree_mesh) = meshes.get_mut(&tree.mesh) {
+                *tree_mesh = mesh;
+            }
+            if let Some(material) = materials.get_mut(&tree.material) {
+                material.color = visualizer.tree_color;
+            }
+            continue;
+        }
+
+        let (mesh, material) = (meshes.add(mesh), materials.add(ColorMaterial::from(visualizer.tree_color)));
+        let tree_entity = commands
+            .spawn(MaterialMesh2dBundle {
+                mesh: Mesh2dHandle(mesh.clone()),
+                material: material.clone(),
+                transform: Transform::from_xyz(0.0, 0.0, TREE_DEPTH),
+                ..default()
+            })
+            .insert((SearchTree { visualizer: visualizer_entity, mesh, material }, Name::new("Search tree")))
+            .id();
+        commands.entity(visualizer.grid).add_child(tree_entity);
+    }
+}
+
+/// Removes the trees of visualizers that are gone.
+pub(crate) fn erase_search_trees(
+    mut commands: Commands,
+    trees: Query<(&SearchTree, Entity)>,
+    visualizers: Query<(), With<SearchVisualizer>>,
+) {
+    for (tree, tree_entity) in &trees {
+        if !visualizers.contains(tree.visualizer) {
+            commands.entity(tree_entity).despawn_recursive();
+        }
+    }
+}
@@ -0,0 +1,59 @@
+//! Reproducible randomness for the randomizer and the map generators.
+//!
+//! `RandomizeWalls`, `GenerateMaze`, `GenerateCave`, `DrunkardWalk`,
+//! `GenerateDungeon`, `GenerateBspDungeon` and `GenerateWfc` don't draw from
+//! the thread's generator but from the [`MapRng`] resource, which hands each
+//! map laid out a seed of its own. The seed is recorded on the grid, see
+//! [`Grid::seed`](crate::grid::Grid::seed), and saved with it in map files. The
+//! same seed and settings lay out the same map again, and a run of maps
+//! generated after [`MapRng::reseed`] is the same run every time, which is what
+//! tests and bug reports need.
+
+use bevy::prelude::*;
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+
+/// Where the randomizer and the map generators get their seeds. Starts from a
+/// random seed; insert one made with [`MapRng::new`] before adding the
+/// `AStarPlugin` to start from a known one.
+#[derive(Resource, Debug, Clone)]
+pub struct MapRng {
+    seed: u64,
+    /// Seed of the next map laid out.
+    next: u64,
+    rng: StdRng,
+}
+
+impl MapRng {
+    pub fn new(seed: u64) -> Self {
+        MapRng { seed, next: seed, rng: StdRng::seed_from_u64(seed) }
+    }
+
+    /// The seed the resource was last started from.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Starts over from `seed`. The next map laid out is generated from
+    /// `seed` itself, so reseeding with the seed recorded on a grid and
+    /// generating it again with the same settings gives the same map.
+    pub fn reseed(&mut self, seed: u64) {
+        *self = MapRng::new(seed);
+    }
+
+    /// The seed to lay out the next map from.
+    pub fn next_seed(&mut self) -> u64 {
+        std::mem::replace(&mut self.next, self.rng.next_u64())
+    }
+
+    /// A generator for the next map, and the seed it was made from.
+    pub fn next_rng(&mut self) -> (u64, StdRng) {
+        let seed = self.next_seed();
+        (seed, StdRng::seed_from_u64(seed))
+    }
+}
+
+impl Default for MapRng {
+    fn default() -> Self {
+        MapRng::new(rand::random())
+    }
+}
@@ -0,0 +1,208 @@
+//! Leak and drift detection for long-running apps.
+//!
+//! [`SoakMonitorPlugin`] samples a set of counters at a fixed interval: live
+//! entities, painted cells and how far they drift from the cells of the grids
+//! they show, journal entries, chunks of unbounded grids, console reports and,
+//! on Linux, resident memory. Apps can add their own with [`SoakMonitor::record`].
+//!
+//! A counter is flagged as growing once the lowest value of each of its last
+//! [`SoakMonitor::WINDOWS`] windows of samples is higher than the one before.
+//! Comparing minimums keeps a counter that merely fluctuates, like memory
+//! between allocator cycles, from being flagged, while a leak raises every
+//! window's floor. Flagged counters are reported to the [`ErrorConsole`].
+//!
+//! The `soak` example drives a headless app through hours of edits, searches,
+//! and spawning and despawning of grids and agents under this monitor.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+    fmt::Display,
+    fs,
+    time::Duration,
+};
+
+use bevy::prelude::*;
+
+#[cfg(feature = "visualizer")]
+use crate::view::CellColors;
+use crate::{console::ErrorConsole, grid::Grids, journal::GridJournal};
+
+/// Reported when a counter has kept growing over the whole observed window.
+#[derive(Debug)]
+pub struct MetricGrowing {
+    pub metric: String,
+    pub from: u64,
+    pub to: u64,
+}
+
+impl Display for MetricGrowing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let MetricGrowing { metric, from, to } = self;
+        write!(f, "{metric} keeps growing: from {from} to {to}")
+    }
+}
+
+impl Error for MetricGrowing {}
+
+#[derive(Resource, Debug, Clone)]
+pub struct SoakMonitor {
+    /// Samples of each counter kept for growth detection; older ones are dropped.
+    pub max_samples: usize,
+    timer: Timer,
+    samples: BTreeMap<String, Vec<u64>>,
+    /// Counters already reported, so each growth is reported once.
+    reported: BTreeSet<String>,
+}
+
+impl Default for SoakMonitor {
+    fn default() -> Self {
+        SoakMonitor::new(Duration::from_secs(10))
+    }
+}
+
+impl SoakMonitor {
+    /// Windows of samples a counter must rise across to be flagged.
+    pub const WINDOWS: usize = 5;
+
+    /// Samples the counters every `interval`.
+    pub fn new(interval: Duration) -> Self {
+        SoakMonitor {
+            max_samples: 1_000,
+            timer: Timer::new(interval, TimerMode::Repeating),
+            samples: BTreeMap::new(),
+            reported: BTreeSet::new(),
+        }
+    }
+
+    /// Adds a sample of `metric`, e.g. the size of a cache the app keeps.
+    pub fn record(&mut self, metric: &str, value: u64) {
+        let samples = self.samples.entry(metric.to_string()).or_default();
+        samples.push(value);
+        if samples.len() > self.max_samples {
+            samples.remove(0);
+        }
+    }
+
+    pub fn samples(&self, metric: &str) -> &[u64] {
+        self.samples.get(metric).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn metrics(&self) -> impl Iterator<Item = &str> + '_ {
+        self.samples.keys().map(String::as_str)
+    }
+
+    /// Every counter whose window minimums have risen monotonically. Needs at
+    /// least two samples per window.
+    pub fn growing(&self) -> Vec<MetricGrowing> {
+        self.samples
+            .iter()
+            .filter_map(|(metric, samples)| {
+                if samples.len() < 2 * Self::WINDOWS {
+                    return None;
+                }
+
+                let window_len = samples.len() / Self::WINDOWS;
+                let recent = &samples[samples.len() - window_len * Self::WINDOWS..];
+                let minimums: Vec<u64> = recent
+                    .chunks(window_len)
+                    .map(|window| window.iter().copied().min().expect("windows aren't empty"))
+                    .collect();
+
+                minimums.windows(2).all(|pair| pair[1] > pair[0]).then(|| MetricGrowing {
+                    metric: metric.clone(),
+                    from: recent[0],
+                    to: *recent.last().expect("checked to have samples"),
+                })
+            })
+            .collect()
+    }
+
+    /// One line per counter with its first, latest and highest sample.
+    pub fn summary(&self) -> String {
+        self.samples
+            .iter()
+            .map(|(metric, samples)| {
+                let first = samples.first().copied().unwrap_or(0);
+                let last = samples.last().copied().unwrap_or(0);
+                let max = samples.iter().copied().max().unwrap_or(0);
+                format!("{metric:>20}: first {first}, latest {last}, max {max}\n")
+            })
+            .collect()
+    }
+}
+
+/// Resident memory of this process in KiB, where the platform exposes it.
+pub fn resident_memory_kib() -> Option<u64> {
+    // The second field of statm is resident pages; pages are 4 KiB on the
+    // platforms this is read on.
+    let statm = fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    Some(pages * 4)
+}
+
+/// Samples the built-in counters into the [`SoakMonitor`] resource and reports
+/// counters that keep growing.
+#[derive(Debug, Clone, Default)]
+pub struct SoakMonitorPlugin {
+    /// Time between samples; the monitor's default when `None`.
+    pub interval: Option<Duration>,
+}
+
+impl Plugin for SoakMonitorPlugin {
+    fn build(&self, app: &mut App) {
+        let monitor = match self.interval {
+            Some(interval) => SoakMonitor::new(interval),
+            None => SoakMonitor::default(),
+        };
+
+        app
+            .insert_resource(monitor)
+            .init_resource::<ErrorConsole>()
+            .add_system(sample_metrics);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn sample_metrics(
+    time: Res<Time>,
+    mut monitor: ResMut<SoakMonitor>,
+    mut console: ResMut<ErrorConsole>,
+    entities: Query<Entity>,
+    #[cfg(feature = "visualizer")] cell_colors: Query<&CellColors>,
+    grids: Grids,
+    journals: Query<&GridJournal>,
+) {
+    if !monitor.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let (mut grid_cells, mut chunks) = (0, 0);
+    for grid in grids.iter() {
+        grid_cells += grid.width() as u64 * grid.height() as u64;
+        chunks += grid.chunk_origins().count() as u64;
+    }
+
+    monitor.record("entities", entities.iter().len() as u64);
+    monitor.record("grid cells", grid_cells);
+    #[cfg(feature = "visualizer")]
+    {
+        // Painted cells without a grid cell, or grid cells left unpainted, once every view has drawn its grid.
+        let painted_cells = cell_colors.iter().map(|colors| colors.width() as u64 * colors.height() as u64).sum();
+        monitor.record("painted cells", painted_cells);
+        monitor.record("paint drift", painted_cells.abs_diff(grid_cells));
+    }
+    monitor.record("chunks", chunks);
+    monitor.record("journal entries", journals.iter().map(|journal| journal.entries().len() as u64).sum());
+    monitor.record("console reports", console.reports().count() as u64);
+
+    if let Some(memory) = resident_memory_kib() {
+        monitor.record("memory KiB", memory);
+    }
+
+    for growth in monitor.growing() {
+        if monitor.reported.insert(growth.metric.clone()) {
+            console.report("soak monitor", &growth);
+        }
+    }
+}
@@ -0,0 +1,152 @@
+//! Memory layouts for the cells of a grid.
+
+use std::{
+    collections::HashMap,
+    ops::{Deref, DerefMut},
+};
+
+use crate::grid::{Cell, CellPos};
+
+/// How a grid keeps its cells in memory, chosen when it is constructed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum StorageKind {
+    /// One `Cell` per position.
+    #[default]
+    Cells,
+    /// One bit per position, for maps made of nothing but walls and floor.
+    /// Takes an eighth of the memory, so more of the map stays in cache.
+    Bitset,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum CellStorage {
+    Cells(Vec<Cell>),
+    /// Set bits are walls.
+    Bitset(Vec<u64>),
+}
+
+impl CellStorage {
+    pub(crate) fn new(kind: StorageKind, len: usize, cell: Cell) -> Self {
+        match kind {
+            StorageKind::Cells => CellStorage::Cells(vec![cell; len]),
+            StorageKind::Bitset => {
+                let word = match cell.is_wall {
+                    true => u64::MAX,
+                    false => 0,
+                };
+                CellStorage::Bitset(vec![word; len.div_ceil(64)])
+            }
+        }
+    }
+
+    pub(crate) fn kind(&self) -> StorageKind {
+        match self {
+            CellStorage::Cells(_) => StorageKind::Cells,
+            CellStorage::Bitset(_) => StorageKind::Bitset,
+        }
+    }
+
+    pub(crate) fn get(&self, index: usize) -> Cell {
+        match self {
+            CellStorage::Cells(cells) => cells[index],
+            CellStorage::Bitset(words) => Cell { is_wall: words[index / 64] & (1 << (index % 64)) != 0 },
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `index` must be below the length the storage was created with.
+    #[cfg(feature = "unchecked-indexing")]
+    pub(crate) unsafe fn get_unchecked(&self, index: usize) -> Cell {
+        match self {
+            CellStorage::Cells(cells) => *cells.get_unchecked(index),
+            CellStorage::Bitset(words) => Cell { is_wall: words.get_unchecked(index / 64) & (1 << (index % 64)) != 0 },
+        }
+    }
+
+    pub(crate) fn set(&mut self, index: usize, cell: Cell) {
+        match self {
+            CellStorage::Cells(cells) => cells[index] = cell,
+            CellStorage::Bitset(words) => match cell.is_wall {
+                true => words[index / 64] |= 1 << (index % 64),
+                false => words[index / 64] &= !(1 << (index % 64)),
+            },
+        }
+    }
+}
+
+/// Cells written outside the bounds of an unbounded grid, in square chunks
+/// created on first write. Cells of a chunk that were never written hold the
+/// fill the chunk was created with.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Chunks(HashMap<(i32, i32), CellStorage>);
+
+impl Chunks {
+    pub(crate) const SIZE: i32 = 32;
+
+    fn locate(cell_pos: CellPos) -> ((i32, i32), usize) {
+        let CellPos(x, y) = cell_pos;
+        let key = (x.div_euclid(Self::SIZE), y.div_euclid(Self::SIZE));
+        let index = y.rem_euclid(Self::SIZE) * Self::SIZE + x.rem_euclid(Self::SIZE);
+        (key, index as usize)
+    }
+
+    pub(crate) fn get(&self, cell_pos: CellPos) -> Option<Cell> {
+        let (key, index) = Self::locate(cell_pos);
+        self.0.get(&key).map(|chunk| chunk.get(index))
+    }
+
+    pub(crate) fn cell_mut(&mut self, cell_pos: CellPos, kind: StorageKind, fill: Cell) -> CellMut<'_> {
+        let (key, index) = Self::locate(cell_pos);
+        let chunk = self
+            .0
+            .entry(key)
+            .or_insert_with(|| CellStorage::new(kind, (Self::SIZE * Self::SIZE) as usize, fill));
+        CellMut::new(chunk, index)
+    }
+
+    /// Bottom left cell of every chunk.
+    pub(crate) fn origins(&self) -> impl Iterator<Item = CellPos> + '_ {
+        self.0.keys().map(|&(x, y)| CellPos(x * Self::SIZE, y * Self::SIZE))
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+/// Write access to one stored cell, returned by `Grid::cell_mut`. The cell is
+/// written back when the guard is dropped, since a bitset has no `Cell` to
+/// borrow.
+pub struct CellMut<'a> {
+    storage: &'a mut CellStorage,
+    index: usize,
+    cell: Cell,
+}
+
+impl<'a> CellMut<'a> {
+    pub(crate) fn new(storage: &'a mut CellStorage, index: usize) -> Self {
+        let cell = storage.get(index);
+        CellMut { storage, index, cell }
+    }
+}
+
+impl Deref for CellMut<'_> {
+    type Target = Cell;
+
+    fn deref(&self) -> &Cell {
+        &self.cell
+    }
+}
+
+impl DerefMut for CellMut<'_> {
+    fn deref_mut(&mut self) -> &mut Cell {
+        &mut self.cell
+    }
+}
+
+impl Drop for CellMut<'_> {
+    fn drop(&mut self) {
+        self.storage.set(self.index, self.cell);
+    }
+}
@@ -0,0 +1,183 @@
+//! Incremental delivery of long paths.
+//!
+//! Instead of searching all the way to a distant goal before the agent can take
+//! its first step, a [`PathStream`] plans the route in bounded segments. The
+//! agent pops waypoints off the front while [`prefetch_path_streams`] plans the
+//! next segment once fewer than `lookahead` waypoints remain buffered.
+//!
+//! Each segment ends on the most promising frontier cell its search budget could
+//! reach, so the stitched route can be longer than the true shortest path. When
+//! segments stop getting closer to the goal (e.g. behind a wall too long for the
+//! budget to see around), the remainder is planned in one unbounded search.
+
+use std::{collections::VecDeque, error::Error, fmt::Display};
+
+use bevy::prelude::*;
+
+use crate::{
+    console::ErrorConsole,
+    grid::{CellPos, Grid, Grids},
+    pathfinding::AStar,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamStatus {
+    /// More segments will be planned as the buffer drains.
+    Streaming,
+    /// The last planned segment ends on the goal.
+    Complete,
+    /// The goal is unreachable from the end of the last segment.
+    Failed,
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct PathStream {
+    pub grid: Entity,
+    pub goal: CellPos,
+    /// Node expansions allowed when planning one segment.
+    pub segment_budget: usize,
+    /// Plan the next segment once this few waypoints are left in the buffer.
+    pub lookahead: usize,
+
+    waypoints: VecDeque<CellPos>,
+    /// Cell the next segment is planned from.
+    planned_until: CellPos,
+    status: StreamStatus,
+
+    closest_distance: f32,
+    stalled_segments: u32,
+}
+
+impl PathStream {
+    pub fn new(grid: Entity, start: CellPos, goal: CellPos) -> Self {
+        PathStream {
+            grid,
+            goal,
+            segment_budget: 2_000,
+            lookahead: 16,
+            waypoints: VecDeque::new(),
+            planned_until: start,
+            status: StreamStatus::Streaming,
+            closest_distance: f32::INFINITY,
+            stalled_segments: 0,
+        }
+    }
+
+    /// Segments in a row that may fail to get closer before giving up on budgets.
+    const MAX_STALLED_SEGMENTS: u32 = 2;
+
+    pub fn with_segment_budget(mut self, segment_budget: usize) -> Self {
+        self.segment_budget = segment_budget;
+        self
+    }
+
+    pub fn with_lookahead(mut self, lookahead: usize) -> Self {
+        self.lookahead = lookahead;
+        self
+    }
+
+    pub fn status(&self) -> StreamStatus {
+        self.status
+    }
+
+    /// Waypoints planned but not consumed yet.
+    pub fn buffered(&self) -> impl Iterator<Item = CellPos> + '_ {
+        self.waypoints.iter().copied()
+    }
+
+    pub fn peek_waypoint(&self) -> Option<CellPos> {
+        self.waypoints.front().copied()
+    }
+
+    pub fn pop_waypoint(&mut self) -> Option<CellPos> {
+        self.waypoints.pop_front()
+    }
+
+    /// True once the goal was reached and every waypoint has been consumed.
+    pub fn is_finished(&self) -> bool {
+        self.status != StreamStatus::Streaming && self.waypoints.is_empty()
+    }
+
+    pub fn needs_prefetch(&self) -> bool {
+        self.status == StreamStatus::Streaming && self.waypoints.len() <= self.lookahead
+    }
+
+    /// Plans one more segment from the end of the buffered route.
+    pub fn prefetch(&mut self, grid: &Grid) {
+        if self.status != StreamStatus::Streaming {
+            return;
+        }
+
+        if self.planned_until == self.goal {
+            self.status = StreamStatus::Complete;
+            return;
+        }
+
+        let budget = match self.stalled_segments >= Self::MAX_STALLED_SEGMENTS {
+            true => usize::MAX,
+            false => self.segment_budget,
+        };
+        let segment = AStar::new(grid).find_partial_path(self.planned_until, self.goal, budget);
+
+        let Some(segment) = segment else {
+            self.status = StreamStatus::Failed;
+            return;
+        };
+
+        // The first cell is where the previous segment ended.
+        self.waypoints.extend(segment.path.cells.iter().skip(1));
+        self.planned_until = *segment.path.cells.last().expect("paths contain at least the start");
+
+        let distance = grid.connectivity().distance(self.planned_until, self.goal);
+        if distance < self.closest_distance {
+            self.closest_distance = distance;
+            self.stalled_segments = 0;
+        } else {
+            self.stalled_segments += 1;
+        }
+
+        if segment.reaches_goal {
+            self.status = StreamStatus::Complete;
+        }
+    }
+}
+
+/// Reported when a stream gives up because its goal can't be reached.
+#[derive(Debug)]
+pub struct StreamFailed {
+    pub entity: Entity,
+    pub from: CellPos,
+    pub goal: CellPos,
+}
+
+impl Display for StreamFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let StreamFailed { entity, from, goal } = self;
+        write!(f, "path stream of entity {entity:?} can't reach {goal:?} from {from:?}")
+    }
+}
+
+impl Error for StreamFailed {}
+
+pub(crate) fn prefetch_path_streams(
+    grids: Grids,
+    mut streams: Query<(&mut PathStream, Entity)>,
+    mut console: ResMut<ErrorConsole>,
+) {
+    for (mut stream, entity) in &mut streams {
+        if !stream.needs_prefetch() {
+            continue;
+        }
+
+        // The grid may still be loading; try again next frame.
+        let Ok(grid) = grids.get(stream.grid) else {
+            continue;
+        };
+
+        stream.prefetch(grid);
+
+        if stream.status == StreamStatus::Failed {
+            console.report("prefetch_path_streams", &StreamFailed { entity, from: stream.planned_until, goal: stream.goal });
+        }
+    }
+}
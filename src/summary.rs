@@ -0,0 +1,79 @@
+//! Reflected facts about grid assets, for the WorldInspector.
+//!
+//! The inspector shows components and resources, and a grid lives in
+//! `Assets<Grid>` behind a handle, so grid editors and views carry a
+//! [`GridSummary`] of the grid they show, refreshed whenever the asset changes.
+
+use bevy::prelude::*;
+
+use crate::grid::{modified_grids, CellPos, Connectivity, Grid, GridHandle};
+
+/// Grids with more cells than this are summarized without their rows.
+const MAX_SUMMARY_CELLS: u32 = 64 * 64;
+
+/// The size, connectivity and cell counts of the grid an entity shows. Only a
+/// view of the asset: changing it doesn't change the grid.
+#[derive(Component, Reflect, Debug, Clone, Default, PartialEq)]
+#[reflect(Component)]
+pub struct GridSummary {
+    pub width: u32,
+    pub height: u32,
+    pub connectivity: Connectivity,
+    pub walls: usize,
+    pub floors: usize,
+    pub portals: usize,
+    /// The cells row by row, top row first, `#` for walls and `.` for floor.
+    /// Empty for grids too big to be read this way.
+    pub rows: Vec<String>,
+}
+
+impl GridSummary {
+    pub fn of(grid: &Grid) -> Self {
+        let walls = grid.iter_cell_pos().filter(|(_, cell)| cell.is_wall).count();
+        let cells = (grid.width() * grid.height()) as usize;
+
+        let rows = match grid.width() * grid.height() <= MAX_SUMMARY_CELLS {
+            true => (0..grid.height() as i32)
+                .rev()
+                .map(|y| {
+                    (0..grid.width() as i32)
+                        .map(|x| match grid.cell(CellPos(x, y)) {
+                            Ok(cell) if cell.is_wall => '#',
+                            _ => '.',
+                        })
+                        .collect()
+                })
+                .collect(),
+            false => Vec::new(),
+        };
+
+        GridSummary {
+            width: grid.width(),
+            height: grid.height(),
+            connectivity: grid.connectivity(),
+            walls,
+            floors: cells - walls,
+            portals: grid.portals().count(),
+            rows,
+        }
+    }
+}
+
+/// Summarizes the grids of new entities, and again whenever their asset is modified.
+pub(crate) fn summarize_grids<T: GridHandle>(
+    mut commands: Commands,
+    mut asset_events: EventReader<AssetEvent<Grid>>,
+    assets: Res<Assets<Grid>>,
+    grids: Query<(&T, Option<&GridSummary>, Entity)>,
+) {
+    let modified = modified_grids(&mut asset_events);
+
+    for (grid_handle, summary, entity) in &grids {
+        if summary.is_some() && !modified.contains(grid_handle.handle()) {
+            continue;
+        }
+        if let Some(grid) = assets.get(grid_handle.handle()) {
+            commands.entity(entity).insert(GridSummary::of(grid));
+        }
+    }
+}
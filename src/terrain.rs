@@ -0,0 +1,147 @@
+//! Terrain kinds and what they cost to cross, per kind of agent.
+//!
+//! A grid's terrain is kept in its `GridLayer<Terrain>`; cells without one are
+//! [`Terrain::PLAIN`]. [`TerrainCosts`] gives every kind its base cost, shared by
+//! all agents, and a [`CostProfile`] adjusts those costs for one kind of agent,
+//! so a boat and a soldier can path over the same grid differently. Profiles
+//! are registered in [`CostProfiles`] and named by path requests.
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::{
+    grid::{CellPos, Grid},
+    pathfinding::StepCost,
+};
+
+/// A kind of ground. The game gives kinds their meaning, e.g.
+/// `const WATER: Terrain = Terrain(1);`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Reflect, FromReflect)]
+pub struct Terrain(pub u8);
+
+impl Terrain {
+    pub const PLAIN: Terrain = Terrain(0);
+
+    /// The terrain of `cell_pos` on `grid`, plain if the grid has no terrain layer.
+    pub fn at(grid: &Grid, cell_pos: CellPos) -> Terrain {
+        grid.layer::<Terrain>().and_then(|layer| layer.get(cell_pos).ok()).copied().unwrap_or(Terrain::PLAIN)
+    }
+}
+
+/// Base cost of entering each kind of terrain, before any profile. Kinds
+/// without a cost cost `1.0`.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct TerrainCosts {
+    costs: HashMap<Terrain, f32>,
+}
+
+impl TerrainCosts {
+    pub fn with_cost(mut self, terrain: Terrain, cost: f32) -> Self {
+        self.set(terrain, cost);
+        self
+    }
+
+    pub fn set(&mut self, terrain: Terrain, cost: f32) -> &mut Self {
+        self.costs.insert(terrain, cost);
+        self
+    }
+
+    pub fn cost(&self, terrain: Terrain) -> f32 {
+        self.costs.get(&terrain).copied().unwrap_or(1.0)
+    }
+}
+
+/// How a profile treats one kind of terrain.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TerrainRule {
+    /// Multiplies the base cost, e.g. `5.0` to avoid water.
+    Scale(f32),
+    /// Crosses it like plain ground, at a cost of `1.0`.
+    Ignore,
+    /// Never enters it.
+    Forbid,
+}
+
+/// Adjustments to the base terrain costs for one kind of agent. Terrain without
+/// a rule keeps its base cost.
+///
+/// Costs below `1.0` let A* return paths that aren't the cheapest, since its
+/// heuristic assumes every step costs at least its length.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CostProfile {
+    rules: HashMap<Terrain, TerrainRule>,
+}
+
+impl CostProfile {
+    pub fn new() -> Self {
+        CostProfile::default()
+    }
+
+    pub fn scale(mut self, terrain: Terrain, factor: f32) -> Self {
+        self.rules.insert(terrain, TerrainRule::Scale(factor));
+        self
+    }
+
+    pub fn ignore(mut self, terrain: Terrain) -> Self {
+        self.rules.insert(terrain, TerrainRule::Ignore);
+        self
+    }
+
+    pub fn forbid(mut self, terrain: Terrain) -> Self {
+        self.rules.insert(terrain, TerrainRule::Forbid);
+        self
+    }
+
+    pub fn rule(&self, terrain: Terrain) -> Option<TerrainRule> {
+        self.rules.get(&terrain).copied()
+    }
+}
+
+/// The cost profiles path requests can name.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct CostProfiles {
+    profiles: HashMap<String, CostProfile>,
+}
+
+impl CostProfiles {
+    /// Registers `profile` under `name`, replacing any registered under the same name.
+    pub fn add(&mut self, name: impl Into<String>, profile: CostProfile) -> &mut Self {
+        self.profiles.insert(name.into(), profile);
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CostProfile> {
+        self.profiles.get(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> + '_ {
+        self.profiles.keys().map(String::as_str)
+    }
+}
+
+/// Steps cost the base cost of the terrain entered, adjusted by a profile.
+#[derive(Debug, Clone, Default)]
+pub struct TerrainCost {
+    pub base: TerrainCosts,
+    pub profile: CostProfile,
+}
+
+impl TerrainCost {
+    pub fn new(base: TerrainCosts, profile: CostProfile) -> Self {
+        TerrainCost { base, profile }
+    }
+}
+
+impl StepCost for TerrainCost {
+    fn step_cost(&self, grid: &Grid, _from: CellPos, to: CellPos) -> Option<f32> {
+        let terrain = Terrain::at(grid, to);
+
+        match self.profile.rule(terrain) {
+            None => Some(self.base.cost(terrain)),
+            Some(TerrainRule::Scale(factor)) => Some(self.base.cost(terrain) * factor),
+            Some(TerrainRule::Ignore) => Some(1.0),
+            Some(TerrainRule::Forbid) => None,
+        }
+    }
+}
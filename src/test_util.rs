@@ -0,0 +1,46 @@
+//! Checks shared by the tests of the modules that save and load grids.
+
+use crate::{
+    dungeon::DungeonRoom,
+    grid::{CellPos, Grid},
+    storage::Chunks,
+    terrain::Terrain,
+};
+
+fn sorted_portals(grid: &Grid) -> Vec<(CellPos, CellPos, f32)> {
+    let mut portals: Vec<_> = grid.portals().map(|(a, portal)| (a, portal.exit, portal.cost)).collect();
+    portals.sort_by_key(|&(CellPos(ax, ay), CellPos(bx, by), _)| (ax, ay, bx, by));
+    portals
+}
+
+fn sorted_chunk_origins(grid: &Grid) -> Vec<CellPos> {
+    let mut origins: Vec<_> = grid.chunk_origins().collect();
+    origins.sort_by_key(|&CellPos(x, y)| (x, y));
+    origins
+}
+
+/// Panics unless `loaded` matches `grid` in everything a map file can hold:
+/// cells, chunks, exits, portals, boundary, connectivity, storage, seed and the
+/// crate's own layers.
+pub(crate) fn assert_same_grid(loaded: &Grid, grid: &Grid) {
+    assert_eq!((loaded.width(), loaded.height()), (grid.width(), grid.height()));
+    assert_eq!(loaded.storage_kind(), grid.storage_kind());
+    assert_eq!(format!("{:?}", loaded.boundary()), format!("{:?}", grid.boundary()));
+    assert_eq!(loaded.connectivity(), grid.connectivity());
+    assert_eq!(loaded.seed(), grid.seed());
+    for (cell_pos, cell) in grid.iter_cell_pos() {
+        assert_eq!(loaded.cell(cell_pos).unwrap(), cell, "{cell_pos:?}");
+        assert_eq!(loaded.exits(cell_pos), grid.exits(cell_pos), "{cell_pos:?}");
+        assert_eq!(Terrain::at(loaded, cell_pos), Terrain::at(grid, cell_pos), "{cell_pos:?}");
+        assert_eq!(DungeonRoom::at(loaded, cell_pos), DungeonRoom::at(grid, cell_pos), "{cell_pos:?}");
+    }
+    assert_eq!(sorted_portals(loaded), sorted_portals(grid));
+
+    let origins = sorted_chunk_origins(grid);
+    assert_eq!(sorted_chunk_origins(loaded), origins);
+    for CellPos(x0, y0) in origins {
+        for (x, y) in (x0..x0 + Chunks::SIZE).flat_map(|x| (y0..y0 + Chunks::SIZE).map(move |y| (x, y))) {
+            assert_eq!(loaded.cell(CellPos(x, y)).unwrap(), grid.cell(CellPos(x, y)).unwrap(), "{x}, {y}");
+        }
+    }
+}
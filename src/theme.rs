@@ -0,0 +1,65 @@
+//! The colors grids are drawn in.
+//!
+//! Cell colors, search overlays and path lines all read the [`GridTheme`]
+//! resource every frame, so changing it recolors everything already drawn.
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::{terrain::Terrain, view::mix};
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct GridTheme {
+    pub wall: Color,
+    pub floor: Color,
+    /// Floor of each kind of terrain; plain floor and kinds without a color
+    /// are drawn in `floor`.
+    pub terrain: HashMap<Terrain, Color>,
+    /// Floor cells with a portal on them.
+    pub portal: Color,
+    /// Frontier of a search.
+    pub open: Color,
+    /// Cells a search has expanded.
+    pub closed: Color,
+    /// Frontier cell a search will expand next.
+    pub next: Color,
+    /// Glow of cells a search has just expanded, when it shows a trail.
+    pub trail: Color,
+    pub path: Color,
+    pub start: Color,
+    pub goal: Color,
+    /// Tint of the cells of a `CellSelection`.
+    pub selection: Color,
+    /// Lowest and highest ends of the score heatmaps.
+    pub heat_low: Color,
+    pub heat_high: Color,
+}
+
+impl Default for GridTheme {
+    fn default() -> Self {
+        GridTheme {
+            wall: Color::BLUE,
+            floor: Color::RED,
+            terrain: HashMap::new(),
+            portal: Color::PURPLE,
+            open: Color::GREEN,
+            closed: Color::DARK_GRAY,
+            next: Color::YELLOW,
+            trail: Color::ORANGE,
+            path: Color::WHITE,
+            start: Color::LIME_GREEN,
+            goal: Color::GOLD,
+            selection: Color::CYAN,
+            heat_low: Color::BLUE,
+            heat_high: Color::RED,
+        }
+    }
+}
+
+impl GridTheme {
+    /// The heatmap color `heat` of the way from its low end to its high end.
+    pub fn heat(&self, heat: f32) -> Color {
+        mix(self.heat_low, self.heat_high, heat)
+    }
+}
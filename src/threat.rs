@@ -0,0 +1,185 @@
+//! Danger shading: where enemies can strike this turn.
+//!
+//! An enemy threatens every cell it can move to within its movement budget,
+//! plus every cell within its attack radius of one of those. The attack area is
+//! a distance field spread from all reachable cells at once, so its cost
+//! doesn't grow with the size of the movement range. Threats from several
+//! enemies are counted per cell, and `ThreatOverlay` shades cells darker the
+//! more enemies threaten them when the `visualizer` feature is on.
+//!
+//! Attacks spread between touching floor cells regardless of exits, so they
+//! don't pass through walls but do cross one-way edges.
+
+use std::collections::{hash_map::Entry, HashMap, VecDeque};
+#[cfg(feature = "visualizer")]
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+
+use crate::{
+    grid::{CellPos, Grid},
+    pathfinding::{AStar, MovementRange},
+};
+#[cfg(feature = "visualizer")]
+use crate::{
+    grid::{CellChangeEvent, Grids},
+    view::{mix, CellColors},
+};
+
+/// How many enemies threaten each cell.
+#[derive(Debug, Clone, Default)]
+pub struct ThreatMap {
+    counts: HashMap<CellPos, u32>,
+}
+
+impl ThreatMap {
+    pub fn new() -> Self {
+        ThreatMap::default()
+    }
+
+    /// Adds one enemy that can move over `range` and attack `attack_radius` steps
+    /// further. Use this to plan movement with a custom step cost.
+    pub fn add_range(&mut self, grid: &Grid, range: &MovementRange, attack_radius: u32) -> &mut Self {
+        for cell_pos in attack_area(grid, range.iter().map(|(cell_pos, _)| cell_pos), attack_radius).into_keys() {
+            *self.counts.entry(cell_pos).or_default() += 1;
+        }
+        self
+    }
+
+    /// Adds one enemy on `start` moving with uniform step costs.
+    pub fn add_enemy(&mut self, grid: &Grid, start: CellPos, movement: f32, attack_radius: u32) -> &mut Self {
+        let range = AStar::new(grid).reachable_within(start, movement);
+        self.add_range(grid, &range, attack_radius)
+    }
+
+    /// Number of enemies that can strike `cell_pos`.
+    pub fn threat_level(&self, cell_pos: CellPos) -> u32 {
+        self.counts.get(&cell_pos).copied().unwrap_or(0)
+    }
+
+    pub fn is_threatened(&self, cell_pos: CellPos) -> bool {
+        self.counts.contains_key(&cell_pos)
+    }
+
+    pub fn max_threat_level(&self) -> u32 {
+        self.counts.values().copied().max().unwrap_or(0)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (CellPos, u32)> + '_ {
+        self.counts.iter().map(|(&cell_pos, &count)| (cell_pos, count))
+    }
+
+    pub fn clear(&mut self) {
+        self.counts.clear();
+    }
+}
+
+/// Steps from the nearest of `sources` to every cell within `radius` of them.
+pub fn attack_area(grid: &Grid, sources: impl IntoIterator<Item = CellPos>, radius: u32) -> HashMap<CellPos, u32> {
+    let mut distances = HashMap::new();
+    let mut queue = VecDeque::new();
+
+    for source in sources {
+        if distances.insert(source, 0).is_none() {
+            queue.push_back(source);
+        }
+    }
+
+    while let Some(cell_pos) = queue.pop_front() {
+        let distance = distances[&cell_pos];
+        if distance == radius {
+            continue;
+        }
+
+        for neighbor in grid.touching(cell_pos, grid.connectivity()) {
+            if let Entry::Vacant(entry) = distances.entry(neighbor) {
+                entry.insert(distance + 1);
+                queue.push_back(neighbor);
+            }
+        }
+    }
+
+    distances
+}
+
+/// An enemy whose threat is shown on the overlay of `grid`. Add it to the
+/// enemies the player selected and remove it to hide them again.
+#[derive(Component, Debug, Clone)]
+pub struct ThreatSource {
+    pub grid: Entity,
+    pub cell_pos: CellPos,
+    pub movement: f32,
+    pub attack_radius: u32,
+}
+
+/// Danger shading over the cells of the grid entity it is inserted on, kept up
+/// to date with that grid's `ThreatSource`s.
+#[cfg(feature = "visualizer")]
+#[derive(Component, Debug, Clone)]
+pub struct ThreatOverlay {
+    pub color: Color,
+    threats: ThreatMap,
+}
+
+#[cfg(feature = "visualizer")]
+impl ThreatOverlay {
+    pub fn new(color: Color) -> Self {
+        ThreatOverlay { color, threats: ThreatMap::new() }
+    }
+
+    pub fn threats(&self) -> &ThreatMap {
+        &self.threats
+    }
+
+    /// Color painted over `cell_pos` on top of `base`, if it is threatened.
+    pub fn cell_tint(&self, cell_pos: CellPos, base: Color) -> Option<Color> {
+        let level = self.threats.threat_level(cell_pos);
+        if level == 0 {
+            return None;
+        }
+
+        let strength = level as f32 / self.threats.max_threat_level() as f32;
+        Some(mix(base, self.color, 0.3 + 0.5 * strength))
+    }
+}
+
+/// Recomputes the overlays whose grid or sources changed since the last frame.
+#[cfg(feature = "visualizer")]
+pub(crate) fn update_threat_overlays(
+    grids: Grids,
+    mut overlays: Query<(&mut ThreatOverlay, Entity)>,
+    new_overlays: Query<(), Added<ThreatOverlay>>,
+    mut cell_events: EventReader<CellChangeEvent>,
+    sources: Query<&ThreatSource>,
+    changed_sources: Query<(), Changed<ThreatSource>>,
+    removed_sources: RemovedComponents<ThreatSource>,
+) {
+    // By asset, so every editor and view of a changed grid is recomputed.
+    let changed_grids: HashSet<&Handle<Grid>> =
+        cell_events.iter().filter_map(|event| grids.handle(event.grid)).collect();
+    let sources_changed = !changed_sources.is_empty() || removed_sources.iter().next().is_some();
+
+    for (mut overlay, grid_entity) in &mut overlays {
+        let grid_changed = grids.handle(grid_entity).is_some_and(|handle| changed_grids.contains(handle));
+        if !sources_changed && !grid_changed && !new_overlays.contains(grid_entity) {
+            continue;
+        }
+
+        let Ok(grid) = grids.get(grid_entity) else {
+            continue;
+        };
+
+        overlay.threats.clear();
+        for source in sources.iter().filter(|source| source.grid == grid_entity) {
+            overlay.threats.add_enemy(grid, source.cell_pos, source.movement, source.attack_radius);
+        }
+    }
+}
+
+/// Paints every overlay over the cell colors of its grid; runs after the views reset them.
+#[cfg(feature = "visualizer")]
+pub(crate) fn tint_threat_cells(mut overlays: Query<(&ThreatOverlay, &mut CellColors)>) {
+    for (overlay, mut colors) in &mut overlays {
+        colors.tint(|cell_pos, base| overlay.cell_tint(cell_pos, base));
+    }
+}
@@ -0,0 +1,139 @@
+//! A tooltip describing the cell under the cursor.
+//!
+//! [`CellTooltipPlugin`] shows, next to the cursor, the coordinates of the
+//! hovered cell, whether it is a wall, its terrain and what it costs to enter,
+//! and its g, h and f scores in the [`SearchVisualizer`] exploring its grid,
+//! if there is one. Handy when a heuristic or cost profile behaves oddly.
+
+use bevy::prelude::*;
+
+use crate::{
+    grid::{CellPos, Grid, Grids},
+    minimap::MainCameraFilter,
+    pathfinding::StepCost,
+    search_view::SearchVisualizer,
+    terrain::{Terrain, TerrainCosts},
+    view::{cell_pos_at, cursor_world_position, GridTexture},
+};
+
+/// Logical pixels between the cursor and the tooltip.
+const CURSOR_GAP: f32 = 16.0;
+
+/// Shows a tooltip with the data of the hovered cell.
+pub struct CellTooltipPlugin {
+    /// Font used by the tooltip, relative to the assets folder.
+    pub font: &'static str,
+}
+
+impl Default for CellTooltipPlugin {
+    fn default() -> Self {
+        CellTooltipPlugin { font: "fonts/FiraMono-Medium.ttf" }
+    }
+}
+
+impl Plugin for CellTooltipPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(TooltipFont(self.font))
+            .add_startup_system(spawn_tooltip)
+            .add_system(update_tooltip.after(crate::ViewSyncSet));
+    }
+}
+
+#[derive(Resource)]
+struct TooltipFont(&'static str);
+
+#[derive(Component)]
+struct CellTooltip;
+
+fn spawn_tooltip(mut commands: Commands, asset_server: Res<AssetServer>, font: Res<TooltipFont>) {
+    let style = TextStyle {
+        font: asset_server.load(font.0),
+        font_size: 14.0,
+        color: Color::WHITE,
+    };
+
+    commands.spawn((
+        TextBundle::from_section("", style).with_style(Style {
+            position_type: PositionType::Absolute,
+            padding: UiRect::all(Val::Px(4.0)),
+            ..default()
+        }),
+        BackgroundColor(Color::rgba(0.0, 0.0, 0.0, 0.7)),
+        CellTooltip,
+        Name::new("Cell tooltip"),
+    ));
+}
+
+/// The lines of the tooltip for `cell_pos` on `grid`.
+fn describe_cell(
+    grid: &Grid,
+    cell_pos: CellPos,
+    visualizer: Option<&SearchVisualizer>,
+    costs: Option<&TerrainCosts>,
+) -> Vec<String> {
+    let CellPos(x, y) = cell_pos;
+    let is_wall = grid.cell(cell_pos).is_ok_and(|cell| cell.is_wall);
+    let mut lines = vec![format!("({x}, {y}) {}", if is_wall { "wall" } else { "floor" })];
+
+    let terrain = Terrain::at(grid, cell_pos);
+    let cost = match visualizer {
+        Some(visualizer) => visualizer.cost.step_cost(grid, cell_pos, cell_pos),
+        None => Some(costs.map_or(1.0, |costs| costs.cost(terrain))),
+    };
+    lines.push(match cost {
+        Some(cost) => format!("terrain {}, cost {cost:.2}", terrain.0),
+        None => format!("terrain {}, forbidden", terrain.0),
+    });
+
+    if let Some(visualizer) = visualizer {
+        let search = &visualizer.search;
+        let h = search.h_score(cell_pos);
+        lines.push(match (search.g_score(cell_pos), search.f_score(cell_pos)) {
+            (Some(g), Some(f)) => format!("g {g:.2}  h {h:.2}  f {f:.2}"),
+            _ => format!("not reached, h {h:.2}"),
+        });
+    }
+    lines
+}
+
+fn update_tooltip(
+    windows: Res<Windows>,
+    costs: Option<Res<TerrainCosts>>,
+    grids: Grids,
+    cameras: Query<(&GlobalTransform, &OrthographicProjection), MainCameraFilter>,
+    drawn_grids: Query<(Entity, &GlobalTransform), With<GridTexture>>,
+    visualizers: Query<&SearchVisualizer>,
+    mut tooltips: Query<(&mut Text, &mut Style, &mut Visibility), With<CellTooltip>>,
+) {
+    let hovered = (|| {
+        let window = windows.get_primary()?;
+        let cursor = window.cursor_position()?;
+        let (camera_transform, projection) = cameras.iter().next()?;
+        let world = cursor_world_position(window, camera_transform, projection)?;
+
+        drawn_grids.iter().find_map(|(grid_entity, grid_transform)| {
+            let grid = grids.get(grid_entity).ok()?;
+            let cell_pos = cell_pos_at(grid, world - grid_transform.translation().truncate())?;
+            let visualizer = visualizers.iter().find(|visualizer| visualizer.grid == grid_entity);
+            Some((cursor, describe_cell(grid, cell_pos, visualizer, costs.as_deref())))
+        })
+    })();
+
+    for (mut text, mut style, mut visibility) in &mut tooltips {
+        let Some((cursor, lines)) = &hovered else {
+            if visibility.is_visible {
+                visibility.is_visible = false;
+            }
+            continue;
+        };
+
+        visibility.is_visible = true;
+        text.sections[0].value = lines.join("\n");
+        style.position = UiRect {
+            left: Val::Px(cursor.x + CURSOR_GAP),
+            bottom: Val::Px(cursor.y + CURSOR_GAP),
+            ..default()
+        };
+    }
+}
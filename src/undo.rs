@@ -0,0 +1,274 @@
+//! Undo and redo of grid edits, built on the [`GridJournal`].
+//!
+//! An [`UndoStack`] inserted next to a `GridJournal` groups what gets journaled
+//! into actions: everything journaled in one frame, or between
+//! [`UndoStack::begin_action`] and [`UndoStack::end_action`] for edits spread
+//! over several frames, such as a stroke of the wall painting brush. Undoing an
+//! action sets its cells back with `SetCellEvent`s, so views, searches and
+//! planners follow it like any other edit, and the journal keeps a record of it.
+//! Journal entries older than the oldest action kept are truncated, so
+//! [`UndoStack::limit`] bounds the journal too.
+//!
+//! [`UndoPlugin`] binds Ctrl+Z to undo and Ctrl+Y or Ctrl+Shift+Z to redo, by
+//! default; see `InputBindings`.
+//! Like the journal, only cells are covered; terrain and other layers aren't.
+
+use std::ops::Range;
+
+use bevy::prelude::*;
+
+use crate::{
+    bindings::InputBindings,
+    editor::SetCellEvent,
+    journal::{GridDiff, GridJournal},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UndoRequest {
+    Undo,
+    Redo,
+}
+
+/// Undoable actions of the grid editor it is inserted on, which also needs a
+/// [`GridJournal`].
+#[derive(Component, Debug, Clone)]
+pub struct UndoStack {
+    /// Most actions kept; the oldest are forgotten past it.
+    pub limit: usize,
+    /// Journal sequence numbers of the actions that can be undone, oldest first.
+    done: Vec<Range<u64>>,
+    /// Changes of the actions undone, most recently undone last.
+    undone: Vec<GridDiff>,
+    /// Sequence number the action being recorded starts at.
+    action_start: Option<u64>,
+    open: bool,
+    requested: Option<UndoRequest>,
+    /// What the edits being applied this frame are, so they aren't recorded as a new action.
+    applying: Option<UndoRequest>,
+}
+
+impl Default for UndoStack {
+    fn default() -> Self {
+        UndoStack {
+            limit: 256,
+            done: Vec::new(),
+            undone: Vec::new(),
+            action_start: None,
+            open: false,
+            requested: None,
+            applying: None,
+        }
+    }
+}
+
+impl UndoStack {
+    pub fn new() -> Self {
+        UndoStack::default()
+    }
+
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Undoes the latest action once the grid systems next run.
+    pub fn undo(&mut self) {
+        self.requested = Some(UndoRequest::Undo);
+    }
+
+    /// Redoes the latest undone action once the grid systems next run.
+    pub fn redo(&mut self) {
+        self.requested = Some(UndoRequest::Redo);
+    }
+
+    /// Records everything journaled from now until [`end_action`](Self::end_action)
+    /// as one action. Undo and redo wait until it ends.
+    pub fn begin_action(&mut self) {
+        self.open = true;
+    }
+
+    pub fn end_action(&mut self) {
+        self.open = false;
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.done.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.undone.is_empty()
+    }
+
+    /// Forgets every action, leaving the journal as it is.
+    pub fn clear(&mut self) {
+        self.done.clear();
+        self.undone.clear();
+    }
+
+    fn push_done(&mut self, seqs: Range<u64>) {
+        self.done.push(seqs);
+        if self.done.len() > self.limit {
+            self.done.drain(..self.done.len() - self.limit);
+        }
+    }
+
+    /// The latest action, unless the journal was truncated past its start since,
+    /// and undoing what is left of it would only undo part of the action. Every
+    /// older action is cut off as well then, so they are all forgotten.
+    fn pop_done(&mut self, journal: &GridJournal) -> Option<Range<u64>> {
+        let seqs = self.done.pop()?;
+        if seqs.start < journal.first_seq() {
+            self.done.clear();
+            return None;
+        }
+        Some(seqs)
+    }
+
+    /// Journal sequence number of the oldest entry an action still needs.
+    fn oldest_needed(&self, next_seq: u64) -> u64 {
+        let action_start = self.action_start.unwrap_or(next_seq);
+        self.done.first().map_or(action_start, |seqs| seqs.start.min(action_start))
+    }
+}
+
+/// Turns undo and redo requests into `SetCellEvent`s, before the edits are applied.
+pub(crate) fn apply_undo_requests(
+    mut stacks: Query<(&mut UndoStack, &GridJournal, Entity)>,
+    mut edits: EventWriter<SetCellEvent>,
+) {
+    for (mut stack, journal, grid_entity) in &mut stacks {
+        if stack.open {
+            continue;
+        }
+        let Some(request) = stack.requested.take() else {
+            continue;
+        };
+
+        let changes = match request {
+            UndoRequest::Undo => {
+                let Some(seqs) = stack.pop_done(journal) else {
+                    continue;
+                };
+                let diff = journal.diff(seqs);
+                let changes = diff.inverse();
+                stack.undone.push(diff);
+                changes
+            }
+            UndoRequest::Redo => {
+                let Some(diff) = stack.undone.pop() else {
+                    continue;
+                };
+                diff
+            }
+        };
+
+        for change in changes.changes() {
+            edits.send(SetCellEvent::new(grid_entity, change.cell_pos, change.new));
+        }
+        stack.applying = Some(request);
+    }
+}
+
+/// Groups what was journaled this frame into actions, once the edits are applied.
+pub(crate) fn record_undo_actions(mut stacks: Query<(&mut UndoStack, &mut GridJournal)>) {
+    for (mut stack, mut journal) in &mut stacks {
+        let next_seq = journal.next_seq();
+        let start = *stack.action_start.get_or_insert(next_seq);
+
+        match stack.applying.take() {
+            Some(UndoRequest::Undo) => {}
+            Some(UndoRequest::Redo) if start < next_seq => stack.push_done(start..next_seq),
+            Some(UndoRequest::Redo) => {}
+            None if stack.open => continue,
+            None if start < next_seq => {
+                stack.push_done(start..next_seq);
+                stack.undone.clear();
+            }
+            None => {}
+        }
+        stack.action_start = Some(next_seq);
+
+        let oldest_needed = stack.oldest_needed(next_seq);
+        if journal.first_seq() < oldest_needed {
+            journal.truncate_before(oldest_needed);
+        }
+    }
+}
+
+/// Undoes the latest action of every [`UndoStack`] with Ctrl+Z, and redoes with
+/// Ctrl+Y or Ctrl+Shift+Z, or the keys the `InputBindings` give.
+pub struct UndoPlugin;
+
+impl Plugin for UndoPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<InputBindings>()
+            .add_system(undo_keys.before(apply_undo_requests));
+    }
+}
+
+fn undo_keys(keys: Res<Input<KeyCode>>, bindings: Res<InputBindings>, mut stacks: Query<&mut UndoStack>) {
+    let undo = bindings.undo.just_pressed(&keys);
+    let redo = bindings.redo.just_pressed(&keys);
+    if !undo && !redo {
+        return;
+    }
+
+    for mut stack in &mut stacks {
+        if undo {
+            stack.undo();
+        } else if redo {
+            stack.redo();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::{Cell, CellPos, Grid};
+
+    /// Journals one wall per action, the way `record_undo_actions` groups them.
+    fn journal_actions(stack: &mut UndoStack, journal: &mut GridJournal, grid: &mut Grid, count: i32) {
+        for x in 0..count {
+            let start = journal.next_seq();
+            journal.record(grid, None, 0.0, CellPos(x, 0), Cell::wall()).unwrap();
+            stack.push_done(start..journal.next_seq());
+            stack.action_start = Some(journal.next_seq());
+        }
+    }
+
+    #[test]
+    fn the_journal_keeps_only_what_kept_actions_need() {
+        let mut grid = Grid::new(8, 1);
+        let mut journal = GridJournal::new();
+        let mut stack = UndoStack::new().with_limit(3);
+        journal_actions(&mut stack, &mut journal, &mut grid, 8);
+
+        let oldest_needed = stack.oldest_needed(journal.next_seq());
+        assert_eq!(oldest_needed, 5);
+        journal.truncate_before(oldest_needed);
+        assert_eq!(journal.entries().len(), 3);
+
+        let seqs = stack.pop_done(&journal).unwrap();
+        assert_eq!(journal.diff(seqs).changes()[0].cell_pos, CellPos(7, 0));
+    }
+
+    #[test]
+    fn actions_truncated_out_of_the_journal_are_not_undone() {
+        let mut grid = Grid::new(4, 2);
+        let mut journal = GridJournal::new();
+        let mut stack = UndoStack::new();
+        journal_actions(&mut stack, &mut journal, &mut grid, 2);
+
+        // One action of two walls, then the app trims the journal between them.
+        let start = journal.next_seq();
+        journal.record(&mut grid, None, 0.0, CellPos(0, 1), Cell::wall()).unwrap();
+        journal.record(&mut grid, None, 0.0, CellPos(1, 1), Cell::wall()).unwrap();
+        stack.push_done(start..journal.next_seq());
+        journal.truncate_before(start + 1);
+
+        assert_eq!(stack.pop_done(&journal), None);
+        assert!(!stack.can_undo());
+    }
+}
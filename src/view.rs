@@ -0,0 +1,539 @@
+//! Drawing grids.
+//!
+//! Each grid entity is drawn as one quad textured with a pixel per cell, so a
+//! 300x300 grid costs a single sprite and draw call rather than one per cell.
+//! The colors are kept in [`CellColors`], following the grid's
+//! `CellChangeEvent`s and painted over by overlays, and only the cells that
+//! changed are copied into the texture, so a frame costs what changed in it
+//! rather than the size of the grid.
+
+use std::{collections::HashMap, ops::Range};
+
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::PrimitiveTopology,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        texture::ImageSampler,
+    },
+};
+
+use crate::{
+    grid::{modified_grids, Cell, CellChangeEvent, CellPos, Connectivity, Grid, GridEditor, GridHandle, UnannouncedChanges},
+    terrain::Terrain,
+    theme::GridTheme,
+};
+
+pub fn cell_transform(grid: &Grid, cell_pos: CellPos) -> Transform {
+    let CellPos(x, y) = cell_pos;
+
+    let x_centered = (x - (grid.width() / 2) as i32) as f32;
+    let y_centered = (y - (grid.height() / 2) as i32) as f32;
+
+    // Hex rows are offset from each other, see `Connectivity::Hex`.
+    let row_shift = match grid.connectivity() == Connectivity::Hex && y.rem_euclid(2) == 1 {
+        true => 0.5,
+        false => 0.0,
+    };
+
+    Transform::from_xyz(x_centered + row_shift, y_centered, 0.0)
+}
+
+/// The cell drawn at `position` relative to the grid entity, the inverse of
+/// [`cell_transform`], or `None` outside the grid.
+pub fn cell_pos_at(grid: &Grid, position: Vec2) -> Option<CellPos> {
+    let y = position.y.round() as i32 + (grid.height() / 2) as i32;
+    let row_shift = match grid.connectivity() == Connectivity::Hex && y.rem_euclid(2) == 1 {
+        true => 0.5,
+        false => 0.0,
+    };
+    let x = (position.x - row_shift).round() as i32 + (grid.width() / 2) as i32;
+
+    let cell_pos = CellPos(x, y);
+    grid.contains_pos(cell_pos).then_some(cell_pos)
+}
+
+/// The world position under the cursor in `window`, seen through a 2D camera
+/// at `camera` with `projection`, or `None` while the cursor is outside it.
+pub(crate) fn cursor_world_position(
+    window: &Window,
+    camera: &GlobalTransform,
+    projection: &OrthographicProjection,
+) -> Option<Vec2> {
+    let cursor = window.cursor_position()?;
+    let offset = cursor - Vec2::new(window.width(), window.height()) / 2.0;
+    Some(camera.translation().truncate() + offset * projection.scale)
+}
+
+/// The bottom left and top right corners of the area the cells of `grid` are
+/// drawn on, relative to the grid entity.
+pub(crate) fn grid_bounds(grid: &Grid) -> (Vec2, Vec2) {
+    let min = cell_transform(grid, CellPos(0, 0)).translation.truncate() - Vec2::splat(0.5);
+    let mut max = min + Vec2::new(grid.width() as f32, grid.height() as f32);
+
+    // Shifted odd rows stick out half a cell.
+    if grid.connectivity() == Connectivity::Hex && grid.height() > 1 {
+        max.x += 0.5;
+    }
+    (min, max)
+}
+
+pub fn cell_color(theme: &GridTheme, grid: &Grid, cell_pos: CellPos, cell: Cell) -> Color {
+    if cell.is_wall {
+        return theme.wall;
+    }
+
+    match grid.portal(cell_pos) {
+        Some(_) => theme.portal,
+        None => theme.terrain.get(&Terrain::at(grid, cell_pos)).copied().unwrap_or(theme.floor),
+    }
+}
+
+/// Blends from `a` towards `b` by `t`, for painting overlays over cell colors.
+pub(crate) fn mix(a: Color, b: Color, t: f32) -> Color {
+    let [r0, g0, b0, a0] = a.as_rgba_f32();
+    let [r1, g1, b1, a1] = b.as_rgba_f32();
+    let lerp = |x: f32, y: f32| x + (y - x) * t;
+
+    Color::rgba(lerp(r0, r1), lerp(g0, g1), lerp(b0, b1), lerp(a0, a1))
+}
+
+/// A line list with an arrow from the center of each first cell pointing towards
+/// its second cell, for drawing per-cell directions. At a `size` of `1.0` the
+/// arrows stay within their cell.
+pub(crate) fn arrow_mesh(grid: &Grid, arrows: impl Iterator<Item = (CellPos, CellPos)>, size: f32) -> Mesh {
+    const HALF_LENGTH: f32 = 0.3;
+    const HEAD_LENGTH: f32 = 0.15;
+
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    for (from, to) in arrows {
+        let center = cell_transform(grid, from).translation.truncate();
+        let direction = (cell_transform(grid, to).translation.truncate() - center).normalize_or_zero();
+        if direction == Vec2::ZERO {
+            continue;
+        }
+
+        let tail = center - direction * HALF_LENGTH * size;
+        let tip = center + direction * HALF_LENGTH * size;
+        // The head's strokes, each turned 150 degrees from the direction.
+        let (sin, cos) = (0.5, -0.75_f32.sqrt());
+        let left = tip + HEAD_LENGTH * size * Vec2::new(direction.x * cos - direction.y * sin, direction.x * sin + direction.y * cos);
+        let right = tip + HEAD_LENGTH * size * Vec2::new(direction.x * cos + direction.y * sin, direction.y * cos - direction.x * sin);
+
+        for point in [tail, tip, tip, left, tip, right] {
+            positions.push(point.extend(0.0).to_array());
+        }
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::LineList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 0.0, 1.0]; positions.len()]);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0.0, 0.0]; positions.len()]);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh
+}
+
+/// The color of every cell of a grid entity, drawn as a single texture. Kept
+/// in step with the grid cell by cell as `CellChangeEvent`s come in, and painted
+/// over by overlays every frame; only the cells that were painted or changed
+/// are visited again, and only their pixels are uploaded.
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct CellColors {
+    width: u32,
+    height: u32,
+    /// Color of every cell on the grid, before overlays.
+    base: Vec<Color>,
+    colors: Vec<Color>,
+    /// Cells painted over since they were last restored to their base color.
+    tinted: Vec<usize>,
+    /// Cells whose color may differ from the texture.
+    dirty: Vec<usize>,
+}
+
+impl CellColors {
+    fn of(theme: &GridTheme, grid: &Grid) -> Self {
+        let mut colors = CellColors {
+            width: grid.width(),
+            height: grid.height(),
+            base: vec![Color::NONE; (grid.width() * grid.height()) as usize],
+            colors: Vec::new(),
+            tinted: Vec::new(),
+            dirty: Vec::new(),
+        };
+        colors.reset(theme, grid);
+        colors.dirty.clear();
+        colors
+    }
+
+    /// Sets every cell back to its color on `grid`.
+    fn reset(&mut self, theme: &GridTheme, grid: &Grid) {
+        let width = self.width as i32;
+        for (index, color) in self.base.iter_mut().enumerate() {
+            let cell_pos = CellPos(index as i32 % width, index as i32 / width);
+            if let Ok(cell) = grid.cell(cell_pos) {
+                *color = cell_color(theme, grid, cell_pos, cell);
+            }
+        }
+
+        self.colors.clone_from(&self.base);
+        self.tinted.clear();
+        self.dirty = (0..self.colors.len()).collect();
+    }
+
+    /// Sets `cell_pos` back to its color on `grid`, after it changed.
+    fn reset_cell(&mut self, theme: &GridTheme, grid: &Grid, cell_pos: CellPos) {
+        let (Some(index), Ok(cell)) = (self.index(cell_pos), grid.cell(cell_pos)) else {
+            return;
+        };
+
+        self.base[index] = cell_color(theme, grid, cell_pos, cell);
+        self.colors[index] = self.base[index];
+        self.dirty.push(index);
+    }
+
+    /// Restores the cells painted over since the last call to their base color,
+    /// for overlays to paint over again.
+    fn clear_tints(&mut self) {
+        for index in self.tinted.drain(..) {
+            self.colors[index] = self.base[index];
+            self.dirty.push(index);
+        }
+    }
+
+    /// Cells whose color may differ from the texture since the last call, each once.
+    fn take_dirty(&mut self) -> Vec<usize> {
+        let mut dirty = std::mem::take(&mut self.dirty);
+        dirty.sort_unstable();
+        dirty.dedup();
+        dirty
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn index(&self, cell_pos: CellPos) -> Option<usize> {
+        let CellPos(x, y) = cell_pos;
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return None;
+        }
+
+        Some((self.width * y as u32 + x as u32) as usize)
+    }
+
+    fn cell_pos(&self, index: usize) -> CellPos {
+        let width = self.width as i32;
+        CellPos(index as i32 % width, index as i32 / width)
+    }
+
+    pub fn get(&self, cell_pos: CellPos) -> Option<Color> {
+        self.index(cell_pos).map(|index| self.colors[index])
+    }
+
+    /// Sets the color of `cell_pos`, if it is in bounds, until the next frame.
+    pub fn set(&mut self, cell_pos: CellPos, color: Color) {
+        if let Some(index) = self.index(cell_pos) {
+            self.paint(index, color);
+        }
+    }
+
+    fn paint(&mut self, index: usize, color: Color) {
+        self.colors[index] = color;
+        self.tinted.push(index);
+        self.dirty.push(index);
+    }
+
+    /// Replaces the color of every cell `tint` returns a color for, given the cell
+    /// and its current color, until the next frame. Visits the whole grid; prefer
+    /// [`CellColors::tint_cells`] when only some cells can be painted.
+    pub fn tint(&mut self, mut tint: impl FnMut(CellPos, Color) -> Option<Color>) {
+        for index in 0..self.colors.len() {
+            if let Some(tinted) = tint(self.cell_pos(index), self.colors[index]) {
+                self.paint(index, tinted);
+            }
+        }
+    }
+
+    /// Like [`CellColors::tint`], but only visits `cells`, skipping those out of bounds.
+    pub fn tint_cells(
+        &mut self,
+        cells: impl IntoIterator<Item = CellPos>,
+        mut tint: impl FnMut(CellPos, Color) -> Option<Color>,
+    ) {
+        for cell_pos in cells {
+            let Some(index) = self.index(cell_pos) else {
+                continue;
+            };
+            if let Some(tinted) = tint(cell_pos, self.colors[index]) {
+                self.paint(index, tinted);
+            }
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (CellPos, Color)> + '_ {
+        self.colors.iter().enumerate().map(|(index, &color)| (self.cell_pos(index), color))
+    }
+}
+
+/// The size and connectivity a grid entity's texture was laid out for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TextureLayout {
+    width: u32,
+    height: u32,
+    connectivity: Connectivity,
+}
+
+impl TextureLayout {
+    fn of(grid: &Grid) -> Self {
+        TextureLayout { width: grid.width(), height: grid.height(), connectivity: grid.connectivity() }
+    }
+
+    /// Pixels per cell along x. Hex rows are offset by half a cell, so hex cells
+    /// are two pixels wide and odd rows start one pixel in.
+    fn cell_pixels(&self) -> u32 {
+        match self.connectivity {
+            Connectivity::Hex => 2,
+            _ => 1,
+        }
+    }
+
+    fn image_size(&self) -> Extent3d {
+        let width = match self.connectivity {
+            Connectivity::Hex => 2 * self.width + 1,
+            _ => self.width,
+        };
+        Extent3d { width, height: self.height, depth_or_array_layers: 1 }
+    }
+
+    /// Size and placement of the quad showing the texture, so every cell lands
+    /// where `cell_transform` puts it.
+    fn quad(&self) -> (Vec2, Transform) {
+        let size = Vec2::new(
+            self.image_size().width as f32 / self.cell_pixels() as f32,
+            self.height as f32,
+        );
+        let left = -((self.width / 2) as f32) - 0.5;
+        let bottom = -((self.height / 2) as f32) - 0.5;
+
+        (size, Transform::from_xyz(left + size.x / 2.0, bottom + size.y / 2.0, 0.0))
+    }
+
+    /// Bytes of the texture data covering `cell_pos`, top row first.
+    fn cell_bytes(&self, cell_pos: CellPos) -> Range<usize> {
+        let CellPos(x, y) = cell_pos;
+        let row = self.height - 1 - y as u32;
+        let shift = (self.connectivity == Connectivity::Hex && y.rem_euclid(2) == 1) as u32;
+        let first = row * self.image_size().width + x as u32 * self.cell_pixels() + shift;
+
+        (first * 4) as usize..((first + self.cell_pixels()) * 4) as usize
+    }
+
+    /// RGBA pixels of `colors`, top row first. Pixels outside every cell are transparent.
+    fn pixels(&self, colors: &CellColors) -> Vec<u8> {
+        let Extent3d { width: image_width, .. } = self.image_size();
+        let mut data = vec![0; (image_width * self.height * 4) as usize];
+
+        for (cell_pos, color) in colors.iter() {
+            write_pixels(&mut data[self.cell_bytes(cell_pos)], color);
+        }
+
+        data
+    }
+
+    fn image(&self, colors: &CellColors) -> Image {
+        let mut image =
+            Image::new(self.image_size(), TextureDimension::D2, self.pixels(colors), TextureFormat::Rgba8UnormSrgb);
+        image.sampler_descriptor = ImageSampler::nearest();
+        image
+    }
+}
+
+/// Fills `bytes` with RGBA pixels of `color`.
+fn write_pixels(bytes: &mut [u8], color: Color) {
+    let rgba = color.as_rgba_f32().map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+    for pixel in bytes.chunks_exact_mut(4) {
+        pixel.copy_from_slice(&rgba);
+    }
+}
+
+/// The texture a grid entity is drawn with, one pixel or two per cell, on a
+/// single quad child.
+#[derive(Component, Debug, Clone)]
+pub struct GridTexture {
+    image: Handle<Image>,
+    quad: Entity,
+    layout: TextureLayout,
+}
+
+impl GridTexture {
+    pub fn image(&self) -> &Handle<Image> {
+        &self.image
+    }
+
+    /// The child entity showing the texture.
+    pub fn quad(&self) -> Entity {
+        self.quad
+    }
+
+    /// Texture pixels per cell along x.
+    pub(crate) fn cell_pixels(&self) -> u32 {
+        self.layout.cell_pixels()
+    }
+}
+
+fn quad_bundle(layout: TextureLayout, image: Handle<Image>) -> SpriteBundle {
+    let (size, transform) = layout.quad();
+    SpriteBundle {
+        transform,
+        texture: image,
+        sprite: Sprite {
+            custom_size: Some(size),
+            ..default()
+        },
+        ..default()
+    }
+}
+
+/// Gives new grid entities their texture, once their grid asset is available.
+pub(crate) fn grid_added<T: GridHandle>(
+    mut commands: Commands,
+    theme: Res<GridTheme>,
+    assets: Res<Assets<Grid>>,
+    mut images: ResMut<Assets<Image>>,
+    new_grid: Query<(&T, Entity), Without<GridTexture>>,
+) {
+    for (grid_handle, entity) in &new_grid {
+        let Some(grid) = assets.get(grid_handle.handle()) else {
+            continue;
+        };
+
+        let layout = TextureLayout::of(grid);
+        let colors = CellColors::of(&theme, grid);
+        let image = images.add(layout.image(&colors));
+        let quad = commands.spawn((quad_bundle(layout, image.clone()), Name::new("Grid texture"))).id();
+
+        commands
+            .entity(entity)
+            .insert((GridTexture { image, quad, layout }, colors))
+            .add_child(quad);
+    }
+}
+
+/// Lays the texture out again when a grid asset changes size or connectivity,
+/// whichever editor or system changed it, so every view of the grid follows.
+pub(crate) fn relayout_cells<T: GridHandle>(
+    mut asset_events: EventReader<AssetEvent<Grid>>,
+    theme: Res<GridTheme>,
+    assets: Res<Assets<Grid>>,
+    mut images: ResMut<Assets<Image>>,
+    mut grid_query: Query<(&T, &mut GridTexture, &mut CellColors)>,
+    mut quads: Query<(&mut Sprite, &mut Transform)>,
+) {
+    let modified = modified_grids(&mut asset_events);
+
+    for (grid_handle, mut texture, mut colors) in &mut grid_query {
+        if !modified.contains(grid_handle.handle()) {
+            continue;
+        }
+        let Some(grid) = assets.get(grid_handle.handle()) else {
+            continue;
+        };
+        let layout = TextureLayout::of(grid);
+        if texture.layout == layout {
+            continue;
+        }
+
+        *colors = CellColors::of(&theme, grid);
+        if let Some(image) = images.get_mut(&texture.image) {
+            *image = layout.image(&colors);
+        }
+        if let Ok((mut sprite, mut transform)) = quads.get_mut(texture.quad) {
+            let (size, quad_transform) = layout.quad();
+            sprite.custom_size = Some(size);
+            *transform = quad_transform;
+        }
+        texture.layout = layout;
+    }
+}
+
+/// Restores the cells overlays painted last frame, and brings the cells of every
+/// grid up to date: those announced by a `CellChangeEvent` one by one, and all of
+/// them when the theme changed or the grid asset was modified without one.
+pub(crate) fn update_cells<T: GridHandle>(
+    theme: Res<GridTheme>,
+    assets: Res<Assets<Grid>>,
+    mut asset_events: EventReader<AssetEvent<Grid>>,
+    mut cell_events: EventReader<CellChangeEvent>,
+    mut unannounced: Local<UnannouncedChanges>,
+    editors: Query<&GridEditor>,
+    mut grid_query: Query<(&T, &mut CellColors)>,
+) {
+    let mut changed_cells: HashMap<&Handle<Grid>, Vec<CellPos>> = HashMap::new();
+    for event in cell_events.iter() {
+        if let Ok(editor) = editors.get(event.grid) {
+            unannounced.announce(&editor.grid);
+            changed_cells.entry(&editor.grid).or_default().push(event.cell_pos);
+        }
+    }
+    let unannounced = unannounced.unannounced(&mut asset_events);
+
+    for (grid_handle, mut colors) in &mut grid_query {
+        let Some(grid) = assets.get(grid_handle.handle()) else {
+            continue;
+        };
+        // A resize this frame is laid out by `relayout_cells` first.
+        if (colors.width, colors.height) != (grid.width(), grid.height()) {
+            continue;
+        }
+
+        colors.clear_tints();
+        if theme.is_changed() || unannounced.contains(grid_handle.handle()) {
+            colors.reset(&theme, grid);
+        } else if let Some(cells) = changed_cells.get(grid_handle.handle()) {
+            for &cell_pos in cells {
+                colors.reset_cell(&theme, grid, cell_pos);
+            }
+        }
+    }
+}
+
+/// Copies the cells whose color changed into the textures, leaving the images
+/// of unchanged grids alone so they aren't uploaded again. Runs after every
+/// overlay has painted.
+pub(crate) fn upload_cell_textures(
+    mut images: ResMut<Assets<Image>>,
+    mut textures: Query<(&GridTexture, &mut CellColors)>,
+) {
+    for (texture, mut colors) in &mut textures {
+        if (colors.width, colors.height) != (texture.layout.width, texture.layout.height) {
+            continue;
+        }
+        let dirty = colors.take_dirty();
+        let Some(image) = images.get(&texture.image) else {
+            continue;
+        };
+
+        let mut cell_pixels = [0; 8];
+        let changed: Vec<usize> = dirty
+            .into_iter()
+            .filter(|&index| {
+                let bytes = texture.layout.cell_bytes(colors.cell_pos(index));
+                let pixels = &mut cell_pixels[..bytes.len()];
+                write_pixels(pixels, colors.colors[index]);
+                image.data[bytes] != *pixels
+            })
+            .collect();
+        if changed.is_empty() {
+            continue;
+        }
+
+        if let Some(image) = images.get_mut(&texture.image) {
+            for index in changed {
+                write_pixels(&mut image.data[texture.layout.cell_bytes(colors.cell_pos(index))], colors.colors[index]);
+            }
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! Agents walking their paths across the grid.
+//!
+//! A [`PathWalker`] moves the entity it is on along a path, cell by cell at a
+//! set speed, gliding between cell centers. It is placed in the space of its
+//! grid entity, so make it a child of the grid or keep the grid at the origin.
+//! A walker that gets a new `ComputedPath` starts walking it, so requesting a
+//! path for a walker is enough to set it off.
+
+use bevy::prelude::*;
+
+use crate::{
+    grid::{CellPos, Grids},
+    request::ComputedPath,
+    view::cell_transform,
+};
+
+/// How far above the grid texture and path lines walkers are drawn.
+const WALKER_DEPTH: f32 = 2.0;
+
+#[derive(Component, Debug, Clone)]
+pub struct PathWalker {
+    pub grid: Entity,
+    /// Cells walked per second.
+    pub speed: f32,
+    path: Vec<CellPos>,
+    /// Steps walked along the path, fractional while between two cells.
+    progress: f32,
+}
+
+impl PathWalker {
+    pub fn new(grid: Entity, speed: f32) -> Self {
+        PathWalker { grid, speed, path: Vec::new(), progress: 0.0 }
+    }
+
+    /// Starts walking `path` from its first cell.
+    pub fn walk(&mut self, path: Vec<CellPos>) {
+        self.path = path;
+        self.progress = 0.0;
+    }
+
+    pub fn path(&self) -> &[CellPos] {
+        &self.path
+    }
+
+    /// The cell the walker last stood on, or `None` before it has a path.
+    pub fn cell_pos(&self) -> Option<CellPos> {
+        let step = (self.progress as usize).min(self.path.len().checked_sub(1)?);
+        Some(self.path[step])
+    }
+
+    /// Whether the walker stands on the last cell of its path.
+    pub fn is_finished(&self) -> bool {
+        self.progress as usize + 1 >= self.path.len()
+    }
+
+    /// Walks on for `seconds`, stopping at the end of the path.
+    pub fn advance(&mut self, seconds: f32) {
+        let last_step = self.path.len().saturating_sub(1) as f32;
+        self.progress = (self.progress + self.speed * seconds).min(last_step);
+    }
+}
+
+/// A sprite walking paths on `grid`.
+#[derive(Bundle)]
+pub struct WalkerBundle {
+    pub walker: PathWalker,
+    #[bundle]
+    pub sprite: SpriteBundle,
+}
+
+impl WalkerBundle {
+    pub fn new(grid: Entity, speed: f32, color: Color) -> Self {
+        WalkerBundle {
+            walker: PathWalker::new(grid, speed),
+            sprite: SpriteBundle {
+                transform: Transform::from_xyz(0.0, 0.0, WALKER_DEPTH),
+                sprite: Sprite {
+                    color,
+                    custom_size: Some(Vec2::splat(0.8)),
+                    ..default()
+                },
+                ..default()
+            },
+        }
+    }
+}
+
+/// Sets walkers off along the paths computed for them.
+pub(crate) fn follow_computed_paths(mut walkers: Query<(&mut PathWalker, &ComputedPath), Changed<ComputedPath>>) {
+    for (mut walker, computed) in &mut walkers {
+        walker.walk(computed.path.cells.clone());
+    }
+}
+
+/// Moves walkers along their paths, keeping their depth.
+pub(crate) fn walk_paths(time: Res<Time>, grids: Grids, mut walkers: Query<(&mut PathWalker, &mut Transform)>) {
+    for (mut walker, mut transform) in &mut walkers {
+        let Ok(grid) = grids.get(walker.grid) else {
+            continue;
+        };
+        walker.advance(time.delta_seconds());
+
+        let Some(from) = walker.cell_pos() else {
+            continue;
+        };
+        let to = walker.path.get(walker.progress as usize + 1).copied().unwrap_or(from);
+        let from = cell_transform(grid, from).translation;
+        let to = cell_transform(grid, to).translation;
+
+        let position = from.lerp(to, walker.progress.fract());
+        transform.translation = position.truncate().extend(transform.translation.z);
+    }
+}
@@ -0,0 +1,373 @@
+//! Maps pieced together from a sample by wave function collapse.
+//!
+//! [`TileSet::from_sample`] takes every square of a small hand-drawn sample,
+//! `tile_size` cells wide, as a tile, wrapping around the edges of the sample,
+//! and counts how often each turns up. Two tiles may sit side by side when
+//! they agree where they overlap, shifted a cell apart. [`collapse`] then
+//! settles the cells of a new map one at a time, each on a tile: always the
+//! cell with the fewest tiles left that fit its neighbors, picking among them
+//! by how common they are in the sample, and striking the tiles that no longer
+//! fit from the cells around it, and from those around them in turn. Each cell
+//! takes the bottom left cell of its tile, so every square of the map is one
+//! of the sample: rooms, corridors and the way they meet come out as the
+//! sample draws them, without copying it whole. When a cell runs out of tiles
+//! the map is started over.
+//!
+//! Inserting [`GenerateWfc`] on a grid editor entity lays such a map over its
+//! grid, like a `BulkEdit`, within its `CellSelection` if it has one. Its
+//! default sample is a few rooms joined by corridors.
+
+use std::{cmp::Reverse, collections::BinaryHeap, error::Error, fmt::Display};
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{
+    console::ErrorConsole,
+    editor::{selection_of, set_all_cells, CellSelection},
+    grid::{Cell, CellPos, Grid, GridEditor, GridNotFound},
+    journal::GridJournal,
+    pattern::CellPattern,
+    seed::MapRng,
+};
+
+/// The sample of [`GenerateWfc::default`], top row first.
+const ROOMS: [&str; 18] = [
+    "################.#",
+    "#..........#####.#",
+    "#....#####.#####.#",
+    "#....#####.#####.#",
+    "#....#####.#####.#",
+    "####.#####.#####.#",
+    "####.#####.#####.#",
+    "####.#####....##.#",
+    "####.#####....##.#",
+    "####.#####....##.#",
+    "..................",
+    "####.###########.#",
+    "####.###########.#",
+    "####....########.#",
+    "####....########.#",
+    "####....########.#",
+    "####.............#",
+    "################.#",
+];
+
+/// Offsets to the neighbors of a cell, in the order of `TileSet::fits`.
+const SHIFTS: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+/// The tiles of a sample and which of them may sit next to which.
+#[derive(Debug, Clone)]
+pub struct TileSet {
+    tiles: Vec<CellPattern>,
+    /// How many times each tile turns up in the sample.
+    weights: Vec<u32>,
+    /// For each of `SHIFTS` and each tile, the tiles that may sit that far
+    /// from it, a bit per tile.
+    fits: [Vec<Vec<u64>>; 4],
+}
+
+impl TileSet {
+    /// The squares `tile_size` cells wide of `sample`, at least one, wrapping
+    /// around its edges. An empty sample has no tiles.
+    pub fn from_sample(sample: &CellPattern, tile_size: u32) -> Self {
+        let size = tile_size.max(1) as i32;
+        let (width, height) = (sample.width() as i32, sample.height() as i32);
+        let mut tiles: Vec<CellPattern> = Vec::new();
+        let mut weights = Vec::new();
+
+        for (x, y) in (0..height).flat_map(|y| (0..width).map(move |x| (x, y))) {
+            let mut tile = CellPattern::new(size as u32, size as u32, Cell::floor());
+            for (dx, dy) in (0..size).flat_map(|dy| (0..size).map(move |dx| (dx, dy))) {
+                let wrapped = CellPos((x + dx).rem_euclid(width), (y + dy).rem_euclid(height));
+                let cell = sample.cell(wrapped).expect("wrapped into the sample");
+                tile.set_cell(CellPos(dx, dy), cell).expect("inside the tile");
+            }
+            match tiles.iter().position(|known| *known == tile) {
+                Some(index) => weights[index] += 1,
+                None => {
+                    tiles.push(tile);
+                    weights.push(1);
+                }
+            }
+        }
+
+        let words = tiles.len().div_ceil(64);
+        let fits = SHIFTS.map(|shift| {
+            tiles
+                .iter()
+                .map(|tile| {
+                    let mut fits = vec![0; words];
+                    for (index, next) in tiles.iter().enumerate() {
+                        if overlap(tile, next, shift) {
+                            fits[index / 64] |= 1 << (index % 64);
+                        }
+                    }
+                    fits
+                })
+                .collect()
+        });
+
+        TileSet { tiles, weights, fits }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+}
+
+/// Whether `next`, shifted by `(dx, dy)` from `tile`, agrees with it on every
+/// cell they share.
+fn overlap(tile: &CellPattern, next: &CellPattern, (dx, dy): (i32, i32)) -> bool {
+    let size = tile.width() as i32;
+    (0..size).flat_map(|y| (0..size).map(move |x| CellPos(x, y))).all(|cell_pos| {
+        let CellPos(x, y) = cell_pos;
+        next.cell(CellPos(x - dx, y - dy)).is_none_or(|cell| tile.cell(cell_pos) == Some(cell))
+    })
+}
+
+/// Why [`collapse`] laid out no map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollapseError {
+    /// The sample was empty, so there is nothing to piece a map together from.
+    NoTiles,
+    /// Every attempt left some cell without a tile that fits.
+    Contradiction { attempts: u32 },
+}
+
+impl Display for CollapseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CollapseError::NoTiles => write!(f, "the sample has no tiles"),
+            CollapseError::Contradiction { attempts } => {
+                write!(f, "every one of {attempts} attempts left a cell no tile of the sample fits")
+            }
+        }
+    }
+}
+impl Error for CollapseError {}
+
+/// A `width` by `height` map pieced together from the tiles of `tile_set`,
+/// started over up to `attempts` times when it runs into a cell no tile fits.
+pub fn collapse(
+    tile_set: &TileSet,
+    width: u32,
+    height: u32,
+    attempts: u32,
+    rng: &mut impl Rng,
+) -> Result<CellPattern, CollapseError> {
+    if tile_set.is_empty() {
+        return Err(CollapseError::NoTiles);
+    }
+
+    let attempts = attempts.max(1);
+    for _ in 0..attempts {
+        let Some(tiles) = Wave::new(tile_set, width, height).collapse(rng) else {
+            continue;
+        };
+        let mut pattern = CellPattern::new(width, height, Cell::floor());
+        for (index, tile) in tiles.into_iter().enumerate() {
+            let cell_pos = CellPos((index as u32 % width) as i32, (index as u32 / width) as i32);
+            let cell = tile_set.tiles[tile].cell(CellPos(0, 0)).expect("tiles are at least a cell wide");
+            pattern.set_cell(cell_pos, cell).expect("indexed inside the pattern");
+        }
+        return Ok(pattern);
+    }
+    Err(CollapseError::Contradiction { attempts })
+}
+
+/// The tiles each cell of a map may still take while it is collapsed.
+struct Wave<'a> {
+    tile_set: &'a TileSet,
+    width: i32,
+    height: i32,
+    /// Words of bits per cell.
+    words: usize,
+    /// The tiles left for each cell, row by row, `words` words each.
+    options: Vec<u64>,
+    /// How many tiles each cell has left.
+    counts: Vec<u32>,
+}
+
+impl<'a> Wave<'a> {
+    fn new(tile_set: &'a TileSet, width: u32, height: u32) -> Self {
+        let (count, words) = (tile_set.len(), tile_set.len().div_ceil(64));
+        let mut every_tile = vec![u64::MAX; words];
+        if count % 64 != 0 {
+            every_tile[words - 1] = (1 << (count % 64)) - 1;
+        }
+        let cells = (width * height) as usize;
+        Wave {
+            tile_set,
+            width: width as i32,
+            height: height as i32,
+            words,
+            options: every_tile.repeat(cells),
+            counts: vec![count as u32; cells],
+        }
+    }
+
+    fn options(&self, index: usize) -> &[u64] {
+        &self.options[index * self.words..(index + 1) * self.words]
+    }
+
+    fn tiles(&self, index: usize) -> impl Iterator<Item = usize> + '_ {
+        let options = self.options(index);
+        (0..self.tile_set.len()).filter(move |&tile| options[tile / 64] & (1 << (tile % 64)) != 0)
+    }
+
+    /// The tile each cell settled on, or `None` if one ran out of tiles.
+    fn collapse(mut self, rng: &mut impl Rng) -> Option<Vec<usize>> {
+        // Cells by how many tiles they have left, ties broken at random. A cell
+        // is pushed again whenever it loses tiles; older entries are skipped.
+        let mut queue: BinaryHeap<(Reverse<u32>, u32, usize)> =
+            (0..self.counts.len()).map(|index| (Reverse(self.counts[index]), rng.gen(), index)).collect();
+
+        while let Some((Reverse(count), _, index)) = queue.pop() {
+            if count != self.counts[index] || count <= 1 {
+                continue;
+            }
+
+            let total: u32 = self.tiles(index).map(|tile| self.tile_set.weights[tile]).sum();
+            let mut roll = rng.gen_range(0..total);
+            let tile = self
+                .tiles(index)
+                .find(|&tile| match roll.checked_sub(self.tile_set.weights[tile]) {
+                    Some(rest) => {
+                        roll = rest;
+                        false
+                    }
+                    None => true,
+                })
+                .expect("the roll is below the total weight");
+
+            let options = &mut self.options[index * self.words..(index + 1) * self.words];
+            options.fill(0);
+            options[tile / 64] = 1 << (tile % 64);
+            self.counts[index] = 1;
+            if !self.propagate(index, &mut queue, rng) {
+                return None;
+            }
+        }
+
+        Some((0..self.counts.len()).map(|index| self.tiles(index).next().expect("every cell settled")).collect())
+    }
+
+    /// Strikes the tiles that no longer fit from the cells around `changed`,
+    /// and on from theirs. Returns false if a cell runs out of tiles.
+    fn propagate(
+        &mut self,
+        changed: usize,
+        queue: &mut BinaryHeap<(Reverse<u32>, u32, usize)>,
+        rng: &mut impl Rng,
+    ) -> bool {
+        let mut stack = vec![changed];
+        let mut fitting = vec![0; self.words];
+
+        while let Some(index) = stack.pop() {
+            let (x, y) = (index as i32 % self.width, index as i32 / self.width);
+            for (shift, (dx, dy)) in SHIFTS.into_iter().enumerate() {
+                let (nx, ny) = (x + dx, y + dy);
+                if nx < 0 || ny < 0 || nx >= self.width || ny >= self.height {
+                    continue;
+                }
+                let neighbor = (ny * self.width + nx) as usize;
+
+                fitting.fill(0);
+                for tile in self.tiles(index) {
+                    for (word, fits) in fitting.iter_mut().zip(&self.tile_set.fits[shift][tile]) {
+                        *word |= fits;
+                    }
+                }
+
+                let options = &mut self.options[neighbor * self.words..(neighbor + 1) * self.words];
+                let mut count = 0;
+                for (word, fits) in options.iter_mut().zip(&fitting) {
+                    *word &= fits;
+                    count += word.count_ones();
+                }
+                if count == self.counts[neighbor] {
+                    continue;
+                }
+                if count == 0 {
+                    return false;
+                }
+                self.counts[neighbor] = count;
+                queue.push((Reverse(count), rng.gen(), neighbor));
+                stack.push(neighbor);
+            }
+        }
+        true
+    }
+}
+
+/// Inserted on a grid editor entity to replace its walls with a map pieced
+/// together from `sample` by [`collapse`], all at once like a `BulkEdit`,
+/// within its `CellSelection` if it has one. Errors go to the `ErrorConsole`,
+/// and leave the grid as it was.
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
+pub struct GenerateWfc {
+    pub sample: CellPattern,
+    /// Width of the squares of the sample the map is pieced together from.
+    /// Larger tiles keep more of the sample's shapes, and run into more cells
+    /// no tile fits; 2 or 3 suits most samples.
+    pub tile_size: u32,
+}
+
+impl GenerateWfc {
+    /// Times a map is started over before giving up.
+    pub const ATTEMPTS: u32 = 10;
+
+    pub fn new(sample: CellPattern, tile_size: u32) -> Self {
+        GenerateWfc { sample, tile_size }
+    }
+}
+
+impl Default for GenerateWfc {
+    /// Rooms joined by corridors, in tiles of 3 cells.
+    fn default() -> Self {
+        let sample = CellPattern::from_rows(ROOMS).expect("the built-in sample is valid");
+        GenerateWfc::new(sample, 3)
+    }
+}
+
+pub(crate) fn generate_wfc(
+    mut commands: Commands,
+    mut grid_query: Query<(&GridEditor, &GenerateWfc, Option<&mut GridJournal>, Entity)>,
+    selections: Query<&CellSelection>,
+    mut assets: ResMut<Assets<Grid>>,
+    mut console: ResMut<ErrorConsole>,
+    time: Res<Time>,
+    mut map_rng: ResMut<MapRng>,
+) {
+    for (grid_editor, generate, journal, entity) in &mut grid_query {
+        commands.entity(entity).remove::<GenerateWfc>();
+
+        let Some(grid) = assets.get(&grid_editor.grid) else {
+            console.report("generate_wfc", &GridNotFound { entity });
+            continue;
+        };
+
+        let (seed, mut rng) = map_rng.next_rng();
+        let tile_set = TileSet::from_sample(&generate.sample, generate.tile_size);
+        let map = match collapse(&tile_set, grid.width(), grid.height(), GenerateWfc::ATTEMPTS, &mut rng) {
+            Ok(map) => map,
+            Err(error) => {
+                console.report("generate_wfc", &error);
+                continue;
+            }
+        };
+        let selection = selection_of(&selections, entity);
+        let changes: Vec<(CellPos, Cell, Cell)> = grid
+            .iter_cell_pos()
+            .filter(|(cell_pos, _)| selection.is_none_or(|selection| selection.cells.contains(cell_pos)))
+            .filter_map(|(cell_pos, old)| Some((cell_pos, old, map.cell(cell_pos)?)))
+            .filter(|(_, old, new)| old != new)
+            .collect();
+        set_all_cells(&mut assets, &grid_editor.grid, journal, time.elapsed_seconds_f64(), changes, Some(seed));
+    }
+}
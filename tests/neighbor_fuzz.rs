@@ -0,0 +1,70 @@
+//! Checks the padded fast path of `Grid::passable_neighbors` against a plain
+//! position-based reference on random grids of both storage kinds, including
+//! resized grids, one-way exits and open borders.
+//!
+//! `cargo test --release --test neighbor_fuzz`
+//! `cargo test --release --features unchecked-indexing --test neighbor_fuzz`
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use a_star::prelude::*;
+
+const ITERATIONS: usize = 500;
+
+fn random_grid(rng: &mut StdRng, storage: StorageKind) -> Grid {
+    let mut grid = Grid::with_storage(rng.gen_range(1..40), rng.gen_range(1..40), storage);
+
+    let fill = Cell { is_wall: rng.gen_bool(0.3) };
+    grid.resize(rng.gen_range(1..40), rng.gen_range(1..40), fill);
+
+    if rng.gen_bool(0.3) {
+        grid.set_boundary(Boundary::Open { margin: rng.gen_range(0..3) });
+    }
+
+    for (cell_pos, _) in grid.iter_cell_pos().collect::<Vec<_>>() {
+        grid.set_cell(cell_pos, Cell { is_wall: rng.gen_bool(0.3) }).unwrap();
+
+        if rng.gen_bool(0.1) {
+            let direction = Direction::ALL[rng.gen_range(0..4)];
+            grid.set_exits(cell_pos, grid.exits(cell_pos).without(direction)).unwrap();
+        }
+    }
+
+    grid
+}
+
+fn reference_neighbors(grid: &Grid, cell_pos: CellPos) -> Vec<CellPos> {
+    grid.adjacent(cell_pos).filter(|&neighbor| !grid.cell(neighbor).unwrap().is_wall).collect()
+}
+
+fn check_storage(storage: StorageKind, seed: u64) {
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    for iteration in 0..ITERATIONS {
+        let grid = random_grid(&mut rng, storage);
+
+        // One cell past the border on every side, to cover the boundary path too.
+        for y in -1..=grid.height() as i32 {
+            for x in -1..=grid.width() as i32 {
+                let cell_pos = CellPos(x, y);
+                if !grid.contains_pos(cell_pos) {
+                    continue;
+                }
+
+                let fast: Vec<_> = grid.passable_neighbors(cell_pos).collect();
+                let reference = reference_neighbors(&grid, cell_pos);
+                assert_eq!(fast, reference, "{storage:?} iteration {iteration}, cell {cell_pos:?}, grid {grid:?}");
+            }
+        }
+    }
+}
+
+#[test]
+fn cell_storage_matches_reference() {
+    check_storage(StorageKind::Cells, 0);
+}
+
+#[test]
+fn bitset_storage_matches_reference() {
+    check_storage(StorageKind::Bitset, 1);
+}
@@ -0,0 +1,34 @@
+//! Editors sharing one grid asset all see the edits announced for any of them.
+
+use bevy::prelude::*;
+
+use a_star::prelude::*;
+
+#[test]
+fn every_editor_of_a_grid_follows_its_cell_changes() {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins).add_plugin(AStarPlugin::headless());
+
+    let grid = GridBuilder::new(5, 3).wall_rect(CellPos(2, 0), CellPos(2, 2)).build();
+    let handle = app.world.resource_mut::<Assets<Grid>>().add(grid);
+    let first = app.world.spawn(GridEditor::new(handle.clone())).id();
+    let second = app.world.spawn(GridEditor::new(handle.clone())).id();
+    app.update();
+
+    let (left, right, door) = (CellPos(0, 1), CellPos(4, 1), CellPos(2, 1));
+    for editor in [first, second] {
+        assert!(!app.world.get::<Regions>(editor).unwrap().is_reachable(left, right));
+    }
+
+    // Open the wall through the first editor, as the editing systems do.
+    app.world.resource_mut::<Assets<Grid>>().get_mut(&handle).unwrap().set_cell(door, Cell::floor()).unwrap();
+    app.world.send_event(CellChangeEvent { grid: first, cell_pos: door, old: Cell::wall(), new: Cell::floor() });
+    app.update();
+    app.update();
+
+    let measured = Clearance::new(app.world.resource::<Assets<Grid>>().get(&handle).unwrap());
+    for editor in [first, second] {
+        assert!(app.world.get::<Regions>(editor).unwrap().is_reachable(left, right), "{editor:?}");
+        assert_eq!(app.world.get::<Clearance>(editor).unwrap().get(door), measured.get(door), "{editor:?}");
+    }
+}